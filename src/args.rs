@@ -0,0 +1,290 @@
+use std::env;
+
+use crate::renderer_backend::error::RendererError;
+
+/// The value following `name` on the command line, given either as
+/// `name value` or `name=value`.
+pub fn flag_value(name: &str) -> Result<Option<String>, RendererError> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == name {
+            return match args.next() {
+                Some(value) => Ok(Some(value)),
+                None => Err(RendererError::InvalidArgument(format!(
+                    "{} expects a value",
+                    name
+                ))),
+            };
+        }
+        if let Some(value) = arg.strip_prefix(name).and_then(|v| v.strip_prefix('=')) {
+            return Ok(Some(value.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `name` appears on the command line as a bare switch.
+pub fn has_flag(name: &str) -> bool {
+    env::args().skip(1).any(|arg| arg == name)
+}
+
+/// Like `flag_value`, falling back to the environment variable `env_name`.
+pub fn flag_or_env(name: &str, env_name: &str) -> Result<Option<String>, RendererError> {
+    Ok(flag_value(name)?.or_else(|| env::var(env_name).ok()))
+}
+
+/// A command-line option, for `--help` and for rejecting unknown ones.
+pub struct Flag {
+    pub name: &'static str,
+    /// What the value looks like, or `None` for a bare switch.
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const FLAGS: &[Flag] = &[
+    Flag {
+        name: "--config",
+        value: Some("FILE"),
+        help: "settings to start with, which the other options override [default: settings.toml next to the executable]",
+    },
+    Flag {
+        name: "--size",
+        value: Some("WIDTHxHEIGHT"),
+        help: "window size in screen coordinates [default: 800x600]",
+    },
+    Flag {
+        name: "--title",
+        value: Some("TEXT"),
+        help: "window title [default: \"It's Graphics Time\"]",
+    },
+    Flag {
+        name: "--undecorated",
+        value: None,
+        help: "open the window without a title bar or border",
+    },
+    Flag {
+        name: "--transparent",
+        value: None,
+        help: "let the desktop show through wherever nothing is drawn",
+    },
+    Flag {
+        name: "--fullscreen",
+        value: None,
+        help: "start fullscreen on the primary monitor",
+    },
+    Flag {
+        name: "--vsync",
+        value: Some("on|off"),
+        help: "wait for vertical blank when presenting [default: on]",
+    },
+    Flag {
+        name: "--msaa",
+        value: Some("1|2|4|8|16"),
+        help: "multisample count, lowered to 1 if unsupported [default: 1]",
+    },
+    Flag {
+        name: "--shadow-resolution",
+        value: Some("TEXELS"),
+        help: "width and height of the shadow map [default: 2048]",
+    },
+    Flag {
+        name: "--max-fps",
+        value: Some("FPS"),
+        help: "frame rate cap while vsync is off; ignored by --bench",
+    },
+    Flag {
+        name: "--icon",
+        value: Some("PATH"),
+        help: "PNG to use as the window icon",
+    },
+    Flag {
+        name: "--hdr",
+        value: None,
+        help: "present to a 16-bit float surface when the display offers one",
+    },
+    Flag {
+        name: "--stencil",
+        value: None,
+        help: "give the depth buffer a stencil aspect, for selection outlines (X)",
+    },
+    Flag {
+        name: "--indirect",
+        value: None,
+        help: "draw the scene's meshes through indirect draws from a shared pool (I)",
+    },
+    Flag {
+        name: "--gpu-cull",
+        value: None,
+        help: "with --indirect, frustum cull in a compute pass that writes the draws (U)",
+    },
+    Flag {
+        name: "--model",
+        value: Some("PATH"),
+        help: "glTF model to add to the scene",
+    },
+    Flag {
+        name: "--debug-view",
+        value: None,
+        help: "open a second window with a camera of its own",
+    },
+    Flag {
+        name: "--shadertoy",
+        value: Some("FILE"),
+        help: "draw the WGSL main_image in FILE over the window in place of the scene, reloading it on changes (R restarts iTime)",
+    },
+    Flag {
+        name: "--upload-stress",
+        value: Some("COUNT"),
+        help: "extra small buffer writes per frame [default: 0]",
+    },
+    Flag {
+        name: "--backend",
+        value: Some("vulkan|dx12|metal|gl|auto"),
+        help: "graphics backend, or $WGPU_BACKEND [default: auto]",
+    },
+    Flag {
+        name: "--adapter",
+        value: Some("NAME"),
+        help: "use the adapter whose name contains NAME, or $WGPU_ADAPTER",
+    },
+    Flag {
+        name: "--power",
+        value: Some("low|high|default"),
+        help: "power preference wgpu picks an adapter by [default: high]",
+    },
+    Flag {
+        name: "--fallback-adapter",
+        value: None,
+        help: "use a software adapter such as lavapipe or WARP",
+    },
+    Flag {
+        name: "--clear-color",
+        value: Some("R,G,B"),
+        help: "background color, components 0 to 1, or $CLEAR_COLOR",
+    },
+    Flag {
+        name: "--headless",
+        value: Some("WIDTHxHEIGHT"),
+        help: "render offscreen without a window and save the frames as PNGs",
+    },
+    Flag {
+        name: "--frames",
+        value: Some("COUNT"),
+        help: "frames to render with --headless [default: 1], or before exiting",
+    },
+    Flag {
+        name: "--bench",
+        value: Some("COUNT"),
+        help: "render COUNT frames without vsync or input, print frame time statistics and exit",
+    },
+    Flag {
+        name: "--bench-out",
+        value: Some("FILE"),
+        help: "with --bench, also write every frame's timings to a CSV file",
+    },
+    Flag {
+        name: "--record",
+        value: Some("DIR"),
+        help: "write every presented frame into DIR as numbered PNGs",
+    },
+    Flag {
+        name: "--record-fps",
+        value: Some("FPS"),
+        help: "with --record, step the scene 1/FPS seconds a frame [default: 60]",
+    },
+    Flag {
+        name: "--record-every",
+        value: Some("N"),
+        help: "with --record, keep only every Nth frame [default: 1]",
+    },
+    Flag {
+        name: "--trace",
+        value: Some("DIR"),
+        help: "write a wgpu API trace into DIR, for bug reports",
+    },
+    Flag {
+        name: "--out",
+        value: Some("DIR"),
+        help: "where --headless writes its frames [default: .]",
+    },
+    Flag {
+        name: "--srgb-comparison",
+        value: None,
+        help: "with --headless, also write sRGB and linear colors side by side",
+    },
+    Flag {
+        name: "--help",
+        value: None,
+        help: "print this help",
+    },
+];
+
+pub fn print_help() {
+    println!("Usage: wgpu_beginner [OPTIONS]\n\nOptions:");
+    let usage: Vec<String> = FLAGS
+        .iter()
+        .map(|flag| match flag.value {
+            Some(value) => format!("{} {}", flag.name, value),
+            None => flag.name.to_string(),
+        })
+        .collect();
+    let width = usage.iter().map(String::len).max().unwrap_or(0);
+    for (flag, usage) in FLAGS.iter().zip(&usage) {
+        println!("  {:width$}  {}", usage, flag.help, width = width);
+    }
+}
+
+/// Fails on the first argument that isn't one of `FLAGS`, or on a switch
+/// given a value.
+pub fn check_flags() -> Result<(), RendererError> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, _)) => (name, true),
+            None => (arg.as_str(), false),
+        };
+        let Some(flag) = FLAGS.iter().find(|flag| flag.name == name) else {
+            return Err(RendererError::InvalidArgument(format!(
+                "unknown argument \"{}\", see --help",
+                arg
+            )));
+        };
+        match (flag.value, inline_value) {
+            (Some(_), false) => {
+                args.next();
+            }
+            (None, true) => {
+                return Err(RendererError::InvalidArgument(format!(
+                    "{} doesn't take a value",
+                    name
+                )));
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Parses a `WIDTHxHEIGHT` value given to `name`. Neither side may be 0.
+pub fn parse_size(name: &str, value: &str) -> Result<(u32, u32), RendererError> {
+    let invalid = || {
+        RendererError::InvalidArgument(format!(
+            "{} expects WIDTHxHEIGHT with both above 0, got \"{}\"",
+            name, value
+        ))
+    };
+    let (width, height) = value.split_once('x').ok_or_else(invalid)?;
+    let width: u32 = width.parse().map_err(|_| invalid())?;
+    let height: u32 = height.parse().map_err(|_| invalid())?;
+    if width == 0 || height == 0 {
+        return Err(invalid());
+    }
+    Ok((width, height))
+}
+
+/// Parses the number given to `name`.
+pub fn parse_count(name: &str, value: &str) -> Result<u32, RendererError> {
+    value.parse().map_err(|_| {
+        RendererError::InvalidArgument(format!("{} expects a number, got \"{}\"", name, value))
+    })
+}
@@ -0,0 +1,153 @@
+use std::fs;
+use std::path::PathBuf;
+
+use web_time::Instant;
+
+use crate::args;
+use crate::renderer_backend::{error::RendererError, gpu_timer::GpuTimings};
+
+/// Simulated time step between benchmark frames, so every run renders the
+/// same frames.
+pub const FRAME_DELTA: f32 = 1.0 / 60.0;
+
+pub struct BenchOptions {
+    pub frames: u32,
+    /// Where to write every frame's timings as CSV.
+    pub out: Option<PathBuf>,
+}
+
+/// Looks for `--bench N [--bench-out FILE]` on the command line. Returns
+/// `None` when `--bench` isn't given.
+pub fn from_args() -> Result<Option<BenchOptions>, RendererError> {
+    let frames = match args::flag_value("--bench")? {
+        Some(count) => args::parse_count("--bench", &count)?,
+        None => return Ok(None),
+    };
+    if frames == 0 {
+        return Err(RendererError::InvalidArgument(
+            "--bench needs at least one frame".to_string(),
+        ));
+    }
+    Ok(Some(BenchOptions {
+        frames: frames,
+        out: args::flag_value("--bench-out")?.map(PathBuf::from),
+    }))
+}
+
+/// Min, mean, 95th and 99th percentile and max of some frame times, in ms.
+struct Summary {
+    min: f32,
+    avg: f32,
+    p95: f32,
+    p99: f32,
+    max: f32,
+}
+
+impl Summary {
+    fn new(samples: impl IntoIterator<Item = f32>) -> Option<Self> {
+        let mut sorted: Vec<f32> = samples.into_iter().collect();
+        if sorted.is_empty() {
+            return None;
+        }
+        sorted.sort_by(f32::total_cmp);
+        // Nearest rank: the smallest sample at least `p` of them don't exceed.
+        let percentile = |p: f32| {
+            let rank = (p * sorted.len() as f32).ceil() as usize;
+            sorted[rank.clamp(1, sorted.len()) - 1]
+        };
+        Some(Summary {
+            min: sorted[0],
+            avg: sorted.iter().sum::<f32>() / sorted.len() as f32,
+            p95: percentile(0.95),
+            p99: percentile(0.99),
+            max: sorted[sorted.len() - 1],
+        })
+    }
+
+    fn print(&self, name: &str) {
+        println!(
+            "{:>4} ms  min {:.3}  avg {:.3}  p95 {:.3}  p99 {:.3}  max {:.3}",
+            name, self.min, self.avg, self.p95, self.p99, self.max
+        );
+    }
+}
+
+/// Times a fixed number of frames of the normal render loop.
+pub struct Bench {
+    options: BenchOptions,
+    start: Instant,
+    frame_start: Instant,
+    /// CPU time of every frame so far: update, encoding, submit and present.
+    cpu_ms: Vec<f32>,
+    /// GPU time of the frames the GPU timer got to, by frame. The timer's
+    /// frame numbers line up with these as long as nothing rendered before
+    /// the benchmark started.
+    gpu_ms: Vec<Option<f32>>,
+}
+
+impl Bench {
+    pub fn new(options: BenchOptions) -> Self {
+        println!("Benchmarking {} frames", options.frames);
+        let now = Instant::now();
+        Bench {
+            cpu_ms: Vec::with_capacity(options.frames as usize),
+            gpu_ms: vec![None; options.frames as usize],
+            options: options,
+            start: now,
+            frame_start: now,
+        }
+    }
+
+    pub fn begin_frame(&mut self) {
+        self.frame_start = Instant::now();
+    }
+
+    /// Records the frame since `begin_frame`, with `gpu`, the GPU timer's
+    /// latest result for some earlier frame, if there is one.
+    pub fn end_frame(&mut self, gpu: Option<GpuTimings>) {
+        self.cpu_ms
+            .push(self.frame_start.elapsed().as_secs_f32() * 1000.0);
+        if let Some(gpu) = gpu {
+            if let Some(slot) = self.gpu_ms.get_mut(gpu.frame as usize) {
+                *slot = Some(gpu.render_ms + gpu.compute_ms.unwrap_or(0.0));
+            }
+        }
+    }
+
+    pub fn finished(&self) -> bool {
+        self.cpu_ms.len() >= self.options.frames as usize
+    }
+
+    /// Prints the statistics and writes the CSV, if one was asked for.
+    pub fn report(&self) -> Result<(), RendererError> {
+        let seconds = self.start.elapsed().as_secs_f32();
+        println!(
+            "{} frames in {:.2} s, {:.1} fps",
+            self.cpu_ms.len(),
+            seconds,
+            self.cpu_ms.len() as f32 / seconds
+        );
+        if let Some(summary) = Summary::new(self.cpu_ms.iter().copied()) {
+            summary.print("cpu");
+        }
+        match Summary::new(self.gpu_ms.iter().flatten().copied()) {
+            Some(summary) => summary.print("gpu"),
+            None => println!("gpu times need timestamp query support"),
+        }
+
+        let Some(path) = &self.options.out else {
+            return Ok(());
+        };
+        let mut csv = String::from("frame,cpu_ms,gpu_ms\n");
+        for (frame, (cpu_ms, gpu_ms)) in self.cpu_ms.iter().zip(&self.gpu_ms).enumerate() {
+            let gpu_ms = gpu_ms.map_or(String::new(), |gpu_ms| format!("{:.4}", gpu_ms));
+            csv.push_str(&format!("{},{:.4},{}\n", frame, cpu_ms, gpu_ms));
+        }
+        fs::write(path, csv).map_err(|error| RendererError::Output {
+            path: path.clone(),
+            error: error,
+        })?;
+        println!("Wrote {}", path.display());
+        Ok(())
+    }
+}
@@ -0,0 +1,339 @@
+use crate::renderer_backend::{
+    bind_group, bind_group_layout, pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    shader::ShaderError,
+    texture::Texture,
+    uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/bloom.wgsl";
+
+/// Where the bright pixels are blurred. Float so values above 1.0 from an
+/// HDR scene survive, and half precision is plenty for a blur.
+pub const BLOOM_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// The largest blur radius the shader loops over; keep in step with
+/// `MAX_BLUR_RADIUS` in the shader.
+pub const MAX_BLUR_RADIUS: f32 = 16.0;
+
+/// What `BloomPass` does, adjustable while it runs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct BloomSettings {
+    /// Brightness, the largest color channel, above which pixels glow.
+    pub threshold: f32,
+    /// How strongly the glow is added back over the scene.
+    pub intensity: f32,
+    /// Half-resolution texels blurred over either side of each pixel.
+    pub radius: f32,
+}
+
+impl Default for BloomSettings {
+    fn default() -> Self {
+        BloomSettings {
+            threshold: 0.8,
+            intensity: 0.6,
+            radius: 6.0,
+        }
+    }
+}
+
+/// Textures that follow the target size.
+struct BloomTargets {
+    /// Full resolution, target format: what the scene is drawn into.
+    input: Texture,
+    input_bind_group: wgpu::BindGroup,
+    /// Half resolution, `BLOOM_FORMAT`. The blur ping-pongs between them
+    /// and ends up back in `bright`.
+    bright: Texture,
+    bright_bind_group: wgpu::BindGroup,
+    blurred: Texture,
+    blurred_bind_group: wgpu::BindGroup,
+}
+
+/// Makes bright parts of the frame glow, in four render passes:
+///
+/// 1. Extract: pixels brighter than the threshold go from the input into
+///    the half-resolution `bright` texture.
+/// 2. Horizontal blur, from `bright` into `blurred`.
+/// 3. Vertical blur, from `blurred` back into `bright`.
+/// 4. Composite: the input plus `bright` times the intensity, written to
+///    the frame.
+pub struct BloomPass {
+    settings: BloomSettings,
+    queue: wgpu::Queue,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group: wgpu::BindGroup,
+    source_bind_group_layout: bind_group_layout::Layout,
+    sampler: wgpu::Sampler,
+    extract_pipeline: wgpu::RenderPipeline,
+    blur_horizontal_pipeline: wgpu::RenderPipeline,
+    blur_vertical_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    targets: BloomTargets,
+    width: u32,
+    height: u32,
+}
+
+impl BloomPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        info: &TargetInfo,
+        settings: BloomSettings,
+    ) -> Result<Self, ShaderError> {
+        let uniform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            uniform_bind_group_layout = builder.build("Bloom Uniform Bind Group Layout");
+        }
+        let source_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_material();
+            source_bind_group_layout = builder.build("Bloom Source Bind Group Layout");
+        }
+
+        let bloom_uniform =
+            uniform::BloomUniform::new(settings.threshold, settings.intensity, settings.radius);
+        let uniform_buffer = uniform::make_uniform_buffer(
+            device,
+            "Bloom Uniform Buffer",
+            bytemuck::bytes_of(&bloom_uniform),
+        );
+        let uniform_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&uniform_bind_group_layout);
+            builder.add_buffer(&uniform_buffer);
+            uniform_bind_group = builder.build("Bloom Uniform Bind Group");
+        }
+
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Bloom Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        let layouts = [&*uniform_bind_group_layout, &*source_bind_group_layout];
+        let composite_layouts = [
+            &*uniform_bind_group_layout,
+            &*source_bind_group_layout,
+            &*source_bind_group_layout,
+        ];
+        let extract_pipeline = Self::build_pipeline(
+            device,
+            BLOOM_FORMAT,
+            "fs_extract",
+            &layouts,
+            "Bloom Extract Pipeline",
+        )?;
+        let blur_horizontal_pipeline = Self::build_pipeline(
+            device,
+            BLOOM_FORMAT,
+            "fs_blur_horizontal",
+            &layouts,
+            "Bloom Horizontal Blur Pipeline",
+        )?;
+        let blur_vertical_pipeline = Self::build_pipeline(
+            device,
+            BLOOM_FORMAT,
+            "fs_blur_vertical",
+            &layouts,
+            "Bloom Vertical Blur Pipeline",
+        )?;
+        let composite_pipeline = Self::build_pipeline(
+            device,
+            info.format,
+            "fs_composite",
+            &composite_layouts,
+            "Bloom Composite Pipeline",
+        )?;
+
+        let targets = Self::create_targets(device, info, &source_bind_group_layout, &sampler);
+        Ok(BloomPass {
+            settings: settings,
+            queue: queue.clone(),
+            uniform_buffer: uniform_buffer,
+            uniform_bind_group: uniform_bind_group,
+            source_bind_group_layout: source_bind_group_layout,
+            sampler: sampler,
+            extract_pipeline: extract_pipeline,
+            blur_horizontal_pipeline: blur_horizontal_pipeline,
+            blur_vertical_pipeline: blur_vertical_pipeline,
+            composite_pipeline: composite_pipeline,
+            targets: targets,
+            width: info.width,
+            height: info.height,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        fragment_entry: &str,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        label: &str,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", fragment_entry);
+        builder.set_pixel_format(format);
+        builder.set_cull_mode(None);
+        for layout in bind_group_layouts {
+            builder.add_bind_group_layout(layout);
+        }
+        builder.build_pipeline(label)
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        source_bind_group_layout: &bind_group_layout::Layout,
+        sampler: &wgpu::Sampler,
+    ) -> BloomTargets {
+        let source_bind_group = |texture: &Texture, label: &str| {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(source_bind_group_layout);
+            builder.add_material(&texture.view, sampler);
+            builder.build(label)
+        };
+
+        let input = Texture::create_sampled_target(
+            device,
+            info.width,
+            info.height,
+            info.format,
+            "Bloom Input Texture",
+        );
+        let (half_width, half_height) = ((info.width / 2).max(1), (info.height / 2).max(1));
+        let bright = Texture::create_sampled_target(
+            device,
+            half_width,
+            half_height,
+            BLOOM_FORMAT,
+            "Bloom Bright Texture",
+        );
+        let blurred = Texture::create_sampled_target(
+            device,
+            half_width,
+            half_height,
+            BLOOM_FORMAT,
+            "Bloom Blurred Texture",
+        );
+        BloomTargets {
+            input_bind_group: source_bind_group(&input, "Bloom Input Bind Group"),
+            bright_bind_group: source_bind_group(&bright, "Bloom Bright Bind Group"),
+            blurred_bind_group: source_bind_group(&blurred, "Bloom Blurred Bind Group"),
+            input: input,
+            bright: bright,
+            blurred: blurred,
+        }
+    }
+
+    pub fn settings(&self) -> BloomSettings {
+        self.settings
+    }
+
+    /// The radius is clamped to `0..=MAX_BLUR_RADIUS`.
+    pub fn set_settings(&mut self, settings: BloomSettings) {
+        self.settings = BloomSettings {
+            radius: settings.radius.clamp(0.0, MAX_BLUR_RADIUS),
+            ..settings
+        };
+        let bloom_uniform = uniform::BloomUniform::new(
+            self.settings.threshold,
+            self.settings.intensity,
+            self.settings.radius,
+        );
+        self.queue
+            .write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&bloom_uniform));
+    }
+
+    /// Every pixel of `view` gets written, so there's nothing to load.
+    fn draw(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        label: &str,
+        pipeline: &wgpu::RenderPipeline,
+        view: &wgpu::TextureView,
+        sources: &[&wgpu::BindGroup],
+    ) {
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some(label),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        for (i, source) in sources.iter().enumerate() {
+            renderpass.set_bind_group(i as u32 + 1, *source, &[]);
+        }
+        renderpass.draw(0..3, 0..1);
+    }
+}
+
+impl RenderPass for BloomPass {
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        let bloom = &self.targets;
+        self.draw(
+            command_encoder,
+            "Bloom Extract Pass",
+            &self.extract_pipeline,
+            &bloom.bright.view,
+            &[&bloom.input_bind_group],
+        );
+        self.draw(
+            command_encoder,
+            "Bloom Horizontal Blur Pass",
+            &self.blur_horizontal_pipeline,
+            &bloom.blurred.view,
+            &[&bloom.bright_bind_group],
+        );
+        self.draw(
+            command_encoder,
+            "Bloom Vertical Blur Pass",
+            &self.blur_vertical_pipeline,
+            &bloom.bright.view,
+            &[&bloom.blurred_bind_group],
+        );
+        self.draw(
+            command_encoder,
+            "Bloom Composite Pass",
+            &self.composite_pipeline,
+            targets.surface,
+            &[&bloom.input_bind_group, &bloom.bright_bind_group],
+        );
+    }
+
+    fn input(&self) -> Option<&wgpu::TextureView> {
+        Some(&self.targets.input.view)
+    }
+
+    /// The target format never changes, so only the textures have to
+    /// follow.
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        if (info.width, info.height) == (self.width, self.height) {
+            return;
+        }
+        self.targets =
+            Self::create_targets(device, info, &self.source_bind_group_layout, &self.sampler);
+        self.width = info.width;
+        self.height = info.height;
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::BTreeMap;
+use std::env::current_exe;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use glm::*;
+use serde::{de, Deserialize, Serialize};
+
+use crate::platform::Key;
+
+pub const FILENAME: &str = "camera_bookmarks.toml";
+pub const SLOTS: usize = 9;
+
+/// A viewpoint to come back to: where the camera was, which way the
+/// controller had it looking and how wide.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Bookmark {
+    #[serde(with = "vec3_list")]
+    pub position: Vec3,
+    /// Degrees, as `CameraController` turns them.
+    pub yaw: f32,
+    pub pitch: f32,
+    pub fov_y: f32,
+}
+
+impl Bookmark {
+    /// `t` of the way from `self` to `other`. Yaw turns the short way round.
+    pub fn lerp(&self, other: &Bookmark, t: f32) -> Bookmark {
+        let mut yaw_turn = (other.yaw - self.yaw).rem_euclid(360.0);
+        if yaw_turn > 180.0 {
+            yaw_turn -= 360.0;
+        }
+        Bookmark {
+            position: self.position + (other.position - self.position) * t,
+            yaw: self.yaw + yaw_turn * t,
+            pitch: self.pitch + (other.pitch - self.pitch) * t,
+            fov_y: self.fov_y + (other.fov_y - self.fov_y) * t,
+        }
+    }
+}
+
+/// The slot Ctrl+`key` saves to and `key` restores, for the number keys
+/// 1 to 9.
+pub fn slot_for_key(key: Key) -> Option<usize> {
+    let keys = [
+        Key::Num1,
+        Key::Num2,
+        Key::Num3,
+        Key::Num4,
+        Key::Num5,
+        Key::Num6,
+        Key::Num7,
+        Key::Num8,
+        Key::Num9,
+    ];
+    keys.iter().position(|&slot_key| slot_key == key)
+}
+
+/// Bookmarks saved with Ctrl+1 to Ctrl+9, kept in `FILENAME` next to the
+/// executable so they outlast the app.
+pub struct CameraBookmarks {
+    path: PathBuf,
+    slots: [Option<Bookmark>; SLOTS],
+}
+
+impl CameraBookmarks {
+    /// Loads the bookmarks saved last time. A file that's missing or can't
+    /// be read leaves every slot empty; it's overwritten by the next save.
+    pub fn load() -> Self {
+        let path = current_exe()
+            .ok()
+            .and_then(|exe| exe.parent().map(|dir| dir.join(FILENAME)))
+            .unwrap_or_else(|| PathBuf::from(FILENAME));
+        let slots = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text).unwrap_or_else(|e| {
+                eprintln!("Ignoring camera bookmarks in {}: {}", path.display(), e);
+                [None; SLOTS]
+            }),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => [None; SLOTS],
+            Err(e) => {
+                eprintln!("Can't read {}: {}", path.display(), e);
+                [None; SLOTS]
+            }
+        };
+        CameraBookmarks {
+            path: path,
+            slots: slots,
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn get(&self, slot: usize) -> Option<Bookmark> {
+        self.slots.get(slot).copied().flatten()
+    }
+
+    /// Puts `bookmark` in `slot` and writes all of them out.
+    pub fn set(&mut self, slot: usize, bookmark: Bookmark) -> io::Result<()> {
+        self.slots[slot] = Some(bookmark);
+        fs::write(&self.path, format(&self.slots))
+    }
+}
+
+/// The file: a table per filled slot, named by its number from 1 like the
+/// keys.
+type BookmarkFile = BTreeMap<String, Bookmark>;
+
+const HEADER: &str = "# Camera bookmarks, saved with Ctrl+1 to Ctrl+9.\n\n";
+
+/// The bookmarks as `FILENAME` holds them.
+pub fn format(slots: &[Option<Bookmark>; SLOTS]) -> String {
+    let file: BookmarkFile = slots
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, bookmark)| Some(((slot + 1).to_string(), (*bookmark)?)))
+        .collect();
+    let text = toml::to_string(&file).expect("bookmarks are plain numbers");
+    HEADER.to_string() + &text
+}
+
+/// Reads what `format` writes. Anything else in the file is an error, so
+/// a damaged file is noticed rather than half loaded.
+pub fn parse(text: &str) -> Result<[Option<Bookmark>; SLOTS], toml::de::Error> {
+    let file: BookmarkFile = toml::from_str(text)?;
+    let mut slots = [None; SLOTS];
+    for (name, bookmark) in file {
+        let slot = name
+            .parse::<usize>()
+            .ok()
+            .filter(|slot| (1..=SLOTS).contains(slot))
+            .ok_or_else(|| de::Error::custom(format!("no bookmark slot [{}]", name)))?;
+        let Bookmark {
+            position: p,
+            yaw,
+            pitch,
+            fov_y,
+        } = bookmark;
+        if ![p.x, p.y, p.z, yaw, pitch, fov_y]
+            .iter()
+            .all(|n| n.is_finite())
+        {
+            return Err(de::Error::custom(format!(
+                "bookmark [{}] isn't finite",
+                name
+            )));
+        }
+        slots[slot - 1] = Some(bookmark);
+    }
+    Ok(slots)
+}
+
+/// Writes a `Vec3` as a list of three numbers.
+mod vec3_list {
+    use glm::{vec3, Vec3};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S: Serializer>(v: &Vec3, serializer: S) -> Result<S::Ok, S::Error> {
+        [v.x, v.y, v.z].serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec3, D::Error> {
+        let [x, y, z] = <[f32; 3]>::deserialize(deserializer)?;
+        Ok(vec3(x, y, z))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slots() -> [Option<Bookmark>; SLOTS] {
+        let mut slots = [None; SLOTS];
+        slots[0] = Some(Bookmark {
+            position: vec3(1.5, -2.0, 0.1),
+            yaw: -90.0,
+            pitch: 12.5,
+            fov_y: 45.0,
+        });
+        slots[8] = Some(Bookmark {
+            position: vec3(0.0, 3.0, -7.25),
+            yaw: 170.0,
+            pitch: -30.0,
+            fov_y: 60.0,
+        });
+        slots
+    }
+
+    #[test]
+    fn bookmarks_survive_the_file() {
+        let slots = slots();
+        assert_eq!(parse(&format(&slots)).unwrap(), slots);
+        assert!(parse("[1]\nposition = [0, 0]\n").is_err());
+        let bookmark = "position = [0, 0, 0]\nyaw = 0\npitch = 0\nfov_y = 45\n";
+        assert!(parse(&format!("[9]\n{}", bookmark)).is_ok());
+        assert!(parse(&format!("[10]\n{}", bookmark)).is_err());
+        assert!(parse(&format!("[1]\n{}roll = 0\n", bookmark)).is_err());
+    }
+
+    /// From 170 to -170 is 20 degrees on, not 340 back.
+    #[test]
+    fn yaw_takes_the_short_way_round() {
+        let from = slots()[8].unwrap();
+        let halfway = from.lerp(
+            &Bookmark {
+                yaw: -170.0,
+                ..from
+            },
+            0.5,
+        );
+        assert!((halfway.yaw.rem_euclid(360.0) - 180.0).abs() < 1e-3);
+    }
+}
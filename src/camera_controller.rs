@@ -0,0 +1,134 @@
+use glm::*;
+
+use crate::camera_bookmarks::Bookmark;
+use crate::input::{Axis, InputState};
+use crate::renderer_backend::camera::Camera;
+
+/// A move from one viewpoint to another that `update_camera` plays out.
+struct Flight {
+    from: Bookmark,
+    to: Bookmark,
+    elapsed: f32,
+}
+
+pub struct CameraController {
+    speed: f32,
+    sensitivity: f32,
+    yaw: f32,
+    pitch: f32,
+    flight: Option<Flight>,
+}
+
+impl CameraController {
+    const MAX_PITCH: f32 = 89.0;
+    /// How long `fly_to` takes, in seconds.
+    const FLIGHT_TIME: f32 = 0.3;
+    /// What bookmarks of an orthographic camera keep as their fov.
+    const DEFAULT_FOV_Y: f32 = 45.0;
+    /// How much more of the world one line of scrolling out shows.
+    const ZOOM_STEP: f32 = 1.1;
+
+    pub fn new(speed: f32, sensitivity: f32) -> Self {
+        CameraController {
+            speed: speed,
+            sensitivity: sensitivity,
+            yaw: -90.0,
+            pitch: 0.0,
+            flight: None,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: f32) {
+        self.speed = speed;
+    }
+
+    /// Degrees turned per screen unit the cursor moves.
+    pub fn set_sensitivity(&mut self, sensitivity: f32) {
+        self.sensitivity = sensitivity;
+    }
+
+    /// Where `camera` is and which way this has it looking.
+    pub fn bookmark(&self, camera: &Camera) -> Bookmark {
+        Bookmark {
+            position: camera.position,
+            yaw: self.yaw,
+            pitch: self.pitch,
+            fov_y: camera.fov_y().unwrap_or(Self::DEFAULT_FOV_Y),
+        }
+    }
+
+    /// Moves `camera` to `bookmark` smoothly over the next `FLIGHT_TIME`,
+    /// turning by yaw and pitch rather than blending between directions.
+    pub fn fly_to(&mut self, camera: &Camera, bookmark: Bookmark) {
+        self.flight = Some(Flight {
+            from: self.bookmark(camera),
+            to: Bookmark {
+                pitch: bookmark.pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH),
+                ..bookmark
+            },
+            elapsed: 0.0,
+        });
+    }
+
+    fn forward(&self) -> Vec3 {
+        let (yaw, pitch) = (radians(self.yaw), radians(self.pitch));
+        vec3(
+            yaw.cos() * pitch.cos(),
+            pitch.sin(),
+            yaw.sin() * pitch.cos(),
+        )
+    }
+
+    /// Moves and turns `camera` by the movement and look inputs, turning it
+    /// by the frame's cursor movement too when `mouse_look` is set. Input
+    /// waits while a `fly_to` is under way.
+    pub fn update_camera(
+        &mut self,
+        camera: &mut Camera,
+        input: &InputState,
+        dt: f32,
+        mouse_look: bool,
+    ) {
+        if let Some(flight) = &mut self.flight {
+            flight.elapsed += dt;
+            let t = (flight.elapsed / Self::FLIGHT_TIME).min(1.0);
+            let at = flight.from.lerp(&flight.to, smoothstep(0.0, 1.0, t));
+            if t >= 1.0 {
+                self.flight = None;
+            }
+            self.yaw = at.yaw;
+            self.pitch = at.pitch;
+            camera.position = at.position;
+            camera.set_fov_y(at.fov_y);
+            camera.target = camera.position + self.forward();
+            return;
+        }
+
+        let (dx, dy) = input.look_delta(mouse_look, dt);
+        self.yaw += dx as f32 * self.sensitivity;
+        self.pitch -= dy as f32 * self.sensitivity;
+        self.pitch = self.pitch.clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+
+        let forward = self.forward();
+        let right = normalize(cross(forward, camera.up));
+
+        let mut direction = right * input.axis(Axis::MoveRight)
+            + forward * input.axis(Axis::MoveForward)
+            + camera.up * input.axis(Axis::MoveUp);
+        // A half tilted stick moves at half speed; two keys no faster than one.
+        if length(direction) > 1.0 {
+            direction = normalize(direction);
+        }
+        camera.position = camera.position + direction * (self.speed * dt);
+        camera.target = camera.position + forward;
+
+        let scroll = input.scroll_delta();
+        if scroll != 0.0 {
+            camera.zoom(Self::ZOOM_STEP.powf(-scroll as f32));
+        }
+    }
+}
@@ -0,0 +1,83 @@
+use std::fmt;
+
+pub const ARG_NAME: &str = "--clear-color";
+pub const ENV_NAME: &str = "CLEAR_COLOR";
+
+/// Seconds for the animated clear color to go once around the hue wheel.
+const HUE_PERIOD: f32 = 6.0;
+
+#[derive(Debug)]
+pub enum ColorParseError {
+    ComponentCount { value: String, count: usize },
+    InvalidComponent { value: String, component: String },
+    OutOfRange { value: String, component: f64 },
+}
+
+impl fmt::Display for ColorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorParseError::ComponentCount { value, count } => write!(
+                f,
+                "clear color \"{}\" has {} components, expected 3 (r,g,b) or 4 (r,g,b,a)",
+                value, count
+            ),
+            ColorParseError::InvalidComponent { value, component } => write!(
+                f,
+                "clear color \"{}\": \"{}\" is not a number",
+                value, component
+            ),
+            ColorParseError::OutOfRange { value, component } => write!(
+                f,
+                "clear color \"{}\": {} is outside 0.0..=1.0",
+                value, component
+            ),
+        }
+    }
+}
+
+impl std::error::Error for ColorParseError {}
+
+/// Parses `r,g,b` or `r,g,b,a` with every component in `0.0..=1.0`.
+pub fn parse(value: &str) -> Result<wgpu::Color, ColorParseError> {
+    let mut components = Vec::new();
+    for component in value.split(',').map(str::trim) {
+        let parsed: f64 = component
+            .parse()
+            .map_err(|_| ColorParseError::InvalidComponent {
+                value: value.to_string(),
+                component: component.to_string(),
+            })?;
+        if !(0.0..=1.0).contains(&parsed) {
+            return Err(ColorParseError::OutOfRange {
+                value: value.to_string(),
+                component: parsed,
+            });
+        }
+        components.push(parsed);
+    }
+
+    match components[..] {
+        [r, g, b] => Ok(wgpu::Color { r, g, b, a: 1.0 }),
+        [r, g, b, a] => Ok(wgpu::Color { r, g, b, a }),
+        _ => Err(ColorParseError::ComponentCount {
+            value: value.to_string(),
+            count: components.len(),
+        }),
+    }
+}
+
+/// A fully saturated color whose hue goes around the wheel every
+/// `HUE_PERIOD` seconds.
+pub fn hue_cycle(time: f32) -> wgpu::Color {
+    let hue = (time / HUE_PERIOD).fract() * 6.0;
+    let x = (1.0 - (hue % 2.0 - 1.0).abs()) as f64;
+    let (r, g, b) = match hue as u32 {
+        0 => (1.0, x, 0.0),
+        1 => (x, 1.0, 0.0),
+        2 => (0.0, 1.0, x),
+        3 => (0.0, x, 1.0),
+        4 => (x, 0.0, 1.0),
+        _ => (1.0, 0.0, x),
+    };
+    wgpu::Color { r, g, b, a: 1.0 }
+}
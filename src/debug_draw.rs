@@ -0,0 +1,273 @@
+use glm::*;
+
+use crate::renderer_backend::{
+    bind_group, bind_group_layout,
+    camera::Camera,
+    color::ColorSpace,
+    frame_uploader::FrameUploader,
+    frustum::{Aabb, Frustum},
+    mesh_builder, pipeline,
+    render_pass::{FrameTargets, TargetInfo},
+    shader::ShaderError,
+    uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/debug_lines.wgsl";
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 3],
+}
+
+impl LineVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+
+    fn layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<LineVertex>() as wgpu::BufferAddress,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Which of the renderer's own debug lines are drawn.
+#[derive(Clone, Copy, Default, PartialEq, Debug)]
+pub struct DebugViews {
+    /// Every object's bounds, green when drawn and red when culled.
+    pub bounds: bool,
+    pub world_axes: bool,
+    /// The frustum culling is frozen to, see `toggle_frustum_freeze`.
+    pub cull_frustum: bool,
+    /// The shadow map, in a corner of the frame.
+    pub shadow_map: bool,
+}
+
+/// Lines added from anywhere during a frame, in world space, and drawn
+/// after the scene. They last one frame: `prepare` uploads the ones added
+/// so far and starts the next frame's list.
+pub struct DebugDraw {
+    vertices: Vec<LineVertex>,
+    /// What `prepare` uploaded, for `record` to draw.
+    vertex_count: u32,
+    vertex_buffer: Option<wgpu::Buffer>,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group_layout: bind_group_layout::Layout,
+    uniform_bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    colors: ColorSpace,
+    depth_test: bool,
+}
+
+impl DebugDraw {
+    pub fn new(device: &wgpu::Device, info: &TargetInfo) -> Result<Self, ShaderError> {
+        let uniform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            uniform_bind_group_layout = builder.build("Debug Line Uniform Bind Group Layout");
+        }
+        let camera_uniform = uniform::CameraUniform::new(&uniform::identity());
+        let uniform_buffer = uniform::make_uniform_buffer(
+            device,
+            "Debug Line Uniform Buffer",
+            bytemuck::bytes_of(&camera_uniform),
+        );
+        let uniform_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&uniform_bind_group_layout);
+            builder.add_buffer(&uniform_buffer);
+            uniform_bind_group = builder.build("Debug Line Uniform Bind Group");
+        }
+        let depth_test = true;
+        let pipeline = Self::build_pipeline(device, info, &uniform_bind_group_layout, depth_test)?;
+        Ok(DebugDraw {
+            vertices: Vec::new(),
+            vertex_count: 0,
+            vertex_buffer: None,
+            uniform_buffer: uniform_buffer,
+            uniform_bind_group_layout: uniform_bind_group_layout,
+            uniform_bind_group: uniform_bind_group,
+            pipeline: pipeline,
+            depth_format: info.depth_format,
+            sample_count: info.sample_count,
+            colors: info.colors,
+            depth_test: depth_test,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        depth_test: bool,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(info.format);
+        builder.set_depth_format(info.depth_format);
+        builder.set_sample_count(info.sample_count);
+        builder.set_topology(wgpu::PrimitiveTopology::LineList, None);
+        builder.set_cull_mode(None);
+        match depth_test {
+            true => builder.set_depth_test(false, wgpu::CompareFunction::LessEqual),
+            false => builder.set_depth_test(false, wgpu::CompareFunction::Always),
+        }
+        builder.add_vertex_buffer_layout(LineVertex::layout());
+        builder.add_bind_group_layout(uniform_bind_group_layout);
+        builder.build_pipeline("Debug Line Pipeline")
+    }
+
+    pub fn depth_test(&self) -> bool {
+        self.depth_test
+    }
+
+    /// Without the depth test the lines show through everything.
+    pub fn set_depth_test(&mut self, device: &wgpu::Device, info: &TargetInfo, depth_test: bool) {
+        match Self::build_pipeline(device, info, &self.uniform_bind_group_layout, depth_test) {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.depth_test = depth_test;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// `color` is authored like other colors in code; see `ColorSpace`.
+    pub fn line(&mut self, a: Vec3, b: Vec3, color: Vec3) {
+        let color = *self.colors.to_linear(color).as_array();
+        self.vertices.extend([
+            LineVertex {
+                position: *a.as_array(),
+                color: color,
+            },
+            LineVertex {
+                position: *b.as_array(),
+                color: color,
+            },
+        ]);
+    }
+
+    /// The twelve edges of `aabb` placed in the world by `model`.
+    pub fn aabb(&mut self, aabb: &Aabb, model: &Mat4, color: Vec3) {
+        let corners: [Vec3; 8] = std::array::from_fn(|i| {
+            let pick = |bit: usize, axis: usize| match i & bit {
+                0 => aabb.min[axis],
+                _ => aabb.max[axis],
+            };
+            let corner = vec3(pick(1, 0), pick(2, 1), pick(4, 2));
+            (*model * corner.extend(1.0)).truncate(3)
+        });
+        self.box_edges(&corners, color);
+    }
+
+    /// `model`'s X, Y and Z axes from its origin, `length` long before it
+    /// scales them, in red, green and blue.
+    pub fn axes(&mut self, model: &Mat4, length: f32) {
+        let origin = (*model * vec4(0.0, 0.0, 0.0, 1.0)).truncate(3);
+        for axis in [
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        ] {
+            let end = (*model * (axis * length).extend(1.0)).truncate(3);
+            self.line(origin, end, axis);
+        }
+    }
+
+    /// The edges of `frustum`, to see it from another camera.
+    pub fn frustum(&mut self, frustum: &Frustum, color: Vec3) {
+        let [n0, n1, n2, n3, f0, f1, f2, f3] = frustum.corners();
+        // `box_edges` wants the corners indexed by bits, x first.
+        self.box_edges(&[n0, n1, n3, n2, f0, f1, f3, f2], color);
+    }
+
+    /// Connects each corner to the three whose index differs in one bit.
+    fn box_edges(&mut self, corners: &[Vec3; 8], color: Vec3) {
+        for i in 0..8 {
+            for bit in [1, 2, 4] {
+                if i & bit == 0 {
+                    self.line(corners[i], corners[i | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Uploads the lines added since the last call for `record` to draw,
+    /// seen from `camera`, and clears them.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut FrameUploader,
+        camera: &Camera,
+    ) {
+        self.vertex_count = self.vertices.len() as u32;
+        if self.vertices.is_empty() {
+            return;
+        }
+        let camera_uniform = uniform::CameraUniform::new(&camera.build_view_projection());
+        uploader.write(&self.uniform_buffer, 0, &[camera_uniform]);
+        let bytes: &[u8] = bytemuck::cast_slice(&self.vertices);
+        mesh_builder::grow_buffer(
+            device,
+            &mut self.vertex_buffer,
+            wgpu::BufferUsages::VERTEX,
+            "Debug Line Vertex Buffer",
+            bytes.len() as u64,
+        );
+        if let Some(vertex_buffer) = &self.vertex_buffer {
+            uploader.write(vertex_buffer, 0, &self.vertices);
+        }
+        self.vertices.clear();
+    }
+
+    /// Draws the lines `prepare` uploaded over the frame so far.
+    pub fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        let Some(vertex_buffer) = self
+            .vertex_buffer
+            .as_ref()
+            .filter(|_| self.vertex_count > 0)
+        else {
+            return;
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Debug Line Pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        targets.apply_viewport(&mut renderpass);
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        renderpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        renderpass.draw(0..self.vertex_count, 0..1);
+    }
+
+    pub fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        self.colors = info.colors;
+        if info.sample_count == self.sample_count && info.depth_format == self.depth_format {
+            return;
+        }
+        match Self::build_pipeline(
+            device,
+            info,
+            &self.uniform_bind_group_layout,
+            self.depth_test,
+        ) {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.depth_format = info.depth_format;
+                self.sample_count = info.sample_count;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,406 @@
+use web_time::Instant;
+
+use crate::platform::{Action, Key, Modifiers, MouseButton, WindowEvent};
+
+use crate::bloom_pass::{self, BloomSettings};
+use crate::grid_pass::GridSettings;
+use crate::post_pass::PostEffect;
+use crate::renderer_backend::{mesh_builder, sampler::SamplerDesc};
+use crate::shadow_pass::{self, ShadowSettings};
+use crate::window_surface::WindowMetrics;
+
+/// Values the debug window edits. Filled from the app state before the UI
+/// runs and written back afterwards, so the UI closure borrows nothing else.
+pub struct DebugSettings {
+    pub adapter_name: String,
+    pub frame_ms: f32,
+    pub fps: f32,
+    pub clear_color: [f32; 3],
+    pub animate_clear_color: bool,
+    pub camera_speed: f32,
+    pub light_direction: [f32; 3],
+    pub light_color: [f32; 3],
+    pub shadow_settings: ShadowSettings,
+    pub wireframe: bool,
+    pub depth_prepass: bool,
+    pub stencil: bool,
+    pub indirect: bool,
+    pub gpu_cull: bool,
+    pub skybox: bool,
+    pub grid: bool,
+    pub grid_settings: GridSettings,
+    pub wave: bool,
+    pub wave_resolution: u32,
+    pub srgb_colors: bool,
+    pub msaa: bool,
+    pub sampler: SamplerDesc,
+    pub bloom: bool,
+    pub bloom_settings: BloomSettings,
+    pub post_effect: PostEffect,
+    pub present_mode: wgpu::PresentMode,
+}
+
+impl DebugSettings {
+    fn show(&mut self, context: &egui::Context) {
+        egui::Window::new("Debug").show(context, |ui| {
+            ui.label(format!("{:.1} ms / {:.0} fps", self.frame_ms, self.fps));
+            ui.label(self.adapter_name.as_str());
+            ui.separator();
+
+            ui.horizontal(|ui| {
+                ui.label("Clear color");
+                ui.color_edit_button_rgb(&mut self.clear_color);
+            });
+            ui.checkbox(&mut self.animate_clear_color, "Animate clear color");
+            ui.add(egui::Slider::new(&mut self.camera_speed, 0.1..=20.0).text("Camera speed"));
+            ui.horizontal(|ui| {
+                ui.label("Light direction");
+                for component in &mut self.light_direction {
+                    ui.add(
+                        egui::DragValue::new(component)
+                            .speed(0.01)
+                            .range(-1.0..=1.0),
+                    );
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("Light color");
+                ui.color_edit_button_rgb(&mut self.light_color);
+            });
+            let shadows = &mut self.shadow_settings;
+            ui.checkbox(&mut shadows.enabled, "Shadows");
+            ui.add(
+                egui::Slider::new(&mut shadows.bias, 0.0..=shadow_pass::MAX_BIAS)
+                    .logarithmic(true)
+                    .text("Shadow bias"),
+            );
+            egui::ComboBox::from_label("Shadow map")
+                .selected_text(format!("{0}x{0}", shadows.resolution))
+                .show_ui(ui, |ui| {
+                    for resolution in [512, 1024, 2048, 4096] {
+                        ui.selectable_value(
+                            &mut shadows.resolution,
+                            resolution,
+                            format!("{0}x{0}", resolution),
+                        );
+                    }
+                });
+            ui.checkbox(&mut self.wireframe, "Wireframe");
+            ui.checkbox(&mut self.depth_prepass, "Depth pre-pass");
+            ui.checkbox(&mut self.stencil, "Stencil buffer");
+            ui.checkbox(&mut self.indirect, "Indirect draws");
+            ui.checkbox(&mut self.gpu_cull, "GPU culling");
+            ui.checkbox(&mut self.skybox, "Skybox");
+            ui.checkbox(&mut self.wave, "Sine wave");
+            ui.add(
+                egui::Slider::new(
+                    &mut self.wave_resolution,
+                    1..=mesh_builder::MAX_WAVE_RESOLUTION,
+                )
+                .text("Wave resolution"),
+            );
+            ui.checkbox(&mut self.srgb_colors, "sRGB-authored colors");
+            ui.checkbox(&mut self.msaa, "4x MSAA");
+
+            let address_mode = self.sampler.address_mode;
+            egui::ComboBox::from_label("Sampler")
+                .selected_text(self.sampler.preset_name())
+                .show_ui(ui, |ui| {
+                    for (name, preset) in SamplerDesc::PRESETS {
+                        let preset = SamplerDesc {
+                            address_mode: address_mode,
+                            ..preset
+                        };
+                        ui.selectable_value(&mut self.sampler, preset, name);
+                    }
+                });
+            egui::ComboBox::from_label("Address mode")
+                .selected_text(format!("{:?}", self.sampler.address_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        wgpu::AddressMode::Repeat,
+                        wgpu::AddressMode::ClampToEdge,
+                        wgpu::AddressMode::MirrorRepeat,
+                    ] {
+                        ui.selectable_value(
+                            &mut self.sampler.address_mode,
+                            mode,
+                            format!("{:?}", mode),
+                        );
+                    }
+                });
+            ui.checkbox(&mut self.grid, "Grid");
+            let grid = &mut self.grid_settings;
+            ui.add(egui::Slider::new(&mut grid.spacing, 0.1..=10.0).text("Grid spacing"));
+            ui.add(egui::Slider::new(&mut grid.major_every, 1..=20).text("Grid major every"));
+            ui.add(
+                egui::Slider::new(&mut grid.fade_distance, 5.0..=200.0).text("Grid fade distance"),
+            );
+            ui.horizontal(|ui| {
+                ui.label("Grid colors");
+                for color in [&mut grid.minor_color, &mut grid.major_color] {
+                    let mut rgba =
+                        [color.r, color.g, color.b, color.a].map(|channel| channel as f32);
+                    if ui.color_edit_button_rgba_unmultiplied(&mut rgba).changed() {
+                        let [r, g, b, a] = rgba.map(|channel| channel as f64);
+                        *color = wgpu::Color {
+                            r: r,
+                            g: g,
+                            b: b,
+                            a: a,
+                        };
+                    }
+                }
+            });
+            ui.checkbox(&mut self.bloom, "Bloom");
+            let bloom = &mut self.bloom_settings;
+            ui.add(egui::Slider::new(&mut bloom.threshold, 0.0..=2.0).text("Bloom threshold"));
+            ui.add(egui::Slider::new(&mut bloom.intensity, 0.0..=4.0).text("Bloom intensity"));
+            ui.add(
+                egui::Slider::new(&mut bloom.radius, 0.0..=bloom_pass::MAX_BLUR_RADIUS)
+                    .text("Bloom radius"),
+            );
+            egui::ComboBox::from_label("Post effect")
+                .selected_text(self.post_effect.name())
+                .show_ui(ui, |ui| {
+                    for effect in PostEffect::ALL {
+                        ui.selectable_value(&mut self.post_effect, effect, effect.name());
+                    }
+                });
+
+            egui::ComboBox::from_label("Present mode")
+                .selected_text(format!("{:?}", self.present_mode))
+                .show_ui(ui, |ui| {
+                    for mode in [
+                        wgpu::PresentMode::Fifo,
+                        wgpu::PresentMode::Mailbox,
+                        wgpu::PresentMode::Immediate,
+                    ] {
+                        ui.selectable_value(&mut self.present_mode, mode, format!("{:?}", mode));
+                    }
+                });
+        });
+    }
+}
+
+/// An egui overlay fed from GLFW events and painted with egui-wgpu on top of
+/// the finished frame.
+pub struct DebugUi {
+    context: egui::Context,
+    renderer: egui_wgpu::Renderer,
+    events: Vec<egui::Event>,
+    modifiers: egui::Modifiers,
+    pointer_position: egui::Pos2,
+    pixels_per_point: f32,
+    start: Instant,
+    pub visible: bool,
+}
+
+impl DebugUi {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat, pixels_per_point: f32) -> Self {
+        DebugUi {
+            context: egui::Context::default(),
+            renderer: egui_wgpu::Renderer::new(device, format, None, 1, false),
+            events: Vec::new(),
+            modifiers: egui::Modifiers::default(),
+            pointer_position: egui::Pos2::ZERO,
+            pixels_per_point: pixels_per_point,
+            start: Instant::now(),
+            visible: true,
+        }
+    }
+
+    /// Follows the window's content scale so the UI stays crisp on high-DPI
+    /// displays.
+    pub fn set_pixels_per_point(&mut self, pixels_per_point: f32) {
+        self.pixels_per_point = pixels_per_point;
+    }
+
+    /// Queues `event` for the next frame, with cursor positions converted
+    /// to pixels through `metrics`. Returns true if egui wants
+    /// the event for itself, in which case it shouldn't drive the camera or
+    /// trigger hotkeys.
+    pub fn handle_event(&mut self, event: &WindowEvent, metrics: &WindowMetrics) -> bool {
+        if !self.visible {
+            return false;
+        }
+
+        match *event {
+            WindowEvent::CursorPos(x, y) => {
+                let (x, y) = metrics.cursor_to_pixels(x, y);
+                self.pointer_position =
+                    egui::pos2(x / self.pixels_per_point, y / self.pixels_per_point);
+                self.events
+                    .push(egui::Event::PointerMoved(self.pointer_position));
+                false
+            }
+            WindowEvent::MouseButton(button, action, modifiers) => {
+                self.modifiers = translate_modifiers(modifiers);
+                let button = match button {
+                    MouseButton::Left => egui::PointerButton::Primary,
+                    MouseButton::Right => egui::PointerButton::Secondary,
+                    MouseButton::Middle => egui::PointerButton::Middle,
+                    _ => return false,
+                };
+                self.events.push(egui::Event::PointerButton {
+                    pos: self.pointer_position,
+                    button: button,
+                    pressed: action == Action::Press,
+                    modifiers: self.modifiers,
+                });
+                // Releases always go through so buttons never get stuck down.
+                action == Action::Press && self.context.wants_pointer_input()
+            }
+            WindowEvent::Scroll(x, y) => {
+                self.events.push(egui::Event::MouseWheel {
+                    unit: egui::MouseWheelUnit::Line,
+                    delta: egui::vec2(x as f32, y as f32),
+                    modifiers: self.modifiers,
+                });
+                self.context.wants_pointer_input()
+            }
+            WindowEvent::Char(character) => {
+                if self.context.wants_keyboard_input() {
+                    self.events.push(egui::Event::Text(character.to_string()));
+                    return true;
+                }
+                false
+            }
+            WindowEvent::Key(key, action, modifiers) => {
+                self.modifiers = translate_modifiers(modifiers);
+                if let Some(key) = translate_key(key) {
+                    self.events.push(egui::Event::Key {
+                        key: key,
+                        physical_key: None,
+                        pressed: action != Action::Release,
+                        repeat: action == Action::Repeat,
+                        modifiers: self.modifiers,
+                    });
+                }
+                action != Action::Release && self.context.wants_keyboard_input()
+            }
+            _ => false,
+        }
+    }
+
+    /// Runs the UI for one frame of a `size_in_pixels` framebuffer.
+    pub fn run(
+        &mut self,
+        size_in_pixels: [u32; 2],
+        settings: &mut DebugSettings,
+    ) -> egui::FullOutput {
+        let size_in_points = egui::vec2(
+            size_in_pixels[0] as f32 / self.pixels_per_point,
+            size_in_pixels[1] as f32 / self.pixels_per_point,
+        );
+        let raw_input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, size_in_points)),
+            time: Some(self.start.elapsed().as_secs_f64()),
+            modifiers: self.modifiers,
+            events: std::mem::take(&mut self.events),
+            ..Default::default()
+        };
+
+        self.context.set_pixels_per_point(self.pixels_per_point);
+        let visible = self.visible;
+        self.context.run(raw_input, |context| {
+            if visible {
+                settings.show(context);
+            }
+        })
+    }
+
+    /// Paints `output` over `view` with a load-op pass. Returns the
+    /// command buffers egui-wgpu recorded for its own uploads, which must be
+    /// submitted before `command_encoder`.
+    pub fn paint(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        command_encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        size_in_pixels: [u32; 2],
+        output: egui::FullOutput,
+    ) -> Vec<wgpu::CommandBuffer> {
+        for (id, image_delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+        }
+
+        let paint_jobs = self
+            .context
+            .tessellate(output.shapes, output.pixels_per_point);
+        let screen_descriptor = egui_wgpu::ScreenDescriptor {
+            size_in_pixels: size_in_pixels,
+            pixels_per_point: output.pixels_per_point,
+        };
+        let command_buffers = self.renderer.update_buffers(
+            device,
+            queue,
+            command_encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+
+        {
+            let render_pass_descriptor = wgpu::RenderPassDescriptor {
+                label: Some("Debug UI Pass"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Load,
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            };
+            let mut renderpass = command_encoder
+                .begin_render_pass(&render_pass_descriptor)
+                .forget_lifetime();
+            self.renderer
+                .render(&mut renderpass, &paint_jobs, &screen_descriptor);
+        }
+
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+        command_buffers
+    }
+}
+
+fn translate_modifiers(modifiers: Modifiers) -> egui::Modifiers {
+    egui::Modifiers {
+        alt: modifiers.alt,
+        ctrl: modifiers.control,
+        shift: modifiers.shift,
+        mac_cmd: false,
+        command: modifiers.control,
+    }
+}
+
+/// The keys egui's text fields and widgets need to be usable.
+fn translate_key(key: Key) -> Option<egui::Key> {
+    let key = match key {
+        Key::Left => egui::Key::ArrowLeft,
+        Key::Right => egui::Key::ArrowRight,
+        Key::Up => egui::Key::ArrowUp,
+        Key::Down => egui::Key::ArrowDown,
+        Key::Backspace => egui::Key::Backspace,
+        Key::Delete => egui::Key::Delete,
+        Key::Enter | Key::KpEnter => egui::Key::Enter,
+        Key::Tab => egui::Key::Tab,
+        Key::Escape => egui::Key::Escape,
+        Key::Home => egui::Key::Home,
+        Key::End => egui::Key::End,
+        Key::A => egui::Key::A,
+        Key::C => egui::Key::C,
+        Key::V => egui::Key::V,
+        Key::X => egui::Key::X,
+        Key::Z => egui::Key::Z,
+        _ => return None,
+    };
+    Some(key)
+}
@@ -0,0 +1,88 @@
+use std::sync::Arc;
+
+use crate::gpu_context::GpuContext;
+use crate::platform::{Action, Key, Window, WindowEvent};
+use crate::renderer::{Renderer, SceneView};
+use crate::renderer_backend::{camera::Camera, error::RendererError};
+use crate::window_surface::WindowSurface;
+
+/// A second window looking down at the scene from a fixed camera above the
+/// grid, drawn with the main window's renderer.
+pub struct DebugView {
+    pub surface: WindowSurface,
+    view: SceneView,
+}
+
+impl DebugView {
+    pub fn new(surface: WindowSurface, renderer: &Renderer) -> Self {
+        let (width, height) = (surface.config.width, surface.config.height);
+        let mut camera = Camera::new(width as f32 / height as f32);
+        camera.position = glm::vec3(0.0, 20.0, 10.0);
+        camera.target = glm::vec3(0.0, -1.5, -15.0);
+        DebugView {
+            view: renderer.create_view(camera, width, height),
+            surface: surface,
+        }
+    }
+
+    /// Moves onto `gpu`'s device, which `renderer` has been recreated on.
+    pub fn recreate(
+        &mut self,
+        gpu: Arc<GpuContext>,
+        renderer: &Renderer,
+    ) -> Result<(), RendererError> {
+        self.surface.set_gpu(gpu)?;
+        let (width, height) = (self.surface.config.width, self.surface.config.height);
+        self.view = renderer.create_view(self.view.camera.clone(), width, height);
+        Ok(())
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.surface.is_open()
+    }
+
+    pub fn handle_event(&mut self, renderer: &Renderer, event: &WindowEvent) {
+        match *event {
+            WindowEvent::FramebufferSize(width, height) => self.resize(renderer, width, height),
+            WindowEvent::Key(Key::Escape, Action::Press, _) => {
+                self.surface.window.set_should_close(true);
+            }
+            _ => {}
+        }
+    }
+
+    fn resize(&mut self, renderer: &Renderer, width: i32, height: i32) {
+        self.surface.resize(width, height);
+        renderer.resize_view(
+            &mut self.view,
+            self.surface.config.width,
+            self.surface.config.height,
+        );
+    }
+
+    pub fn render_frame(&mut self, renderer: &mut Renderer) -> Result<(), RendererError> {
+        for attempt in 0..2 {
+            let view = &mut self.view;
+            let result = self.surface.render(|command_encoder, image_view, _| {
+                renderer.encode_view(command_encoder, image_view, view);
+                Vec::new()
+            });
+            match result {
+                Ok(_) | Err(wgpu::SurfaceError::Timeout) => break,
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) if attempt == 0 => {
+                    let (width, height) = self.surface.window.framebuffer_size();
+                    self.resize(renderer, width, height);
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    return Err(RendererError::Surface(wgpu::SurfaceError::OutOfMemory));
+                }
+                Err(e) => {
+                    eprintln!("Skipping debug view frame: {}", e);
+                    break;
+                }
+            }
+        }
+        renderer.frame_submitted();
+        Ok(())
+    }
+}
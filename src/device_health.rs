@@ -0,0 +1,79 @@
+use std::sync::{Arc, Mutex};
+
+/// What the device's error callbacks know about where the app is, and what
+/// went wrong.
+#[derive(Default)]
+struct Status {
+    frame: u64,
+    stage: &'static str,
+    failure: Option<String>,
+    /// Set once the device has been replaced, so its callbacks go quiet
+    /// when it's finally dropped.
+    retired: bool,
+}
+
+/// Handlers for validation errors and device loss that log them and leave
+/// a failure for the main loop to pick up, rather than panicking.
+///
+/// wgpu reports most validation errors from inside the call that caused
+/// them, so the stage set just before a piece of work is usually the one to
+/// blame.
+pub struct DeviceHealth {
+    status: Arc<Mutex<Status>>,
+}
+
+impl DeviceHealth {
+    pub fn install(device: &wgpu::Device) -> Self {
+        let status = Arc::new(Mutex::new(Status::default()));
+
+        let error_status = status.clone();
+        device.on_uncaptured_error(Box::new(move |error| {
+            let mut status = error_status.lock().unwrap();
+            if status.retired {
+                return;
+            }
+            eprintln!(
+                "GPU error in frame {} ({}): {}",
+                status.frame, status.stage, error
+            );
+            status.failure.get_or_insert_with(|| error.to_string());
+        }));
+
+        let lost_status = status.clone();
+        device.set_device_lost_callback(move |reason, message| {
+            let mut status = lost_status.lock().unwrap();
+            if status.retired {
+                return;
+            }
+            eprintln!(
+                "GPU device lost in frame {} ({}): {:?} {}",
+                status.frame, status.stage, reason, message
+            );
+            status
+                .failure
+                .get_or_insert_with(|| format!("device lost: {}", message));
+        });
+
+        DeviceHealth { status: status }
+    }
+
+    pub fn begin_frame(&self, frame: u64) {
+        self.status.lock().unwrap().frame = frame;
+    }
+
+    /// Names the work about to be recorded in any error reported from now on.
+    pub fn set_stage(&self, stage: &'static str) {
+        self.status.lock().unwrap().stage = stage;
+    }
+
+    /// The first error since the last call, if there was one.
+    pub fn take_failure(&self) -> Option<String> {
+        self.status.lock().unwrap().failure.take()
+    }
+}
+
+impl Drop for DeviceHealth {
+    fn drop(&mut self) {
+        self.status.lock().unwrap().retired = true;
+    }
+}
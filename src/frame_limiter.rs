@@ -0,0 +1,66 @@
+use std::thread;
+use std::time::Duration;
+
+use web_time::Instant;
+
+/// Holds the frame rate down to a cap by waiting out what's left of each
+/// frame's budget. Sleeping alone overshoots by up to a scheduler tick, so
+/// it sleeps until shortly before the deadline and spins the rest.
+pub struct FrameLimiter {
+    budget: Option<Duration>,
+    deadline: Option<Instant>,
+}
+
+impl FrameLimiter {
+    /// How long before the deadline sleeping gives way to spinning.
+    const SPIN_MARGIN: Duration = Duration::from_millis(1);
+
+    pub fn new() -> Self {
+        FrameLimiter {
+            budget: None,
+            deadline: None,
+        }
+    }
+
+    pub fn max_fps(&self) -> Option<u32> {
+        self.budget
+            .map(|budget| (1.0 / budget.as_secs_f64()).round() as u32)
+    }
+
+    /// Caps the frame rate at `max_fps` frames a second, or lifts the cap.
+    pub fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.budget = max_fps
+            .filter(|&fps| fps > 0)
+            .map(|fps| Duration::from_secs_f64(1.0 / fps as f64));
+        self.deadline = None;
+    }
+
+    /// Waits until a frame's budget has passed since the last `wait` ended.
+    /// A frame that ran over starts the next budget from now, rather than
+    /// letting the following frames rush to catch up.
+    pub fn wait(&mut self) {
+        let Some(budget) = self.budget else {
+            return;
+        };
+        let now = Instant::now();
+        let deadline = match self.deadline {
+            Some(deadline) if deadline > now => deadline,
+            _ => {
+                self.deadline = Some(now + budget);
+                return;
+            }
+        };
+        if let Some(sleep) = (deadline - now).checked_sub(Self::SPIN_MARGIN) {
+            thread::sleep(sleep);
+        }
+        while Instant::now() < deadline {
+            std::hint::spin_loop();
+        }
+        self.deadline = Some(deadline + budget);
+    }
+
+    /// Forgets the last deadline, e.g. after not rendering for a while.
+    pub fn reset(&mut self) {
+        self.deadline = None;
+    }
+}
@@ -0,0 +1,310 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc;
+use std::thread;
+
+use crate::args;
+use crate::renderer_backend::{error::RendererError, screenshot};
+
+/// Readback buffers a recording takes turns with. Frames that come while
+/// all of them wait for the GPU are dropped.
+const STAGING_BUFFERS: usize = 3;
+/// Frames read back but not yet encoded. Frames that come while this many
+/// wait for the encoder thread are dropped.
+const ENCODE_QUEUE: usize = 8;
+
+pub struct RecordOptions {
+    pub out_dir: PathBuf,
+    /// Simulated frames per second, so the clip plays at the speed the
+    /// scene was meant to move however slowly it was recorded.
+    pub fps: u32,
+    /// Keep one of this many presented frames.
+    pub every: u32,
+}
+
+impl RecordOptions {
+    /// Simulated time step between presented frames.
+    pub fn frame_delta(&self) -> f32 {
+        1.0 / self.fps as f32
+    }
+}
+
+/// Looks for `--record DIR [--record-fps FPS] [--record-every N]` on the
+/// command line. Returns `None` when `--record` isn't given.
+pub fn from_args() -> Result<Option<RecordOptions>, RendererError> {
+    let Some(out_dir) = args::flag_value("--record")? else {
+        return Ok(None);
+    };
+    let fps = match args::flag_value("--record-fps")? {
+        Some(fps) => args::parse_count("--record-fps", &fps)?,
+        None => 60,
+    };
+    let every = match args::flag_value("--record-every")? {
+        Some(count) => args::parse_count("--record-every", &count)?,
+        None => 1,
+    };
+    if fps == 0 || every == 0 {
+        return Err(RendererError::InvalidArgument(
+            "--record-fps and --record-every have to be above 0".to_string(),
+        ));
+    }
+    Ok(Some(RecordOptions {
+        out_dir: PathBuf::from(out_dir),
+        fps: fps,
+        every: every,
+    }))
+}
+
+struct Staging {
+    buffer: wgpu::Buffer,
+    width: u32,
+    height: u32,
+}
+
+/// A frame copied into `staging`, mapping once `receiver` is set.
+struct InFlight {
+    index: u64,
+    staging: Staging,
+    receiver: Option<mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>>,
+}
+
+struct EncodeJob {
+    path: PathBuf,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+}
+
+/// Writes presented frames to `frame_NNNNNN.png`, numbered in the order
+/// they were presented. Copies go into a few staging buffers mapped a
+/// frame or two later, so the GPU doesn't wait on the readback, and PNGs
+/// are encoded on a thread of their own. A frame either can't keep up
+/// with is dropped with a message, leaving a gap in the numbers.
+pub struct FrameRecorder {
+    options: RecordOptions,
+    swap_red_blue: bool,
+    /// Frames presented since recording started.
+    presented: u64,
+    /// The number the next kept frame is written with.
+    next_index: u64,
+    dropped: u64,
+    free: Vec<Staging>,
+    /// Oldest first; frames are handed to the encoder in this order.
+    in_flight: VecDeque<InFlight>,
+    sender: Option<mpsc::SyncSender<EncodeJob>>,
+    /// Returns how many frames it wrote.
+    encoder: Option<thread::JoinHandle<u64>>,
+}
+
+impl FrameRecorder {
+    /// Creates the output directory and starts the encoder thread, for
+    /// frames of `format`.
+    pub fn new(options: RecordOptions, format: wgpu::TextureFormat) -> Result<Self, RendererError> {
+        let swap_red_blue = screenshot::swaps_red_blue(format)?;
+        let output_error = |error| RendererError::Output {
+            path: options.out_dir.clone(),
+            error: error,
+        };
+        fs::create_dir_all(&options.out_dir).map_err(output_error)?;
+
+        let (sender, receiver) = mpsc::sync_channel::<EncodeJob>(ENCODE_QUEUE);
+        let encoder = thread::Builder::new()
+            .name("frame encoder".to_string())
+            .spawn(move || {
+                let mut written = 0;
+                for job in receiver {
+                    match screenshot::save_png(&job.path, job.width, job.height, job.rgba) {
+                        Ok(_) => written += 1,
+                        Err(e) => eprintln!("Recording: {}", e),
+                    }
+                }
+                written
+            })
+            .map_err(output_error)?;
+        println!("Recording to {}", options.out_dir.display());
+
+        Ok(FrameRecorder {
+            options: options,
+            swap_red_blue: swap_red_blue,
+            presented: 0,
+            next_index: 0,
+            dropped: 0,
+            free: Vec::new(),
+            in_flight: VecDeque::new(),
+            sender: Some(sender),
+            encoder: Some(encoder),
+        })
+    }
+
+    pub fn frame_delta(&self) -> f32 {
+        self.options.frame_delta()
+    }
+
+    /// Records a copy of `texture`, the frame about to be presented, if
+    /// it's one to keep. `texture` needs `COPY_SRC`.
+    pub fn capture(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        texture: &wgpu::Texture,
+    ) {
+        let presented = self.presented;
+        self.presented += 1;
+        if presented % self.options.every as u64 != 0 {
+            return;
+        }
+        let index = self.next_index;
+        self.next_index += 1;
+
+        let (width, height) = (texture.width(), texture.height());
+        // Buffers from before a resize are the wrong size.
+        self.free
+            .retain(|staging| staging.width == width && staging.height == height);
+        let staging = match self.free.pop() {
+            Some(staging) => staging,
+            None if self.in_flight.len() < STAGING_BUFFERS => Staging {
+                buffer: screenshot::create_readback_buffer(device, width, height),
+                width: width,
+                height: height,
+            },
+            None => {
+                self.drop_frame(index, "every staging buffer is still being read back");
+                return;
+            }
+        };
+        screenshot::copy_to_buffer(command_encoder, texture, &staging.buffer);
+        self.in_flight.push_back(InFlight {
+            index: index,
+            staging: staging,
+            receiver: None,
+        });
+    }
+
+    /// Starts mapping the copies `capture` recorded. Call once their
+    /// command buffer has been submitted.
+    pub fn frame_submitted(&mut self) {
+        for frame in self
+            .in_flight
+            .iter_mut()
+            .filter(|frame| frame.receiver.is_none())
+        {
+            let (sender, receiver) = mpsc::channel();
+            frame
+                .staging
+                .buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            frame.receiver = Some(receiver);
+        }
+    }
+
+    /// Hands the frames that have been read back to the encoder thread,
+    /// without waiting for the rest.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        self.drain(device, false);
+    }
+
+    /// Reads back the oldest frames, as far as they're mapped. With `wait`
+    /// it waits for the GPU and for room in the encoder's queue rather than
+    /// dropping frames.
+    fn drain(&mut self, device: &wgpu::Device, wait: bool) {
+        let _ = device.poll(match wait {
+            true => wgpu::Maintain::Wait,
+            false => wgpu::Maintain::Poll,
+        });
+        loop {
+            let result = match self
+                .in_flight
+                .front()
+                .and_then(|frame| frame.receiver.as_ref())
+            {
+                Some(receiver) => match receiver.try_recv() {
+                    Ok(result) => result.map_err(|e| e.to_string()),
+                    Err(mpsc::TryRecvError::Empty) => break,
+                    Err(mpsc::TryRecvError::Disconnected) => {
+                        Err("the map callback was dropped".to_string())
+                    }
+                },
+                None => break,
+            };
+            let Some(frame) = self.in_flight.pop_front() else {
+                break;
+            };
+            if let Err(error) = result {
+                self.drop_frame(frame.index, &format!("can't map it: {}", error));
+                continue;
+            }
+
+            let staging = frame.staging;
+            let rgba = screenshot::to_rgba(
+                &staging.buffer.slice(..).get_mapped_range(),
+                staging.width,
+                staging.height,
+                self.swap_red_blue,
+            );
+            staging.buffer.unmap();
+            let job = EncodeJob {
+                path: self
+                    .options
+                    .out_dir
+                    .join(format!("frame_{:06}.png", frame.index)),
+                width: staging.width,
+                height: staging.height,
+                rgba: rgba,
+            };
+            self.free.push(staging);
+            self.encode(frame.index, job, wait);
+        }
+    }
+
+    fn encode(&mut self, index: u64, job: EncodeJob, wait: bool) {
+        let Some(sender) = &self.sender else {
+            return;
+        };
+        let sent = match wait {
+            true => sender.send(job).map_err(|_| "the encoder thread stopped"),
+            false => sender.try_send(job).map_err(|e| match e {
+                mpsc::TrySendError::Full(_) => "the PNG encoder is behind",
+                mpsc::TrySendError::Disconnected(_) => "the encoder thread stopped",
+            }),
+        };
+        if let Err(reason) = sent {
+            self.drop_frame(index, reason);
+        }
+    }
+
+    fn drop_frame(&mut self, index: u64, reason: &str) {
+        self.dropped += 1;
+        eprintln!("Recording: dropped frame {} ({})", index, reason);
+    }
+
+    /// Forgets the frames in flight, whose buffers belonged to a device
+    /// that's gone; they're reported as dropped.
+    pub fn device_lost(&mut self) {
+        while let Some(frame) = self.in_flight.pop_front() {
+            self.drop_frame(frame.index, "the device was lost");
+        }
+        self.free.clear();
+    }
+
+    /// Waits until every frame in flight has been read back and written.
+    pub fn finish(mut self, device: &wgpu::Device) {
+        self.frame_submitted();
+        self.drain(device, true);
+        drop(self.sender.take());
+        let written = self
+            .encoder
+            .take()
+            .and_then(|encoder| encoder.join().ok())
+            .unwrap_or(0);
+        println!(
+            "Recorded {} frames to {}, {} dropped",
+            written,
+            self.options.out_dir.display(),
+            self.dropped
+        );
+    }
+}
@@ -0,0 +1,76 @@
+use std::time::Duration;
+
+use web_time::Instant;
+
+pub struct FrameTimer {
+    start: Instant,
+    last_frame: Instant,
+    delta: f32,
+    sample_start: Instant,
+    sample_frames: u32,
+    fps: f32,
+    frame_ms: f32,
+}
+
+impl FrameTimer {
+    const MAX_DELTA: f32 = 0.1;
+    const SAMPLE_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new() -> Self {
+        let now = Instant::now();
+        FrameTimer {
+            start: now,
+            last_frame: now,
+            delta: 0.0,
+            sample_start: now,
+            sample_frames: 0,
+            fps: 0.0,
+            frame_ms: 0.0,
+        }
+    }
+
+    /// Advances the timer by one frame. Returns true when the smoothed
+    /// statistics were refreshed, which happens at most once per second.
+    pub fn tick(&mut self) -> bool {
+        let now = Instant::now();
+        self.delta = (now - self.last_frame).as_secs_f32().min(Self::MAX_DELTA);
+        self.last_frame = now;
+
+        self.sample_frames += 1;
+        let sample_time = now - self.sample_start;
+        if sample_time < Self::SAMPLE_INTERVAL {
+            return false;
+        }
+        self.fps = self.sample_frames as f32 / sample_time.as_secs_f32();
+        self.frame_ms = 1000.0 / self.fps;
+        self.sample_frames = 0;
+        self.sample_start = now;
+        true
+    }
+
+    /// Starts timing afresh, so the next `tick` doesn't count the time spent
+    /// not rendering, e.g. while minimized.
+    pub fn reset(&mut self) {
+        let now = Instant::now();
+        self.last_frame = now;
+        self.delta = 0.0;
+        self.sample_start = now;
+        self.sample_frames = 0;
+    }
+
+    pub fn delta_seconds(&self) -> f32 {
+        self.delta
+    }
+
+    pub fn elapsed_seconds(&self) -> f32 {
+        self.start.elapsed().as_secs_f32()
+    }
+
+    pub fn fps(&self) -> f32 {
+        self.fps
+    }
+
+    pub fn frame_ms(&self) -> f32 {
+        self.frame_ms
+    }
+}
@@ -0,0 +1,85 @@
+use gilrs::{Axis, Button, Event, EventType, GamepadId, Gilrs};
+
+use crate::input::{Command, GamepadState, InputState};
+
+/// How far a stick has to tilt before it counts, out of 1.
+const DEADZONE: f32 = 0.15;
+
+/// Takes the deadzone out of a stick's tilt, scaling what's left back up to
+/// reach 1, so the stick starts moving things smoothly.
+fn apply_deadzone((x, y): (f32, f32)) -> (f32, f32) {
+    let tilt = (x * x + y * y).sqrt();
+    if tilt <= DEADZONE {
+        return (0.0, 0.0);
+    }
+    let scale = ((tilt - DEADZONE) / (1.0 - DEADZONE)).min(1.0) / tilt;
+    (x * scale, y * scale)
+}
+
+/// Reads one gamepad at a time into an `InputState`: the first connected,
+/// until it's unplugged and the next connected one takes over.
+pub struct Gamepads {
+    gilrs: Gilrs,
+    active: Option<GamepadId>,
+}
+
+impl Gamepads {
+    /// `None`, with a warning, when there's no way to read gamepads here.
+    pub fn new() -> Option<Self> {
+        let gilrs = match Gilrs::new() {
+            Ok(gilrs) => gilrs,
+            Err(e) => {
+                eprintln!("Gamepads unavailable: {}", e);
+                return None;
+            }
+        };
+        let mut gamepads = Gamepads {
+            gilrs: gilrs,
+            active: None,
+        };
+        gamepads.pick_active();
+        Some(gamepads)
+    }
+
+    fn pick_active(&mut self) {
+        self.active = self.gilrs.gamepads().next().map(|(id, _)| id);
+        if let Some(id) = self.active {
+            println!("Gamepad: {}", self.gilrs.gamepad(id).name());
+        }
+    }
+
+    /// Handles what happened since the last poll, including controllers
+    /// coming and going, and hands `input` the active one's state. Without
+    /// a controller this only drains gilrs' event queue.
+    pub fn poll(&mut self, input: &mut InputState) {
+        while let Some(Event { id, event, .. }) = self.gilrs.next_event() {
+            match event {
+                EventType::Connected if self.active.is_none() => self.pick_active(),
+                EventType::Disconnected if self.active == Some(id) => {
+                    println!("Gamepad disconnected");
+                    self.pick_active();
+                }
+                EventType::ButtonPressed(Button::South, _) if self.active == Some(id) => {
+                    input.push_command(Command::CycleSelection);
+                }
+                _ => {}
+            }
+        }
+
+        let state = self.active.map(|id| {
+            let gamepad = self.gilrs.gamepad(id);
+            GamepadState {
+                left_stick: apply_deadzone((
+                    gamepad.value(Axis::LeftStickX),
+                    gamepad.value(Axis::LeftStickY),
+                )),
+                right_stick: apply_deadzone((
+                    gamepad.value(Axis::RightStickX),
+                    gamepad.value(Axis::RightStickY),
+                )),
+                fast: gamepad.is_pressed(Button::LeftThumb),
+            }
+        });
+        input.set_gamepad(state);
+    }
+}
@@ -0,0 +1,40 @@
+use crate::renderer;
+use crate::renderer_backend::{adapter, error::RendererError};
+
+/// The device-level objects every window shares. Each window has a surface
+/// of its own but they all present images rendered with this device.
+pub struct GpuContext {
+    pub instance: wgpu::Instance,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+}
+
+impl GpuContext {
+    /// Picks an adapter that can present to `compatible_surface`, which must
+    /// come from `instance`, and opens the device on it.
+    pub async fn new(
+        instance: wgpu::Instance,
+        compatible_surface: &wgpu::Surface<'_>,
+        options: &adapter::AdapterOptions,
+    ) -> Result<Self, RendererError> {
+        let adapter = adapter::request(&instance, Some(compatible_surface), options).await?;
+        let (device, queue) = renderer::request_device(&adapter, options.trace.as_deref()).await?;
+        Ok(GpuContext {
+            instance: instance,
+            adapter: adapter,
+            device: device,
+            queue: queue,
+        })
+    }
+
+    /// Opens a new device in place of this one once it's been lost, on
+    /// whichever adapter `options` picks now.
+    pub async fn recreate(
+        &self,
+        compatible_surface: &wgpu::Surface<'_>,
+        options: &adapter::AdapterOptions,
+    ) -> Result<Self, RendererError> {
+        Self::new(self.instance.clone(), compatible_surface, options).await
+    }
+}
@@ -0,0 +1,193 @@
+use crate::renderer_backend::{
+    bind_group, bind_group_layout,
+    camera::Camera,
+    color::ColorSpace,
+    frame_uploader::FrameUploader,
+    pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    shader::ShaderError,
+    uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/grid.wgsl";
+
+/// How `GridPass` draws the ground, adjustable while it runs.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct GridSettings {
+    /// World units between minor lines.
+    pub spacing: f32,
+    /// Every this many minor lines is a major one.
+    pub major_every: u32,
+    /// Colors as authored in code, alpha included; see `ColorSpace`.
+    pub minor_color: wgpu::Color,
+    pub major_color: wgpu::Color,
+    /// How far from the camera the grid has faded out completely.
+    pub fade_distance: f32,
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        GridSettings {
+            spacing: 1.0,
+            major_every: 10,
+            minor_color: wgpu::Color {
+                r: 0.5,
+                g: 0.5,
+                b: 0.5,
+                a: 0.35,
+            },
+            major_color: wgpu::Color {
+                r: 0.8,
+                g: 0.8,
+                b: 0.8,
+                a: 0.7,
+            },
+            fade_distance: 60.0,
+        }
+    }
+}
+
+/// The ground plane y = 0 as an endless grid, found per pixel by casting
+/// the camera ray through it, so there's no mesh to run out of. It writes
+/// its own depth for the test, so the scene hides it, but not into the
+/// buffer, and blends over the skybox. Transparent meshes are drawn before
+/// it, so it shows through them unblended.
+pub struct GridPass {
+    settings: GridSettings,
+    pipeline: wgpu::RenderPipeline,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    colors: ColorSpace,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group_layout: bind_group_layout::Layout,
+    uniform_bind_group: wgpu::BindGroup,
+}
+
+impl GridPass {
+    pub fn new(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        settings: GridSettings,
+    ) -> Result<Self, ShaderError> {
+        let uniform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            uniform_bind_group_layout = builder.build("Grid Uniform Bind Group Layout");
+        }
+        let grid_uniform = Self::grid_uniform(&settings, info.colors, &Camera::new(1.0));
+        let uniform_buffer = uniform::make_uniform_buffer(
+            device,
+            "Grid Uniform Buffer",
+            bytemuck::bytes_of(&grid_uniform),
+        );
+        let uniform_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&uniform_bind_group_layout);
+            builder.add_buffer(&uniform_buffer);
+            uniform_bind_group = builder.build("Grid Uniform Bind Group");
+        }
+
+        let pipeline = Self::build_pipeline(device, info, &uniform_bind_group_layout)?;
+        Ok(GridPass {
+            settings: settings,
+            pipeline: pipeline,
+            depth_format: info.depth_format,
+            sample_count: info.sample_count,
+            colors: info.colors,
+            uniform_buffer: uniform_buffer,
+            uniform_bind_group_layout: uniform_bind_group_layout,
+            uniform_bind_group: uniform_bind_group,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(info.format);
+        builder.set_depth_format(info.depth_format);
+        builder.set_depth_test(false, wgpu::CompareFunction::LessEqual);
+        builder.set_blend_state(Some(wgpu::BlendState::ALPHA_BLENDING));
+        builder.set_cull_mode(None);
+        builder.set_sample_count(info.sample_count);
+        builder.add_bind_group_layout(uniform_bind_group_layout);
+        builder.build_pipeline("Grid Pipeline")
+    }
+
+    fn grid_uniform(
+        settings: &GridSettings,
+        colors: ColorSpace,
+        camera: &Camera,
+    ) -> uniform::GridUniform {
+        let color = |color: wgpu::Color| {
+            let color = colors.to_linear_color(color);
+            [color.r, color.g, color.b, color.a].map(|channel| channel as f32)
+        };
+        uniform::GridUniform::new(
+            &camera.build_view_projection(),
+            &camera.position,
+            settings.spacing,
+            settings.major_every,
+            color(settings.minor_color),
+            color(settings.major_color),
+            settings.fade_distance,
+        )
+    }
+
+    pub fn settings(&self) -> GridSettings {
+        self.settings
+    }
+
+    /// The spacing is kept above 0 and there's at least one minor line per
+    /// major one.
+    pub fn set_settings(&mut self, settings: GridSettings) {
+        self.settings = GridSettings {
+            spacing: settings.spacing.max(0.001),
+            major_every: settings.major_every.max(1),
+            ..settings
+        };
+    }
+}
+
+impl RenderPass for GridPass {
+    fn prepare(&mut self, uploader: &mut FrameUploader, camera: &Camera) {
+        let grid_uniform = Self::grid_uniform(&self.settings, self.colors, camera);
+        uploader.write(&self.uniform_buffer, 0, &[grid_uniform]);
+    }
+
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Grid Pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        targets.apply_viewport(&mut renderpass);
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        self.colors = info.colors;
+        if info.sample_count == self.sample_count && info.depth_format == self.depth_format {
+            return;
+        }
+        match Self::build_pipeline(device, info, &self.uniform_bind_group_layout) {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.depth_format = info.depth_format;
+                self.sample_count = info.sample_count;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::args;
+use crate::renderer::Renderer;
+use crate::renderer_backend::{
+    adapter::AdapterOptions, color::ColorSpace, error::RendererError, screenshot,
+};
+
+/// Simulated time step between headless frames.
+const FRAME_DELTA: f32 = 1.0 / 60.0;
+
+pub struct HeadlessOptions {
+    pub width: u32,
+    pub height: u32,
+    pub frames: u32,
+    pub out_dir: PathBuf,
+    pub srgb_comparison: bool,
+    /// Draw the scene's meshes indirectly, to compare against direct draws.
+    pub indirect: bool,
+    /// With `indirect`, cull in a compute pass instead of on the CPU.
+    pub gpu_cull: bool,
+}
+
+/// Looks for `--headless WIDTHxHEIGHT [--frames N] [--out DIR]
+/// [--srgb-comparison] [--indirect [--gpu-cull]]` on the command line. Returns `None` when
+/// `--headless` isn't given.
+pub fn from_args() -> Result<Option<HeadlessOptions>, RendererError> {
+    let (width, height) = match args::flag_value("--headless")? {
+        Some(size) => args::parse_size("--headless", &size)?,
+        None => return Ok(None),
+    };
+
+    let frames = match args::flag_value("--frames")? {
+        Some(count) => args::parse_count("--frames", &count)?,
+        None => 1,
+    };
+    let out_dir = args::flag_value("--out")?.unwrap_or_else(|| ".".to_string());
+
+    Ok(Some(HeadlessOptions {
+        width: width,
+        height: height,
+        frames: frames,
+        out_dir: PathBuf::from(out_dir),
+        srgb_comparison: args::has_flag("--srgb-comparison"),
+        indirect: args::has_flag("--indirect"),
+        gpu_cull: args::has_flag("--gpu-cull"),
+    }))
+}
+
+/// Renders `options.frames` frames without a window and writes each one to
+/// `frame_NNNN.png` in the output directory.
+pub async fn run(
+    options: HeadlessOptions,
+    clear_color: Option<wgpu::Color>,
+    adapter_options: &AdapterOptions,
+) -> Result<(), RendererError> {
+    fs::create_dir_all(&options.out_dir).map_err(|error| RendererError::Output {
+        path: options.out_dir.clone(),
+        error: error,
+    })?;
+
+    let mut renderer = Renderer::headless(options.width, options.height, adapter_options).await?;
+    if let Some(color) = clear_color {
+        renderer.set_clear_color(color);
+    }
+    renderer.set_indirect(options.indirect);
+    if options.gpu_cull {
+        renderer.set_gpu_cull(true);
+    }
+
+    for frame in 0..options.frames {
+        renderer.update(FRAME_DELTA);
+        let path = options.out_dir.join(format!("frame_{:04}.png", frame));
+        renderer.capture_frame(&path)?;
+        println!("Wrote {}", path.display());
+    }
+
+    if options.srgb_comparison {
+        let path = options.out_dir.join("srgb_comparison.png");
+        write_srgb_comparison(&mut renderer, &path)?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
+
+/// The last frame drawn with sRGB-authored colors on the left and the same
+/// colors passed through as linear on the right.
+fn write_srgb_comparison(renderer: &mut Renderer, path: &Path) -> Result<(), RendererError> {
+    let colors = renderer.colors();
+    renderer.set_colors(ColorSpace::Srgb);
+    let srgb = renderer.render_offscreen()?;
+    renderer.set_colors(ColorSpace::Linear);
+    let linear = renderer.render_offscreen()?;
+    renderer.set_colors(colors);
+
+    let (width, height) = (renderer.config.width, renderer.config.height);
+    let rgba = screenshot::side_by_side(width, height, &srgb, &linear);
+    screenshot::save_png(path, width * 2, height, rgba)?;
+    Ok(())
+}
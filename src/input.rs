@@ -0,0 +1,246 @@
+use std::collections::HashSet;
+
+use crate::platform::{Action, Key, MouseButton, WindowEvent};
+
+/// A movement the controllers read, from the keyboard or a gamepad alike.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Axis {
+    /// Camera movement to the right, forward and up.
+    MoveRight,
+    MoveForward,
+    MoveUp,
+    /// Movement of the selected node along X, Y and Z.
+    NodeX,
+    NodeY,
+    NodeZ,
+}
+
+impl Axis {
+    /// The keys that push the axis towards -1 and +1.
+    fn keys(self) -> (&'static [Key], &'static [Key]) {
+        match self {
+            Axis::MoveRight => (&[Key::A], &[Key::D]),
+            Axis::MoveForward => (&[Key::S], &[Key::W]),
+            Axis::MoveUp => (&[Key::LeftShift], &[Key::Space]),
+            Axis::NodeX => (&[Key::Left], &[Key::Right]),
+            Axis::NodeY => (&[Key::Down], &[Key::Up]),
+            Axis::NodeZ => (&[Key::PageUp], &[Key::PageDown]),
+        }
+    }
+}
+
+/// Something to do once, from a key or a gamepad button press.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Command {
+    /// Select the next scene node.
+    CycleSelection,
+}
+
+/// What the gamepad's left stick moves.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum StickTarget {
+    Camera,
+    Node,
+}
+
+/// The sticks and buttons read from the active gamepad.
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GamepadState {
+    /// Right and up, each -1 to 1, with the deadzone already taken out.
+    pub left_stick: (f32, f32),
+    pub right_stick: (f32, f32),
+    pub fast: bool,
+}
+
+pub struct InputState {
+    pressed_keys: HashSet<Key>,
+    pressed_buttons: HashSet<MouseButton>,
+    mouse_position: Option<(f64, f64)>,
+    mouse_delta: (f64, f64),
+    /// Lines scrolled up since the last `end_frame`.
+    scroll_delta: f64,
+    gamepad: Option<GamepadState>,
+    stick_target: StickTarget,
+    commands: Vec<Command>,
+}
+
+impl InputState {
+    /// How far a fully tilted right stick turns the camera in a second, in
+    /// the cursor pixels `mouse_delta` counts.
+    const STICK_LOOK_SPEED: f64 = 800.0;
+
+    pub fn new() -> Self {
+        InputState {
+            pressed_keys: HashSet::new(),
+            pressed_buttons: HashSet::new(),
+            mouse_position: None,
+            mouse_delta: (0.0, 0.0),
+            scroll_delta: 0.0,
+            gamepad: None,
+            stick_target: StickTarget::Camera,
+            commands: Vec::new(),
+        }
+    }
+
+    pub fn process_event(&mut self, event: &WindowEvent) {
+        match *event {
+            WindowEvent::Key(key, Action::Press, _) => {
+                self.pressed_keys.insert(key);
+                if key == Key::T {
+                    self.commands.push(Command::CycleSelection);
+                }
+            }
+            WindowEvent::Key(key, Action::Release, _) => {
+                self.pressed_keys.remove(&key);
+            }
+            WindowEvent::MouseButton(button, Action::Press, _) => {
+                self.pressed_buttons.insert(button);
+            }
+            WindowEvent::MouseButton(button, Action::Release, _) => {
+                self.pressed_buttons.remove(&button);
+            }
+            WindowEvent::CursorPos(x, y) => {
+                if let Some((last_x, last_y)) = self.mouse_position {
+                    self.mouse_delta.0 += x - last_x;
+                    self.mouse_delta.1 += y - last_y;
+                }
+                self.mouse_position = Some((x, y));
+            }
+            WindowEvent::Scroll(_, y) => {
+                self.scroll_delta += y;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn is_key_pressed(&self, key: Key) -> bool {
+        self.pressed_keys.contains(&key)
+    }
+
+    pub fn is_button_pressed(&self, button: MouseButton) -> bool {
+        self.pressed_buttons.contains(&button)
+    }
+
+    pub fn mouse_position(&self) -> Option<(f64, f64)> {
+        self.mouse_position
+    }
+
+    /// Cursor movement accumulated since the last `end_frame`.
+    pub fn mouse_delta(&self) -> (f64, f64) {
+        self.mouse_delta
+    }
+
+    /// Lines scrolled up, away from the user, since the last `end_frame`.
+    pub fn scroll_delta(&self) -> f64 {
+        self.scroll_delta
+    }
+
+    /// How far `axis` is pushed, from -1 to 1: all the way by its keys,
+    /// partly by a tilted stick. While a node key is held, Shift speeds the
+    /// node up instead of moving the camera down.
+    pub fn axis(&self, axis: Axis) -> f32 {
+        let (negative, positive) = axis.keys();
+        let held = |keys: &[Key]| keys.iter().any(|&key| self.is_key_pressed(key));
+        let lowered = held(negative) && !(axis == Axis::MoveUp && self.moving_node());
+        let mut value = held(positive) as i32 as f32 - lowered as i32 as f32;
+        if let Some(gamepad) = &self.gamepad {
+            let (x, y) = gamepad.left_stick;
+            value += match (self.stick_target, axis) {
+                (StickTarget::Camera, Axis::MoveRight) | (StickTarget::Node, Axis::NodeX) => x,
+                (StickTarget::Camera, Axis::MoveForward) | (StickTarget::Node, Axis::NodeY) => y,
+                _ => 0.0,
+            };
+        }
+        value.clamp(-1.0, 1.0)
+    }
+
+    /// Whether any key moving the selected node is held.
+    fn moving_node(&self) -> bool {
+        [Axis::NodeX, Axis::NodeY, Axis::NodeZ].iter().any(|axis| {
+            let (negative, positive) = axis.keys();
+            negative
+                .iter()
+                .chain(positive)
+                .any(|&key| self.is_key_pressed(key))
+        })
+    }
+
+    /// Whether either Shift or the gamepad's fast button is held.
+    pub fn fast(&self) -> bool {
+        self.is_key_pressed(Key::LeftShift)
+            || self.is_key_pressed(Key::RightShift)
+            || self.gamepad.is_some_and(|gamepad| gamepad.fast)
+    }
+
+    /// How far to turn the camera for a frame of `dt` seconds, in cursor
+    /// pixels: the right stick's tilt, plus `mouse_delta` when `mouse` is set.
+    pub fn look_delta(&self, mouse: bool, dt: f32) -> (f64, f64) {
+        let (mut dx, mut dy) = match mouse {
+            true => self.mouse_delta,
+            false => (0.0, 0.0),
+        };
+        if let Some(gamepad) = &self.gamepad {
+            let (x, y) = gamepad.right_stick;
+            let scale = Self::STICK_LOOK_SPEED * dt as f64;
+            dx += x as f64 * scale;
+            dy -= y as f64 * scale;
+        }
+        (dx, dy)
+    }
+
+    /// Points the gamepad's left stick at the camera or the selected node.
+    pub fn set_stick_target(&mut self, target: StickTarget) {
+        self.stick_target = target;
+    }
+
+    /// The active gamepad's state, or `None` once it's gone.
+    pub fn set_gamepad(&mut self, gamepad: Option<GamepadState>) {
+        self.gamepad = gamepad;
+    }
+
+    pub fn push_command(&mut self, command: Command) {
+        self.commands.push(command);
+    }
+
+    /// The commands given since the last call, oldest first.
+    pub fn take_commands(&mut self) -> Vec<Command> {
+        std::mem::take(&mut self.commands)
+    }
+
+    /// Forgets the last cursor position so the next movement doesn't produce
+    /// a delta, e.g. after the cursor was captured and recentered.
+    pub fn reset_mouse(&mut self) {
+        self.mouse_position = None;
+        self.mouse_delta = (0.0, 0.0);
+    }
+
+    pub fn end_frame(&mut self) {
+        self.mouse_delta = (0.0, 0.0);
+        self.scroll_delta = 0.0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::platform::Modifiers;
+
+    fn press(input: &mut InputState, key: Key) {
+        input.process_event(&WindowEvent::Key(key, Action::Press, Modifiers::default()));
+    }
+
+    #[test]
+    fn shift_speeds_up_the_node_without_lowering_the_camera() {
+        let mut input = InputState::new();
+        press(&mut input, Key::LeftShift);
+        assert_eq!(input.axis(Axis::MoveUp), -1.0);
+
+        press(&mut input, Key::Up);
+        assert_eq!(input.axis(Axis::NodeY), 1.0);
+        assert!(input.fast());
+        assert_eq!(input.axis(Axis::MoveUp), 0.0);
+        // Space still lifts the camera.
+        press(&mut input, Key::Space);
+        assert_eq!(input.axis(Axis::MoveUp), 1.0);
+    }
+}
@@ -0,0 +1,50 @@
+use crate::renderer_backend::{pipeline, shader::ShaderError, viewport::Viewport};
+
+pub const SHADER_FILENAME: &str = "shaders/letterbox.wgsl";
+
+/// Paints the bars around a fixed-aspect viewport black. Recorded last,
+/// straight into the frame, after anything that reads or writes it whole.
+pub struct LetterboxPass {
+    pipeline: wgpu::RenderPipeline,
+}
+
+impl LetterboxPass {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Result<Self, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(format);
+        builder.set_cull_mode(None);
+        let pipeline = builder.build_pipeline("Letterbox Pipeline")?;
+        Ok(LetterboxPass { pipeline: pipeline })
+    }
+
+    pub fn record(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        bars: &[Viewport],
+    ) {
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Letterbox Pass"),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        renderpass.set_pipeline(&self.pipeline);
+        for bar in bars {
+            renderpass.set_scissor_rect(bar.x, bar.y, bar.width, bar.height);
+            renderpass.draw(0..3, 0..1);
+        }
+    }
+}
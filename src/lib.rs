@@ -0,0 +1,45 @@
+//! Everything but the window and event loop in `main.rs`, as a library so
+//! the tests in `tests/` can build renderers and passes of their own.
+
+pub mod args;
+pub mod bench;
+pub mod bloom_pass;
+pub mod camera_bookmarks;
+pub mod camera_controller;
+pub mod clear_color;
+pub mod debug_draw;
+pub mod debug_ui;
+pub mod debug_view;
+pub mod device_health;
+pub mod frame_limiter;
+pub mod frame_recorder;
+pub mod frame_timer;
+#[cfg(feature = "gamepad")]
+pub mod gamepad;
+pub mod gpu_context;
+pub mod grid_pass;
+pub mod headless;
+pub mod input;
+pub mod letterbox_pass;
+pub mod object_controller;
+pub mod particles;
+pub mod platform;
+pub mod post_pass;
+pub mod renderer;
+pub mod renderer_backend;
+pub mod settings;
+pub mod shadertoy_pass;
+pub mod shadow_pass;
+pub mod skybox_pass;
+pub mod sprite_demo;
+pub mod test_support;
+pub mod tilemap_demo;
+pub mod triangle_pass;
+pub mod window_config;
+pub mod window_surface;
+
+/// A pseudo-random number in 0..1 that only depends on `seed`.
+pub fn hash_unit(seed: f32) -> f32 {
+    let x = (seed * 12.9898).sin() * 43758.5453;
+    x - x.floor()
+}
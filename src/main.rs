@@ -1,232 +1,1654 @@
-use glfw::{fail_on_errors, Action, Context, Key, Window};
-mod renderer_backend;
-use renderer_backend::{bind_group_layout, material::Material, mesh_builder, pipeline};
-
-struct State<'a> {
-    instance: wgpu::Instance,
-    surface: wgpu::Surface<'a>,
-    device: wgpu::Device,
-    queue: wgpu::Queue,
-    config: wgpu::SurfaceConfiguration,
-    size: (i32, i32),
-    window: &'a mut Window,
-    render_pipeline: wgpu::RenderPipeline,
-    triangle_mesh: wgpu::Buffer,
-    quad_mesh: mesh_builder::Mesh,
-    triangle_material: Material,
-    quad_material: Material,
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "gamepad")]
+use wgpu_beginner::gamepad;
+use wgpu_beginner::{
+    args, bench, camera_bookmarks, camera_controller, debug_ui, debug_view, device_health,
+    frame_limiter, frame_recorder, frame_timer, gpu_context, grid_pass, hash_unit, headless, input,
+    object_controller, particles, platform, renderer, renderer_backend, settings, shadertoy_pass,
+    sprite_demo, tilemap_demo, window_config, window_surface,
+};
+
+use bench::Bench;
+use camera_bookmarks::{Bookmark, CameraBookmarks};
+use camera_controller::CameraController;
+use debug_ui::{DebugSettings, DebugUi};
+use debug_view::DebugView;
+use device_health::DeviceHealth;
+use frame_limiter::FrameLimiter;
+use frame_recorder::{FrameRecorder, RecordOptions};
+use frame_timer::FrameTimer;
+use gpu_context::GpuContext;
+use input::{Command, InputState, StickTarget};
+use object_controller::ObjectController;
+use particles::{Emitter, ParticleBlend};
+use platform::{Action, Flow, Key, MouseButton, NativeWindow, Window, WindowEvent};
+use renderer::Renderer;
+use renderer_backend::{
+    adapter,
+    color::ColorSpace,
+    error::RendererError,
+    scene::{Children, NodeId, Scene, Transform},
+    screenshot, shader,
+    sprite_batch::SpriteBatch,
+    text::TextRenderer,
+    transform_buffer::ObjectId,
+};
+use settings::{Settings, SettingsWatcher};
+use shadertoy_pass::ShadertoyPass;
+use sprite_demo::BouncingSprites;
+use tilemap_demo::TilemapDemo;
+use window_config::WindowConfig;
+use window_surface::{FormatRequest, WindowMetrics, WindowSurface};
+
+/// Gap between the stats text and the window's corner at a content scale
+/// of 1.
+const TEXT_MARGIN: f32 = 8.0;
+
+/// The aspect ratio K letterboxes the scene to.
+const LETTERBOX_ASPECT: f32 = 16.0 / 9.0;
+
+struct State {
+    gpu: Arc<GpuContext>,
+    /// Where GPU errors end up; `recover` checks it after every frame.
+    health: DeviceHealth,
+    /// Frames rendered so far, for error messages.
+    frame: u64,
+    surface: WindowSurface,
+    /// Kept up to date from size and scale events. Cursor positions and the
+    /// window size are in screen coordinates; everything drawn, including
+    /// the viewport, scissor rects, text and UI, is in framebuffer pixels.
+    /// Conversions between the two go through this.
+    metrics: WindowMetrics,
+    cursor_captured: bool,
+    capture_held: bool,
+    input: InputState,
+    /// `None` without the `gamepad` feature or when gilrs can't start.
+    #[cfg(feature = "gamepad")]
+    gamepads: Option<gamepad::Gamepads>,
+    camera_controller: CameraController,
+    bookmarks: CameraBookmarks,
+    object_controller: ObjectController,
+    renderer: Renderer,
+    debug_ui: DebugUi,
+    text: TextRenderer,
+    sprites: SpriteBatch,
+    /// The bouncing sprites Q toggles, made the first time they're shown.
+    sprite_demo: Option<BouncingSprites>,
+    show_sprite_demo: bool,
+    /// The scrolling tilemap F3 toggles.
+    tilemap_demo: Option<TilemapDemo>,
+    /// Whether clicks pick by casting a ray against the scene's meshes
+    /// rather than reading the GPU's id buffer back, toggled with F6.
+    ray_picking: bool,
+    frame_ms: f32,
+    fps: f32,
+    frame_limiter: FrameLimiter,
+    /// The last frame time measured with the depth pre-pass off and on,
+    /// to compare the two.
+    prepass_frame_ms: [Option<f32>; 2],
+    /// Cubes added with N, newest last, for Delete to take away again.
+    spawned: Vec<NodeId>,
+    /// Set by `--record`.
+    recorder: Option<FrameRecorder>,
+    /// Set by `--shadertoy`, drawn in place of everything but the overlay.
+    shadertoy: Option<ShadertoyPass>,
+    iconified: bool,
+    zero_sized: bool,
 }
 
-impl<'a> State<'a> {
-    async fn new(window: &'a mut Window) -> Self {
-        let size = window.get_framebuffer_size();
-        let instance_descriptor = wgpu::InstanceDescriptor {
-            backends: wgpu::Backends::DX12,
-            ..Default::default()
+impl State {
+    /// Renders to `window`, which was opened as `window_config` describes;
+    /// the surface starts out at its framebuffer size. The clear color and
+    /// camera controls come from `settings`.
+    async fn new(
+        window: NativeWindow,
+        window_config: &WindowConfig,
+        settings: &Settings,
+        adapter_options: &adapter::AdapterOptions,
+        hdr: bool,
+    ) -> Result<Self, RendererError> {
+        let instance = adapter::create_window_instance(adapter_options).await;
+        let surface = window.create_surface(&instance)?;
+        let gpu = Arc::new(GpuContext::new(instance, &surface, adapter_options).await?);
+        let health = DeviceHealth::install(&gpu.device);
+        let format = FormatRequest::Preferred { hdr: hdr };
+        let mut surface = WindowSurface::new(gpu.clone(), window, surface, format)?;
+        let see_through = match window_config.transparent {
+            true => surface.set_see_through(),
+            false => None,
         };
-        let instance = wgpu::Instance::new(&instance_descriptor);
 
-        let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(&window) }.unwrap();
-        let surface = unsafe { instance.create_surface_unsafe(target) }.unwrap();
+        let mut renderer = Renderer::new(
+            gpu.adapter.clone(),
+            gpu.device.clone(),
+            gpu.queue.clone(),
+            surface.target_config(),
+        )?;
+        match surface.view_format() == surface.config.format {
+            true => println!("Surface format: {:?}", surface.config.format),
+            false => println!(
+                "Surface format: {:?}, drawn through {:?} views",
+                surface.config.format,
+                surface.view_format()
+            ),
+        }
+        if hdr && surface.config.format != window_surface::HDR_FORMAT {
+            println!(
+                "HDR: {:?} is not supported, tonemapping to {:?}",
+                window_surface::HDR_FORMAT,
+                surface.view_format()
+            );
+            renderer.set_tonemap(true);
+        }
+        renderer.set_clear_color(settings.clear_color());
+        renderer.set_shadow_settings(settings.shadows);
+        match see_through {
+            Some(alpha_mode) => {
+                println!("Transparent window: {:?} alpha", alpha_mode);
+                renderer.set_alpha_mode(alpha_mode);
+                renderer.set_clear_color(wgpu::Color::TRANSPARENT);
+                // It would cover the desktop everywhere.
+                if renderer.skybox() {
+                    renderer.toggle_skybox();
+                }
+            }
+            None if window_config.transparent => {
+                eprintln!("Warning: transparent windows aren't supported here, staying opaque")
+            }
+            None => {}
+        }
+        let metrics = surface.metrics();
+        let debug_ui = DebugUi::new(
+            &renderer.device,
+            renderer.config.format,
+            metrics.content_scale,
+        );
+        let mut text = TextRenderer::new(
+            &renderer.device,
+            &renderer.queue,
+            renderer.config.format,
+            renderer.config.width,
+            renderer.config.height,
+        )?;
+        text.set_content_scale(metrics.content_scale);
+        let sprites = SpriteBatch::new(
+            &renderer.device,
+            &renderer.queue,
+            renderer.config.format,
+            renderer.config.width,
+            renderer.config.height,
+        )?;
 
-        let adapter_descriptor = wgpu::RequestAdapterOptionsBase {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        };
+        Ok(Self {
+            gpu,
+            health,
+            frame: 0,
+            surface,
+            metrics,
+            input: InputState::new(),
+            #[cfg(feature = "gamepad")]
+            gamepads: gamepad::Gamepads::new(),
+            cursor_captured: false,
+            capture_held: false,
+            camera_controller: CameraController::new(
+                settings.camera.speed,
+                settings.camera.sensitivity,
+            ),
+            bookmarks: CameraBookmarks::load(),
+            object_controller: ObjectController::new(1.0),
+            renderer,
+            debug_ui,
+            text,
+            sprites,
+            sprite_demo: None,
+            show_sprite_demo: false,
+            tilemap_demo: None,
+            ray_picking: false,
+            frame_ms: 0.0,
+            fps: 0.0,
+            frame_limiter: FrameLimiter::new(),
+            prepass_frame_ms: [None; 2],
+            spawned: Vec::new(),
+            iconified: false,
+            zero_sized: false,
+            recorder: None,
+            shadertoy: None,
+        })
+    }
+
+    fn handle_input(&mut self, event: &WindowEvent) -> bool {
+        if !self.cursor_captured && self.debug_ui.handle_event(event, &self.metrics) {
+            return true;
+        }
+        self.input.process_event(event);
+
+        if let WindowEvent::Key(key, Action::Press, modifiers) = event {
+            if let Some(slot) = camera_bookmarks::slot_for_key(*key) {
+                match modifiers.control {
+                    true => self.save_bookmark(slot),
+                    false => self.restore_bookmark(slot),
+                }
+                return true;
+            }
+        }
+        match event {
+            WindowEvent::ContentScale(x, _) => {
+                // Moving onto a monitor with a different scale can change
+                // the framebuffer size without a resize event of its own.
+                self.debug_ui.set_pixels_per_point(*x);
+                self.text.set_content_scale(*x);
+                let (width, height) = self.surface.window.framebuffer_size();
+                self.resize(width, height);
+                true
+            }
+            WindowEvent::Size(_, _) => {
+                self.metrics = self.surface.metrics();
+                false
+            }
+            WindowEvent::Iconify(iconified) => {
+                self.iconified = *iconified;
+                if !iconified {
+                    let (width, height) = self.surface.window.framebuffer_size();
+                    self.resize(width, height);
+                }
+                true
+            }
+            WindowEvent::Key(Key::F1, Action::Press, _) => {
+                self.debug_ui.visible = !self.debug_ui.visible;
+                true
+            }
+            WindowEvent::Key(Key::F5, Action::Press, _) => {
+                self.renderer.reload_shaders();
+                true
+            }
+            WindowEvent::Key(Key::V, Action::Press, _) => {
+                self.cycle_present_mode();
+                true
+            }
+            WindowEvent::Key(Key::Z, Action::Press, _) => {
+                self.renderer.toggle_wireframe();
+                true
+            }
+            WindowEvent::Key(Key::C, Action::Press, _) => {
+                self.renderer.toggle_clear_color_animation();
+                true
+            }
+            WindowEvent::Key(Key::F, Action::Press, modifiers) if modifiers.shift => {
+                self.renderer.toggle_frustum_freeze();
+                true
+            }
+            WindowEvent::Key(Key::F, Action::Press, _) => {
+                self.frame_selected();
+                true
+            }
+            WindowEvent::Key(Key::B, Action::Press, _) => {
+                self.renderer.toggle_skybox();
+                true
+            }
+            WindowEvent::Key(Key::R, Action::Press, _) if self.shadertoy.is_some() => {
+                if let Some(shadertoy) = &mut self.shadertoy {
+                    shadertoy.reset_time();
+                }
+                true
+            }
+            WindowEvent::Key(Key::R, Action::Press, _) => {
+                self.renderer.toggle_grid();
+                true
+            }
+            WindowEvent::Key(Key::G, Action::Press, _) => {
+                self.renderer.toggle_wave();
+                true
+            }
+            WindowEvent::Key(Key::P, Action::Press, _) => {
+                let paused = !self.renderer.paused();
+                self.renderer.set_paused(paused);
+                true
+            }
+            WindowEvent::Key(Key::F10, Action::Press, _) => {
+                self.renderer.cycle_post_effect();
+                true
+            }
+            WindowEvent::Key(Key::O, Action::Press, _) => {
+                self.renderer.toggle_bloom();
+                true
+            }
+            WindowEvent::Key(Key::E, Action::Press, _) => {
+                let depth_prepass = !self.renderer.depth_prepass();
+                self.renderer.set_depth_prepass(depth_prepass);
+                true
+            }
+            WindowEvent::Key(Key::X, Action::Press, _) => {
+                let objects = self.renderer.selectable_objects();
+                self.cycle_selection(&objects);
+                true
+            }
+            WindowEvent::Key(Key::H, Action::Press, modifiers) if modifiers.shift => {
+                let depth_test = !self.renderer.debug_depth_test();
+                self.renderer.set_debug_depth_test(depth_test);
+                println!(
+                    "Debug lines: {}",
+                    if depth_test { "depth tested" } else { "on top" }
+                );
+                true
+            }
+            WindowEvent::Key(Key::H, Action::Press, _) => {
+                let mut views = self.renderer.debug_views();
+                views.bounds = !views.bounds;
+                println!("Bounds: {}", if views.bounds { "on" } else { "off" });
+                self.renderer.set_debug_views(views);
+                true
+            }
+            WindowEvent::Key(Key::J, Action::Press, _) => {
+                let mut views = self.renderer.debug_views();
+                views.world_axes = !views.world_axes;
+                println!(
+                    "World axes: {}",
+                    if views.world_axes { "on" } else { "off" }
+                );
+                self.renderer.set_debug_views(views);
+                true
+            }
+            WindowEvent::Key(Key::Y, Action::Press, _) => {
+                let mut views = self.renderer.debug_views();
+                views.cull_frustum = !views.cull_frustum;
+                println!(
+                    "Culling frustum lines: {}",
+                    if views.cull_frustum { "on" } else { "off" }
+                );
+                self.renderer.set_debug_views(views);
+                true
+            }
+            WindowEvent::Key(Key::I, Action::Press, _) => {
+                let indirect = !self.renderer.indirect();
+                self.renderer.set_indirect(indirect);
+                true
+            }
+            WindowEvent::Key(Key::U, Action::Press, _) => {
+                let gpu_cull = !self.renderer.gpu_cull();
+                self.renderer.set_gpu_cull(gpu_cull);
+                true
+            }
+            WindowEvent::Key(Key::K, Action::Press, _) => {
+                let aspect = match self.renderer.fixed_aspect() {
+                    Some(_) => None,
+                    None => Some(LETTERBOX_ASPECT),
+                };
+                self.set_fixed_aspect(aspect);
+                true
+            }
+            WindowEvent::Key(Key::L, Action::Press, _) => {
+                self.renderer.toggle_colors();
+                true
+            }
+            WindowEvent::Key(Key::F11, Action::Press, _) => {
+                self.toggle_fullscreen();
+                true
+            }
+            WindowEvent::Key(Key::Tab, Action::Press, _) => {
+                self.capture_held = false;
+                self.set_cursor_captured(!self.cursor_captured);
+                true
+            }
+            WindowEvent::MouseButton(MouseButton::Left, Action::Press, _)
+                if !self.cursor_captured =>
+            {
+                let (x, y) = self.surface.window.cursor_pos();
+                self.pick(x, y);
+                true
+            }
+            WindowEvent::MouseButton(MouseButton::Right, Action::Press, _) => {
+                if !self.cursor_captured {
+                    self.capture_held = true;
+                    self.set_cursor_captured(true);
+                }
+                true
+            }
+            WindowEvent::MouseButton(MouseButton::Right, Action::Release, _) => {
+                if self.capture_held {
+                    self.capture_held = false;
+                    self.set_cursor_captured(false);
+                }
+                true
+            }
+            WindowEvent::Focus(false) => {
+                self.capture_held = false;
+                self.set_cursor_captured(false);
+                false
+            }
+            WindowEvent::Focus(true) => {
+                // The framebuffer may have changed while another window had
+                // focus, e.g. after alt-tabbing out of fullscreen.
+                let (width, height) = self.surface.window.framebuffer_size();
+                self.resize(width, height);
+                false
+            }
+            WindowEvent::Key(Key::F12, Action::Press, _) => {
+                self.take_screenshot();
+                true
+            }
+            WindowEvent::Key(Key::F8, Action::Press, _) => {
+                self.trigger_validation_error();
+                true
+            }
+            WindowEvent::Key(Key::N, Action::Press, _) => {
+                self.spawn_cube();
+                true
+            }
+            WindowEvent::Key(Key::Delete, Action::Press, _) => {
+                self.despawn_cube();
+                true
+            }
+            WindowEvent::Key(Key::F2, Action::Press, _) => {
+                self.renderer.toggle_projection();
+                true
+            }
+            WindowEvent::Key(Key::F4, Action::Press, _) => {
+                self.cycle_particle_emitter();
+                true
+            }
+            WindowEvent::Key(Key::F3, Action::Press, _) => {
+                self.toggle_tilemap_demo();
+                true
+            }
+            WindowEvent::Key(Key::F6, Action::Press, _) => {
+                self.ray_picking = !self.ray_picking;
+                println!(
+                    "Picking: {}",
+                    match self.ray_picking {
+                        true => "ray casting against the scene",
+                        false => "GPU id buffer",
+                    }
+                );
+                true
+            }
+            WindowEvent::Key(Key::F7, Action::Press, modifiers) if modifiers.shift => {
+                let mut settings = self.renderer.shadow_settings();
+                settings.enabled = !settings.enabled;
+                println!("Shadows: {}", if settings.enabled { "on" } else { "off" });
+                self.renderer.set_shadow_settings(settings);
+                true
+            }
+            WindowEvent::Key(Key::F7, Action::Press, _) => {
+                let mut views = self.renderer.debug_views();
+                views.shadow_map = !views.shadow_map;
+                println!(
+                    "Shadow map overlay: {}",
+                    if views.shadow_map { "on" } else { "off" }
+                );
+                self.renderer.set_debug_views(views);
+                true
+            }
+            WindowEvent::Key(Key::F9, Action::Press, _) => {
+                self.renderer.toggle_light_freeze();
+                true
+            }
+            WindowEvent::Key(Key::Q, Action::Press, _) => {
+                self.toggle_sprite_demo();
+                true
+            }
+            WindowEvent::Key(Key::M, Action::Press, _) => {
+                let sample_count = if self.renderer.sample_count > 1 { 1 } else { 4 };
+                self.renderer.set_sample_count(sample_count);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        if captured == self.cursor_captured {
+            return;
+        }
+        self.cursor_captured = captured;
+        self.surface.window.set_cursor_captured(captured);
+        // Disabling the cursor recenters it; don't turn that jump into a delta.
+        self.input.reset_mouse();
+    }
 
-        let adapter = instance.request_adapter(&adapter_descriptor).await.unwrap();
+    /// Reads the gamepad, when built with the `gamepad` feature.
+    fn poll_gamepads(&mut self) {
+        #[cfg(feature = "gamepad")]
+        if let Some(gamepads) = &mut self.gamepads {
+            gamepads.poll(&mut self.input);
+        }
+    }
 
-        let device_descriptor = wgpu::DeviceDescriptor {
-            required_features: wgpu::Features::empty(),
-            required_limits: wgpu::Limits::default(),
-            label: Some("Device"),
-            memory_hints: wgpu::MemoryHints::Performance,
+    fn update(&mut self, dt: f32) {
+        self.health.set_stage("update");
+        if let Some(shadertoy) = &mut self.shadertoy {
+            let cursor = self
+                .input
+                .mouse_position()
+                .map(|(x, y)| self.metrics.cursor_to_pixels(x, y));
+            let pressed = self.input.is_button_pressed(MouseButton::Left);
+            shadertoy.set_mouse(cursor, pressed, self.renderer.config.height);
+            shadertoy.update(dt, self.renderer.config.format);
+            self.input.end_frame();
+            return;
+        }
+        for command in self.input.take_commands() {
+            match command {
+                Command::CycleSelection => {
+                    let objects = self.renderer.scene_selectable_objects();
+                    self.cycle_selection(&objects);
+                }
+            }
+        }
+        if let Some(picked) = self.renderer.take_pick() {
+            self.set_selected(picked);
+        }
+        let stick_target = match self.renderer.selected_node() {
+            Some(_) => StickTarget::Node,
+            None => StickTarget::Camera,
         };
+        self.input.set_stick_target(stick_target);
+        self.camera_controller.update_camera(
+            &mut self.renderer.camera,
+            &self.input,
+            dt,
+            self.cursor_captured,
+        );
+        self.move_selected_node(dt);
+        self.input.end_frame();
+        if let Some(sprite_demo) = self.sprite_demo.as_mut().filter(|_| self.show_sprite_demo) {
+            sprite_demo.update(dt, self.renderer.config.width, self.renderer.config.height);
+        }
+        if let Some(tilemap_demo) = &mut self.tilemap_demo {
+            tilemap_demo.update(dt, &self.renderer.camera);
+        }
 
-        let (device, queue) = adapter
-            .request_device(&device_descriptor, None)
-            .await
-            .unwrap();
+        self.renderer.update(dt);
+    }
+
+    /// Points the scene's directional light along `direction` (the way the
+    /// light travels) and gives it `color`.
+    fn set_light(&mut self, direction: glm::Vec3, color: glm::Vec3) {
+        self.renderer.set_light(direction, color);
+    }
+
+    /// Spacing, line colors and fade of the ground grid R toggles.
+    fn set_grid_settings(&mut self, settings: grid_pass::GridSettings) {
+        self.renderer.set_grid_settings(settings);
+    }
+
+    fn present_mode(&self) -> wgpu::PresentMode {
+        self.surface.present_mode()
+    }
 
-        let surface_capabilities = surface.get_capabilities(&adapter);
-        let surface_format = surface_capabilities
-            .formats
-            .iter()
-            .copied()
-            .filter(|f| f.is_srgb())
-            .next()
-            .unwrap_or(surface_capabilities.formats[0]);
+    fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        self.surface.set_present_mode(mode)
+    }
 
-        let config = wgpu::SurfaceConfiguration {
-            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
-            format: surface_format,
-            width: size.0 as u32,
-            height: size.1 as u32,
-            present_mode: surface_capabilities.present_modes[0],
-            alpha_mode: surface_capabilities.alpha_modes[0],
-            view_formats: vec![],
-            desired_maximum_frame_latency: 2,
+    /// Presents with `Fifo` when `vsync` is set, and otherwise with
+    /// `Immediate` or, failing that, `Mailbox`.
+    fn set_vsync(&mut self, vsync: bool) {
+        let modes: &[wgpu::PresentMode] = match vsync {
+            true => &[wgpu::PresentMode::Fifo],
+            false => &[wgpu::PresentMode::Immediate, wgpu::PresentMode::Mailbox],
         };
+        if !modes.iter().any(|&mode| self.set_present_mode(mode)) {
+            eprintln!(
+                "Warning: none of {:?} is supported, presenting with {:?}",
+                modes,
+                self.present_mode()
+            );
+        }
+    }
 
-        surface.configure(&device, &config);
+    fn cycle_present_mode(&mut self) {
+        const MODES: [wgpu::PresentMode; 3] = [
+            wgpu::PresentMode::Fifo,
+            wgpu::PresentMode::Mailbox,
+            wgpu::PresentMode::Immediate,
+        ];
+        let current = MODES.iter().position(|&mode| mode == self.present_mode());
+        let start = current.map_or(0, |index| index + 1);
+        for offset in 0..MODES.len() {
+            if self.set_present_mode(MODES[(start + offset) % MODES.len()]) {
+                break;
+            }
+        }
+        println!("Present mode: {:?}", self.present_mode());
+    }
 
-        let triangle_mesh = mesh_builder::make_triangle(&device);
-        let quad_mesh = mesh_builder::make_quad(&device);
+    /// Caps the frame rate at `max_fps` while presenting doesn't wait for
+    /// vertical blank, which paces frames well enough by itself.
+    fn set_max_fps(&mut self, max_fps: Option<u32>) {
+        self.frame_limiter.set_max_fps(max_fps);
+    }
 
-        let material_bind_group_layout: wgpu::BindGroupLayout;
+    /// Waits out the rest of the frame under `set_max_fps`, unless the
+    /// present mode already did.
+    fn limit_frame(&mut self) {
+        match self.present_mode() {
+            wgpu::PresentMode::Fifo | wgpu::PresentMode::FifoRelaxed => self.frame_limiter.reset(),
+            _ => self.frame_limiter.wait(),
+        }
+    }
+
+    fn set_frame_stats(&mut self, frame_ms: f32, fps: f32) {
+        self.frame_ms = frame_ms;
+        self.fps = fps;
+        self.prepass_frame_ms[self.renderer.depth_prepass() as usize] = Some(frame_ms);
+    }
+
+    fn debug_settings(&self) -> DebugSettings {
+        let clear_color = self.renderer.clear_color();
+        let (light_direction, light_color) = self.renderer.light();
+        DebugSettings {
+            adapter_name: self.renderer.adapter.get_info().name,
+            frame_ms: self.frame_ms,
+            fps: self.fps,
+            clear_color: [
+                clear_color.r as f32,
+                clear_color.g as f32,
+                clear_color.b as f32,
+            ],
+            animate_clear_color: self.renderer.animate_clear_color(),
+            camera_speed: self.camera_controller.speed(),
+            light_direction: *light_direction.as_array(),
+            light_color: *light_color.as_array(),
+            shadow_settings: self.renderer.shadow_settings(),
+            wireframe: self.renderer.wireframe(),
+            depth_prepass: self.renderer.depth_prepass(),
+            stencil: self.renderer.stencil(),
+            indirect: self.renderer.indirect(),
+            gpu_cull: self.renderer.gpu_cull(),
+            skybox: self.renderer.skybox(),
+            grid: self.renderer.grid(),
+            grid_settings: self.renderer.grid_settings(),
+            wave: self.renderer.wave(),
+            wave_resolution: self.renderer.wave_resolution(),
+            srgb_colors: self.renderer.colors() == ColorSpace::Srgb,
+            msaa: self.renderer.sample_count > 1,
+            sampler: self.renderer.sampler(),
+            bloom: self.renderer.bloom(),
+            bloom_settings: self.renderer.bloom_settings(),
+            post_effect: self.renderer.post_effect(),
+            present_mode: self.present_mode(),
+        }
+    }
+
+    /// Applies whatever the debug window changed since `debug_settings`.
+    fn apply_debug_settings(&mut self, settings: DebugSettings) {
+        let current = self.debug_settings();
+        if settings.clear_color != current.clear_color {
+            let [r, g, b] = settings.clear_color;
+            self.renderer.set_clear_color(wgpu::Color {
+                r: r as f64,
+                g: g as f64,
+                b: b as f64,
+                a: self.renderer.clear_color().a,
+            });
+        }
+        if settings.animate_clear_color != current.animate_clear_color {
+            self.renderer.toggle_clear_color_animation();
+        }
+        self.camera_controller.set_speed(settings.camera_speed);
+        if settings.light_direction != current.light_direction
+            || settings.light_color != current.light_color
+        {
+            self.set_light(
+                *glm::Vec3::from_array(&settings.light_direction),
+                *glm::Vec3::from_array(&settings.light_color),
+            );
+        }
+        if settings.shadow_settings != current.shadow_settings {
+            self.renderer.set_shadow_settings(settings.shadow_settings);
+        }
+        if settings.wireframe != current.wireframe {
+            self.renderer.toggle_wireframe();
+        }
+        if settings.depth_prepass != current.depth_prepass {
+            self.renderer.set_depth_prepass(settings.depth_prepass);
+        }
+        if settings.stencil != current.stencil {
+            self.renderer.set_stencil(settings.stencil);
+        }
+        if settings.indirect != current.indirect {
+            self.renderer.set_indirect(settings.indirect);
+        }
+        if settings.gpu_cull != current.gpu_cull {
+            self.renderer.set_gpu_cull(settings.gpu_cull);
+        }
+        if settings.skybox != current.skybox {
+            self.renderer.toggle_skybox();
+        }
+        if settings.grid != current.grid {
+            self.renderer.toggle_grid();
+        }
+        if settings.grid_settings != current.grid_settings {
+            self.set_grid_settings(settings.grid_settings);
+        }
+        if settings.wave != current.wave {
+            self.renderer.toggle_wave();
+        }
+        if settings.wave_resolution != current.wave_resolution {
+            self.renderer.set_wave_resolution(settings.wave_resolution);
+        }
+        if settings.srgb_colors != current.srgb_colors {
+            self.renderer.toggle_colors();
+        }
+        if settings.msaa != current.msaa {
+            self.renderer
+                .set_sample_count(if settings.msaa { 4 } else { 1 });
+        }
+        if settings.sampler != current.sampler {
+            self.renderer.set_sampler(settings.sampler);
+        }
+        if settings.bloom != current.bloom {
+            self.renderer.toggle_bloom();
+        }
+        if settings.bloom_settings != current.bloom_settings {
+            self.renderer.set_bloom_settings(settings.bloom_settings);
+        }
+        if settings.post_effect != current.post_effect {
+            self.renderer.set_post_effect(settings.post_effect);
+        }
+        if settings.present_mode != current.present_mode
+            && !self.set_present_mode(settings.present_mode)
         {
-            let mut builder = bind_group_layout::Builder::new(&device);
-            builder.add_material();
-            material_bind_group_layout = builder.build("Material Bind Group Layout");
+            eprintln!("{:?} is not supported", settings.present_mode);
+        }
+    }
+
+    /// Letterboxes the scene to `aspect`, width over height, or lets it fill
+    /// the window again with `None`. The text overlay and debug UI still
+    /// cover the whole window.
+    fn set_fixed_aspect(&mut self, aspect: Option<f32>) {
+        self.renderer.set_fixed_aspect(aspect);
+    }
+
+    /// Converts a GLFW cursor position to pixels from the scene viewport's
+    /// corner, for anything that picks from what the scene shows. `None`
+    /// when the cursor is over the letterbox bars.
+    fn cursor_in_viewport(&self, x: f64, y: f64) -> Option<(f32, f32)> {
+        let (x, y) = self.metrics.cursor_to_pixels(x, y);
+        match self.renderer.viewport() {
+            Some(viewport) => viewport.to_viewport(x, y),
+            None => Some((x, y)),
+        }
+    }
+
+    /// Selects whatever is drawn under the cursor, given in GLFW's window
+    /// coordinates, once the GPU has read it back, or right away with
+    /// `ray_picking` or without a GPU picker. Clicking empty space or the
+    /// letterbox bars clears the selection.
+    fn pick(&mut self, cursor_x: f64, cursor_y: f64) {
+        let Some((x, y)) = self.cursor_in_viewport(cursor_x, cursor_y) else {
+            self.set_selected(None);
+            return;
+        };
+        if self.ray_picking || !self.renderer.gpu_picking() {
+            let hit = self.renderer.raycast(x, y);
+            if let Some((_, hit)) = &hit {
+                println!(
+                    "Hit {:?} {:.2} away at {:.2?}",
+                    hit.entity,
+                    hit.distance,
+                    hit.point.as_array()
+                );
+            }
+            self.set_selected(hit.map(|(object, _)| object));
+            return;
+        }
+        // The pick target is the whole framebuffer, bars included.
+        let (x, y) = self.metrics.cursor_to_pixels(cursor_x, cursor_y);
+        self.renderer
+            .request_pick(x.max(0.0) as u32, y.max(0.0) as u32);
+    }
+
+    /// Flies the camera back or forward until the selected object fills the
+    /// view, without turning it.
+    fn frame_selected(&mut self) {
+        let Some(bounds) = self.renderer.selected_bounds() else {
+            println!("Nothing selected to frame, click something or press X");
+            return;
+        };
+        let camera = &self.renderer.camera;
+        let mut framed = camera.clone();
+        framed.frame(&bounds);
+        let bookmark = Bookmark {
+            position: framed.position,
+            ..self.camera_controller.bookmark(camera)
+        };
+        self.camera_controller.fly_to(camera, bookmark);
+        // Orthographic views change size rather than fly.
+        self.renderer.camera.projection = framed.projection;
+    }
+
+    /// Keeps the current viewpoint in `slot` for `restore_bookmark`, across
+    /// restarts too.
+    fn save_bookmark(&mut self, slot: usize) {
+        let bookmark = self.camera_controller.bookmark(&self.renderer.camera);
+        match self.bookmarks.set(slot, bookmark) {
+            Ok(_) => println!("Saved camera bookmark {}", slot + 1),
+            Err(e) => eprintln!(
+                "Camera bookmark {} kept until exit, can't write {}: {}",
+                slot + 1,
+                self.bookmarks.path().display(),
+                e
+            ),
+        }
+    }
+
+    fn restore_bookmark(&mut self, slot: usize) {
+        match self.bookmarks.get(slot) {
+            Some(bookmark) => self
+                .camera_controller
+                .fly_to(&self.renderer.camera, bookmark),
+            None => println!(
+                "No camera bookmark {}, Ctrl+{} saves one",
+                slot + 1,
+                slot + 1
+            ),
         }
+    }
 
-        let render_pipeline: wgpu::RenderPipeline;
+    /// Applies what saving the settings file changed from `old` to `new`,
+    /// as far as it can change while running, with a line for each change.
+    fn apply_settings(&mut self, old: &Settings, new: &Settings) {
+        if new.display.clear_color != old.display.clear_color {
+            self.renderer.set_clear_color(new.clear_color());
+            println!("Settings: clear color {}", new.display.clear_color);
+        }
+        if new.shadows != old.shadows {
+            self.renderer.set_shadow_settings(new.shadows);
+            println!(
+                "Settings: shadows {}, bias {}",
+                if new.shadows.enabled { "on" } else { "off" },
+                new.shadows.bias
+            );
+        }
+        if new.camera.speed != old.camera.speed {
+            self.camera_controller.set_speed(new.camera.speed);
+            println!("Settings: camera speed {}", new.camera.speed);
+        }
+        if new.camera.sensitivity != old.camera.sensitivity {
+            self.camera_controller
+                .set_sensitivity(new.camera.sensitivity);
+            println!("Settings: camera sensitivity {}", new.camera.sensitivity);
+        }
+        if new.display.vsync != old.display.vsync
+            || new.display.present_mode != old.display.present_mode
         {
-            let mut builder = pipeline::Builder::new(&device);
-            builder.set_shader_module("shaders/shader.wgsl", "vs_main", "fs_main");
-            builder.set_pixel_format(config.format);
-            builder.add_vertex_buffer_layout(mesh_builder::Vertex::get_layout());
-            builder.add_bind_group_layout(&material_bind_group_layout);
-            render_pipeline = builder.build_pipeline("Render Pipeline");
-        }
-
-        let triangle_material = Material::new(
-            "img/satin.jpg",
-            &device,
-            &queue,
-            &material_bind_group_layout,
+            match new.display.present_mode {
+                Some(mode) if !self.set_present_mode(mode.to_wgpu()) => {
+                    eprintln!("Warning: {:?} is not supported", mode.to_wgpu())
+                }
+                Some(_) => {}
+                None => self.set_vsync(new.display.vsync),
+            }
+            println!("Settings: present mode {:?}", self.present_mode());
+        }
+        if new.display.msaa != old.display.msaa {
+            self.renderer.set_sample_count(new.display.msaa);
+            println!("Settings: {}x MSAA", self.renderer.sample_count);
+        }
+        if new.display.max_fps != old.display.max_fps {
+            self.set_max_fps(new.display.max_fps);
+            match new.display.max_fps {
+                Some(fps) => println!("Settings: frame rate capped at {}", fps),
+                None => println!("Settings: frame rate uncapped"),
+            }
+        }
+
+        let restart_only = [
+            ("window", new.window != old.window),
+            ("gpu.backend", new.gpu.backend != old.gpu.backend),
+            ("gpu.adapter", new.gpu.adapter != old.gpu.adapter),
+            ("gpu.power", new.gpu.power != old.gpu.power),
+            (
+                "gpu.fallback_adapter",
+                new.gpu.fallback_adapter != old.gpu.fallback_adapter,
+            ),
+        ];
+        for (name, _) in restart_only.iter().filter(|(_, changed)| *changed) {
+            println!("Settings: {} changed, which requires restart", name);
+        }
+    }
+
+    /// Outlines `selected` in the main window, or nothing. Needs the stencil
+    /// buffer, see `--stencil`.
+    fn set_selected(&mut self, selected: Option<ObjectId>) {
+        self.renderer.set_selected(selected);
+    }
+
+    fn scene_mut(&mut self) -> &mut Scene {
+        self.renderer.scene_mut()
+    }
+
+    /// Adds a small cube at a pseudo-random spot in front of where the
+    /// camera starts, every other one textured.
+    fn spawn_cube(&mut self) {
+        let seed = self.frame as f32;
+        let translation = glm::vec3(
+            hash_unit(seed) * 6.0 - 3.0,
+            hash_unit(seed + 1.0) * 2.0 - 0.5,
+            -2.0 - hash_unit(seed + 2.0) * 4.0,
         );
-        let quad_material = Material::new(
-            "img/grunge.jpg",
-            &device,
-            &queue,
-            &material_bind_group_layout,
+        let material = match self.spawned.len() % 2 {
+            0 => renderer::SCENE_PLAIN_MATERIAL,
+            _ => renderer::SCENE_TEXTURED_MATERIAL,
+        };
+        let scene = self.scene_mut();
+        let cube = scene.add_node(None);
+        scene.set_local_transform(
+            cube,
+            Transform {
+                translation: translation,
+                rotation: glm::vec3(0.0, hash_unit(seed + 3.0) * std::f32::consts::TAU, 0.0),
+                scale: glm::vec3(0.3, 0.3, 0.3),
+            },
         );
+        scene.set_mesh(cube, Some(renderer::SCENE_CUBE_MESH));
+        scene.set_material(cube, material);
+        self.spawned.push(cube);
+        println!("Cubes added: {}", self.spawned.len());
+    }
 
-        Self {
-            instance,
-            window,
-            surface,
-            device,
-            queue,
-            config,
-            size,
-            render_pipeline,
-            triangle_mesh,
-            quad_mesh,
-            triangle_material,
-            quad_material,
+    /// Removes the cube added last, if there's one left.
+    fn despawn_cube(&mut self) {
+        let Some(cube) = self.spawned.pop() else {
+            return;
+        };
+        self.scene_mut().remove(cube, Children::Remove);
+        println!("Cubes added: {}", self.spawned.len());
+    }
+
+    /// Moves the selected scene node, if there is one, with the arrow keys
+    /// and PageUp and PageDown.
+    fn move_selected_node(&mut self, dt: f32) {
+        let Some(node) = self.renderer.selected_node() else {
+            return;
+        };
+        let mut transform = self.renderer.scene().local_transform(node);
+        if self
+            .object_controller
+            .update_transform(&mut transform, &self.input, dt)
+        {
+            self.scene_mut().set_local_transform(node, transform);
         }
     }
 
-    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
-        let drawable = self.surface.get_current_texture()?;
-        let image_view_descriptor = wgpu::TextureViewDescriptor::default();
-        let image_view = drawable.texture.create_view(&image_view_descriptor);
-
-        let command_encoder_descriptor = wgpu::CommandEncoderDescriptor {
-            label: Some("Render Encoder"),
-        };
-
-        let mut command_encoder = self
-            .device
-            .create_command_encoder(&command_encoder_descriptor);
-
-        let color_attachment = wgpu::RenderPassColorAttachment {
-            view: &image_view,
-            resolve_target: None,
-            ops: wgpu::Operations {
-                load: wgpu::LoadOp::Clear(wgpu::Color {
-                    r: 0.2,
-                    g: 0.4,
-                    b: 0.6,
-                    a: 0.0,
-                }),
-                store: wgpu::StoreOp::Store,
-            },
+    /// Selects the object after the selected one in `objects`, then nothing,
+    /// then the first. Selects the first when the selected one isn't there.
+    fn cycle_selection(&mut self, objects: &[ObjectId]) {
+        let position = self
+            .renderer
+            .selected()
+            .map(|selected| objects.iter().position(|&object| object == selected));
+        let next = match position {
+            Some(Some(i)) => objects.get(i + 1).copied(),
+            Some(None) | None => objects.first().copied(),
         };
+        self.set_selected(next);
+    }
+
+    /// Switches between windowed mode and fullscreen, see
+    /// `Window::toggle_fullscreen`.
+    fn toggle_fullscreen(&mut self) {
+        self.surface.window.toggle_fullscreen();
+        let (width, height) = self.surface.window.framebuffer_size();
+        self.resize(width, height);
+    }
+
+    /// Draws `path`'s shader in place of the scene from now on.
+    fn start_shadertoy(&mut self, path: &Path) {
+        self.shadertoy = Some(ShadertoyPass::new(
+            &self.renderer.device,
+            &self.renderer.queue,
+            self.renderer.config.format,
+            path,
+        ));
+    }
+
+    /// Writes every presented frame, or every few, into `options.out_dir`
+    /// from now on, overlay and debug UI included.
+    fn start_recording(&mut self, options: RecordOptions) -> Result<(), RendererError> {
+        if !self.surface.set_copyable(true) {
+            return Err(RendererError::InvalidArgument(
+                "--record: frames can't be copied out of this window's surface".to_string(),
+            ));
+        }
+        self.recorder = Some(FrameRecorder::new(options, self.renderer.config.format)?);
+        Ok(())
+    }
+
+    /// Writes out the frames still being recorded before the app exits.
+    fn finish_recording(&mut self) {
+        if let Some(recorder) = self.recorder.take() {
+            recorder.finish(&self.renderer.device);
+        }
+    }
 
-        let render_pass_descriptor = wgpu::RenderPassDescriptor {
-            label: Some("Render Pass"),
-            color_attachments: &[Some(color_attachment)],
-            depth_stencil_attachment: None,
-            timestamp_writes: None,
-            occlusion_query_set: None,
+    fn toggle_sprite_demo(&mut self) {
+        self.show_sprite_demo = !self.show_sprite_demo;
+        if !self.show_sprite_demo {
+            println!("Sprite demo: off");
+            return;
+        }
+        if self.sprite_demo.is_some() {
+            println!("Sprite demo: {} sprites", sprite_demo::SPRITE_COUNT);
+            return;
+        }
+        match BouncingSprites::new(
+            &self.renderer.device,
+            &self.renderer.queue,
+            &mut self.sprites,
+            sprite_demo::SPRITE_COUNT,
+            self.renderer.config.width,
+            self.renderer.config.height,
+        ) {
+            Ok(sprite_demo) => {
+                println!("Sprite demo: {} sprites", sprite_demo::SPRITE_COUNT);
+                self.sprite_demo = Some(sprite_demo);
+            }
+            Err(e) => {
+                eprintln!("Sprite demo: {}", e);
+                self.show_sprite_demo = false;
+            }
+        }
+    }
+
+    /// Switches the particles between sparks and smoke.
+    fn cycle_particle_emitter(&mut self) {
+        let (name, emitter) = match self.renderer.particle_emitter().blend {
+            ParticleBlend::Additive => ("smoke", Emitter::smoke()),
+            ParticleBlend::Alpha => ("fountain", Emitter::fountain()),
         };
+        println!("Particles: {}", name);
+        self.renderer.set_particle_emitter(emitter);
+    }
+
+    fn toggle_tilemap_demo(&mut self) {
+        if self.tilemap_demo.take().is_some() {
+            println!("Tilemap demo: off");
+            return;
+        }
+        match TilemapDemo::new(
+            &self.renderer.device,
+            &self.renderer.queue,
+            self.renderer.config.format,
+            self.renderer.config.width,
+            self.renderer.config.height,
+        ) {
+            Ok(tilemap_demo) => {
+                println!("Tilemap demo: {}", tilemap_demo::MAP_PATH);
+                self.tilemap_demo = Some(tilemap_demo);
+            }
+            Err(e) => eprintln!("Tilemap demo: {}", e),
+        }
+    }
 
+    fn take_screenshot(&mut self) {
+        // Reading the frame back waits on the GPU, which a browser tab can't.
+        if cfg!(target_arch = "wasm32") {
+            eprintln!("Screenshots aren't supported in the browser");
+            return;
+        }
+        let path = PathBuf::from(screenshot::timestamped_filename());
+        match self.renderer.capture_frame(&path) {
+            Ok(_) => println!("Saved {}", path.display()),
+            Err(e) => eprintln!("Screenshot failed: {}", e),
+        }
+    }
+
+    /// Writes past the end of a buffer, to try out the error handlers and
+    /// device recreation.
+    fn trigger_validation_error(&self) {
+        println!("Triggering a validation error");
+        self.health.set_stage("validation error test");
+        let buffer = self.gpu.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Validation Error Buffer"),
+            size: 4,
+            usage: wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        self.gpu.queue.write_buffer(&buffer, 0, &[0; 8]);
+    }
+
+    /// Opens a new device after the old one failed and rebuilds everything
+    /// that was created on it, keeping the scene and settings.
+    async fn recreate(
+        &mut self,
+        adapter_options: &adapter::AdapterOptions,
+    ) -> Result<(), RendererError> {
+        let gpu = Arc::new(
+            self.gpu
+                .recreate(self.surface.surface(), adapter_options)
+                .await?,
+        );
+        self.health = DeviceHealth::install(&gpu.device);
+        self.surface.set_gpu(gpu.clone())?;
+        self.renderer
+            .recreate(gpu.adapter.clone(), gpu.device.clone(), gpu.queue.clone())?;
+        self.debug_ui = DebugUi::new(
+            &self.renderer.device,
+            self.renderer.config.format,
+            self.metrics.content_scale,
+        );
+        self.text = TextRenderer::new(
+            &self.renderer.device,
+            &self.renderer.queue,
+            self.renderer.config.format,
+            self.renderer.config.width,
+            self.renderer.config.height,
+        )?;
+        self.text.set_content_scale(self.metrics.content_scale);
+        self.sprites = SpriteBatch::new(
+            &self.renderer.device,
+            &self.renderer.queue,
+            self.renderer.config.format,
+            self.renderer.config.width,
+            self.renderer.config.height,
+        )?;
+        // Its atlas was on the old device; it's made again when next shown.
+        self.sprite_demo = None;
+        if self.show_sprite_demo {
+            self.show_sprite_demo = false;
+            self.toggle_sprite_demo();
+        }
+        if self.tilemap_demo.take().is_some() {
+            self.toggle_tilemap_demo();
+        }
+        if let Some(path) = self
+            .shadertoy
+            .take()
+            .map(|shadertoy| shadertoy.path().to_path_buf())
         {
-            let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
-            renderpass.set_pipeline(&self.render_pipeline);
-
-            renderpass.set_bind_group(0, &self.quad_material.bind_group, &[]);
-            renderpass.set_vertex_buffer(0, self.quad_mesh.vertex_buffer.slice(..));
-            renderpass.set_index_buffer(
-                self.quad_mesh.index_buffer.slice(..),
-                wgpu::IndexFormat::Uint16,
-            );
-            renderpass.draw_indexed(0..6, 0, 0..1);
+            self.start_shadertoy(&path);
+        }
+        if let Some(recorder) = &mut self.recorder {
+            recorder.device_lost();
+        }
+        self.gpu = gpu;
+        Ok(())
+    }
 
-            renderpass.set_bind_group(0, &self.triangle_material.bind_group, &[]);
-            renderpass.set_vertex_buffer(0, self.triangle_mesh.slice(..));
-            renderpass.draw(0..3, 0..1);
+    fn render(&mut self) -> Result<(), wgpu::SurfaceError> {
+        self.frame += 1;
+        self.health.begin_frame(self.frame);
+        let size_in_pixels = [self.renderer.config.width, self.renderer.config.height];
+        let margin = TEXT_MARGIN * self.metrics.content_scale;
+        let stats = self.stats_text();
+        let mut settings = self.debug_settings();
+        if let Some(sprite_demo) = self.sprite_demo.as_ref().filter(|_| self.show_sprite_demo) {
+            sprite_demo.queue(&mut self.sprites);
         }
-        self.queue.submit(std::iter::once(command_encoder.finish()));
 
-        drawable.present();
+        let renderer = &mut self.renderer;
+        let text = &mut self.text;
+        let sprites = &mut self.sprites;
+        let tilemap_demo = &mut self.tilemap_demo;
+        let shadertoy = &mut self.shadertoy;
+        let debug_ui = &mut self.debug_ui;
+        let recorder = &mut self.recorder;
+        let health = &self.health;
+        health.set_stage("acquire");
+        self.surface
+            .render(|command_encoder, image_view, texture| {
+                match shadertoy {
+                    Some(shadertoy) => {
+                        health.set_stage("shadertoy");
+                        let [width, height] = size_in_pixels;
+                        shadertoy.encode(command_encoder, image_view, width, height);
+                    }
+                    None => {
+                        health.set_stage("scene");
+                        renderer.encode(command_encoder, image_view);
+                        if let Some(tilemap_demo) = tilemap_demo {
+                            health.set_stage("tilemap");
+                            tilemap_demo.encode(command_encoder, image_view);
+                        }
+                        health.set_stage("sprites");
+                        sprites.encode(command_encoder, image_view);
+                    }
+                }
+                health.set_stage("text");
+                text.queue_text(margin, margin, &stats, [1.0, 1.0, 1.0, 1.0]);
+                text.encode(command_encoder, image_view);
+
+                health.set_stage("debug ui");
+                let ui_output = debug_ui.run(size_in_pixels, &mut settings);
+                let command_buffers = debug_ui.paint(
+                    &renderer.device,
+                    &renderer.queue,
+                    command_encoder,
+                    image_view,
+                    size_in_pixels,
+                    ui_output,
+                );
+                if let Some(recorder) = recorder {
+                    recorder.capture(&renderer.device, command_encoder, texture);
+                }
+                health.set_stage("submit");
+                command_buffers
+            })?;
+        self.renderer.frame_submitted();
+        if let Some(recorder) = &mut self.recorder {
+            recorder.frame_submitted();
+            recorder.poll(&self.renderer.device);
+        }
+        self.apply_debug_settings(settings);
 
         Ok(())
     }
 
-    fn resize(&mut self, new_size: (i32, i32)) {
-        if new_size.0 > 0 && new_size.1 > 0 {
-            self.size = new_size;
-            self.config.width = new_size.0 as u32;
-            self.config.height = new_size.1 as u32;
-            self.surface.configure(&self.device, &self.config);
+    /// Frame time and camera position for the top-left corner.
+    fn stats_text(&self) -> String {
+        if let Some(shadertoy) = &self.shadertoy {
+            return format!(
+                "{:.1} ms / {:.0} fps\n{}\n{}",
+                self.frame_ms,
+                self.fps,
+                shadertoy.path().display(),
+                shadertoy.error().unwrap_or_default()
+            );
+        }
+        let position = self.renderer.camera.position;
+        let culling = self.renderer.cull_stats();
+        let uploads = self.renderer.upload_stats();
+        let cap = match self.frame_limiter.max_fps() {
+            Some(max_fps) => format!(" (cap {})", max_fps),
+            None => String::new(),
+        };
+        let stats = format!(
+            "{:.1} ms / {:.0} fps{}\ncamera {:.2} {:.2} {:.2}\nobjects {} drawn / {} culled\n{} draw calls ({})\nuploads {} writes / {} copies / {:.1} KiB",
+            self.frame_ms,
+            self.fps,
+            cap,
+            position.x,
+            position.y,
+            position.z,
+            culling.drawn,
+            culling.culled,
+            self.renderer.draw_calls(),
+            self.renderer.draw_path(),
+            uploads.writes,
+            uploads.copies,
+            uploads.bytes as f32 / 1024.0
+        );
+        let [prepass_off, prepass_on] = self.prepass_frame_ms.map(|frame_ms| match frame_ms {
+            Some(frame_ms) => format!("{:.1} ms", frame_ms),
+            None => "-".to_string(),
+        });
+        let stats = format!(
+            "{}
+depth pre-pass off {} / on {}",
+            stats, prepass_off, prepass_on
+        );
+        let particles = self.renderer.particle_stats();
+        let stats = format!(
+            "{}\nparticles {} live of {} ({})",
+            stats,
+            particles.live,
+            particles.capacity,
+            if particles.on_gpu { "gpu" } else { "cpu" }
+        );
+        let sprites = self.sprites.stats();
+        let stats = match sprites.sprites {
+            0 => stats,
+            _ => format!(
+                "{}\nsprites {} in {} batches / room for {} / {} reallocations",
+                stats, sprites.sprites, sprites.batches, sprites.capacity, sprites.reallocations
+            ),
+        };
+        let stats = match &self.tilemap_demo {
+            Some(tilemap_demo) => {
+                let tilemap = tilemap_demo.stats();
+                format!(
+                    "{}\ntilemap {} of {} chunks drawn / {} rebuilt",
+                    stats, tilemap.drawn, tilemap.chunks, tilemap.rebuilt
+                )
+            }
+            None => stats,
+        };
+        let stats = match self.renderer.gpu_cull_stats() {
+            Some(gpu_cull) => format!(
+                "{}\ngpu cull {} visible / {} differ from cpu",
+                stats, gpu_cull.visible, gpu_cull.mismatches
+            ),
+            None => stats,
+        };
+        match self.renderer.gpu_timings() {
+            Some(timings) => match timings.compute_ms {
+                Some(compute_ms) => format!(
+                    "{}\ngpu {:.2} ms render / {:.2} ms compute",
+                    stats, timings.render_ms, compute_ms
+                ),
+                None => format!("{}\ngpu {:.2} ms render", stats, timings.render_ms),
+            },
+            None => stats,
         }
     }
-}
 
-async fn run() {
-    let mut glfw = glfw::init(fail_on_errors!()).unwrap();
+    fn render_frame(&mut self) -> Result<(), RendererError> {
+        for attempt in 0..2 {
+            match self.render() {
+                Ok(_) | Err(wgpu::SurfaceError::Timeout) => break,
+                Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) if attempt == 0 => {
+                    let (width, height) = self.surface.window.framebuffer_size();
+                    self.resize(width, height);
+                }
+                Err(wgpu::SurfaceError::OutOfMemory) => {
+                    return Err(RendererError::Surface(wgpu::SurfaceError::OutOfMemory));
+                }
+                Err(e) => {
+                    eprintln!("Skipping frame: {}", e);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// While minimized the window isn't rendered; a zero-sized framebuffer
+    /// can't be presented to.
+    fn minimized(&self) -> bool {
+        self.iconified || self.zero_sized
+    }
 
-    let (mut window, events) = glfw
-        .create_window(800, 600, "It's Graphics Time", glfw::WindowMode::Windowed)
-        .unwrap();
+    /// Reconfigures for a framebuffer of `width` by `height` pixels. A zero
+    /// size leaves the surface as it is until a real size comes in.
+    fn resize(&mut self, width: i32, height: i32) {
+        self.metrics = self.surface.metrics();
+        self.zero_sized = width <= 0 || height <= 0;
+        if self.zero_sized {
+            return;
+        }
+        self.surface.resize(width, height);
+        self.renderer
+            .resize(self.surface.config.width, self.surface.config.height);
+        self.text
+            .resize(self.renderer.config.width, self.renderer.config.height);
+        self.sprites
+            .resize(self.renderer.config.width, self.renderer.config.height);
+        if let Some(tilemap_demo) = &mut self.tilemap_demo {
+            tilemap_demo.resize(self.renderer.config.width, self.renderer.config.height);
+        }
+    }
+}
 
-    window.set_key_polling(true); //set to all polling if you want  all events
-    window.set_size_polling(true);
+/// Opens the window for a second view onto the scene.
+fn open_debug_window(context: &mut platform::Context<'_>) -> Result<NativeWindow, RendererError> {
+    let window_config = WindowConfig {
+        title: "Debug View".to_string(),
+        width: 640,
+        height: 480,
+        ..WindowConfig::default()
+    };
+    NativeWindow::create(context, &window_config)
+}
 
-    window.make_current();
+/// Shows the scene in `window` from `open_debug_window`, sharing the main
+/// window's device and surface format.
+fn open_debug_view(window: NativeWindow, state: &State) -> Result<DebugView, RendererError> {
+    let surface = window.create_surface(&state.gpu.instance)?;
+    let surface = WindowSurface::new(
+        state.gpu.clone(),
+        window,
+        surface,
+        FormatRequest::Exactly(state.renderer.config.format),
+    )?;
+    Ok(DebugView::new(surface, &state.renderer))
+}
 
-    let mut state = State::new(&mut window).await;
+/// The windowed app, with what the main loop keeps from one frame to the
+/// next.
+struct App {
+    state: State,
+    debug_view: Option<DebugView>,
+    adapter_options: adapter::AdapterOptions,
+    settings_watcher: SettingsWatcher,
+    frame_timer: FrameTimer,
+    /// Keeps a trace down to a few frames.
+    frame_limit: Option<u64>,
+    bench: Option<Bench>,
+    paused: bool,
+    /// Whether the device has been recreated already.
+    recreated: bool,
+}
 
-    while !state.window.should_close() {
-        glfw.poll_events();
-        for (_, event) in glfw::flush_messages(&events) {
+impl platform::App for App {
+    fn frame(&mut self) -> Result<Flow, RendererError> {
+        let App {
+            state,
+            debug_view,
+            adapter_options,
+            settings_watcher,
+            frame_timer,
+            frame_limit,
+            bench,
+            paused,
+            recreated,
+        } = self;
+        if !state.surface.is_open() && !debug_view.as_ref().is_some_and(DebugView::is_open) {
+            state.finish_recording();
+            return Ok(Flow::Exit);
+        }
+        if bench.is_none() {
+            state.poll_gamepads();
+            if let Some((old, new)) = settings_watcher.poll() {
+                state.apply_settings(&old, &new);
+            }
+        }
+        for event in state.surface.take_events() {
+            // Input would make benchmark runs differ; only resizes get through.
+            if bench.is_some() {
+                if let WindowEvent::FramebufferSize(width, height) = event {
+                    state.resize(width, height);
+                }
+                continue;
+            }
+            if state.handle_input(&event) {
+                continue;
+            }
             match event {
-                glfw::WindowEvent::Key(Key::Escape, _, Action::Press, _) => {
-                    state.window.set_should_close(true);
+                WindowEvent::Key(Key::Escape, Action::Press, _) => {
+                    state.surface.window.set_should_close(true);
                 }
-                glfw::WindowEvent::FramebufferSize(width, height) => {
-                    state.resize((width, height));
+                WindowEvent::FramebufferSize(width, height) => {
+                    state.resize(width, height);
                 }
                 _ => {}
             }
         }
+        if let Some(debug_view) = debug_view.as_mut() {
+            for event in debug_view.surface.take_events() {
+                debug_view.handle_event(&state.renderer, &event);
+            }
+        }
+
+        if state.surface.is_open() && state.surface.window.should_close() {
+            state.surface.close();
+        }
+        if let Some(debug_view) = debug_view.as_mut() {
+            if debug_view.is_open() && debug_view.surface.window.should_close() {
+                debug_view.surface.close();
+            }
+        }
+
+        // Nothing to draw while the main window is minimized, unless the
+        // debug view is still showing the scene.
+        let was_paused = *paused;
+        *paused = state.minimized() && !debug_view.as_ref().is_some_and(DebugView::is_open);
+        if *paused {
+            return Ok(Flow::Wait);
+        }
+        if was_paused {
+            frame_timer.reset();
+            state.frame_limiter.reset();
+        }
+
+        if frame_timer.tick() {
+            state.set_frame_stats(frame_timer.frame_ms(), frame_timer.fps());
+        }
+        if let Some(bench) = bench.as_mut() {
+            bench.begin_frame();
+        }
+        match (&*bench, &state.recorder) {
+            (Some(_), _) => state.update(bench::FRAME_DELTA),
+            (None, Some(recorder)) => state.update(recorder.frame_delta()),
+            (None, None) => state.update(frame_timer.delta_seconds()),
+        }
 
-        match state.render() {
-            Ok(_) => {}
-            Err(wgpu::SurfaceError::Lost | wgpu::SurfaceError::Outdated) => {
-                state.resize(state.size);
+        if state.surface.is_open() && !state.minimized() {
+            state.render_frame()?;
+            state.surface.window.swap_buffers();
+            state.limit_frame();
+            if let Some(bench) = bench.as_mut() {
+                bench.end_frame(state.renderer.gpu_timings());
+                if bench.finished() {
+                    state.finish_recording();
+                    bench.report()?;
+                    return Ok(Flow::Exit);
+                }
+            }
+            if frame_limit.is_some_and(|limit| state.frame >= limit) {
+                state.finish_recording();
+                return Ok(Flow::Exit);
             }
-            Err(e) => eprint!("{:?}", e),
         }
-        state.window.swap_buffers();
+        if let Some(debug_view) = debug_view.as_mut().filter(|view| view.is_open()) {
+            state.health.set_stage("debug view");
+            debug_view.render_frame(&mut state.renderer)?;
+        }
+
+        // Everything gets one fresh device; failing on that too is fatal.
+        if let Some(failure) = state.health.take_failure() {
+            // A browser tab can't wait for a new device mid-frame.
+            if *recreated || cfg!(target_arch = "wasm32") {
+                return Err(RendererError::DeviceFailed(failure));
+            }
+            println!("Recreating the GPU device");
+            pollster::block_on(state.recreate(adapter_options))?;
+            if let Some(debug_view) = debug_view.as_mut() {
+                debug_view.recreate(state.gpu.clone(), &state.renderer)?;
+            }
+            *recreated = true;
+            frame_timer.reset();
+        }
+        Ok(Flow::Poll)
+    }
+}
+
+fn run() -> Result<(), RendererError> {
+    if args::has_flag("--help") {
+        args::print_help();
+        return Ok(());
+    }
+    args::check_flags()?;
+    let (mut settings, settings_path) = Settings::load()?;
+    let settings_watcher = SettingsWatcher::new(settings_path, settings.clone());
+    settings.apply_args()?;
+    let clear_color = settings.clear_color();
+    let adapter_options = settings.adapter_options(match args::flag_value("--trace")? {
+        Some(dir) => Some(adapter::trace_dir(&dir)?),
+        None => None,
+    })?;
+
+    if let Some(options) = headless::from_args()? {
+        return pollster::block_on(headless::run(options, Some(clear_color), &adapter_options));
+    }
+
+    // Everything is checked before any window opens.
+    let options = window_config::from_args(&settings)?;
+    let hdr = args::has_flag("--hdr");
+    let stencil = args::has_flag("--stencil");
+    let indirect = args::has_flag("--indirect");
+    let gpu_cull = args::has_flag("--gpu-cull");
+    // Keeps a trace down to a few frames.
+    let frame_limit = match args::flag_value("--frames")? {
+        Some(count) => Some(args::parse_count("--frames", &count)? as u64),
+        None => None,
+    };
+    let bench_options = bench::from_args()?;
+    let record_options = frame_recorder::from_args()?;
+    let model = args::flag_value("--model")?;
+    let upload_stress = match args::flag_value("--upload-stress")? {
+        Some(count) => args::parse_count("--upload-stress", &count)?,
+        None => 0,
+    };
+    let debug_view = args::has_flag("--debug-view");
+    let shadertoy = args::flag_value("--shadertoy")?.map(PathBuf::from);
+    if let Some(path) = &shadertoy {
+        shader::resolve(path)?;
     }
+
+    platform::run(move |context| {
+        let window = NativeWindow::create(context, &options.window)?;
+        let debug_window = match debug_view {
+            true => Some(open_debug_window(context)?),
+            false => None,
+        };
+        // The rest waits on the GPU, which the browser build can only do
+        // asynchronously.
+        Ok(async move {
+            let mut state =
+                State::new(window, &options.window, &settings, &adapter_options, hdr).await?;
+            if options.fullscreen {
+                state.toggle_fullscreen();
+            }
+            // A benchmark runs as fast as it can.
+            state.set_vsync(options.vsync && bench_options.is_none());
+            if let Some(mode) = options.present_mode.filter(|_| bench_options.is_none()) {
+                if !state.set_present_mode(mode) {
+                    eprintln!("Warning: {:?} is not supported, keeping vsync", mode);
+                }
+            }
+            if bench_options.is_none() {
+                state.set_max_fps(options.max_fps);
+            }
+            if options.sample_count != state.renderer.sample_count {
+                state.renderer.set_sample_count(options.sample_count);
+            }
+            if stencil {
+                state.renderer.set_stencil(true);
+            }
+            state.renderer.set_indirect(indirect);
+            if gpu_cull {
+                state.renderer.set_gpu_cull(true);
+            }
+            if let Some(model) = model {
+                state.renderer.load_model(&PathBuf::from(model))?;
+            }
+            state.renderer.set_upload_stress(upload_stress);
+            if let Some(path) = &shadertoy {
+                state.start_shadertoy(path);
+            }
+            if let Some(options) = record_options {
+                state.start_recording(options)?;
+            }
+            let debug_view = match debug_window {
+                Some(window) => Some(open_debug_view(window, &state)?),
+                None => None,
+            };
+
+            Ok::<_, RendererError>(App {
+                state: state,
+                debug_view: debug_view,
+                adapter_options: adapter_options,
+                settings_watcher: settings_watcher,
+                frame_timer: FrameTimer::new(),
+                frame_limit: frame_limit,
+                bench: bench_options.map(Bench::new),
+                paused: false,
+                recreated: false,
+            })
+        })
+    })
 }
 
 fn main() {
-    pollster::block_on(run())
+    #[cfg(target_arch = "wasm32")]
+    console_error_panic_hook::set_once();
+    if let Err(e) = run() {
+        #[cfg(target_arch = "wasm32")]
+        web_sys::console::error_1(&format!("Error: {}", e).into());
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
@@ -0,0 +1,42 @@
+use glm::*;
+
+use crate::input::{Axis, InputState};
+use crate::renderer_backend::scene::Transform;
+
+/// Moves a scene node along the node axes, in its parent's space.
+pub struct ObjectController {
+    /// In units a second.
+    speed: f32,
+}
+
+impl ObjectController {
+    /// How much faster the node moves while `InputState::fast` holds.
+    const FAST_FACTOR: f32 = 5.0;
+
+    pub fn new(speed: f32) -> Self {
+        ObjectController { speed: speed }
+    }
+
+    /// Moves `transform` by the node axes for `dt` seconds. Returns whether
+    /// any were pushed.
+    pub fn update_transform(&self, transform: &mut Transform, input: &InputState, dt: f32) -> bool {
+        let mut direction = vec3(
+            input.axis(Axis::NodeX),
+            input.axis(Axis::NodeY),
+            input.axis(Axis::NodeZ),
+        );
+        if length(direction) == 0.0 {
+            return false;
+        }
+        if length(direction) > 1.0 {
+            direction = normalize(direction);
+        }
+
+        let mut speed = self.speed;
+        if input.fast() {
+            speed *= Self::FAST_FACTOR;
+        }
+        transform.translation = transform.translation + direction * (speed * dt);
+        true
+    }
+}
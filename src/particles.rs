@@ -0,0 +1,643 @@
+use std::ops::Range;
+use std::sync::mpsc;
+
+use glm::{vec3, Vec3};
+use wgpu::util::DeviceExt;
+
+use crate::hash_unit;
+use crate::renderer_backend::{
+    bind_group, bind_group_layout, compute, frame_uploader::FrameUploader, pipeline,
+    shader::ShaderError,
+};
+
+pub const COMPUTE_SHADER_FILENAME: &str = "shaders/particle_compute.wgsl";
+pub const RENDER_SHADER_FILENAME: &str = "shaders/particles.wgsl";
+
+/// Bytes of the live and spawned counters the compute pass keeps.
+const COUNTERS_SIZE: u64 = 2 * std::mem::size_of::<u32>() as u64;
+
+/// One particle, in the storage buffer the compute pass updates and the
+/// vertex buffer the billboards are drawn from. It's dead once `age`
+/// reaches `lifetime`, until the emitter reuses its slot.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Particle {
+    position: [f32; 3],
+    age: f32,
+    velocity: [f32; 3],
+    lifetime: f32,
+    color: [f32; 4],
+    size: f32,
+    _padding: [f32; 3],
+}
+
+// Has to match the WGSL struct byte for byte.
+const _: () = assert!(std::mem::size_of::<Particle>() == 64);
+const _: () = assert!(std::mem::offset_of!(Particle, size) == 48);
+
+impl Particle {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 4] = wgpu::vertex_attr_array![
+        0 => Float32x4,
+        1 => Float32x4,
+        2 => Float32x4,
+        3 => Float32,
+    ];
+
+    pub const DEAD: Particle = Particle {
+        position: [0.0; 3],
+        age: 1.0,
+        velocity: [0.0; 3],
+        lifetime: 0.0,
+        color: [0.0; 4],
+        size: 0.0,
+        _padding: [0.0; 3],
+    };
+
+    pub fn get_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<Particle>() as u64,
+            step_mode: wgpu::VertexStepMode::Instance,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+
+    pub fn is_alive(&self) -> bool {
+        self.age < self.lifetime
+    }
+}
+
+/// How the billboards are blended over what's behind them.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ParticleBlend {
+    /// Adds up, for sparks and fire.
+    Additive,
+    /// Covers by alpha, for smoke.
+    Alpha,
+}
+
+/// The directions particles start out in: up to `angle` degrees away from
+/// `direction`, at `speed` units a second.
+#[derive(Clone, PartialEq, Debug)]
+pub struct VelocityCone {
+    pub direction: Vec3,
+    pub angle: f32,
+    pub speed: Range<f32>,
+}
+
+/// Where and how particles come to life, and what happens to them after.
+/// Can be swapped at any time with `ParticleSystem::set_emitter`; particles
+/// already alive carry on as they were.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Emitter {
+    pub position: Vec3,
+    /// Particles a second, as long as there are dead ones to reuse.
+    pub spawn_rate: f32,
+    pub initial_velocity_cone: VelocityCone,
+    /// Seconds each particle lives, picked for each one.
+    pub lifetime_range: Range<f32>,
+    /// Units a second squared, for every particle.
+    pub gravity: Vec3,
+    /// World units across a billboard.
+    pub size: f32,
+    /// The color a particle is born with and fades towards, before it
+    /// fades out near the end of its life.
+    pub start_color: [f32; 4],
+    pub end_color: [f32; 4],
+    pub blend: ParticleBlend,
+}
+
+impl Emitter {
+    /// Sparks shooting up from the left of the scene.
+    pub fn fountain() -> Self {
+        Emitter {
+            position: vec3(-1.6, -1.0, -1.5),
+            spawn_rate: 2500.0,
+            initial_velocity_cone: VelocityCone {
+                direction: vec3(0.0, 1.0, 0.0),
+                angle: 15.0,
+                speed: 2.5..4.0,
+            },
+            lifetime_range: 1.5..2.5,
+            gravity: vec3(0.0, -2.0, 0.0),
+            size: 0.03,
+            start_color: [1.0, 0.9, 0.3, 1.0],
+            end_color: [1.0, 0.3, 0.1, 1.0],
+            blend: ParticleBlend::Additive,
+        }
+    }
+
+    /// Slow smoke rising and spreading from the same spot.
+    pub fn smoke() -> Self {
+        Emitter {
+            position: vec3(-1.6, -1.0, -1.5),
+            spawn_rate: 300.0,
+            initial_velocity_cone: VelocityCone {
+                direction: vec3(0.0, 1.0, 0.0),
+                angle: 25.0,
+                speed: 0.2..0.5,
+            },
+            lifetime_range: 3.0..5.0,
+            gravity: vec3(0.05, 0.1, 0.0),
+            size: 0.3,
+            start_color: [0.6, 0.6, 0.6, 0.5],
+            end_color: [0.3, 0.3, 0.3, 0.2],
+            blend: ParticleBlend::Alpha,
+        }
+    }
+}
+
+/// The particle render pipeline for each `ParticleBlend`.
+pub struct ParticlePipelines {
+    pub additive: wgpu::RenderPipeline,
+    pub alpha: wgpu::RenderPipeline,
+}
+
+impl ParticlePipelines {
+    pub fn get(&self, blend: ParticleBlend) -> &wgpu::RenderPipeline {
+        match blend {
+            ParticleBlend::Additive => &self.additive,
+            ParticleBlend::Alpha => &self.alpha,
+        }
+    }
+}
+
+/// What the emitter produced, with `live` read back a few frames late when
+/// the particles are simulated on the GPU.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ParticleStats {
+    pub live: u32,
+    pub capacity: u32,
+    pub on_gpu: bool,
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SimulationParams {
+    origin: [f32; 3],
+    dt: f32,
+    direction: [f32; 3],
+    /// The cone's half angle, in radians.
+    cone_angle: f32,
+    gravity: [f32; 3],
+    size: f32,
+    start_color: [f32; 4],
+    end_color: [f32; 4],
+    speed: [f32; 2],
+    lifetime: [f32; 2],
+    count: u32,
+    spawn_count: u32,
+    seed: u32,
+    _padding: u32,
+}
+
+// Has to match the WGSL struct byte for byte.
+const _: () = assert!(std::mem::size_of::<SimulationParams>() == 112);
+
+impl SimulationParams {
+    fn new(emitter: &Emitter, dt: f32, count: u32, spawn_count: u32, seed: u32) -> Self {
+        let cone = &emitter.initial_velocity_cone;
+        SimulationParams {
+            origin: *emitter.position.as_array(),
+            dt: dt,
+            direction: *glm::normalize(cone.direction).as_array(),
+            cone_angle: glm::radians(cone.angle),
+            gravity: *emitter.gravity.as_array(),
+            size: emitter.size,
+            start_color: emitter.start_color,
+            end_color: emitter.end_color,
+            speed: [cone.speed.start, cone.speed.end],
+            lifetime: [emitter.lifetime_range.start, emitter.lifetime_range.end],
+            count: count,
+            spawn_count: spawn_count,
+            seed: seed,
+            _padding: 0,
+        }
+    }
+}
+
+/// A start velocity in the cone, from three random numbers in 0..1; the
+/// same as `spawn_velocity` in the compute shader.
+fn spawn_velocity(params: &SimulationParams, random: [f32; 3]) -> Vec3 {
+    let direction = *Vec3::from_array(&params.direction);
+    let cos_theta = 1.0 - random[0] * (1.0 - params.cone_angle.cos());
+    let sin_theta = (1.0 - cos_theta * cos_theta).max(0.0).sqrt();
+    let phi = std::f32::consts::TAU * random[1];
+    let helper = match direction.y.abs() < 0.99 {
+        true => vec3(0.0, 1.0, 0.0),
+        false => vec3(1.0, 0.0, 0.0),
+    };
+    let tangent = glm::normalize(glm::cross(helper, direction));
+    let bitangent = glm::cross(direction, tangent);
+    let around = tangent * phi.cos() + bitangent * phi.sin();
+    let speed = params.speed[0] + (params.speed[1] - params.speed[0]) * random[2];
+    (direction * cos_theta + around * sin_theta) * speed
+}
+
+fn mix(a: [f32; 4], b: [f32; 4], t: f32) -> [f32; 4] {
+    [0, 1, 2, 3].map(|i| a[i] + (b[i] - a[i]) * t)
+}
+
+/// One step of the simulation on the CPU, the same as the compute shader
+/// does: the living age and move, and up to `spawn_count` of the dead are
+/// reborn in place at the emitter. Returns how many are alive after.
+pub fn step_particles(
+    particles: &mut [Particle],
+    emitter: &Emitter,
+    dt: f32,
+    spawn_count: u32,
+    seed: u32,
+) -> u32 {
+    let params = SimulationParams::new(emitter, dt, particles.len() as u32, spawn_count, seed);
+    let gravity = *Vec3::from_array(&params.gravity);
+    let mut spawned = 0;
+    let mut live = 0;
+    for (index, particle) in particles.iter_mut().enumerate() {
+        if !particle.is_alive() {
+            if spawned == spawn_count {
+                continue;
+            }
+            spawned += 1;
+            let random =
+                |salt: u32| hash_unit((seed as f32 * 0.618 + index as f32) * 3.7 + salt as f32);
+            let velocity = spawn_velocity(&params, [random(0), random(1), random(2)]);
+            let lifetime =
+                params.lifetime[0] + (params.lifetime[1] - params.lifetime[0]) * random(3);
+            *particle = Particle {
+                position: params.origin,
+                age: 0.0,
+                velocity: *velocity.as_array(),
+                lifetime: lifetime,
+                color: params.start_color,
+                size: params.size,
+                _padding: [0.0; 3],
+            };
+            live += 1;
+            continue;
+        }
+        particle.age += dt;
+        if !particle.is_alive() {
+            continue;
+        }
+        let velocity = *Vec3::from_array(&particle.velocity) + gravity * dt;
+        let position = *Vec3::from_array(&particle.position) + velocity * dt;
+        particle.velocity = *velocity.as_array();
+        particle.position = *position.as_array();
+        particle.color = mix(
+            params.start_color,
+            params.end_color,
+            particle.age / particle.lifetime,
+        );
+        live += 1;
+    }
+    live
+}
+
+enum Readback {
+    Free,
+    /// The counters were copied into the readback buffer this frame.
+    Recorded,
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+/// The compute pass running the simulation, and the counters it reads
+/// back the live count through.
+struct GpuSimulation {
+    params_buffer: wgpu::Buffer,
+    counter_buffer: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+    readback: Readback,
+    pipeline: wgpu::ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    workgroup_size: u32,
+    step_pending: bool,
+}
+
+enum Simulation {
+    Gpu(GpuSimulation),
+    /// Without compute shaders the particles live here and the whole pool
+    /// is uploaded every step.
+    Cpu(Vec<Particle>),
+}
+
+/// A pool of particles with room for `capacity`, fixed when it's created,
+/// fed by an `Emitter`. They're simulated by a compute shader or, where
+/// there are none, on the CPU, and drawn as camera-facing instanced quads
+/// straight from the pool; the vertex shader collapses the dead ones.
+pub struct ParticleSystem {
+    buffer: wgpu::Buffer,
+    simulation: Simulation,
+    emitter: Emitter,
+    capacity: u32,
+    /// Fractions of a particle the spawn rate has built up towards the next.
+    spawn_budget: f32,
+    steps: u32,
+    live: u32,
+}
+
+impl ParticleSystem {
+    pub fn new(
+        device: &wgpu::Device,
+        capacity: u32,
+        workgroup_size: u32,
+        use_compute: bool,
+    ) -> Result<Self, ShaderError> {
+        let particles = vec![Particle::DEAD; capacity as usize];
+        let usage = match use_compute {
+            true => wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::VERTEX,
+            false => wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        };
+        let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Buffer"),
+            contents: bytemuck::cast_slice(&particles),
+            usage: usage,
+        });
+        let emitter = Emitter::fountain();
+
+        let simulation = match use_compute {
+            true => Simulation::Gpu(GpuSimulation::new(
+                device,
+                &buffer,
+                &emitter,
+                capacity,
+                workgroup_size,
+            )?),
+            false => Simulation::Cpu(particles),
+        };
+
+        Ok(ParticleSystem {
+            buffer: buffer,
+            simulation: simulation,
+            emitter: emitter,
+            capacity: capacity,
+            spawn_budget: 0.0,
+            steps: 0,
+            live: 0,
+        })
+    }
+
+    /// The render pipeline drawing the particles with `blend`; `builder`
+    /// carries the target formats and sample count of the pass they're
+    /// drawn in.
+    pub fn build_render_pipeline<'a>(
+        mut builder: pipeline::Builder<'a>,
+        camera_bind_group_layout: &'a wgpu::BindGroupLayout,
+        blend: ParticleBlend,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        builder.set_shader_module(RENDER_SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_topology(wgpu::PrimitiveTopology::TriangleStrip, None);
+        builder.set_cull_mode(None);
+        builder.add_vertex_buffer_layout(Particle::get_layout());
+        builder.add_bind_group_layout(camera_bind_group_layout);
+        // Tested against the scene, but they don't hide each other.
+        builder.set_depth_test(false, wgpu::CompareFunction::LessEqual);
+        builder.set_blend_state(Some(match blend {
+            ParticleBlend::Additive => wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::SrcAlpha,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                },
+                alpha: wgpu::BlendComponent::OVER,
+            },
+            ParticleBlend::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+        }));
+        let label = match blend {
+            ParticleBlend::Additive => "Additive Particle Render Pipeline",
+            ParticleBlend::Alpha => "Alpha Particle Render Pipeline",
+        };
+        builder.build_pipeline(label)
+    }
+
+    pub fn emitter(&self) -> &Emitter {
+        &self.emitter
+    }
+
+    pub fn set_emitter(&mut self, emitter: Emitter) {
+        self.emitter = emitter;
+    }
+
+    pub fn stats(&self) -> ParticleStats {
+        ParticleStats {
+            live: self.live,
+            capacity: self.capacity,
+            on_gpu: matches!(self.simulation, Simulation::Gpu(_)),
+        }
+    }
+
+    /// Steps the simulation `dt` seconds on: right away on the CPU, or
+    /// queued for the next `encode_compute` on the GPU, picking up the live
+    /// count of an earlier step if it's been read back by now.
+    pub fn update(&mut self, device: &wgpu::Device, uploader: &mut FrameUploader, dt: f32) {
+        self.spawn_budget += self.emitter.spawn_rate.max(0.0) * dt;
+        let spawn_count = (self.spawn_budget.floor() as u32).min(self.capacity);
+        self.spawn_budget = self.spawn_budget.fract();
+        self.steps = self.steps.wrapping_add(1);
+
+        match &mut self.simulation {
+            Simulation::Cpu(particles) => {
+                self.live = step_particles(particles, &self.emitter, dt, spawn_count, self.steps);
+                uploader.write(&self.buffer, 0, particles);
+            }
+            Simulation::Gpu(gpu) => {
+                if let Some(live) = gpu.poll(device) {
+                    self.live = live;
+                }
+                let params = SimulationParams::new(
+                    &self.emitter,
+                    dt,
+                    self.capacity,
+                    spawn_count,
+                    self.steps,
+                );
+                uploader.write(&gpu.params_buffer, 0, &[params]);
+                gpu.step_pending = true;
+            }
+        }
+    }
+
+    /// Records the compute pass for the pending step, if any, and returns
+    /// whether it did. Must come before the render pass that draws the
+    /// particles in the same encoder.
+    pub fn encode_compute(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        timestamp_writes: Option<wgpu::ComputePassTimestampWrites>,
+    ) -> bool {
+        let Simulation::Gpu(gpu) = &mut self.simulation else {
+            return false;
+        };
+        if !gpu.step_pending {
+            return false;
+        }
+        gpu.step_pending = false;
+        command_encoder.clear_buffer(&gpu.counter_buffer, 0, None);
+
+        {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Particle Compute Pass"),
+                    timestamp_writes: timestamp_writes,
+                });
+            compute_pass.set_pipeline(&gpu.pipeline);
+            compute_pass.set_bind_group(0, &gpu.bind_group, &[]);
+            compute_pass.dispatch_workgroups(self.capacity.div_ceil(gpu.workgroup_size), 1, 1);
+        }
+
+        if matches!(gpu.readback, Readback::Free) {
+            command_encoder.copy_buffer_to_buffer(
+                &gpu.counter_buffer,
+                0,
+                &gpu.readback_buffer,
+                0,
+                COUNTERS_SIZE,
+            );
+            gpu.readback = Readback::Recorded;
+        }
+        true
+    }
+
+    /// Starts reading back the live count recorded by `encode_compute`.
+    /// Call once its command buffer has been submitted.
+    pub fn frame_submitted(&mut self) {
+        if let Simulation::Gpu(gpu) = &mut self.simulation {
+            gpu.frame_submitted();
+        }
+    }
+
+    /// Draws the particles; the caller sets the particle pipeline for
+    /// `emitter().blend` and the camera bind group first.
+    pub fn draw(&self, renderpass: &mut wgpu::RenderPass) {
+        renderpass.set_vertex_buffer(0, self.buffer.slice(..));
+        renderpass.draw(0..4, 0..self.capacity);
+    }
+}
+
+impl GpuSimulation {
+    fn new(
+        device: &wgpu::Device,
+        particle_buffer: &wgpu::Buffer,
+        emitter: &Emitter,
+        capacity: u32,
+        workgroup_size: u32,
+    ) -> Result<Self, ShaderError> {
+        let params = SimulationParams::new(emitter, 0.0, capacity, 0, 0);
+        let params_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Particle Params Buffer"),
+            contents: bytemuck::bytes_of(&params),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+        let counter_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Counter Buffer"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_SRC
+                | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Particle Counter Readback Buffer"),
+            size: COUNTERS_SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, false);
+            builder.add_uniform_buffer(wgpu::ShaderStages::COMPUTE);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, false);
+            bind_group_layout = builder.build("Particle Compute Bind Group Layout");
+        }
+
+        let bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&bind_group_layout);
+            builder.add_buffer(particle_buffer);
+            builder.add_buffer(&params_buffer);
+            builder.add_buffer(&counter_buffer);
+            bind_group = builder.build("Particle Compute Bind Group");
+        }
+
+        let pipeline: wgpu::ComputePipeline;
+        {
+            let mut builder = compute::Builder::new(device);
+            builder.set_shader_module(COMPUTE_SHADER_FILENAME, "cs_main");
+            builder.set_constant("workgroup_size", workgroup_size as f64);
+            builder.add_bind_group_layout(&bind_group_layout);
+            pipeline = builder.build_pipeline("Particle Compute Pipeline")?;
+        }
+
+        Ok(GpuSimulation {
+            params_buffer: params_buffer,
+            counter_buffer: counter_buffer,
+            readback_buffer: readback_buffer,
+            readback: Readback::Free,
+            pipeline: pipeline,
+            bind_group: bind_group,
+            workgroup_size: workgroup_size,
+            step_pending: false,
+        })
+    }
+
+    fn frame_submitted(&mut self) {
+        if !matches!(self.readback, Readback::Recorded) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Readback::Mapping(receiver);
+    }
+
+    /// The live count of a finished readback, if there is one.
+    fn poll(&mut self, device: &wgpu::Device) -> Option<u32> {
+        let Readback::Mapping(receiver) = &self.readback else {
+            return None;
+        };
+        let _ = device.poll(wgpu::Maintain::Poll);
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.readback = Readback::Free;
+                return None;
+            }
+        };
+        let live = result.ok().map(|_| {
+            let bytes = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, u32>(&bytes)[0]
+        });
+        if live.is_some() {
+            self.readback_buffer.unmap();
+        }
+        self.readback = Readback::Free;
+        live
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Particles that all live one second, through the CPU simulation: the
+    /// pool caps the spawns, everything dies on time, and the dead slots are
+    /// reused from the front.
+    #[test]
+    fn cpu_particles_recycle_dead_slots() {
+        let mut pool = vec![Particle::DEAD; 64];
+        let mut emitter = Emitter::fountain();
+        emitter.lifetime_range = 1.0..1.0;
+        let steps = [(0.1, 100, 64), (0.5, 0, 64), (0.6, 0, 0), (0.1, 10, 10)];
+        for (step, &(dt, spawn, expected)) in steps.iter().enumerate() {
+            let live = step_particles(&mut pool, &emitter, dt, spawn, step as u32);
+            assert_eq!(live, expected, "step {}", step);
+        }
+        let alive: Vec<usize> = (0..pool.len()).filter(|&i| pool[i].is_alive()).collect();
+        assert_eq!(alive, (0..10).collect::<Vec<_>>());
+    }
+}
@@ -0,0 +1,368 @@
+use std::future::Future;
+
+use glfw::{fail_on_errors, Context as _, GlfwReceiver, PWindow};
+
+use super::{
+    Action, App, Flow, Key, Modifiers, MouseButton, Window, WindowEvent, PAUSED_WAIT_SECONDS,
+};
+use crate::renderer_backend::error::RendererError;
+use crate::window_config::{self, WindowConfig};
+
+/// What windows are created with.
+pub struct Context<'a> {
+    glfw: &'a mut glfw::Glfw,
+}
+
+pub struct NativeWindow {
+    window: PWindow,
+    events: GlfwReceiver<(f64, glfw::WindowEvent)>,
+    /// Position and size to go back to when leaving fullscreen.
+    windowed_geometry: Option<(i32, i32, i32, i32)>,
+}
+
+impl NativeWindow {
+    /// Creates a windowed GLFW window as `config` describes, reporting
+    /// every event the app handles. The hints are reset afterwards so
+    /// windows created later start from GLFW's defaults. An icon that can't
+    /// be loaded is warned about and left out.
+    pub fn create(context: &mut Context, config: &WindowConfig) -> Result<Self, RendererError> {
+        let glfw = &mut *context.glfw;
+        glfw.window_hint(glfw::WindowHint::Resizable(config.resizable));
+        glfw.window_hint(glfw::WindowHint::Decorated(config.decorated));
+        glfw.window_hint(glfw::WindowHint::Maximized(config.maximized));
+        glfw.window_hint(glfw::WindowHint::TransparentFramebuffer(config.transparent));
+        let window = glfw.create_window(
+            config.width.max(1),
+            config.height.max(1),
+            &config.title,
+            glfw::WindowMode::Windowed,
+        );
+        glfw.default_window_hints();
+        let (mut window, events) = window.ok_or(RendererError::CreateWindow)?;
+
+        if let Some(icon_path) = &config.icon_path {
+            match window_config::load_icon(icon_path) {
+                Ok(icon) => window.set_icon_from_pixels(vec![glfw::PixelImage {
+                    width: icon.width(),
+                    height: icon.height(),
+                    // GLFW reads the pixels as RGBA bytes in memory order.
+                    pixels: icon
+                        .pixels()
+                        .map(|pixel| u32::from_ne_bytes(pixel.0))
+                        .collect(),
+                }]),
+                Err(e) => eprintln!(
+                    "Warning: can't load window icon {}: {}",
+                    icon_path.display(),
+                    e
+                ),
+            }
+        }
+
+        window.set_key_polling(true);
+        window.set_framebuffer_size_polling(true);
+        window.set_size_polling(true);
+        window.set_cursor_pos_polling(true);
+        window.set_mouse_button_polling(true);
+        window.set_scroll_polling(true);
+        window.set_char_polling(true);
+        window.set_content_scale_polling(true);
+        window.set_focus_polling(true);
+        window.set_iconify_polling(true);
+        window.make_current();
+
+        Ok(NativeWindow {
+            window: window,
+            events: events,
+            windowed_geometry: None,
+        })
+    }
+}
+
+impl Window for NativeWindow {
+    fn create_surface(
+        &self,
+        instance: &wgpu::Instance,
+    ) -> Result<wgpu::Surface<'static>, RendererError> {
+        let target = unsafe { wgpu::SurfaceTargetUnsafe::from_window(&*self.window) }
+            .map_err(|e| RendererError::WindowHandle(e.to_string()))?;
+        let surface = unsafe { instance.create_surface_unsafe(target) }?;
+        Ok(surface)
+    }
+
+    fn take_events(&self) -> Vec<WindowEvent> {
+        glfw::flush_messages(&self.events)
+            .filter_map(|(_, event)| translate_event(event))
+            .collect()
+    }
+
+    fn size(&self) -> (i32, i32) {
+        self.window.get_size()
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        self.window.get_framebuffer_size()
+    }
+
+    fn content_scale(&self) -> f32 {
+        self.window.get_content_scale().0
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.window.get_cursor_pos()
+    }
+
+    fn should_close(&self) -> bool {
+        self.window.should_close()
+    }
+
+    fn set_should_close(&mut self, close: bool) {
+        self.window.set_should_close(close);
+    }
+
+    fn hide(&mut self) {
+        self.window.hide();
+    }
+
+    fn set_cursor_captured(&mut self, captured: bool) {
+        let supports_raw_motion = self.window.glfw.supports_raw_motion();
+        match captured {
+            true => {
+                self.window.set_cursor_mode(glfw::CursorMode::Disabled);
+                if supports_raw_motion {
+                    self.window.set_raw_mouse_motion(true);
+                }
+            }
+            false => {
+                if supports_raw_motion {
+                    self.window.set_raw_mouse_motion(false);
+                }
+                self.window.set_cursor_mode(glfw::CursorMode::Normal);
+            }
+        }
+    }
+
+    /// Fullscreen is at the monitor's current video mode.
+    fn toggle_fullscreen(&mut self) {
+        match self.windowed_geometry.take() {
+            Some((x, y, width, height)) => {
+                self.window.set_monitor(
+                    glfw::WindowMode::Windowed,
+                    x,
+                    y,
+                    width as u32,
+                    height as u32,
+                    None,
+                );
+            }
+            None => {
+                let (x, y) = self.window.get_pos();
+                let (width, height) = self.window.get_size();
+                let mut glfw = self.window.glfw.clone();
+                let window = &mut *self.window;
+                let entered = glfw.with_connected_monitors(|_, monitors| {
+                    let monitor = monitors
+                        .iter()
+                        .max_by_key(|monitor| overlap(monitor, (x, y, width, height)))?;
+                    let mode = monitor.get_video_mode()?;
+                    window.set_monitor(
+                        glfw::WindowMode::FullScreen(monitor),
+                        0,
+                        0,
+                        mode.width,
+                        mode.height,
+                        Some(mode.refresh_rate),
+                    );
+                    Some(())
+                });
+                match entered {
+                    Some(_) => self.windowed_geometry = Some((x, y, width, height)),
+                    None => eprintln!("No monitor to go fullscreen on"),
+                }
+            }
+        }
+    }
+
+    fn is_framebuffer_transparent(&self) -> bool {
+        self.window.is_framebuffer_transparent()
+    }
+
+    fn swap_buffers(&mut self) {
+        self.window.swap_buffers();
+    }
+}
+
+/// Area of the window rectangle `(x, y, width, height)` that lies on `monitor`.
+fn overlap(monitor: &glfw::Monitor, (x, y, width, height): (i32, i32, i32, i32)) -> i64 {
+    let (monitor_x, monitor_y) = monitor.get_pos();
+    let (monitor_width, monitor_height) = match monitor.get_video_mode() {
+        Some(mode) => (mode.width as i32, mode.height as i32),
+        None => return 0,
+    };
+    let overlap_width = (x + width).min(monitor_x + monitor_width) - x.max(monitor_x);
+    let overlap_height = (y + height).min(monitor_y + monitor_height) - y.max(monitor_y);
+    overlap_width.max(0) as i64 * overlap_height.max(0) as i64
+}
+
+/// Starts GLFW, lets `start` open the windows, waits for the future it
+/// returns to set up the rest of the app and runs the app until it exits,
+/// polling for events before every frame.
+pub fn run<A, S>(
+    start: impl FnOnce(&mut Context) -> Result<S, RendererError>,
+) -> Result<(), RendererError>
+where
+    A: App,
+    S: Future<Output = Result<A, RendererError>>,
+{
+    let mut glfw =
+        glfw::init(fail_on_errors!()).map_err(|e| RendererError::Windowing(e.to_string()))?;
+    let setup = start(&mut Context { glfw: &mut glfw })?;
+    let mut app = pollster::block_on(setup)?;
+    let mut flow = Flow::Poll;
+    loop {
+        match flow {
+            Flow::Poll => glfw.poll_events(),
+            Flow::Wait => glfw.wait_events_timeout(PAUSED_WAIT_SECONDS),
+            Flow::Exit => return Ok(()),
+        }
+        flow = app.frame()?;
+    }
+}
+
+fn translate_event(event: glfw::WindowEvent) -> Option<WindowEvent> {
+    let event = match event {
+        glfw::WindowEvent::Key(key, _, action, modifiers) => WindowEvent::Key(
+            translate_key(key),
+            translate_action(action),
+            translate_modifiers(modifiers),
+        ),
+        glfw::WindowEvent::Char(character) => WindowEvent::Char(character),
+        glfw::WindowEvent::CursorPos(x, y) => WindowEvent::CursorPos(x, y),
+        glfw::WindowEvent::MouseButton(button, action, modifiers) => {
+            let button = match button {
+                glfw::MouseButton::Button1 => MouseButton::Left,
+                glfw::MouseButton::Button2 => MouseButton::Right,
+                glfw::MouseButton::Button3 => MouseButton::Middle,
+                button => MouseButton::Other(button as u16),
+            };
+            WindowEvent::MouseButton(
+                button,
+                translate_action(action),
+                translate_modifiers(modifiers),
+            )
+        }
+        glfw::WindowEvent::Scroll(x, y) => WindowEvent::Scroll(x, y),
+        glfw::WindowEvent::Size(width, height) => WindowEvent::Size(width, height),
+        glfw::WindowEvent::FramebufferSize(width, height) => {
+            WindowEvent::FramebufferSize(width, height)
+        }
+        glfw::WindowEvent::ContentScale(x, y) => WindowEvent::ContentScale(x, y),
+        glfw::WindowEvent::Focus(focused) => WindowEvent::Focus(focused),
+        glfw::WindowEvent::Iconify(iconified) => WindowEvent::Iconify(iconified),
+        _ => return None,
+    };
+    Some(event)
+}
+
+fn translate_action(action: glfw::Action) -> Action {
+    match action {
+        glfw::Action::Press => Action::Press,
+        glfw::Action::Release => Action::Release,
+        glfw::Action::Repeat => Action::Repeat,
+    }
+}
+
+fn translate_modifiers(modifiers: glfw::Modifiers) -> Modifiers {
+    Modifiers {
+        shift: modifiers.contains(glfw::Modifiers::Shift),
+        control: modifiers.contains(glfw::Modifiers::Control),
+        alt: modifiers.contains(glfw::Modifiers::Alt),
+        super_key: modifiers.contains(glfw::Modifiers::Super),
+    }
+}
+
+/// `Key` names its keys as GLFW does.
+macro_rules! translate_keys {
+    ($key:expr, $($name:ident),* $(,)?) => {
+        match $key {
+            $(glfw::Key::$name => Key::$name,)*
+            _ => Key::Unknown,
+        }
+    };
+}
+
+fn translate_key(key: glfw::Key) -> Key {
+    translate_keys!(
+        key,
+        A,
+        B,
+        C,
+        D,
+        E,
+        F,
+        G,
+        H,
+        I,
+        J,
+        K,
+        L,
+        M,
+        N,
+        O,
+        P,
+        Q,
+        R,
+        S,
+        T,
+        U,
+        V,
+        W,
+        X,
+        Y,
+        Z,
+        Num0,
+        Num1,
+        Num2,
+        Num3,
+        Num4,
+        Num5,
+        Num6,
+        Num7,
+        Num8,
+        Num9,
+        F1,
+        F2,
+        F3,
+        F4,
+        F5,
+        F6,
+        F7,
+        F8,
+        F9,
+        F10,
+        F11,
+        F12,
+        Escape,
+        Enter,
+        KpEnter,
+        Tab,
+        Backspace,
+        Insert,
+        Delete,
+        Left,
+        Right,
+        Up,
+        Down,
+        PageUp,
+        PageDown,
+        Home,
+        End,
+        Space,
+        LeftShift,
+        RightShift,
+        LeftControl,
+        RightControl,
+        LeftAlt,
+        RightAlt,
+    )
+}
@@ -0,0 +1,211 @@
+//! The windowing library windows and events come from: GLFW by default, or
+//! winit when built with `--no-default-features --features winit`, which is
+//! also what the browser build uses. Events are translated into the types
+//! here, so the rest of the app doesn't care which one it runs on.
+
+#[cfg(all(target_arch = "wasm32", feature = "glfw"))]
+compile_error!(
+    "GLFW doesn't run in the browser, build with --no-default-features --features winit"
+);
+#[cfg(all(feature = "glfw", feature = "winit"))]
+compile_error!("the glfw and winit features can't both be enabled");
+#[cfg(not(any(feature = "glfw", feature = "winit")))]
+compile_error!("one of the glfw and winit features has to be enabled");
+
+#[cfg(feature = "glfw")]
+mod glfw_window;
+#[cfg(feature = "winit")]
+mod winit_window;
+
+#[cfg(feature = "glfw")]
+pub use glfw_window::{run, Context, NativeWindow};
+#[cfg(feature = "winit")]
+pub use winit_window::{run, Context, NativeWindow};
+
+use crate::renderer_backend::error::RendererError;
+
+/// How long `run` waits for events while the app has nothing to render.
+pub const PAUSED_WAIT_SECONDS: f64 = 0.25;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Action {
+    Press,
+    Release,
+    /// A key held down long enough to repeat.
+    Repeat,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    Other(u16),
+}
+
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub super_key: bool,
+}
+
+/// The keys the app and the debug UI react to, by their place on a US
+/// layout; anything else is `Unknown`.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub enum Key {
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Num0,
+    Num1,
+    Num2,
+    Num3,
+    Num4,
+    Num5,
+    Num6,
+    Num7,
+    Num8,
+    Num9,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    Escape,
+    Enter,
+    KpEnter,
+    Tab,
+    Backspace,
+    Insert,
+    Delete,
+    Left,
+    Right,
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Space,
+    LeftShift,
+    RightShift,
+    LeftControl,
+    RightControl,
+    LeftAlt,
+    RightAlt,
+    Unknown,
+}
+
+/// What a window reports. Sizes and cursor positions are in screen
+/// coordinates except for `FramebufferSize`, see `WindowMetrics`.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum WindowEvent {
+    Key(Key, Action, Modifiers),
+    /// Text typed, after the keyboard layout.
+    Char(char),
+    CursorPos(f64, f64),
+    MouseButton(MouseButton, Action, Modifiers),
+    /// In lines, right and up.
+    Scroll(f64, f64),
+    Size(i32, i32),
+    FramebufferSize(i32, i32),
+    ContentScale(f32, f32),
+    Focus(bool),
+    Iconify(bool),
+}
+
+/// What the app needs of a window, from either library.
+pub trait Window {
+    /// A surface presenting to the window. It must not outlive the window,
+    /// which `WindowSurface` takes care of.
+    fn create_surface(
+        &self,
+        instance: &wgpu::Instance,
+    ) -> Result<wgpu::Surface<'static>, RendererError>;
+
+    /// Events received since the last call.
+    fn take_events(&self) -> Vec<WindowEvent>;
+
+    /// In screen coordinates.
+    fn size(&self) -> (i32, i32);
+
+    /// In pixels.
+    fn framebuffer_size(&self) -> (i32, i32);
+
+    /// How much the monitor asks content to be scaled by, e.g. 2.0 at 200%.
+    fn content_scale(&self) -> f32;
+
+    /// In screen coordinates.
+    fn cursor_pos(&self) -> (f64, f64);
+
+    fn should_close(&self) -> bool;
+
+    fn set_should_close(&mut self, close: bool);
+
+    fn hide(&mut self);
+
+    /// Hides the cursor and keeps it in the window, reporting how far the
+    /// mouse moves rather than where the cursor is, for mouse look.
+    fn set_cursor_captured(&mut self, captured: bool);
+
+    /// Switches between windowed mode and fullscreen on the monitor the
+    /// window overlaps most, restoring the window's position and size on
+    /// the way back.
+    fn toggle_fullscreen(&mut self);
+
+    /// Whether the framebuffer's alpha reaches the compositor.
+    fn is_framebuffer_transparent(&self) -> bool;
+
+    /// Called after a frame has been presented.
+    fn swap_buffers(&mut self) {}
+}
+
+/// What to do after `App::frame`.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Flow {
+    /// Render the next frame as soon as events have been polled.
+    Poll,
+    /// Nothing to render; sleep until an event comes in or
+    /// `PAUSED_WAIT_SECONDS` have passed.
+    Wait,
+    Exit,
+}
+
+/// What `run` drives, once a frame for each time round the event loop.
+pub trait App {
+    /// Handles the events the windows gathered since the last call and
+    /// renders a frame.
+    fn frame(&mut self) -> Result<Flow, RendererError>;
+}
@@ -0,0 +1,581 @@
+use std::cell::RefCell;
+use std::future::Future;
+use std::path::Path;
+use std::rc::{Rc, Weak};
+use std::sync::Arc;
+use std::time::Duration;
+
+use web_time::Instant;
+
+use winit::application::ApplicationHandler;
+use winit::dpi::LogicalSize;
+use winit::event::{DeviceEvent, DeviceId, ElementState, MouseScrollDelta};
+use winit::event_loop::{ActiveEventLoop, ControlFlow, EventLoop};
+use winit::keyboard::{KeyCode, PhysicalKey};
+use winit::window::{CursorGrabMode, Fullscreen, Icon, WindowId};
+
+use super::{
+    Action, App, Flow, Key, Modifiers, MouseButton, Window, WindowEvent, PAUSED_WAIT_SECONDS,
+};
+use crate::renderer_backend::error::RendererError;
+use crate::window_config::{self, WindowConfig};
+
+/// Pixels of a touchpad scroll that count as a line of a mouse wheel's.
+const PIXELS_PER_LINE: f64 = 20.0;
+
+/// What the event loop keeps for a window, with the events it translated
+/// since the window last took them.
+struct Shared {
+    events: Vec<WindowEvent>,
+    modifiers: Modifiers,
+    scale_factor: f64,
+    /// In screen coordinates; moved by the mouse's own motion while the
+    /// cursor is captured.
+    cursor: (f64, f64),
+    captured: bool,
+    should_close: bool,
+    /// For asking for frames, see `Handler::poll`.
+    #[cfg(target_arch = "wasm32")]
+    window: Weak<winit::window::Window>,
+}
+
+/// What windows are created with: the running event loop, which windows
+/// register with to get their events.
+pub struct Context<'a> {
+    event_loop: &'a ActiveEventLoop,
+    windows: &'a mut Vec<(WindowId, Weak<RefCell<Shared>>)>,
+}
+
+pub struct NativeWindow {
+    window: Arc<winit::window::Window>,
+    shared: Rc<RefCell<Shared>>,
+    /// winit can't be asked whether it got a transparent framebuffer, only
+    /// told to try for one.
+    transparent: bool,
+}
+
+impl NativeWindow {
+    /// Creates a window as `config` describes. An icon that can't be loaded
+    /// is warned about and left out. In the browser the window is a canvas
+    /// added to the page, which sizes it (see index.html), so `config`'s
+    /// size doesn't apply there.
+    pub fn create(context: &mut Context, config: &WindowConfig) -> Result<Self, RendererError> {
+        let mut attributes = winit::window::Window::default_attributes()
+            .with_title(config.title.clone())
+            .with_resizable(config.resizable)
+            .with_decorations(config.decorated)
+            .with_maximized(config.maximized)
+            .with_transparent(config.transparent);
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            attributes = attributes
+                .with_inner_size(LogicalSize::new(config.width.max(1), config.height.max(1)));
+        }
+        #[cfg(target_arch = "wasm32")]
+        {
+            use winit::platform::web::WindowAttributesExtWebSys;
+            attributes = attributes.with_append(true);
+        }
+        if let Some(icon_path) = &config.icon_path {
+            match load_icon(icon_path) {
+                Ok(icon) => attributes = attributes.with_window_icon(Some(icon)),
+                Err(e) => eprintln!(
+                    "Warning: can't load window icon {}: {}",
+                    icon_path.display(),
+                    e
+                ),
+            }
+        }
+        let window = context
+            .event_loop
+            .create_window(attributes)
+            .map_err(|_| RendererError::CreateWindow)?;
+        let window = Arc::new(window);
+
+        let shared = Rc::new(RefCell::new(Shared {
+            events: Vec::new(),
+            modifiers: Modifiers::default(),
+            scale_factor: window.scale_factor(),
+            cursor: (0.0, 0.0),
+            captured: false,
+            should_close: false,
+            #[cfg(target_arch = "wasm32")]
+            window: Arc::downgrade(&window),
+        }));
+        context.windows.push((window.id(), Rc::downgrade(&shared)));
+        Ok(NativeWindow {
+            window: window,
+            shared: shared,
+            transparent: config.transparent,
+        })
+    }
+}
+
+fn load_icon(path: &Path) -> Result<Icon, String> {
+    let icon = window_config::load_icon(path).map_err(|e| e.to_string())?;
+    let (width, height) = icon.dimensions();
+    Icon::from_rgba(icon.into_raw(), width, height).map_err(|e| e.to_string())
+}
+
+impl Window for NativeWindow {
+    fn create_surface(
+        &self,
+        instance: &wgpu::Instance,
+    ) -> Result<wgpu::Surface<'static>, RendererError> {
+        // The surface keeps the window alive through its own reference.
+        let surface = instance.create_surface(self.window.clone())?;
+        Ok(surface)
+    }
+
+    fn take_events(&self) -> Vec<WindowEvent> {
+        std::mem::take(&mut self.shared.borrow_mut().events)
+    }
+
+    fn size(&self) -> (i32, i32) {
+        let size = self
+            .window
+            .inner_size()
+            .to_logical::<f64>(self.window.scale_factor());
+        (size.width.round() as i32, size.height.round() as i32)
+    }
+
+    fn framebuffer_size(&self) -> (i32, i32) {
+        let size = self.window.inner_size();
+        (size.width as i32, size.height as i32)
+    }
+
+    fn content_scale(&self) -> f32 {
+        self.window.scale_factor() as f32
+    }
+
+    fn cursor_pos(&self) -> (f64, f64) {
+        self.shared.borrow().cursor
+    }
+
+    fn should_close(&self) -> bool {
+        self.shared.borrow().should_close
+    }
+
+    fn set_should_close(&mut self, close: bool) {
+        self.shared.borrow_mut().should_close = close;
+    }
+
+    fn hide(&mut self) {
+        self.window.set_visible(false);
+    }
+
+    /// Locks the cursor in place where the platform can, and otherwise
+    /// keeps it inside the window.
+    fn set_cursor_captured(&mut self, captured: bool) {
+        let grabbed = match captured {
+            true => self
+                .window
+                .set_cursor_grab(CursorGrabMode::Locked)
+                .or_else(|_| self.window.set_cursor_grab(CursorGrabMode::Confined)),
+            false => self.window.set_cursor_grab(CursorGrabMode::None),
+        };
+        if let Err(e) = grabbed {
+            eprintln!("Can't capture the cursor: {}", e);
+        }
+        self.window.set_cursor_visible(!captured);
+        self.shared.borrow_mut().captured = captured;
+    }
+
+    /// Fullscreen is borderless, at the monitor's desktop resolution.
+    fn toggle_fullscreen(&mut self) {
+        let fullscreen = match self.window.fullscreen() {
+            Some(_) => None,
+            None => Some(Fullscreen::Borderless(self.window.current_monitor())),
+        };
+        self.window.set_fullscreen(fullscreen);
+    }
+
+    fn is_framebuffer_transparent(&self) -> bool {
+        self.transparent
+    }
+}
+
+struct Handler<A, F> {
+    start: Option<F>,
+    app: Option<A>,
+    /// Where the app lands once it's set up, in the browser.
+    #[cfg(target_arch = "wasm32")]
+    starting: Rc<RefCell<Option<Result<A, RendererError>>>>,
+    windows: Vec<(WindowId, Weak<RefCell<Shared>>)>,
+    #[cfg(not(target_arch = "wasm32"))]
+    result: Result<(), RendererError>,
+}
+
+impl<A: App, F> Handler<A, F> {
+    /// In the browser nothing is returned to, so the error goes to the
+    /// console.
+    fn fail(&mut self, event_loop: &ActiveEventLoop, error: RendererError) {
+        #[cfg(target_arch = "wasm32")]
+        web_sys::console::error_1(&format!("Error: {}", error).into());
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            self.result = Err(error);
+        }
+        event_loop.exit();
+    }
+
+    fn frame(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(app) = &mut self.app else {
+            return;
+        };
+        match app.frame() {
+            Ok(Flow::Poll) => self.poll(event_loop),
+            Ok(Flow::Wait) => event_loop.set_control_flow(ControlFlow::WaitUntil(
+                Instant::now() + Duration::from_secs_f64(PAUSED_WAIT_SECONDS),
+            )),
+            Ok(Flow::Exit) => event_loop.exit(),
+            Err(e) => self.fail(event_loop, e),
+        }
+    }
+
+    /// Has the next frame follow right away. In the browser that's at the
+    /// next `requestAnimationFrame`, which winit turns a redraw request
+    /// into.
+    fn poll(&self, event_loop: &ActiveEventLoop) {
+        #[cfg(not(target_arch = "wasm32"))]
+        event_loop.set_control_flow(ControlFlow::Poll);
+        #[cfg(target_arch = "wasm32")]
+        {
+            event_loop.set_control_flow(ControlFlow::Wait);
+            self.request_redraw();
+        }
+    }
+
+    /// One frame draws every window, so one window is asked for it.
+    #[cfg(target_arch = "wasm32")]
+    fn request_redraw(&self) {
+        for (_, shared) in &self.windows {
+            let Some(shared) = shared.upgrade() else {
+                continue;
+            };
+            if let Some(window) = shared.borrow().window.upgrade() {
+                window.request_redraw();
+                return;
+            }
+        }
+    }
+}
+
+impl<A, F, S> ApplicationHandler for Handler<A, F>
+where
+    A: App + 'static,
+    F: FnOnce(&mut Context) -> Result<S, RendererError>,
+    S: Future<Output = Result<A, RendererError>> + 'static,
+{
+    /// Windows can only be created once the event loop is running, so the
+    /// app starts here. Natively the rest of its setup is waited for; the
+    /// browser can't be blocked, so there it runs on its own and the app
+    /// is picked up in `about_to_wait` once it's done.
+    fn resumed(&mut self, event_loop: &ActiveEventLoop) {
+        let Some(start) = self.start.take() else {
+            return;
+        };
+        let mut context = Context {
+            event_loop: event_loop,
+            windows: &mut self.windows,
+        };
+        match start(&mut context) {
+            #[cfg(not(target_arch = "wasm32"))]
+            Ok(setup) => match pollster::block_on(setup) {
+                Ok(app) => self.app = Some(app),
+                Err(e) => self.fail(event_loop, e),
+            },
+            #[cfg(target_arch = "wasm32")]
+            Ok(setup) => {
+                let starting = self.starting.clone();
+                wasm_bindgen_futures::spawn_local(async move {
+                    *starting.borrow_mut() = Some(setup.await);
+                });
+                event_loop.set_control_flow(ControlFlow::Poll);
+            }
+            Err(e) => self.fail(event_loop, e),
+        }
+    }
+
+    /// A paused app looks again once its wait is over. Natively that
+    /// happens in `about_to_wait` anyway.
+    #[cfg(target_arch = "wasm32")]
+    fn new_events(&mut self, _event_loop: &ActiveEventLoop, cause: winit::event::StartCause) {
+        if let winit::event::StartCause::ResumeTimeReached { .. } = cause {
+            self.request_redraw();
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), allow(unused_variables))]
+    fn window_event(
+        &mut self,
+        event_loop: &ActiveEventLoop,
+        window_id: WindowId,
+        event: winit::event::WindowEvent,
+    ) {
+        // Frames go by these in the browser, see `poll`.
+        #[cfg(target_arch = "wasm32")]
+        if let winit::event::WindowEvent::RedrawRequested = event {
+            self.frame(event_loop);
+            return;
+        }
+        let shared = self
+            .windows
+            .iter()
+            .find(|(id, _)| *id == window_id)
+            .and_then(|(_, shared)| shared.upgrade());
+        if let Some(shared) = shared {
+            translate_event(&mut shared.borrow_mut(), event);
+        }
+    }
+
+    /// A locked cursor doesn't move, so mouse look goes by how far the
+    /// mouse itself did, as GLFW's raw motion does.
+    fn device_event(
+        &mut self,
+        _event_loop: &ActiveEventLoop,
+        _device_id: DeviceId,
+        event: DeviceEvent,
+    ) {
+        let DeviceEvent::MouseMotion { delta: (dx, dy) } = event else {
+            return;
+        };
+        for shared in self
+            .windows
+            .iter()
+            .filter_map(|(_, shared)| shared.upgrade())
+        {
+            let mut shared = shared.borrow_mut();
+            if shared.captured {
+                shared.cursor = (shared.cursor.0 + dx, shared.cursor.1 + dy);
+                let (x, y) = shared.cursor;
+                shared.events.push(WindowEvent::CursorPos(x, y));
+            }
+        }
+    }
+
+    /// Renders a frame once the events that came in have been handled. In
+    /// the browser frames are drawn on redraw requests instead, and this
+    /// only waits for the app to be set up.
+    fn about_to_wait(&mut self, event_loop: &ActiveEventLoop) {
+        self.windows.retain(|(_, shared)| shared.strong_count() > 0);
+        #[cfg(not(target_arch = "wasm32"))]
+        self.frame(event_loop);
+        #[cfg(target_arch = "wasm32")]
+        {
+            let started = self.starting.borrow_mut().take();
+            match started {
+                Some(Ok(app)) => {
+                    self.app = Some(app);
+                    self.poll(event_loop);
+                }
+                Some(Err(e)) => self.fail(event_loop, e),
+                None => {}
+            }
+        }
+    }
+}
+
+/// Starts winit's event loop, lets `start` open the windows once it's
+/// running and runs the app until it exits, a frame each time the loop
+/// has handled the events that came in. The future `start` returns sets up
+/// the rest of the app. In the browser the page runs the loop, so this
+/// returns straight away.
+pub fn run<A, S>(
+    start: impl FnOnce(&mut Context) -> Result<S, RendererError> + 'static,
+) -> Result<(), RendererError>
+where
+    A: App + 'static,
+    S: Future<Output = Result<A, RendererError>> + 'static,
+{
+    let event_loop = EventLoop::new().map_err(|e| RendererError::Windowing(e.to_string()))?;
+    let handler = Handler {
+        start: Some(start),
+        app: None,
+        #[cfg(target_arch = "wasm32")]
+        starting: Rc::default(),
+        windows: Vec::new(),
+        #[cfg(not(target_arch = "wasm32"))]
+        result: Ok(()),
+    };
+    #[cfg(target_arch = "wasm32")]
+    {
+        use winit::platform::web::EventLoopExtWebSys;
+        event_loop.spawn_app(handler);
+        Ok(())
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        let mut handler = handler;
+        event_loop
+            .run_app(&mut handler)
+            .map_err(|e| RendererError::Windowing(e.to_string()))?;
+        handler.result
+    }
+}
+
+/// Queues what `event` means for the app on its window. winit reports
+/// sizes and positions in pixels; they're turned into screen coordinates
+/// with the window's scale factor, which is what GLFW reports them in.
+fn translate_event(shared: &mut Shared, event: winit::event::WindowEvent) {
+    use winit::event::WindowEvent as W;
+    let scale_factor = shared.scale_factor;
+    let event = match event {
+        W::CloseRequested => {
+            shared.should_close = true;
+            return;
+        }
+        W::Resized(size) => {
+            let logical = size.to_logical::<f64>(scale_factor);
+            shared.events.push(WindowEvent::Size(
+                logical.width.round() as i32,
+                logical.height.round() as i32,
+            ));
+            WindowEvent::FramebufferSize(size.width as i32, size.height as i32)
+        }
+        W::ScaleFactorChanged { scale_factor, .. } => {
+            shared.scale_factor = scale_factor;
+            WindowEvent::ContentScale(scale_factor as f32, scale_factor as f32)
+        }
+        W::Focused(focused) => WindowEvent::Focus(focused),
+        // The closest winit comes to telling a window was minimized.
+        W::Occluded(occluded) => WindowEvent::Iconify(occluded),
+        W::ModifiersChanged(modifiers) => {
+            let state = modifiers.state();
+            shared.modifiers = Modifiers {
+                shift: state.shift_key(),
+                control: state.control_key(),
+                alt: state.alt_key(),
+                super_key: state.super_key(),
+            };
+            return;
+        }
+        W::KeyboardInput { event, .. } => {
+            let key = match event.physical_key {
+                PhysicalKey::Code(code) => translate_key(code),
+                PhysicalKey::Unidentified(_) => Key::Unknown,
+            };
+            let action = match (event.state, event.repeat) {
+                (ElementState::Released, _) => Action::Release,
+                (ElementState::Pressed, true) => Action::Repeat,
+                (ElementState::Pressed, false) => Action::Press,
+            };
+            shared
+                .events
+                .push(WindowEvent::Key(key, action, shared.modifiers));
+            if let Some(text) = event.text.filter(|_| event.state == ElementState::Pressed) {
+                let characters = text.chars().filter(|character| !character.is_control());
+                shared.events.extend(characters.map(WindowEvent::Char));
+            }
+            return;
+        }
+        W::CursorMoved { position, .. } => {
+            // The mouse's own motion takes over, see `device_event`.
+            if shared.captured {
+                return;
+            }
+            let position = position.to_logical::<f64>(scale_factor);
+            shared.cursor = (position.x, position.y);
+            WindowEvent::CursorPos(position.x, position.y)
+        }
+        W::MouseInput { state, button, .. } => {
+            let button = match button {
+                winit::event::MouseButton::Left => MouseButton::Left,
+                winit::event::MouseButton::Right => MouseButton::Right,
+                winit::event::MouseButton::Middle => MouseButton::Middle,
+                winit::event::MouseButton::Back => MouseButton::Other(3),
+                winit::event::MouseButton::Forward => MouseButton::Other(4),
+                winit::event::MouseButton::Other(button) => MouseButton::Other(button),
+            };
+            let action = match state {
+                ElementState::Pressed => Action::Press,
+                ElementState::Released => Action::Release,
+            };
+            WindowEvent::MouseButton(button, action, shared.modifiers)
+        }
+        W::MouseWheel { delta, .. } => match delta {
+            MouseScrollDelta::LineDelta(x, y) => WindowEvent::Scroll(x as f64, y as f64),
+            MouseScrollDelta::PixelDelta(position) => {
+                WindowEvent::Scroll(position.x / PIXELS_PER_LINE, position.y / PIXELS_PER_LINE)
+            }
+        },
+        _ => return,
+    };
+    shared.events.push(event);
+}
+
+fn translate_key(code: KeyCode) -> Key {
+    match code {
+        KeyCode::KeyA => Key::A,
+        KeyCode::KeyB => Key::B,
+        KeyCode::KeyC => Key::C,
+        KeyCode::KeyD => Key::D,
+        KeyCode::KeyE => Key::E,
+        KeyCode::KeyF => Key::F,
+        KeyCode::KeyG => Key::G,
+        KeyCode::KeyH => Key::H,
+        KeyCode::KeyI => Key::I,
+        KeyCode::KeyJ => Key::J,
+        KeyCode::KeyK => Key::K,
+        KeyCode::KeyL => Key::L,
+        KeyCode::KeyM => Key::M,
+        KeyCode::KeyN => Key::N,
+        KeyCode::KeyO => Key::O,
+        KeyCode::KeyP => Key::P,
+        KeyCode::KeyQ => Key::Q,
+        KeyCode::KeyR => Key::R,
+        KeyCode::KeyS => Key::S,
+        KeyCode::KeyT => Key::T,
+        KeyCode::KeyU => Key::U,
+        KeyCode::KeyV => Key::V,
+        KeyCode::KeyW => Key::W,
+        KeyCode::KeyX => Key::X,
+        KeyCode::KeyY => Key::Y,
+        KeyCode::KeyZ => Key::Z,
+        KeyCode::Digit0 => Key::Num0,
+        KeyCode::Digit1 => Key::Num1,
+        KeyCode::Digit2 => Key::Num2,
+        KeyCode::Digit3 => Key::Num3,
+        KeyCode::Digit4 => Key::Num4,
+        KeyCode::Digit5 => Key::Num5,
+        KeyCode::Digit6 => Key::Num6,
+        KeyCode::Digit7 => Key::Num7,
+        KeyCode::Digit8 => Key::Num8,
+        KeyCode::Digit9 => Key::Num9,
+        KeyCode::F1 => Key::F1,
+        KeyCode::F2 => Key::F2,
+        KeyCode::F3 => Key::F3,
+        KeyCode::F4 => Key::F4,
+        KeyCode::F5 => Key::F5,
+        KeyCode::F6 => Key::F6,
+        KeyCode::F7 => Key::F7,
+        KeyCode::F8 => Key::F8,
+        KeyCode::F9 => Key::F9,
+        KeyCode::F10 => Key::F10,
+        KeyCode::F11 => Key::F11,
+        KeyCode::F12 => Key::F12,
+        KeyCode::Escape => Key::Escape,
+        KeyCode::Enter => Key::Enter,
+        KeyCode::NumpadEnter => Key::KpEnter,
+        KeyCode::Tab => Key::Tab,
+        KeyCode::Backspace => Key::Backspace,
+        KeyCode::Insert => Key::Insert,
+        KeyCode::Delete => Key::Delete,
+        KeyCode::ArrowLeft => Key::Left,
+        KeyCode::ArrowRight => Key::Right,
+        KeyCode::ArrowUp => Key::Up,
+        KeyCode::ArrowDown => Key::Down,
+        KeyCode::PageUp => Key::PageUp,
+        KeyCode::PageDown => Key::PageDown,
+        KeyCode::Home => Key::Home,
+        KeyCode::End => Key::End,
+        KeyCode::Space => Key::Space,
+        KeyCode::ShiftLeft => Key::LeftShift,
+        KeyCode::ShiftRight => Key::RightShift,
+        KeyCode::ControlLeft => Key::LeftControl,
+        KeyCode::ControlRight => Key::RightControl,
+        KeyCode::AltLeft => Key::LeftAlt,
+        KeyCode::AltRight => Key::RightAlt,
+        _ => Key::Unknown,
+    }
+}
@@ -0,0 +1,213 @@
+use crate::renderer_backend::{
+    bind_group, bind_group_layout, pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    shader::ShaderError,
+    texture::Texture,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/post.wgsl";
+
+/// What the post-processing pass does to the finished frame.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PostEffect {
+    /// No post-processing: the scene is drawn straight into the frame.
+    Passthrough,
+    Invert,
+    Grayscale,
+}
+
+impl PostEffect {
+    pub const ALL: [PostEffect; 3] = [
+        PostEffect::Passthrough,
+        PostEffect::Invert,
+        PostEffect::Grayscale,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PostEffect::Passthrough => "Passthrough",
+            PostEffect::Invert => "Invert",
+            PostEffect::Grayscale => "Grayscale",
+        }
+    }
+
+    pub fn next(self) -> Self {
+        let index = PostEffect::ALL.iter().position(|&e| e == self).unwrap();
+        PostEffect::ALL[(index + 1) % PostEffect::ALL.len()]
+    }
+
+    /// The shader's `effect` constant.
+    fn constant(self) -> f64 {
+        match self {
+            PostEffect::Passthrough => 0.0,
+            PostEffect::Invert => 1.0,
+            PostEffect::Grayscale => 2.0,
+        }
+    }
+}
+
+/// Draws a fullscreen triangle that reads the frame rendered so far from an
+/// intermediate texture and writes it, with `effect` applied, to the target.
+pub struct PostPass {
+    effect: PostEffect,
+    /// Whether the output is divided by its alpha, for compositors that
+    /// take straight alpha.
+    unpremultiply: bool,
+    pipeline: wgpu::RenderPipeline,
+    input_bind_group_layout: bind_group_layout::Layout,
+    input_bind_group: wgpu::BindGroup,
+    input: Texture,
+    width: u32,
+    height: u32,
+    format: wgpu::TextureFormat,
+}
+
+impl PostPass {
+    pub fn new(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        effect: PostEffect,
+    ) -> Result<Self, ShaderError> {
+        let input_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_texture(wgpu::ShaderStages::FRAGMENT);
+            input_bind_group_layout = builder.build("Post Input Bind Group Layout");
+        }
+        let pipeline =
+            Self::build_pipeline(device, info.format, &input_bind_group_layout, effect, false)?;
+        let (input, input_bind_group) = Self::create_input(device, info, &input_bind_group_layout);
+        Ok(PostPass {
+            effect: effect,
+            unpremultiply: false,
+            pipeline: pipeline,
+            input_bind_group_layout: input_bind_group_layout,
+            input_bind_group: input_bind_group,
+            input: input,
+            width: info.width,
+            height: info.height,
+            format: info.format,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        input_bind_group_layout: &wgpu::BindGroupLayout,
+        effect: PostEffect,
+        unpremultiply: bool,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(format);
+        builder.set_cull_mode(None);
+        builder.set_constant("effect", effect.constant());
+        builder.set_constant("unpremultiply", if unpremultiply { 1.0 } else { 0.0 });
+        builder.add_bind_group_layout(input_bind_group_layout);
+        builder.build_pipeline("Post Pipeline")
+    }
+
+    fn create_input(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        input_bind_group_layout: &bind_group_layout::Layout,
+    ) -> (Texture, wgpu::BindGroup) {
+        let input = Texture::create_sampled_target(
+            device,
+            info.width,
+            info.height,
+            info.format,
+            "Post Input Texture",
+        );
+        let input_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(input_bind_group_layout);
+            builder.add_texture(&input.view);
+            input_bind_group = builder.build("Post Input Bind Group");
+        }
+        (input, input_bind_group)
+    }
+
+    pub fn effect(&self) -> PostEffect {
+        self.effect
+    }
+
+    /// Keeps the current effect if the new pipeline fails to build.
+    pub fn set_effect(&mut self, device: &wgpu::Device, effect: PostEffect) {
+        if let Some(pipeline) = self.rebuild(device, effect, self.unpremultiply) {
+            self.pipeline = pipeline;
+            self.effect = effect;
+        }
+    }
+
+    pub fn unpremultiply(&self) -> bool {
+        self.unpremultiply
+    }
+
+    /// The frame is rendered with premultiplied alpha; this turns it into
+    /// straight alpha on the way out.
+    pub fn set_unpremultiply(&mut self, device: &wgpu::Device, unpremultiply: bool) {
+        if let Some(pipeline) = self.rebuild(device, self.effect, unpremultiply) {
+            self.pipeline = pipeline;
+            self.unpremultiply = unpremultiply;
+        }
+    }
+
+    fn rebuild(
+        &self,
+        device: &wgpu::Device,
+        effect: PostEffect,
+        unpremultiply: bool,
+    ) -> Option<wgpu::RenderPipeline> {
+        let layout = &self.input_bind_group_layout;
+        match Self::build_pipeline(device, self.format, layout, effect, unpremultiply) {
+            Ok(pipeline) => Some(pipeline),
+            Err(e) => {
+                eprintln!("{}", e);
+                None
+            }
+        }
+    }
+}
+
+impl RenderPass for PostPass {
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        // Every pixel gets written, so there's nothing to load.
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: targets.surface,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Post Pass"),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.input_bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+    }
+
+    fn input(&self) -> Option<&wgpu::TextureView> {
+        Some(&self.input.view)
+    }
+
+    /// The target format never changes, so only the input has to follow.
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        if (info.width, info.height) == (self.width, self.height) {
+            return;
+        }
+        (self.input, self.input_bind_group) =
+            Self::create_input(device, info, &self.input_bind_group_layout);
+        self.width = info.width;
+        self.height = info.height;
+    }
+}
@@ -0,0 +1,3130 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::bloom_pass::{BloomPass, BloomSettings};
+use crate::clear_color;
+use crate::debug_draw::{DebugDraw, DebugViews};
+use crate::grid_pass::{GridPass, GridSettings};
+use crate::letterbox_pass::LetterboxPass;
+use crate::particles::{Emitter, ParticleBlend, ParticlePipelines, ParticleStats, ParticleSystem};
+use crate::post_pass::{PostEffect, PostPass};
+use crate::renderer_backend::{
+    adapter, bind_group, bind_group_layout,
+    camera::{Camera, Projection},
+    color::ColorSpace,
+    dynamic_uniform::DynamicUniformBuffer,
+    error::RendererError,
+    frame_uploader::{FrameUploader, UploadStats},
+    frustum::{Aabb, Frustum},
+    gpu_cull::{GpuCullStats, GpuCuller},
+    gpu_timer::{GpuTimer, GpuTimings},
+    indirect::IndirectDraws,
+    instance::{self, Instance},
+    material::Material,
+    mesh_builder,
+    mesh_pool::MeshPool,
+    per_draw::{self, ModelTransform},
+    picker::{self, Picker},
+    pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    sampler::{CachedSampler, SamplerCache, SamplerDesc},
+    scene::{Hit, NodeId, Scene, Transform},
+    screenshot::{self, ScreenshotError},
+    shader,
+    texture::Texture,
+    texture_atlas::AtlasRegion,
+    transform_buffer::{self, ObjectId, TransformBuffer},
+    uniform,
+    viewport::Viewport,
+};
+use crate::shadow_pass::{self, ShadowPass, ShadowSettings};
+use crate::skybox_pass::SkyboxPass;
+use crate::triangle_pass::TrianglePass;
+
+pub const SHADER_FILENAME: &str = "shaders/shader.wgsl";
+pub const PUSH_CONSTANT_SHADER_FILENAME: &str = "shaders/shader_push_constants.wgsl";
+pub const DYNAMIC_OFFSET_SHADER_FILENAME: &str = "shaders/shader_dynamic_offsets.wgsl";
+/// Appended to whichever of the three the renderer draws with.
+pub const SCENE_COMMON_SHADER_FILENAME: &str = "shaders/scene_common.wgsl";
+pub const DEFAULT_CLEAR_COLOR: wgpu::Color = wgpu::Color {
+    r: 0.2,
+    g: 0.4,
+    b: 0.6,
+    a: 0.0,
+};
+
+/// Down and slightly from the front-left; see `LightUniform`.
+pub const DEFAULT_LIGHT_DIRECTION: [f32; 3] = [0.4, -1.0, -0.6];
+pub const DEFAULT_LIGHT_COLOR: [f32; 3] = [1.0, 1.0, 1.0];
+/// How much of the light color reaches surfaces facing away from it.
+pub const AMBIENT_STRENGTH: f32 = 0.15;
+
+pub const PARTICLE_COUNT: u32 = 10_000;
+pub const PARTICLE_WORKGROUP_SIZE: u32 = 64;
+
+/// Quads per side of the sine wave sheet to begin with.
+pub const DEFAULT_WAVE_RESOLUTION: u32 = 48;
+const WAVE_SIZE: f32 = 6.0;
+
+/// Spacing of the `set_upload_stress` writes: twice their size, so no two
+/// are adjacent and each one costs a copy of its own.
+const UPLOAD_STRESS_STRIDE: u64 = 32;
+
+/// How much bigger than the selected object its outline is drawn.
+const OUTLINE_SCALE: f32 = 1.05;
+
+/// The material color the selected scene node is drawn with, warm like its
+/// outline.
+const SELECTION_TINT: [f32; 4] = [1.0, 0.6, 0.3, 1.0];
+
+/// Meshes scene nodes can be drawn with, as `Scene::set_mesh` indices.
+pub const SCENE_SPHERE_MESH: usize = 0;
+pub const SCENE_CUBE_MESH: usize = 1;
+/// Unit squares facing +Z, half transparent.
+pub const SCENE_RED_PANE_MESH: usize = 2;
+pub const SCENE_BLUE_PANE_MESH: usize = 3;
+/// A line list of the X, Y and Z axes, a unit long.
+pub const SCENE_AXES_MESH: usize = 4;
+/// Materials scene nodes can be drawn with, as `Scene::set_material`
+/// indices: plain white, the quad's texture, or plain white blended by the
+/// vertex alpha.
+pub const SCENE_PLAIN_MATERIAL: usize = 0;
+pub const SCENE_TEXTURED_MATERIAL: usize = 1;
+pub const SCENE_GLASS_MATERIAL: usize = 2;
+
+/// Format the headless renderer draws into and reads back from.
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Everything needed to draw the scene, independent of where the frame ends
+/// up. `config` describes the render target: the surface configuration when
+/// there is a window, or a stand-in with the offscreen size and format when
+/// running headless.
+pub struct Renderer {
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub config: wgpu::SurfaceConfiguration,
+    uploader: FrameUploader,
+    upload_stress: Option<(wgpu::Buffer, u32)>,
+    pub sample_count: u32,
+    msaa_texture: Option<Texture>,
+    /// `Texture::DEPTH_FORMAT`, or `DEPTH_STENCIL_FORMAT` once stencil is
+    /// turned on.
+    depth_format: wgpu::TextureFormat,
+    depth_texture: Texture,
+    pipelines: ScenePipelines,
+    wireframe: bool,
+    depth_prepass: bool,
+    tonemap: bool,
+    particle_pipelines: ParticlePipelines,
+    /// Simulated on the CPU without compute shaders, as on WebGL2.
+    particles: ParticleSystem,
+    clear_color: wgpu::Color,
+    animate_clear_color: bool,
+    colors: ColorSpace,
+    material_bind_group_layout: bind_group_layout::Layout,
+    samplers: SamplerCache,
+    sampler: CachedSampler,
+    pub camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    globals_buffer: wgpu::Buffer,
+    light_direction: glm::Vec3,
+    light_color: glm::Vec3,
+    light_buffer: wgpu::Buffer,
+    camera_bind_group_layout: bind_group_layout::Layout,
+    camera_bind_group: wgpu::BindGroup,
+    /// Drawn before the scene, which the camera bind groups sample it in.
+    shadow_pass: ShadowPass,
+    uniform_bind_group_layout: bind_group_layout::Layout,
+    per_draw: per_draw::Mode,
+    model_transform: ModelTransform,
+    transform_bind_group_layout: bind_group_layout::Layout,
+    transforms: TransformBuffer,
+    object_binding: transform_buffer::Binding,
+    object_uniforms: Option<DynamicUniformBuffer<Instance>>,
+    object_offsets: Vec<u32>,
+    quad_object: ObjectId,
+    cube_object: ObjectId,
+    selected: Option<ObjectId>,
+    grid_objects: Vec<ObjectId>,
+    /// The scene shader and the common part appended to it.
+    shader_watchers: [shader::Watcher; 2],
+    triangle_mesh: mesh_builder::Mesh,
+    quad_mesh: mesh_builder::Mesh,
+    cube_mesh: mesh_builder::Mesh,
+    wave_mesh: mesh_builder::Mesh,
+    wave_object: ObjectId,
+    wave_resolution: u32,
+    wave: bool,
+    models: Vec<(mesh_builder::ModelPart, ObjectId)>,
+    /// Spheres circling each other to begin with, and whatever else gets
+    /// added.
+    scene: Scene,
+    scene_meshes: Vec<mesh_builder::Mesh>,
+    /// Every scene node with a mesh, and the object its world transform
+    /// goes to, kept in step with the scene by `sync_scene_objects`.
+    scene_objects: Vec<(NodeId, ObjectId)>,
+    /// Objects of scene nodes that went away, for new ones to reuse.
+    free_objects: Vec<ObjectId>,
+    /// The scene nodes `update` turns, and how fast in radians a second.
+    orbits: Vec<(NodeId, f32)>,
+    /// Where `models` came from, to load them again in `recreate`.
+    model_paths: Vec<PathBuf>,
+    object_visible: Vec<bool>,
+    cull_stats: CullStats,
+    /// Set while the scene's meshes are drawn indirectly, see `set_indirect`.
+    indirect: Option<IndirectPath>,
+    /// Mesh draw calls recorded since `encode` started.
+    draw_calls: Cell<u32>,
+    /// What `draw_calls` came to over the last `encode`.
+    frame_draw_calls: u32,
+    frozen_frustum: Option<Frustum>,
+    time: f32,
+    /// The `dt` of the last `update`.
+    delta_time: f32,
+    paused: bool,
+    triangle_material: Material,
+    quad_material: Material,
+    glass_material: Material,
+    /// Copies of the scene materials tinted `SELECTION_TINT`, by
+    /// `Scene::set_material` index, for the selected scene node.
+    selection_materials: Vec<Material>,
+    passes: Vec<PassSlot>,
+    skybox_pass: PassId,
+    grid_pass: GridPass,
+    grid: bool,
+    bloom_pass: BloomPass,
+    bloom: bool,
+    post_pass: PostPass,
+    /// Width over height the scene keeps, whatever the target's shape.
+    fixed_aspect: Option<f32>,
+    /// Where the scene goes when `fixed_aspect` is set.
+    viewport: Option<Viewport>,
+    letterbox_pass: LetterboxPass,
+    debug_draw: DebugDraw,
+    debug_views: DebugViews,
+    gpu_timer: Option<GpuTimer>,
+    /// Only with storage transforms, like the pick pipeline.
+    picker: Option<Picker>,
+}
+
+/// Which part of the scene pass a scene pipeline is built for.
+#[derive(Clone, Copy)]
+enum SceneStage {
+    /// Tests and writes depth itself.
+    Color,
+    /// Writes depth only, ahead of the color pass.
+    DepthPrepass,
+    /// Follows a depth pre-pass: only the fragments that ended up nearest
+    /// get shaded, and depth is left as the pre-pass wrote it.
+    ColorAfterPrepass,
+    /// Marks the selected object's silhouette in the stencil buffer.
+    OutlineMask,
+    /// Draws the selected object grown a little, outside its mask.
+    Outline,
+    /// Blends over what's drawn so far and tests depth without writing it,
+    /// so transparent meshes drawn back to front show each other.
+    Transparent,
+    /// Meshes of another topology than triangle lists, in vertex colors.
+    Unlit(wgpu::PrimitiveTopology),
+    /// Object ids for `Picker`, tested against the scene's depth, or, when
+    /// that's multisampled, against depth of their own.
+    Pick,
+    /// Depth from the light into the shadow map, with the light's
+    /// view-projection in place of the camera's.
+    Shadow,
+}
+
+/// The topologies besides triangle lists scene meshes can have.
+const UNLIT_TOPOLOGIES: [wgpu::PrimitiveTopology; 3] = [
+    wgpu::PrimitiveTopology::LineList,
+    wgpu::PrimitiveTopology::LineStrip,
+    wgpu::PrimitiveTopology::PointList,
+];
+
+/// The pipelines the scene's meshes are drawn with.
+struct ScenePipelines {
+    render: wgpu::RenderPipeline,
+    wireframe: Option<wgpu::RenderPipeline>,
+    depth_prepass: Option<wgpu::RenderPipeline>,
+    transparent: wgpu::RenderPipeline,
+    /// One for each of `UNLIT_TOPOLOGIES`.
+    unlit: HashMap<wgpu::PrimitiveTopology, wgpu::RenderPipeline>,
+    /// The mask and outline pipelines, when there's a stencil buffer.
+    outline: Option<(wgpu::RenderPipeline, wgpu::RenderPipeline)>,
+    /// Only with storage transforms, whose instance index is the object.
+    pick: Option<wgpu::RenderPipeline>,
+    shadow: wgpu::RenderPipeline,
+}
+
+/// The scene's meshes packed into one pool, and this frame's draws of them.
+struct IndirectPath {
+    pool: MeshPool,
+    draws: IndirectDraws,
+    /// Culls and writes the draws on the GPU instead, see `set_gpu_cull`.
+    gpu_cull: Option<GpuCuller>,
+}
+
+/// How many objects the last frame drew and how many frustum culling
+/// skipped.
+#[derive(Clone, Copy, Default)]
+pub struct CullStats {
+    pub drawn: u32,
+    pub culled: u32,
+}
+
+/// Refers to a pass added with `add_pass`.
+#[derive(Clone, Copy)]
+pub struct PassId(usize);
+
+struct PassSlot {
+    pass: Box<dyn RenderPass>,
+    enabled: bool,
+}
+
+/// Another camera onto the same scene with render targets of its own, for
+/// drawing it into a second window. Created and driven by the `Renderer`.
+pub struct SceneView {
+    pub camera: Camera,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    /// Of the shadow map `camera_bind_group` holds.
+    shadow_resolution: u32,
+    config: wgpu::SurfaceConfiguration,
+    sample_count: u32,
+    depth_format: wgpu::TextureFormat,
+    msaa_texture: Option<Texture>,
+    depth_texture: Texture,
+}
+
+/// Requests a device with the optional features the renderer can make use
+/// of, when the adapter has them. With `trace` the device records every API
+/// call into that directory, where wgpu supports it.
+pub async fn request_device(
+    adapter: &wgpu::Adapter,
+    trace: Option<&Path>,
+) -> Result<(wgpu::Device, wgpu::Queue), RendererError> {
+    let supports_wireframe = adapter
+        .features()
+        .contains(wgpu::Features::POLYGON_MODE_LINE);
+    if !supports_wireframe {
+        eprintln!("Warning: POLYGON_MODE_LINE is not supported, wireframe mode is disabled");
+    }
+    let mut required_features = match supports_wireframe {
+        true => wgpu::Features::POLYGON_MODE_LINE,
+        false => wgpu::Features::empty(),
+    };
+
+    // WebGL2 can't meet WebGPU's limits; the render target can still be as
+    // large as the adapter allows.
+    let mut required_limits = match adapter.get_info().backend {
+        wgpu::Backend::Gl if cfg!(target_arch = "wasm32") => {
+            wgpu::Limits::downlevel_webgl2_defaults().using_resolution(adapter.limits())
+        }
+        _ => wgpu::Limits::default(),
+    };
+    match per_draw::Mode::for_adapter(adapter) {
+        per_draw::Mode::PushConstants => {
+            println!("Per-draw data: push constants");
+            required_features |= wgpu::Features::PUSH_CONSTANTS;
+            required_limits.max_push_constant_size = per_draw::PUSH_CONSTANT_SIZE;
+        }
+        per_draw::Mode::UniformBuffer => {
+            println!("Per-draw data: uniform buffers (push constants not supported)");
+        }
+    }
+
+    if adapter.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+        required_features |= wgpu::Features::TIMESTAMP_QUERY;
+    }
+    // For the indirect draw path; multi-draw only cuts down the draw calls.
+    for feature in [
+        wgpu::Features::INDIRECT_FIRST_INSTANCE,
+        wgpu::Features::MULTI_DRAW_INDIRECT,
+        wgpu::Features::MULTI_DRAW_INDIRECT_COUNT,
+    ] {
+        if adapter.features().contains(feature) {
+            required_features |= feature;
+        }
+    }
+
+    let device_descriptor = wgpu::DeviceDescriptor {
+        required_features: required_features,
+        required_limits: required_limits,
+        label: Some("Device"),
+        memory_hints: wgpu::MemoryHints::Performance,
+    };
+
+    if let Some(dir) = trace {
+        match adapter::TRACE_SUPPORTED {
+            true => println!("Writing an API trace to {}", dir.display()),
+            false => eprintln!(
+                "Warning: this build of wgpu can't write API traces, {} will stay empty",
+                dir.display()
+            ),
+        }
+    }
+    let (device, queue) = adapter.request_device(&device_descriptor, trace).await?;
+    Ok((device, queue))
+}
+
+impl Renderer {
+    pub fn new(
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+        config: wgpu::SurfaceConfiguration,
+    ) -> Result<Self, RendererError> {
+        let sample_count = 1;
+        let msaa_texture = None;
+        let gpu_timer = GpuTimer::new(&device, &queue);
+        let depth_format = Texture::DEPTH_FORMAT;
+        let depth_texture = Texture::create_depth_texture(
+            &device,
+            &config,
+            depth_format,
+            sample_count,
+            "Depth Texture",
+        );
+
+        let colors = ColorSpace::Srgb;
+        let triangle_mesh = mesh_builder::make_triangle(&device, colors);
+        let quad_mesh = mesh_builder::make_textured_quad(&device, colors, &AtlasRegion::FULL);
+        let cube_mesh = mesh_builder::load_obj(&device, Path::new("models/cube.obj"))?;
+        let wave_mesh = mesh_builder::make_mesh(
+            &device,
+            "Wave",
+            &mesh_builder::wave_vertices(DEFAULT_WAVE_RESOLUTION, WAVE_SIZE, 0.0),
+            Some(&mesh_builder::wave_indices(DEFAULT_WAVE_RESOLUTION)),
+        );
+
+        let material_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(&device);
+            // The base color texture, then the normal map, then the color.
+            builder.add_material();
+            builder.add_material();
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            material_bind_group_layout = builder.build("Material Bind Group Layout");
+        }
+
+        let uniform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(&device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            uniform_bind_group_layout = builder.build("Uniform Bind Group Layout");
+        }
+
+        // The camera for the vertex stage, the light for the fragment stage
+        // and the globals for both, then the shadow map for the fragment
+        // stage: its uniform, the depth texture and the comparison sampler.
+        let camera_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(&device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX_FRAGMENT);
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            builder.add_depth_texture(wgpu::ShaderStages::FRAGMENT);
+            builder.add_comparison_sampler(wgpu::ShaderStages::FRAGMENT);
+            camera_bind_group_layout = builder.build("Camera Bind Group Layout");
+        }
+
+        let object_binding = transform_buffer::Binding::for_device(&adapter, &device);
+        let transform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(&device);
+            match object_binding {
+                transform_buffer::Binding::Storage => {
+                    builder.add_storage_buffer(wgpu::ShaderStages::VERTEX, true)
+                }
+                transform_buffer::Binding::DynamicOffsets => {
+                    println!(
+                        "Object transforms: dynamic uniform offsets (no vertex storage buffers)"
+                    );
+                    builder.add_dynamic_uniform_buffer(
+                        wgpu::ShaderStages::VERTEX,
+                        std::mem::size_of::<Instance>() as u64,
+                    )
+                }
+            }
+            transform_bind_group_layout = builder.build("Transform Bind Group Layout");
+        }
+
+        let grid_transforms = Self::grid_transforms(0.0);
+        let object_capacity = grid_transforms.len() as u32 + 3;
+        let (mut transforms, object_uniforms) = match object_binding {
+            transform_buffer::Binding::Storage => (
+                TransformBuffer::new(&device, Some(&transform_bind_group_layout), object_capacity),
+                None,
+            ),
+            transform_buffer::Binding::DynamicOffsets => (
+                TransformBuffer::new(&device, None, object_capacity),
+                Some(DynamicUniformBuffer::new(
+                    &device,
+                    &transform_bind_group_layout,
+                    object_capacity,
+                )),
+            ),
+        };
+        let quad_object = transforms.push(uniform::identity());
+        let cube_object = transforms.push(Self::cube_transform(0.0));
+        let grid_objects: Vec<ObjectId> = grid_transforms
+            .into_iter()
+            .map(|transform| transforms.push(transform))
+            .collect();
+        let wave_object =
+            transforms.push(instance::transform(glm::vec3(0.0, -0.8, -4.0), 0.0, 1.0));
+        let (mut scene, orbits) = Self::orbit_scene();
+        Self::add_panes(&mut scene);
+        let axes = scene.add_node(None);
+        scene.set_mesh(axes, Some(SCENE_AXES_MESH));
+        let mut red_pane = mesh_builder::quad_data(1.0);
+        let mut blue_pane = mesh_builder::quad_data(1.0);
+        red_pane.set_color(glm::vec3(1.0, 0.1, 0.1), 0.5);
+        blue_pane.set_color(glm::vec3(0.1, 0.3, 1.0), 0.5);
+        let scene_meshes = vec![
+            mesh_builder::make_uv_sphere(&device, 0.5, 16, 24),
+            mesh_builder::make_cube(&device, 1.0),
+            red_pane.to_mesh(&device, "Red Pane"),
+            blue_pane.to_mesh(&device, "Blue Pane"),
+            mesh_builder::make_axes(&device, 1.0),
+        ];
+        let scene_objects: Vec<(NodeId, ObjectId)> = scene
+            .meshes()
+            .map(|(node, _, world)| (node, transforms.push(*world)))
+            .collect();
+        let mut uploader = FrameUploader::new();
+        transforms.flush(&device, &mut uploader, &transform_bind_group_layout);
+
+        let mut camera = Camera::new(config.width as f32 / config.height as f32);
+        camera.set_viewport(config.width, config.height);
+        let camera_uniform = uniform::CameraUniform::new(&camera.build_view_projection());
+        let camera_buffer = uniform::make_uniform_buffer(
+            &device,
+            "Camera Uniform Buffer",
+            bytemuck::bytes_of(&camera_uniform),
+        );
+        let light_direction = *glm::Vec3::from_array(&DEFAULT_LIGHT_DIRECTION);
+        let light_color = *glm::Vec3::from_array(&DEFAULT_LIGHT_COLOR);
+        let light_uniform =
+            uniform::LightUniform::new(&light_direction, &light_color, AMBIENT_STRENGTH);
+        let light_buffer = uniform::make_uniform_buffer(
+            &device,
+            "Light Uniform Buffer",
+            bytemuck::bytes_of(&light_uniform),
+        );
+
+        let globals_uniform = uniform::GlobalsUniform::new(0.0, 0.0, config.width, config.height);
+        let globals_buffer = uniform::make_uniform_buffer(
+            &device,
+            "Globals Uniform Buffer",
+            bytemuck::bytes_of(&globals_uniform),
+        );
+
+        let shadow_pass = ShadowPass::new(&device, config.format, ShadowSettings::default())?;
+        let camera_bind_group = Self::build_camera_bind_group(
+            &device,
+            &camera_bind_group_layout,
+            &camera_buffer,
+            &light_buffer,
+            &globals_buffer,
+            &shadow_pass,
+            "Camera Bind Group",
+        );
+
+        // The dynamic offset shader takes its model matrix from a uniform.
+        let per_draw = match object_binding {
+            transform_buffer::Binding::Storage => per_draw::Mode::for_device(&device),
+            transform_buffer::Binding::DynamicOffsets => per_draw::Mode::UniformBuffer,
+        };
+        let model_transform = ModelTransform::new(
+            &device,
+            per_draw,
+            &uniform_bind_group_layout,
+            &uniform::identity(),
+            "Model Transform",
+        );
+
+        let pipelines = Self::build_render_pipelines(
+            &device,
+            config.format,
+            depth_format,
+            sample_count,
+            false,
+            false,
+            per_draw,
+            object_binding,
+            &camera_bind_group_layout,
+            shadow_pass.caster_bind_group_layout(),
+            &uniform_bind_group_layout,
+            &material_bind_group_layout,
+            &transform_bind_group_layout,
+        )?;
+        let picker = pipelines
+            .pick
+            .as_ref()
+            .map(|_| Picker::new(&device, &config, depth_format, sample_count));
+        let particle_pipelines = Self::build_particle_pipelines(
+            &device,
+            config.format,
+            depth_format,
+            sample_count,
+            &camera_bind_group_layout,
+        )?;
+        let supports_compute = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS);
+        if !supports_compute {
+            eprintln!(
+                "Warning: compute shaders are not supported, particles are simulated on the CPU"
+            );
+        }
+        let particles = ParticleSystem::new(
+            &device,
+            PARTICLE_COUNT,
+            PARTICLE_WORKGROUP_SIZE,
+            supports_compute,
+        )?;
+        let shader_watchers = [
+            Self::shader_filename(per_draw, object_binding),
+            SCENE_COMMON_SHADER_FILENAME,
+        ]
+        .map(|filename| shader::Watcher::new(Path::new(filename)));
+
+        let mut samplers = SamplerCache::new(&adapter);
+        let sampler = samplers.get(&device, &SamplerDesc::TRILINEAR);
+        let triangle_material = Material::new(
+            "img/satin.jpg",
+            &device,
+            &queue,
+            &material_bind_group_layout,
+            &sampler.sampler,
+        )?;
+        let mut quad_material = Material::new(
+            "img/grunge.jpg",
+            &device,
+            &queue,
+            &material_bind_group_layout,
+            &sampler.sampler,
+        )?;
+        quad_material.load_normal_map(
+            "img/grunge_normal.png",
+            &device,
+            &queue,
+            &material_bind_group_layout,
+            &sampler.sampler,
+        )?;
+        let mut glass_material = triangle_material.clone();
+        glass_material.set_transparent(true);
+        let selection_materials = Self::selection_materials(
+            &[&triangle_material, &quad_material, &glass_material],
+            &device,
+            &material_bind_group_layout,
+            &sampler.sampler,
+        );
+
+        let info = Self::target_info(&config, depth_format, sample_count, colors, &sampler);
+        let triangle_pass = TrianglePass::new(
+            &device,
+            &info,
+            &camera_bind_group_layout,
+            &camera_bind_group,
+            &material_bind_group_layout,
+            &triangle_material,
+        )?;
+        let skybox_pass = SkyboxPass::new(&device, &queue, &info)?;
+        let grid_pass = GridPass::new(&device, &info, GridSettings::default())?;
+        let bloom_pass = BloomPass::new(&device, &queue, &info, BloomSettings::default())?;
+        let post_pass = PostPass::new(&device, &info, PostEffect::Passthrough)?;
+        let letterbox_pass = LetterboxPass::new(&device, config.format)?;
+        let debug_draw = DebugDraw::new(&device, &info)?;
+        let passes = vec![
+            PassSlot {
+                pass: Box::new(triangle_pass),
+                enabled: true,
+            },
+            PassSlot {
+                pass: Box::new(skybox_pass),
+                enabled: false,
+            },
+        ];
+
+        Ok(Self {
+            adapter,
+            device,
+            queue,
+            config,
+            uploader,
+            upload_stress: None,
+            sample_count,
+            msaa_texture,
+            depth_format,
+            depth_texture,
+            pipelines,
+            wireframe: false,
+            depth_prepass: false,
+            tonemap: false,
+            particle_pipelines,
+            particles,
+            clear_color: DEFAULT_CLEAR_COLOR,
+            animate_clear_color: false,
+            colors,
+            material_bind_group_layout,
+            samplers,
+            sampler,
+            camera,
+            camera_buffer,
+            globals_buffer,
+            light_direction,
+            light_color,
+            light_buffer,
+            camera_bind_group_layout,
+            camera_bind_group,
+            shadow_pass,
+            uniform_bind_group_layout,
+            per_draw,
+            model_transform,
+            transform_bind_group_layout,
+            transforms,
+            object_binding,
+            object_uniforms,
+            object_offsets: Vec::new(),
+            quad_object,
+            cube_object,
+            selected: None,
+            grid_objects,
+            shader_watchers,
+            triangle_mesh,
+            quad_mesh,
+            cube_mesh,
+            wave_mesh,
+            wave_object,
+            wave_resolution: DEFAULT_WAVE_RESOLUTION,
+            wave: false,
+            models: Vec::new(),
+            scene,
+            scene_meshes,
+            scene_objects,
+            free_objects: Vec::new(),
+            orbits,
+            model_paths: Vec::new(),
+            object_visible: Vec::new(),
+            cull_stats: CullStats::default(),
+            indirect: None,
+            draw_calls: Cell::new(0),
+            frame_draw_calls: 0,
+            frozen_frustum: None,
+            time: 0.0,
+            delta_time: 0.0,
+            paused: false,
+            triangle_material,
+            quad_material,
+            glass_material,
+            selection_materials,
+            passes,
+            skybox_pass: PassId(1),
+            grid_pass,
+            grid: false,
+            bloom_pass,
+            bloom: false,
+            post_pass,
+            fixed_aspect: None,
+            viewport: None,
+            letterbox_pass,
+            debug_draw,
+            debug_views: DebugViews::default(),
+            gpu_timer,
+            picker,
+        })
+    }
+
+    /// A renderer without a window or surface that draws into offscreen
+    /// `HEADLESS_FORMAT` targets of the given size, on a software adapter if
+    /// there's no GPU.
+    pub async fn headless(
+        width: u32,
+        height: u32,
+        adapter_options: &adapter::AdapterOptions,
+    ) -> Result<Self, RendererError> {
+        let instance = adapter::create_instance(adapter_options);
+        let adapter = adapter::request_headless(&instance, adapter_options).await?;
+        let (device, queue) = request_device(&adapter, adapter_options.trace.as_deref()).await?;
+
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            format: HEADLESS_FORMAT,
+            width: width.max(1),
+            height: height.max(1),
+            present_mode: wgpu::PresentMode::Fifo,
+            alpha_mode: wgpu::CompositeAlphaMode::Opaque,
+            view_formats: vec![],
+            desired_maximum_frame_latency: 2,
+        };
+
+        Self::new(adapter, device, queue, config)
+    }
+
+    /// Builds everything again on a new device, after the old one was lost,
+    /// and carries the camera, scene and settings over. Passes added with
+    /// `add_pass` hold resources of the old device and are dropped.
+    pub fn recreate(
+        &mut self,
+        adapter: wgpu::Adapter,
+        device: wgpu::Device,
+        queue: wgpu::Queue,
+    ) -> Result<(), RendererError> {
+        let mut renderer = Self::new(adapter, device, queue, self.config.clone())?;
+        renderer.camera = self.camera.clone();
+        renderer.time = self.time;
+        renderer.paused = self.paused;
+        // The scene keeps its node ids; their objects are handed out anew.
+        renderer.scene = std::mem::replace(&mut self.scene, Scene::new());
+        renderer.orbits = self.orbits.clone();
+        let stale_objects = std::mem::take(&mut renderer.scene_objects);
+        renderer
+            .free_objects
+            .extend(stale_objects.into_iter().map(|(_, object)| object));
+        renderer.frozen_frustum = self.frozen_frustum;
+        renderer.set_alpha_mode(self.config.alpha_mode);
+        renderer.clear_color = self.clear_color;
+        renderer.animate_clear_color = self.animate_clear_color;
+        renderer.set_light(self.light_direction, self.light_color);
+        renderer.set_model_matrix(*self.model_transform.matrix());
+        if self.colors != renderer.colors {
+            renderer.set_colors(self.colors);
+        }
+        for path in &self.model_paths {
+            renderer.load_model(path)?;
+        }
+        if self.wave {
+            renderer.toggle_wave();
+        }
+        renderer.set_wave_resolution(self.wave_resolution);
+
+        renderer.set_stencil(self.stencil());
+        renderer.set_sample_count(self.sample_count);
+        renderer.set_tonemap(self.tonemap);
+        renderer.set_depth_prepass(self.depth_prepass);
+        if self.wireframe {
+            renderer.toggle_wireframe();
+        }
+        renderer.set_sampler(self.sampler());
+        renderer.set_pass_enabled(renderer.skybox_pass, self.skybox());
+        renderer.grid = self.grid;
+        renderer.set_grid_settings(self.grid_settings());
+        renderer.bloom = self.bloom;
+        renderer.set_bloom_settings(self.bloom_settings());
+        renderer.set_post_effect(self.post_effect());
+        renderer.set_fixed_aspect(self.fixed_aspect);
+        renderer.set_selected(self.selected);
+        renderer.set_indirect(self.indirect());
+        renderer.set_gpu_cull(self.gpu_cull());
+        renderer.debug_views = self.debug_views;
+        renderer.set_shadow_settings(self.shadow_settings());
+        if self.shadow_pass.frozen() {
+            renderer
+                .shadow_pass
+                .freeze(self.shadow_pass.light_view_projection());
+        }
+        renderer.set_debug_depth_test(self.debug_draw.depth_test());
+        if let Some((_, count)) = &self.upload_stress {
+            renderer.set_upload_stress(*count);
+        }
+        *self = renderer;
+        Ok(())
+    }
+
+    /// Group 0 of the scene pipelines: `camera_buffer`'s view-projection,
+    /// the light and the globals, then the shadow map and what it's sampled
+    /// with.
+    fn build_camera_bind_group(
+        device: &wgpu::Device,
+        layout: &bind_group_layout::Layout,
+        camera_buffer: &wgpu::Buffer,
+        light_buffer: &wgpu::Buffer,
+        globals_buffer: &wgpu::Buffer,
+        shadow_pass: &ShadowPass,
+        label: &str,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_buffer(camera_buffer);
+        builder.add_buffer(light_buffer);
+        builder.add_buffer(globals_buffer);
+        builder.add_buffer(shadow_pass.uniform_buffer());
+        builder.add_texture(shadow_pass.view());
+        builder.add_sampler(shadow_pass.sampler());
+        builder.build(label)
+    }
+
+    /// A pipeline builder targeting the main pass: surface format, depth
+    /// buffer and the current MSAA sample count.
+    fn scene_pipeline_builder(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> pipeline::Builder<'_> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_pixel_format(format);
+        builder.set_depth_format(depth_format);
+        builder.set_sample_count(sample_count);
+        builder
+    }
+
+    fn build_particle_pipelines(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<ParticlePipelines, shader::ShaderError> {
+        let build = |blend: ParticleBlend| {
+            ParticleSystem::build_render_pipeline(
+                Self::scene_pipeline_builder(device, format, depth_format, sample_count),
+                camera_bind_group_layout,
+                blend,
+            )
+        };
+        Ok(ParticlePipelines {
+            additive: build(ParticleBlend::Additive)?,
+            alpha: build(ParticleBlend::Alpha)?,
+        })
+    }
+
+    fn shader_filename(
+        per_draw: per_draw::Mode,
+        object_binding: transform_buffer::Binding,
+    ) -> &'static str {
+        match (per_draw, object_binding) {
+            (_, transform_buffer::Binding::DynamicOffsets) => DYNAMIC_OFFSET_SHADER_FILENAME,
+            (per_draw::Mode::PushConstants, _) => PUSH_CONSTANT_SHADER_FILENAME,
+            (per_draw::Mode::UniformBuffer, _) => SHADER_FILENAME,
+        }
+    }
+
+    fn build_render_pipeline(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        stage: SceneStage,
+        polygon_mode: wgpu::PolygonMode,
+        tonemap: bool,
+        per_draw: per_draw::Mode,
+        object_binding: transform_buffer::Binding,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        shadow_bind_group_layout: &wgpu::BindGroupLayout,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<wgpu::RenderPipeline, shader::ShaderError> {
+        let mut builder = Self::scene_pipeline_builder(device, format, depth_format, sample_count);
+        let (vertex_entry, fragment_entry) = match stage {
+            SceneStage::OutlineMask | SceneStage::Outline => ("vs_main", "fs_outline"),
+            SceneStage::Unlit(_) => ("vs_main", "fs_unlit"),
+            SceneStage::Pick => ("vs_pick", "fs_pick"),
+            _ => ("vs_main", "fs_main"),
+        };
+        let filename = Self::shader_filename(per_draw, object_binding);
+        // The common part goes after the shader so the lines in errors are
+        // the shader file's own.
+        let source = format!(
+            "{}\n{}",
+            shader::read_wgsl(Path::new(filename))?,
+            shader::read_wgsl(Path::new(SCENE_COMMON_SHADER_FILENAME))?
+        );
+        builder.set_shader_source(filename, source, vertex_entry, fragment_entry);
+        builder.set_polygon_mode(polygon_mode);
+        builder.set_constant("tonemap", if tonemap { 1.0 } else { 0.0 });
+        // The vertex stage and everything feeding it stays the same in every
+        // stage, so the pre-pass and the color pass agree on depth exactly.
+        let label = match stage {
+            SceneStage::Color => "Render Pipeline",
+            SceneStage::DepthPrepass => {
+                builder.set_depth_only(true);
+                "Depth Pre-pass Pipeline"
+            }
+            SceneStage::ColorAfterPrepass => {
+                builder.set_depth_test(false, wgpu::CompareFunction::Equal);
+                "Render Pipeline After Depth Pre-pass"
+            }
+            SceneStage::OutlineMask => {
+                // The whole silhouette, hidden parts included, marks the
+                // stencil and nothing else.
+                builder.set_depth_test(false, wgpu::CompareFunction::Always);
+                builder.set_write_mask(wgpu::ColorWrites::empty());
+                builder.set_stencil(
+                    wgpu::CompareFunction::Always,
+                    wgpu::StencilOperation::Keep,
+                    wgpu::StencilOperation::Replace,
+                    0xff,
+                    0xff,
+                );
+                "Outline Mask Pipeline"
+            }
+            SceneStage::Outline => {
+                // Grown a little and drawn where the mask isn't, which
+                // leaves a rim around the silhouette.
+                builder.set_depth_test(false, wgpu::CompareFunction::Always);
+                builder.set_constant("outline_scale", OUTLINE_SCALE as f64);
+                builder.set_stencil(
+                    wgpu::CompareFunction::NotEqual,
+                    wgpu::StencilOperation::Keep,
+                    wgpu::StencilOperation::Keep,
+                    0xff,
+                    0x00,
+                );
+                "Outline Pipeline"
+            }
+            SceneStage::Transparent => {
+                builder.set_blend_state(Some(wgpu::BlendState::ALPHA_BLENDING));
+                builder.set_depth_test(false, wgpu::CompareFunction::LessEqual);
+                // Both sides of a pane show through it.
+                builder.set_cull_mode(None);
+                "Transparent Pipeline"
+            }
+            SceneStage::Unlit(topology) => {
+                builder.set_topology(topology, None);
+                builder.set_cull_mode(None);
+                match topology {
+                    wgpu::PrimitiveTopology::PointList => "Point Pipeline",
+                    _ => "Line Pipeline",
+                }
+            }
+            SceneStage::Pick => {
+                builder.set_pixel_format(picker::ID_FORMAT);
+                builder.set_sample_count(1);
+                builder.set_blend_state(None);
+                // Only the picker's own depth buffer is written.
+                builder.set_depth_test(sample_count > 1, wgpu::CompareFunction::LessEqual);
+                builder.set_cull_mode(None);
+                "Pick Pipeline"
+            }
+            SceneStage::Shadow => {
+                builder.set_depth_format(shadow_pass::FORMAT);
+                builder.set_sample_count(1);
+                builder.set_depth_only(true);
+                "Shadow Pipeline"
+            }
+        };
+        builder.add_vertex_buffer_layout(mesh_builder::Vertex::get_layout());
+        builder.add_bind_group_layout(match stage {
+            SceneStage::Shadow => shadow_bind_group_layout,
+            _ => camera_bind_group_layout,
+        });
+        builder.add_bind_group_layout(material_bind_group_layout);
+        builder.add_bind_group_layout(transform_bind_group_layout);
+        match per_draw {
+            per_draw::Mode::PushConstants => builder.add_push_constant_range(
+                wgpu::ShaderStages::VERTEX,
+                0..per_draw::PUSH_CONSTANT_SIZE,
+            ),
+            per_draw::Mode::UniformBuffer => {
+                builder.add_bind_group_layout(uniform_bind_group_layout)
+            }
+        }
+        builder.build_pipeline(label)
+    }
+
+    /// Builds the filled pipeline plus, when the device supports line
+    /// rasterization, an otherwise identical wireframe one. With
+    /// `depth_prepass` there's also the pre-pass pipeline, and the filled
+    /// one only shades what the pre-pass left in the depth buffer; the
+    /// wireframe one always tests depth itself. The outline pipelines need
+    /// a `depth_format` with stencil.
+    fn build_render_pipelines(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        depth_prepass: bool,
+        tonemap: bool,
+        per_draw: per_draw::Mode,
+        object_binding: transform_buffer::Binding,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        shadow_bind_group_layout: &wgpu::BindGroupLayout,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+        transform_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<ScenePipelines, shader::ShaderError> {
+        let build = |stage: SceneStage, polygon_mode: wgpu::PolygonMode| {
+            Self::build_render_pipeline(
+                device,
+                format,
+                depth_format,
+                sample_count,
+                stage,
+                polygon_mode,
+                tonemap,
+                per_draw,
+                object_binding,
+                camera_bind_group_layout,
+                shadow_bind_group_layout,
+                uniform_bind_group_layout,
+                material_bind_group_layout,
+                transform_bind_group_layout,
+            )
+        };
+
+        let render = match depth_prepass {
+            true => build(SceneStage::ColorAfterPrepass, wgpu::PolygonMode::Fill)?,
+            false => build(SceneStage::Color, wgpu::PolygonMode::Fill)?,
+        };
+        let wireframe = match device
+            .features()
+            .contains(wgpu::Features::POLYGON_MODE_LINE)
+        {
+            true => Some(build(SceneStage::Color, wgpu::PolygonMode::Line)?),
+            false => None,
+        };
+        let depth_prepass = match depth_prepass {
+            true => Some(build(SceneStage::DepthPrepass, wgpu::PolygonMode::Fill)?),
+            false => None,
+        };
+        let transparent = build(SceneStage::Transparent, wgpu::PolygonMode::Fill)?;
+        let mut unlit = HashMap::new();
+        for topology in UNLIT_TOPOLOGIES {
+            unlit.insert(
+                topology,
+                build(SceneStage::Unlit(topology), wgpu::PolygonMode::Fill)?,
+            );
+        }
+        let outline = match depth_format.has_stencil_aspect() {
+            true => Some((
+                build(SceneStage::OutlineMask, wgpu::PolygonMode::Fill)?,
+                build(SceneStage::Outline, wgpu::PolygonMode::Fill)?,
+            )),
+            false => None,
+        };
+        let pick = match object_binding {
+            transform_buffer::Binding::Storage => {
+                Some(build(SceneStage::Pick, wgpu::PolygonMode::Fill)?)
+            }
+            transform_buffer::Binding::DynamicOffsets => None,
+        };
+        let shadow = build(SceneStage::Shadow, wgpu::PolygonMode::Fill)?;
+        Ok(ScenePipelines {
+            render: render,
+            wireframe: wireframe,
+            depth_prepass: depth_prepass,
+            transparent: transparent,
+            unlit: unlit,
+            outline: outline,
+            pick: pick,
+            shadow: shadow,
+        })
+    }
+
+    fn rebuild_pipelines(
+        &mut self,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Result<(), shader::ShaderError> {
+        let pipelines = Self::build_render_pipelines(
+            &self.device,
+            self.config.format,
+            depth_format,
+            sample_count,
+            self.depth_prepass,
+            self.tonemap,
+            self.per_draw,
+            self.object_binding,
+            &self.camera_bind_group_layout,
+            self.shadow_pass.caster_bind_group_layout(),
+            &self.uniform_bind_group_layout,
+            &self.material_bind_group_layout,
+            &self.transform_bind_group_layout,
+        )?;
+        let particle_pipelines = Self::build_particle_pipelines(
+            &self.device,
+            self.config.format,
+            depth_format,
+            sample_count,
+            &self.camera_bind_group_layout,
+        )?;
+        self.pipelines = pipelines;
+        self.particle_pipelines = particle_pipelines;
+        Ok(())
+    }
+
+    pub fn reload_shaders(&mut self) {
+        match self.rebuild_pipelines(self.depth_format, self.sample_count) {
+            Ok(_) => println!(
+                "Reloaded {}",
+                Self::shader_filename(self.per_draw, self.object_binding)
+            ),
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    /// Tonemaps the scene into 0..1, for when HDR output was wanted but the
+    /// target can't hold values above 1.0.
+    pub fn set_tonemap(&mut self, tonemap: bool) {
+        let previous = self.tonemap;
+        self.tonemap = tonemap;
+        if let Err(e) = self.rebuild_pipelines(self.depth_format, self.sample_count) {
+            eprintln!("{}", e);
+            self.tonemap = previous;
+        }
+    }
+
+    /// Draws the opaque meshes depth-only before the color pass, so only the
+    /// nearest fragment of each pixel runs the fragment shader.
+    pub fn set_depth_prepass(&mut self, depth_prepass: bool) {
+        let previous = self.depth_prepass;
+        self.depth_prepass = depth_prepass;
+        match self.rebuild_pipelines(self.depth_format, self.sample_count) {
+            Ok(_) => println!(
+                "Depth pre-pass: {}",
+                if depth_prepass { "on" } else { "off" }
+            ),
+            Err(e) => {
+                eprintln!("{}", e);
+                self.depth_prepass = previous;
+            }
+        }
+    }
+
+    pub fn depth_prepass(&self) -> bool {
+        self.depth_prepass
+    }
+
+    pub fn toggle_wireframe(&mut self) {
+        if self.pipelines.wireframe.is_none() {
+            eprintln!("Wireframe mode is not supported on this device");
+            return;
+        }
+        self.wireframe = !self.wireframe;
+        println!("Wireframe: {}", if self.wireframe { "on" } else { "off" });
+    }
+
+    /// Switches the camera between perspective and orthographic, keeping
+    /// the scene about the same size on screen.
+    pub fn toggle_projection(&mut self) {
+        self.camera.projection = self.camera.toggled_projection();
+        match self.camera.projection {
+            Projection::Perspective { fov_y, .. } => {
+                println!("Projection: perspective, {:.0} degrees", fov_y)
+            }
+            Projection::Orthographic { .. } => println!("Projection: orthographic"),
+        }
+    }
+
+    pub fn toggle_clear_color_animation(&mut self) {
+        self.animate_clear_color = !self.animate_clear_color;
+        println!(
+            "Clear color animation: {}",
+            if self.animate_clear_color {
+                "on"
+            } else {
+                "off"
+            }
+        );
+    }
+
+    pub fn colors(&self) -> ColorSpace {
+        self.colors
+    }
+
+    /// Switches how vertex and clear colors are interpreted, rebuilding the
+    /// meshes that bake them.
+    pub fn set_colors(&mut self, colors: ColorSpace) {
+        self.colors = colors;
+        self.triangle_mesh = mesh_builder::make_triangle(&self.device, colors);
+        self.quad_mesh = mesh_builder::make_textured_quad(&self.device, colors, &AtlasRegion::FULL);
+        self.notify_passes();
+    }
+
+    pub fn toggle_colors(&mut self) {
+        self.set_colors(self.colors.toggled());
+        println!("Colors authored as: {:?}", self.colors);
+    }
+
+    fn supports_sample_count(&self, sample_count: u32) -> bool {
+        [self.config.format, self.depth_format]
+            .iter()
+            .all(|&format| {
+                self.adapter
+                    .get_texture_format_features(format)
+                    .flags
+                    .sample_count_supported(sample_count)
+            })
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        let sample_count = match self.supports_sample_count(sample_count) {
+            true => sample_count,
+            false => {
+                eprintln!(
+                    "{}x MSAA is not supported, falling back to 1x",
+                    sample_count
+                );
+                1
+            }
+        };
+
+        match self.rebuild_pipelines(self.depth_format, sample_count) {
+            Ok(_) => {
+                self.sample_count = sample_count;
+                self.create_render_targets();
+                println!("MSAA: {}x", sample_count);
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    pub fn stencil(&self) -> bool {
+        self.depth_format.has_stencil_aspect()
+    }
+
+    /// Switches the depth buffer to `Texture::DEPTH_STENCIL_FORMAT`, which
+    /// the selection outline needs, or back.
+    pub fn set_stencil(&mut self, stencil: bool) {
+        let depth_format = match stencil {
+            true => Texture::DEPTH_STENCIL_FORMAT,
+            false => Texture::DEPTH_FORMAT,
+        };
+        match self.rebuild_pipelines(depth_format, self.sample_count) {
+            Ok(_) => {
+                self.depth_format = depth_format;
+                self.create_render_targets();
+                println!("Depth format: {:?}", depth_format);
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+
+    pub fn selected(&self) -> Option<ObjectId> {
+        self.selected
+    }
+
+    /// Outlines `selected`, which takes a stencil buffer; see `set_stencil`.
+    pub fn set_selected(&mut self, selected: Option<ObjectId>) {
+        if selected.is_some() && !self.stencil() {
+            eprintln!("Selection outlines need the stencil buffer, e.g. --stencil");
+        }
+        self.selected = selected;
+    }
+
+    /// Every object `set_selected` can outline, in draw order.
+    pub fn selectable_objects(&self) -> Vec<ObjectId> {
+        let mut objects = vec![self.quad_object, self.cube_object];
+        if self.wave {
+            objects.push(self.wave_object);
+        }
+        objects.extend(self.models.iter().map(|(_, object)| *object));
+        objects.extend(self.scene_selectable_objects());
+        objects.extend(self.grid_objects.iter().copied());
+        objects
+    }
+
+    /// The selectable objects that are scene nodes, in draw order.
+    pub fn scene_selectable_objects(&self) -> Vec<ObjectId> {
+        self.scene_objects
+            .iter()
+            .filter(|(node, _)| self.scene.visible(*node))
+            .map(|(_, object)| *object)
+            .collect()
+    }
+
+    /// Asks which selectable object is drawn at pixel `x`, `y` of the
+    /// target; `take_pick` has the answer a frame or two later. Picking
+    /// needs storage transforms, see `transform_buffer::Binding`.
+    pub fn request_pick(&mut self, x: u32, y: u32) {
+        match &mut self.picker {
+            Some(picker) => picker.request(x, y),
+            None => eprintln!("Picking needs vertex shader storage buffers"),
+        }
+    }
+
+    /// Whether `request_pick` works here.
+    pub fn gpu_picking(&self) -> bool {
+        self.picker.is_some()
+    }
+
+    /// The scene node under pixel `x`, `y` of the scene viewport, found
+    /// right away on the CPU by casting a ray against the scene's meshes,
+    /// with its object. Unlike `request_pick` it only sees scene nodes.
+    pub fn raycast(&self, x: f32, y: f32) -> Option<(ObjectId, Hit)> {
+        let size = match &self.viewport {
+            Some(viewport) => (viewport.width, viewport.height),
+            None => (self.config.width, self.config.height),
+        };
+        let ray = self
+            .camera
+            .screen_to_ray((x, y), (size.0 as f32, size.1 as f32));
+        // Scene nodes are drawn through the model matrix too.
+        let model = *self.model_transform.matrix();
+        let mut hit = self
+            .scene
+            .raycast(&ray.transformed(&glm::inverse(&model)), &self.scene_meshes)?;
+        hit.point = (model * hit.point.extend(1.0)).truncate(3);
+        self.scene_objects
+            .iter()
+            .find(|&&(node, _)| node == hit.entity)
+            .map(|&(_, object)| (object, hit))
+    }
+
+    /// The answer to the last `request_pick`, once it's back: the object at
+    /// the pixel, or `None` where there's nothing drawn.
+    pub fn take_pick(&mut self) -> Option<Option<ObjectId>> {
+        let id = self.picker.as_mut()?.poll(&self.device)?;
+        Some(
+            self.selectable_objects()
+                .into_iter()
+                .find(|object| object.index() + 1 == id),
+        )
+    }
+
+    /// The world-space box around the selected object, for `Camera::frame`.
+    pub fn selected_bounds(&self) -> Option<Aabb> {
+        let selected = self.selected?;
+        let model = *self.model_transform.matrix();
+        let instance = self.transforms.instances()[selected.index() as usize];
+        self.drawables()
+            .into_iter()
+            .chain(self.scene_draws())
+            .find(|(_, _, objects)| objects.contains(&selected.index()))
+            .map(|(mesh, _, _)| mesh.bounds.transformed(&(model * instance.matrix())))
+    }
+
+    /// The scene node drawn as the selected object, if it's one.
+    pub fn selected_node(&self) -> Option<NodeId> {
+        let selected = self.selected?;
+        self.scene_objects
+            .iter()
+            .find(|&&(_, object)| object == selected)
+            .map(|&(node, _)| node)
+    }
+
+    fn target_info(
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+        colors: ColorSpace,
+        sampler: &CachedSampler,
+    ) -> TargetInfo {
+        TargetInfo {
+            width: config.width,
+            height: config.height,
+            format: config.format,
+            depth_format: depth_format,
+            sample_count: sample_count,
+            colors: colors,
+            sampler: sampler.clone(),
+        }
+    }
+
+    /// Lets every pass catch up with a change to the targets, colors or
+    /// sampler.
+    fn notify_passes(&mut self) {
+        let info = Self::target_info(
+            &self.config,
+            self.depth_format,
+            self.sample_count,
+            self.colors,
+            &self.sampler,
+        );
+        for slot in &mut self.passes {
+            slot.pass.resize(&self.device, &info);
+        }
+        self.grid_pass.resize(&self.device, &info);
+        self.bloom_pass.resize(&self.device, &info);
+        self.post_pass.resize(&self.device, &info);
+        self.debug_draw.resize(&self.device, &info);
+    }
+
+    /// The sampler materials are drawn with, after any clamping.
+    pub fn sampler(&self) -> SamplerDesc {
+        self.sampler.desc
+    }
+
+    /// Rebuilds every material to sample with `desc`. Anisotropy the device
+    /// can't do is clamped with a warning.
+    pub fn set_sampler(&mut self, desc: SamplerDesc) {
+        self.sampler = self.samplers.get(&self.device, &desc);
+        let layout = &self.material_bind_group_layout;
+        let sampler = &self.sampler.sampler;
+        self.triangle_material
+            .set_sampler(&self.device, layout, sampler);
+        self.quad_material
+            .set_sampler(&self.device, layout, sampler);
+        self.glass_material
+            .set_sampler(&self.device, layout, sampler);
+        self.selection_materials = Self::selection_materials(
+            &[
+                &self.triangle_material,
+                &self.quad_material,
+                &self.glass_material,
+            ],
+            &self.device,
+            layout,
+            sampler,
+        );
+        for (part, _) in &mut self.models {
+            part.material.set_sampler(&self.device, layout, sampler);
+        }
+        self.notify_passes();
+        println!(
+            "Sampler: {}, {:?}",
+            self.sampler.desc.preset_name(),
+            self.sampler.desc.address_mode
+        );
+    }
+
+    /// Appends a pass recorded after the scene and the passes added before it.
+    pub fn add_pass(&mut self, pass: Box<dyn RenderPass>) -> PassId {
+        self.passes.push(PassSlot {
+            pass: pass,
+            enabled: true,
+        });
+        PassId(self.passes.len() - 1)
+    }
+
+    /// Disabled passes aren't recorded but still follow target changes.
+    pub fn set_pass_enabled(&mut self, id: PassId, enabled: bool) {
+        self.passes[id.0].enabled = enabled;
+    }
+
+    pub fn pass_enabled(&self, id: PassId) -> bool {
+        self.passes[id.0].enabled
+    }
+
+    pub fn skybox(&self) -> bool {
+        self.pass_enabled(self.skybox_pass)
+    }
+
+    pub fn toggle_skybox(&mut self) {
+        let enabled = !self.skybox();
+        self.set_pass_enabled(self.skybox_pass, enabled);
+        println!("Skybox: {}", if enabled { "on" } else { "off" });
+    }
+
+    pub fn grid(&self) -> bool {
+        self.grid
+    }
+
+    pub fn toggle_grid(&mut self) {
+        self.grid = !self.grid;
+        println!("Grid: {}", if self.grid { "on" } else { "off" });
+    }
+
+    pub fn grid_settings(&self) -> GridSettings {
+        self.grid_pass.settings()
+    }
+
+    pub fn set_grid_settings(&mut self, settings: GridSettings) {
+        self.grid_pass.set_settings(settings);
+    }
+
+    pub fn bloom(&self) -> bool {
+        self.bloom
+    }
+
+    pub fn toggle_bloom(&mut self) {
+        self.bloom = !self.bloom;
+        println!("Bloom: {}", if self.bloom { "on" } else { "off" });
+    }
+
+    pub fn bloom_settings(&self) -> BloomSettings {
+        self.bloom_pass.settings()
+    }
+
+    pub fn set_bloom_settings(&mut self, settings: BloomSettings) {
+        self.bloom_pass.set_settings(settings);
+    }
+
+    pub fn post_effect(&self) -> PostEffect {
+        self.post_pass.effect()
+    }
+
+    pub fn set_post_effect(&mut self, effect: PostEffect) {
+        self.post_pass.set_effect(&self.device, effect);
+        println!("Post effect: {}", self.post_pass.effect().name());
+    }
+
+    /// How the target's alpha gets composited; see `wgpu::CompositeAlphaMode`.
+    /// The frame always has premultiplied alpha, since alpha blending over
+    /// a background cleared to 0 produces it, so postmultiplied compositing
+    /// costs a post pass to divide it out again.
+    pub fn set_alpha_mode(&mut self, alpha_mode: wgpu::CompositeAlphaMode) {
+        self.config.alpha_mode = alpha_mode;
+        let unpremultiply = alpha_mode == wgpu::CompositeAlphaMode::PostMultiplied;
+        self.post_pass
+            .set_unpremultiply(&self.device, unpremultiply);
+    }
+
+    pub fn cycle_post_effect(&mut self) {
+        self.set_post_effect(self.post_effect().next());
+    }
+
+    /// The post-processing passes to run this frame, in order: bloom, then
+    /// the post effect. With bloom off, the effect on passthrough and
+    /// nothing to unpremultiply there are none, so the frame is exactly what
+    /// direct rendering gives.
+    fn post_passes(&self) -> Vec<&dyn RenderPass> {
+        let mut post_passes: Vec<&dyn RenderPass> = Vec::new();
+        if self.bloom {
+            post_passes.push(&self.bloom_pass);
+        }
+        if self.post_pass.effect() != PostEffect::Passthrough || self.post_pass.unpremultiply() {
+            post_passes.push(&self.post_pass);
+        }
+        post_passes
+    }
+
+    /// The MSAA color target, when multisampling, and the depth target for
+    /// drawing the scene at `config`'s size.
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (Option<Texture>, Texture) {
+        let msaa_texture = match sample_count > 1 {
+            true => Some(Texture::create_msaa_texture(
+                device,
+                config,
+                sample_count,
+                "MSAA Texture",
+            )),
+            false => None,
+        };
+        let depth_texture = Texture::create_depth_texture(
+            device,
+            config,
+            depth_format,
+            sample_count,
+            "Depth Texture",
+        );
+        (msaa_texture, depth_texture)
+    }
+
+    fn create_render_targets(&mut self) {
+        (self.msaa_texture, self.depth_texture) = Self::create_targets(
+            &self.device,
+            &self.config,
+            self.depth_format,
+            self.sample_count,
+        );
+        if let Some(picker) = &mut self.picker {
+            picker.resize(
+                &self.device,
+                &self.config,
+                self.depth_format,
+                self.sample_count,
+            );
+        }
+        self.notify_passes();
+    }
+
+    fn cube_transform(time: f32) -> glm::Mat4 {
+        instance::transform(glm::vec3(1.6, 0.0, -1.0), 0.5 + time, 0.6)
+    }
+
+    fn grid_transforms(time: f32) -> Vec<glm::Mat4> {
+        const GRID_SIZE: i32 = 50;
+        let mut transforms = Vec::with_capacity((GRID_SIZE * GRID_SIZE) as usize);
+        for x in 0..GRID_SIZE {
+            for z in 0..GRID_SIZE {
+                let position = glm::vec3((x - GRID_SIZE / 2) as f32, -1.5, -(z as f32) - 3.0);
+                let rotation = time + (x + z) as f32 * 0.1;
+                transforms.push(instance::transform(position, rotation, 0.3));
+            }
+        }
+        transforms
+    }
+
+    /// A planet circling a hub with a moon circling it in turn. The moon's
+    /// orbit and size are relative to the planet's.
+    fn orbit_scene() -> (Scene, Vec<(NodeId, f32)>) {
+        let mut scene = Scene::new();
+        let hub = scene.add_node(None);
+        scene.set_local_transform(
+            hub,
+            Transform {
+                translation: glm::vec3(-1.8, 0.8, -2.0),
+                ..Transform::default()
+            },
+        );
+        let planet = scene.add_node(Some(hub));
+        scene.set_local_transform(
+            planet,
+            Transform {
+                translation: glm::vec3(0.9, 0.0, 0.0),
+                scale: glm::vec3(0.4, 0.4, 0.4),
+                ..Transform::default()
+            },
+        );
+        scene.set_mesh(planet, Some(SCENE_SPHERE_MESH));
+        let moon = scene.add_node(Some(planet));
+        scene.set_local_transform(
+            moon,
+            Transform {
+                translation: glm::vec3(1.2, 0.0, 0.0),
+                scale: glm::vec3(0.4, 0.4, 0.4),
+                ..Transform::default()
+            },
+        );
+        scene.set_mesh(moon, Some(SCENE_SPHERE_MESH));
+        scene.update_world_transforms();
+        (scene, vec![(hub, 0.8), (planet, 2.0)])
+    }
+
+    /// Two overlapping translucent panes, one behind the other.
+    fn add_panes(scene: &mut Scene) {
+        for (mesh, translation) in [
+            (SCENE_RED_PANE_MESH, glm::vec3(1.6, 0.6, -2.0)),
+            (SCENE_BLUE_PANE_MESH, glm::vec3(2.0, 0.3, -2.8)),
+        ] {
+            let pane = scene.add_node(None);
+            scene.set_local_transform(
+                pane,
+                Transform {
+                    translation: translation,
+                    ..Transform::default()
+                },
+            );
+            scene.set_mesh(pane, Some(mesh));
+            scene.set_material(pane, SCENE_GLASS_MATERIAL);
+        }
+        scene.update_world_transforms();
+    }
+
+    /// Advances the animations by `dt` seconds of animation time, none
+    /// while paused.
+    pub fn update(&mut self, dt: f32) {
+        let dt = if self.paused { 0.0 } else { dt };
+        self.delta_time = dt;
+        self.time += dt;
+        if self.animate_clear_color {
+            self.clear_color = clear_color::hue_cycle(self.time);
+        }
+        self.particles.update(&self.device, &mut self.uploader, dt);
+        if let Some((buffer, count)) = &self.upload_stress {
+            for i in 0..*count {
+                let value = [self.time, i as f32, 0.0, 1.0];
+                self.uploader
+                    .write(buffer, i as u64 * UPLOAD_STRESS_STRIDE, &value);
+            }
+        }
+
+        self.transforms
+            .update(self.cube_object, Self::cube_transform(self.time));
+        for (&id, transform) in self
+            .grid_objects
+            .iter()
+            .zip(Self::grid_transforms(self.time))
+        {
+            self.transforms.update(id, transform);
+        }
+        for &(node, speed) in &self.orbits {
+            let mut transform = self.scene.local_transform(node);
+            transform.rotation.y = self.time * speed;
+            self.scene.set_local_transform(node, transform);
+        }
+        self.scene.update_world_transforms();
+        self.sync_scene_objects();
+        self.transforms.flush(
+            &self.device,
+            &mut self.uploader,
+            &self.transform_bind_group_layout,
+        );
+
+        if self.wave {
+            let vertices = mesh_builder::wave_vertices(self.wave_resolution, WAVE_SIZE, self.time);
+            self.wave_mesh
+                .update_vertices(&self.device, &self.queue, &vertices);
+        }
+
+        // Every watcher has to be asked to keep up with its file.
+        let changed = self
+            .shader_watchers
+            .iter_mut()
+            .fold(false, |changed, watcher| watcher.changed() || changed);
+        if changed {
+            self.reload_shaders();
+        }
+    }
+
+    pub fn wave(&self) -> bool {
+        self.wave
+    }
+
+    /// Shows or hides the sine wave sheet, whose vertices are rebuilt every
+    /// frame while it's shown.
+    pub fn toggle_wave(&mut self) {
+        self.wave = !self.wave;
+        println!("Sine wave: {}", if self.wave { "on" } else { "off" });
+    }
+
+    pub fn wave_resolution(&self) -> u32 {
+        self.wave_resolution
+    }
+
+    /// Regrids the wave with `resolution` quads per side, growing its
+    /// buffers when they're too small and reusing them otherwise.
+    pub fn set_wave_resolution(&mut self, resolution: u32) {
+        self.wave_resolution = resolution.clamp(1, mesh_builder::MAX_WAVE_RESOLUTION);
+        let indices = mesh_builder::wave_indices(self.wave_resolution);
+        self.wave_mesh
+            .update_indices(&self.device, &self.queue, &indices);
+        let vertices = mesh_builder::wave_vertices(self.wave_resolution, WAVE_SIZE, self.time);
+        self.wave_mesh
+            .update_vertices(&self.device, &self.queue, &vertices);
+    }
+
+    /// Points the directional light along `direction`, away from the light,
+    /// and sets its color.
+    pub fn set_light(&mut self, direction: glm::Vec3, color: glm::Vec3) {
+        self.light_direction = direction;
+        self.light_color = color;
+        let light_uniform = uniform::LightUniform::new(&direction, &color, AMBIENT_STRENGTH);
+        self.queue
+            .write_buffer(&self.light_buffer, 0, bytemuck::bytes_of(&light_uniform));
+    }
+
+    /// The light's direction and color as last set.
+    pub fn light(&self) -> (glm::Vec3, glm::Vec3) {
+        (self.light_direction, self.light_color)
+    }
+
+    /// Adds every mesh of a glTF model to the scene at its node's transform.
+    pub fn load_model(&mut self, path: &Path) -> Result<(), RendererError> {
+        let parts = mesh_builder::load_gltf(
+            &self.device,
+            &self.queue,
+            &self.material_bind_group_layout,
+            &self.sampler.sampler,
+            path,
+        )?;
+        println!("Loaded {} with {} meshes", path.display(), parts.len());
+        self.model_paths.push(path.to_path_buf());
+        for part in parts {
+            let object = self.transforms.push(part.transform);
+            self.models.push((part, object));
+        }
+        self.transforms.flush(
+            &self.device,
+            &mut self.uploader,
+            &self.transform_bind_group_layout,
+        );
+        Ok(())
+    }
+
+    pub fn set_model_matrix(&mut self, m: glm::Mat4) {
+        self.model_transform.set(&self.queue, &m);
+    }
+
+    /// The clear color as authored; see `colors` for how it's converted.
+    pub fn clear_color(&self) -> wgpu::Color {
+        self.clear_color
+    }
+
+    pub fn set_clear_color(&mut self, color: wgpu::Color) {
+        self.clear_color = color;
+    }
+
+    pub fn animate_clear_color(&self) -> bool {
+        self.animate_clear_color
+    }
+
+    pub fn wireframe(&self) -> bool {
+        self.wireframe
+    }
+
+    fn update_camera(&mut self) {
+        let camera_uniform = uniform::CameraUniform::new(&self.camera.build_view_projection());
+        self.uploader
+            .write(&self.camera_buffer, 0, &[camera_uniform]);
+    }
+
+    fn update_globals(&mut self) {
+        let globals_uniform = uniform::GlobalsUniform::new(
+            self.time,
+            self.delta_time,
+            self.config.width,
+            self.config.height,
+        );
+        self.uploader
+            .write(&self.globals_buffer, 0, &[globals_uniform]);
+    }
+
+    pub fn paused(&self) -> bool {
+        self.paused
+    }
+
+    /// Stops the animation clock, and everything driven by it, while the
+    /// frames keep coming and the camera keeps moving.
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+        println!("Animation: {}", if paused { "paused" } else { "running" });
+    }
+
+    /// Draws the scene into `target`, which must match `config`'s size and
+    /// format.
+    pub fn render(&mut self, target: &wgpu::TextureView) {
+        let command_encoder_descriptor = wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        };
+
+        let mut command_encoder = self
+            .device
+            .create_command_encoder(&command_encoder_descriptor);
+        self.encode(&mut command_encoder, target);
+        self.queue.submit(std::iter::once(command_encoder.finish()));
+        self.frame_submitted();
+    }
+
+    /// Records the frame's compute and render passes into `command_encoder`
+    /// so callers can add their own passes before submitting.
+    pub fn encode(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+    ) {
+        self.update_camera();
+        self.update_globals();
+        self.cull_objects();
+        let pick = self.picker.as_mut().and_then(Picker::take_request);
+        for slot in self.passes.iter_mut().filter(|slot| slot.enabled) {
+            slot.pass.prepare(&mut self.uploader, &self.camera);
+        }
+        if self.grid {
+            self.grid_pass.prepare(&mut self.uploader, &self.camera);
+        }
+        self.add_debug_views();
+        self.debug_draw
+            .prepare(&self.device, &mut self.uploader, &self.camera);
+        self.upload_object_uniforms();
+        self.prepare_shadows();
+        self.prepare_indirect(command_encoder);
+        self.uploader.record(&self.device, command_encoder);
+        self.encode_gpu_cull(command_encoder);
+        self.draw_calls.set(0);
+
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.begin_frame(&self.device);
+        }
+        let compute_timestamps = self
+            .gpu_timer
+            .as_ref()
+            .and_then(GpuTimer::compute_pass_writes);
+        let computed = self
+            .particles
+            .encode_compute(command_encoder, compute_timestamps);
+
+        // With post-processing the scene goes into the first post pass's
+        // input, each post pass writes the next one's and the last writes
+        // `target`.
+        let post_passes = self.post_passes();
+        let scene_target = post_passes
+            .first()
+            .and_then(|pass| pass.input())
+            .unwrap_or(target);
+        let targets = FrameTargets {
+            surface: scene_target,
+            color: match &self.msaa_texture {
+                Some(msaa_texture) => &msaa_texture.view,
+                None => scene_target,
+            },
+            resolve: self.msaa_texture.as_ref().map(|_| scene_target),
+            depth: &self.depth_texture.view,
+            stencil: self.stencil(),
+            viewport: self.viewport,
+        };
+        let timestamp_writes = self
+            .gpu_timer
+            .as_ref()
+            .and_then(GpuTimer::render_pass_writes);
+        // The scene samples the shadow map, so it's drawn first.
+        self.encode_shadows(command_encoder);
+        self.encode_scene(
+            command_encoder,
+            &targets,
+            &self.camera,
+            &self.camera_bind_group,
+            timestamp_writes,
+        );
+        for slot in self.passes.iter().filter(|slot| slot.enabled) {
+            slot.pass.record(command_encoder, &targets);
+        }
+        // After the skybox, which it blends over.
+        if self.grid {
+            self.grid_pass.record(command_encoder, &targets);
+        }
+        // After the passes, so the skybox doesn't draw over the outline.
+        self.encode_outline(command_encoder, &targets, &self.camera_bind_group);
+        self.debug_draw.record(command_encoder, &targets);
+        if let Some(pixel) = pick {
+            self.encode_pick(command_encoder, &targets, pixel);
+        }
+        for (i, pass) in post_passes.iter().enumerate() {
+            let output = post_passes
+                .get(i + 1)
+                .and_then(|next| next.input())
+                .unwrap_or(target);
+            let targets = FrameTargets {
+                surface: output,
+                color: output,
+                resolve: None,
+                depth: &self.depth_texture.view,
+                stencil: self.stencil(),
+                viewport: None,
+            };
+            pass.record(command_encoder, &targets);
+        }
+        if let Some(viewport) = &self.viewport {
+            let bars = viewport.bars(self.config.width, self.config.height);
+            self.letterbox_pass.record(command_encoder, target, &bars);
+        }
+        if self.debug_views.shadow_map {
+            self.shadow_pass.record_overlay(
+                command_encoder,
+                target,
+                self.config.width,
+                self.config.height,
+            );
+        }
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.resolve(command_encoder, computed);
+        }
+        self.frame_draw_calls = self.draw_calls.get();
+    }
+
+    /// Recycles the frame's staging buffers and lets the GPU timer and the
+    /// GPU culler start reading back the frame just submitted. Call after
+    /// submitting the command buffer `encode` or `encode_view` recorded into.
+    pub fn frame_submitted(&mut self) {
+        self.uploader.recall();
+        if let Some(gpu_timer) = &mut self.gpu_timer {
+            gpu_timer.frame_submitted();
+        }
+        if let Some(gpu_cull) = self.gpu_culler_mut() {
+            gpu_cull.frame_submitted();
+        }
+        if let Some(picker) = &mut self.picker {
+            picker.frame_submitted();
+        }
+        self.particles.frame_submitted();
+    }
+
+    /// What the last recorded frame uploaded through the staging belt.
+    pub fn upload_stats(&self) -> UploadStats {
+        self.uploader.stats()
+    }
+
+    /// Writes `count` small, scattered updates into a scratch buffer every
+    /// frame, in addition to the scene's own, to stress the uploader. 0
+    /// turns it off.
+    pub fn set_upload_stress(&mut self, count: u32) {
+        self.upload_stress = match count {
+            0 => None,
+            _ => Some((
+                self.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Upload Stress Buffer"),
+                    size: count as u64 * UPLOAD_STRESS_STRIDE,
+                    usage: wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                count,
+            )),
+        };
+    }
+
+    /// Pass timings from a few frames ago, or `None` without timestamp
+    /// query support.
+    pub fn gpu_timings(&self) -> Option<GpuTimings> {
+        self.gpu_timer.as_ref().and_then(GpuTimer::latest)
+    }
+
+    /// The grid was pushed in one go, so its objects are contiguous.
+    fn grid_objects(&self) -> Range<u32> {
+        let grid_start = self.grid_objects[0].index();
+        grid_start..grid_start + self.grid_objects.len() as u32
+    }
+
+    /// Tests every object against the camera's frustum, or the frozen one,
+    /// and remembers which to draw.
+    fn cull_objects(&mut self) {
+        let frustum = self.cull_frustum();
+        let model = *self.model_transform.matrix();
+        let instances = self.transforms.instances();
+
+        let mut draws = vec![
+            (&self.quad_mesh.bounds, self.quad_object.instances()),
+            (&self.cube_mesh.bounds, self.cube_object.instances()),
+            (&self.triangle_mesh.bounds, self.grid_objects()),
+        ];
+        if self.wave {
+            draws.push((&self.wave_mesh.bounds, self.wave_object.instances()));
+        }
+        draws.extend(
+            self.models
+                .iter()
+                .map(|(part, object)| (&part.mesh.bounds, object.instances())),
+        );
+        for (mesh, _, objects) in self.scene_draws() {
+            draws.push((&mesh.bounds, objects));
+        }
+
+        let mut visible = vec![true; instances.len()];
+        let mut stats = CullStats::default();
+        for (bounds, objects) in draws {
+            for object in objects {
+                let inside =
+                    frustum.intersects(bounds, &(model * instances[object as usize].matrix()));
+                visible[object as usize] = inside;
+                match inside {
+                    true => stats.drawn += 1,
+                    false => stats.culled += 1,
+                }
+            }
+        }
+        self.object_visible = visible;
+        self.cull_stats = stats;
+    }
+
+    /// The camera's frustum, or the frozen one.
+    fn cull_frustum(&self) -> Frustum {
+        self.frozen_frustum
+            .unwrap_or_else(|| Frustum::from_matrix(&self.camera.build_view_projection()))
+    }
+
+    pub fn cull_stats(&self) -> CullStats {
+        self.cull_stats
+    }
+
+    /// Mesh draw calls the last frame recorded, over every scene pass.
+    pub fn draw_calls(&self) -> u32 {
+        self.frame_draw_calls
+    }
+
+    pub fn indirect(&self) -> bool {
+        self.indirect.is_some()
+    }
+
+    /// Draws the scene's meshes from a shared mesh pool through indirect
+    /// draws, or directly from each mesh's buffers. Needs storage
+    /// transforms, since every object in a batch reads its own, and
+    /// `Features::INDIRECT_FIRST_INSTANCE` to say which.
+    pub fn set_indirect(&mut self, indirect: bool) {
+        if indirect == self.indirect() {
+            return;
+        }
+        if indirect && self.object_uniforms.is_some() {
+            eprintln!("Indirect draws need storage transforms, staying with direct draws");
+            return;
+        }
+        if indirect
+            && !self
+                .device
+                .features()
+                .contains(wgpu::Features::INDIRECT_FIRST_INSTANCE)
+        {
+            eprintln!("INDIRECT_FIRST_INSTANCE is not supported, staying with direct draws");
+            return;
+        }
+        self.indirect = indirect.then(|| IndirectPath {
+            pool: MeshPool::new(),
+            draws: IndirectDraws::new(&self.device),
+            gpu_cull: None,
+        });
+        println!("Draw path: {}", self.draw_path());
+    }
+
+    /// "direct", "indirect", "multi-draw indirect" or "GPU-culled indirect".
+    pub fn draw_path(&self) -> &'static str {
+        match &self.indirect {
+            Some(indirect) if indirect.gpu_cull.is_some() => "GPU-culled indirect",
+            Some(indirect) if indirect.draws.multi_draw() => "multi-draw indirect",
+            Some(_) => "indirect",
+            None => "direct",
+        }
+    }
+
+    pub fn gpu_cull(&self) -> bool {
+        self.indirect
+            .as_ref()
+            .is_some_and(|indirect| indirect.gpu_cull.is_some())
+    }
+
+    fn gpu_culler_mut(&mut self) -> Option<&mut GpuCuller> {
+        self.indirect.as_mut()?.gpu_cull.as_mut()
+    }
+
+    /// Frustum culls on the indirect path in a compute pass, which writes
+    /// the draws itself, rather than drawing what `cull_objects` kept. The
+    /// CPU culler keeps running either way so `gpu_cull_stats` can say
+    /// where the two disagree; turning this off goes back to its draws.
+    pub fn set_gpu_cull(&mut self, gpu_cull: bool) {
+        if gpu_cull == self.gpu_cull() {
+            return;
+        }
+        let Some(indirect) = &mut self.indirect else {
+            eprintln!("GPU culling needs the indirect draw path");
+            return;
+        };
+        indirect.gpu_cull = match gpu_cull {
+            true => match GpuCuller::new(&self.device) {
+                Ok(gpu_cull) => {
+                    if !gpu_cull.count_draws() {
+                        println!(
+                            "MULTI_DRAW_INDIRECT_COUNT is not supported, drawing every culled slot"
+                        );
+                    }
+                    Some(gpu_cull)
+                }
+                Err(e) => {
+                    eprintln!("{}", e);
+                    return;
+                }
+            },
+            false => None,
+        };
+        println!("Draw path: {}", self.draw_path());
+    }
+
+    /// What the GPU culler kept a few frames ago and how far that was from
+    /// the CPU culler, while it's on.
+    pub fn gpu_cull_stats(&self) -> Option<GpuCullStats> {
+        self.indirect.as_ref()?.gpu_cull.as_ref()?.latest()
+    }
+
+    pub fn particle_stats(&self) -> ParticleStats {
+        self.particles.stats()
+    }
+
+    pub fn particle_emitter(&self) -> &Emitter {
+        self.particles.emitter()
+    }
+
+    /// Changes how new particles are spawned from the next step on.
+    pub fn set_particle_emitter(&mut self, emitter: Emitter) {
+        self.particles.set_emitter(emitter);
+    }
+
+    /// Lines drawn over this frame only, in world space. Add to them any time
+    /// before `encode`.
+    pub fn debug_draw(&mut self) -> &mut DebugDraw {
+        &mut self.debug_draw
+    }
+
+    pub fn debug_views(&self) -> DebugViews {
+        self.debug_views
+    }
+
+    pub fn set_debug_views(&mut self, views: DebugViews) {
+        if views.cull_frustum && self.frozen_frustum.is_none() {
+            println!("The culling frustum shows once it's frozen");
+        }
+        self.debug_views = views;
+    }
+
+    pub fn debug_depth_test(&self) -> bool {
+        self.debug_draw.depth_test()
+    }
+
+    /// Whether debug lines hide behind the scene or show through it.
+    pub fn set_debug_depth_test(&mut self, depth_test: bool) {
+        let info = Self::target_info(
+            &self.config,
+            self.depth_format,
+            self.sample_count,
+            self.colors,
+            &self.sampler,
+        );
+        self.debug_draw
+            .set_depth_test(&self.device, &info, depth_test);
+    }
+
+    /// Adds this frame's lines for the views `debug_views` turns on.
+    fn add_debug_views(&mut self) {
+        let views = self.debug_views;
+        if views.world_axes {
+            self.debug_draw.axes(&uniform::identity(), 1.0);
+        }
+        if let Some(frustum) = self.frozen_frustum.filter(|_| views.cull_frustum) {
+            self.debug_draw.frustum(&frustum, glm::vec3(1.0, 1.0, 0.0));
+        }
+        if self.shadow_pass.frozen() {
+            let frustum = Frustum::from_matrix(&self.shadow_pass.light_view_projection());
+            self.debug_draw.frustum(&frustum, glm::vec3(1.0, 0.5, 0.0));
+        }
+        if !views.bounds {
+            return;
+        }
+        let model = *self.model_transform.matrix();
+        let instances = self.transforms.instances();
+        let mut boxes = Vec::new();
+        let draws = self
+            .drawables()
+            .into_iter()
+            .chain(
+                self.scene_draws()
+                    .into_iter()
+                    .filter(|(mesh, material, _)| {
+                        mesh.topology() != wgpu::PrimitiveTopology::TriangleList
+                            || material.transparent()
+                    }),
+            );
+        for (mesh, _, objects) in draws {
+            for object in objects {
+                let world = model * instances[object as usize].matrix();
+                boxes.push((mesh.bounds, world, self.visible(object)));
+            }
+        }
+        for (bounds, world, visible) in boxes {
+            let color = match visible {
+                true => glm::vec3(0.0, 1.0, 0.0),
+                false => glm::vec3(1.0, 0.0, 0.0),
+            };
+            self.debug_draw.aabb(&bounds, &world, color);
+        }
+    }
+
+    /// Keeps culling against the frustum as it is now while the camera moves
+    /// on, so objects can be seen popping out at its edges. Toggles back.
+    pub fn toggle_frustum_freeze(&mut self) {
+        self.frozen_frustum = match self.frozen_frustum {
+            Some(_) => None,
+            None => Some(Frustum::from_matrix(&self.camera.build_view_projection())),
+        };
+        println!(
+            "Culling frustum: {}",
+            if self.frozen_frustum.is_some() {
+                "frozen"
+            } else {
+                "following the camera"
+            }
+        );
+    }
+
+    /// With `Binding::DynamicOffsets`, copies every object's transform into
+    /// this frame's dynamic uniform buffer and records its offset.
+    fn upload_object_uniforms(&mut self) {
+        let Some(object_uniforms) = &mut self.object_uniforms else {
+            return;
+        };
+        object_uniforms.reset();
+        self.object_offsets.clear();
+        for transform in self.transforms.instances() {
+            self.object_offsets.push(object_uniforms.alloc(transform));
+        }
+        object_uniforms.flush(
+            &self.device,
+            &mut self.uploader,
+            &self.transform_bind_group_layout,
+        );
+    }
+
+    /// Draws `mesh` once per object in `objects` that survived culling:
+    /// instanced straight from the transform buffer, one draw per run of
+    /// visible objects, or one draw per object at its dynamic offset.
+    fn draw_objects<'a>(
+        &'a self,
+        renderpass: &mut wgpu::RenderPass<'a>,
+        mesh: &'a mesh_builder::Mesh,
+        objects: Range<u32>,
+    ) {
+        self.draw_runs(renderpass, mesh, self.visible_runs(objects));
+    }
+
+    /// Draws `mesh` once per object in `runs`, culled or not.
+    fn draw_runs<'a>(
+        &'a self,
+        renderpass: &mut wgpu::RenderPass<'a>,
+        mesh: &'a mesh_builder::Mesh,
+        runs: Vec<Range<u32>>,
+    ) {
+        match (&self.object_uniforms, self.transforms.bind_group()) {
+            (Some(object_uniforms), _) => {
+                for object in runs.into_iter().flatten() {
+                    let offset = self.object_offsets[object as usize];
+                    renderpass.set_bind_group(2, object_uniforms.bind_group(), &[offset]);
+                    mesh.draw(renderpass);
+                    self.count_draw_calls(1);
+                }
+            }
+            (None, Some(bind_group)) => {
+                renderpass.set_bind_group(2, bind_group, &[]);
+                for run in runs {
+                    mesh.draw_instanced(renderpass, run);
+                    self.count_draw_calls(1);
+                }
+            }
+            (None, None) => unreachable!("storage transforms always have a bind group"),
+        }
+    }
+
+    /// Objects added since the last `cull_objects` are drawn.
+    fn visible(&self, object: u32) -> bool {
+        self.object_visible
+            .get(object as usize)
+            .copied()
+            .unwrap_or(true)
+    }
+
+    /// The runs of consecutive objects in `objects` that survived culling.
+    fn visible_runs(&self, objects: Range<u32>) -> Vec<Range<u32>> {
+        let mut runs = Vec::new();
+        let mut run_start = None;
+        for object in objects.clone() {
+            match (self.visible(object), run_start) {
+                (true, None) => run_start = Some(object),
+                (false, Some(start)) => {
+                    runs.push(start..object);
+                    run_start = None;
+                }
+                _ => {}
+            }
+        }
+        if let Some(start) = run_start {
+            runs.push(start..objects.end);
+        }
+        runs
+    }
+
+    /// The opaque meshes, with whichever scene pipeline `renderpass` has set.
+    fn draw_meshes<'a>(
+        &'a self,
+        renderpass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        renderpass.set_bind_group(0, camera_bind_group, &[]);
+        self.model_transform.bind(renderpass);
+
+        let drawables = self.drawables();
+        match &self.indirect {
+            Some(indirect) => {
+                if !indirect.pool.bind(renderpass) {
+                    return;
+                }
+                if let Some(bind_group) = self.transforms.bind_group() {
+                    renderpass.set_bind_group(2, bind_group, &[]);
+                }
+                if let Some(gpu_cull) = &indirect.gpu_cull {
+                    for (i, batch) in gpu_cull.batches().iter().enumerate() {
+                        let Some((_, material, _)) = drawables.get(batch.key) else {
+                            continue;
+                        };
+                        renderpass.set_bind_group(1, &material.bind_group, &[]);
+                        self.count_draw_calls(gpu_cull.draw(renderpass, i));
+                    }
+                    return;
+                }
+                for batch in indirect.draws.batches() {
+                    let Some((_, material, _)) = drawables.get(batch.key) else {
+                        continue;
+                    };
+                    renderpass.set_bind_group(1, &material.bind_group, &[]);
+                    self.count_draw_calls(indirect.draws.draw(renderpass, &batch.entries));
+                }
+            }
+            None => {
+                for (mesh, material, objects) in drawables {
+                    renderpass.set_bind_group(1, &material.bind_group, &[]);
+                    self.draw_objects(renderpass, mesh, objects);
+                }
+            }
+        }
+    }
+
+    /// The scene meshes that aren't triangle lists, each with the pipeline
+    /// for its topology, always drawn directly.
+    fn draw_unlit<'a>(
+        &'a self,
+        renderpass: &mut wgpu::RenderPass<'a>,
+        camera_bind_group: &'a wgpu::BindGroup,
+    ) {
+        let draws = self.scene_draws();
+        let mut draws = draws
+            .into_iter()
+            .filter(|(mesh, _, _)| mesh.topology() != wgpu::PrimitiveTopology::TriangleList)
+            .peekable();
+        if draws.peek().is_none() {
+            return;
+        }
+        renderpass.set_bind_group(0, camera_bind_group, &[]);
+        self.model_transform.bind(renderpass);
+        for (mesh, material, objects) in draws {
+            let Some(pipeline) = self.pipelines.unlit.get(&mesh.topology()) else {
+                continue;
+            };
+            renderpass.set_pipeline(pipeline);
+            renderpass.set_bind_group(1, &material.bind_group, &[]);
+            self.draw_objects(renderpass, mesh, objects);
+        }
+    }
+
+    /// The transparent meshes, back to front, always drawn directly.
+    fn draw_transparent<'a>(
+        &'a self,
+        renderpass: &mut wgpu::RenderPass<'a>,
+        camera: &Camera,
+        camera_bind_group: &'a wgpu::BindGroup,
+        pipeline: &'a wgpu::RenderPipeline,
+    ) {
+        let draws = self.transparent_draws(camera);
+        if draws.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(pipeline);
+        renderpass.set_bind_group(0, camera_bind_group, &[]);
+        self.model_transform.bind(renderpass);
+        for (mesh, material, objects) in draws {
+            renderpass.set_bind_group(1, &material.bind_group, &[]);
+            self.draw_objects(renderpass, mesh, objects);
+        }
+    }
+
+    fn count_draw_calls(&self, count: u32) {
+        self.draw_calls.set(self.draw_calls.get() + count);
+    }
+
+    /// Packs the drawables into the mesh pool and writes one indirect entry
+    /// per run of visible objects, as `draw_objects` would draw them.
+    /// Neighbouring drawables with the same material share a batch; they
+    /// aren't regrouped, so the draw order, and with it every depth tie,
+    /// matches the direct path. With GPU culling every object becomes a
+    /// candidate for the compute pass instead.
+    fn prepare_indirect(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        let Some(mut indirect) = self.indirect.take() else {
+            return;
+        };
+        let drawables = self.drawables();
+        let meshes: Vec<&mesh_builder::Mesh> = drawables.iter().map(|(mesh, _, _)| *mesh).collect();
+        indirect
+            .pool
+            .update(&self.device, &self.queue, command_encoder, &meshes);
+
+        if let Some(gpu_cull) = &mut indirect.gpu_cull {
+            gpu_cull.clear();
+            let mut batch_key = 0;
+            for (i, (mesh, material, objects)) in drawables.iter().enumerate() {
+                if material.bind_group != drawables[batch_key].1.bind_group {
+                    batch_key = i;
+                }
+                gpu_cull.begin_batch(batch_key);
+                let Some(pooled) = indirect.pool.get(mesh) else {
+                    continue;
+                };
+                for object in objects.clone() {
+                    gpu_cull.push(pooled, object, &mesh.bounds);
+                }
+            }
+            let frustum = self.cull_frustum();
+            if let Some(transform_buffer) = self.transforms.buffer() {
+                gpu_cull.prepare(
+                    &self.device,
+                    &mut self.uploader,
+                    &frustum,
+                    self.model_transform.matrix(),
+                    transform_buffer,
+                    self.transforms.instances().len() as u32,
+                );
+            }
+            self.indirect = Some(indirect);
+            return;
+        }
+
+        indirect.draws.clear();
+        let mut batch_key = 0;
+        for (i, (mesh, material, objects)) in drawables.iter().enumerate() {
+            if material.bind_group != drawables[batch_key].1.bind_group {
+                batch_key = i;
+            }
+            indirect.draws.begin_batch(batch_key);
+            let Some(pooled) = indirect.pool.get(mesh) else {
+                continue;
+            };
+            for run in self.visible_runs(objects.clone()) {
+                indirect.draws.push(pooled, run);
+            }
+        }
+        indirect.draws.upload(&self.device, &mut self.uploader);
+        self.indirect = Some(indirect);
+    }
+
+    /// Records the GPU cull, if it's on, after the uploads it reads.
+    fn encode_gpu_cull(&mut self, command_encoder: &mut wgpu::CommandEncoder) {
+        let Some(gpu_cull) = self
+            .indirect
+            .as_mut()
+            .and_then(|indirect| indirect.gpu_cull.as_mut())
+        else {
+            return;
+        };
+        gpu_cull.poll(&self.device);
+        gpu_cull.encode(&self.device, command_encoder, &self.object_visible);
+    }
+
+    /// The mesh and material of each visible scene node that has a mesh,
+    /// with the node's object. The selected node's material is tinted.
+    fn scene_draws(&self) -> Vec<(&mesh_builder::Mesh, &Material, Range<u32>)> {
+        self.scene_objects
+            .iter()
+            .filter(|&&(node, _)| self.scene.visible(node))
+            .filter_map(|&(node, object)| {
+                let mesh = self.scene_meshes.get(self.scene.mesh(node)?)?;
+                let material = match self.scene.material(node) {
+                    _ if self.selected == Some(object) => self
+                        .selection_materials
+                        .get(self.scene.material(node))
+                        .unwrap_or(&self.selection_materials[SCENE_PLAIN_MATERIAL]),
+                    SCENE_TEXTURED_MATERIAL => &self.quad_material,
+                    SCENE_GLASS_MATERIAL => &self.glass_material,
+                    _ => &self.triangle_material,
+                };
+                Some((mesh, material, object.instances()))
+            })
+            .collect()
+    }
+
+    /// Copies of `materials` with their color set to `SELECTION_TINT`.
+    fn selection_materials(
+        materials: &[&Material],
+        device: &wgpu::Device,
+        layout: &bind_group_layout::Layout,
+        sampler: &wgpu::Sampler,
+    ) -> Vec<Material> {
+        materials
+            .iter()
+            .map(|&material| {
+                let mut tinted = material.clone();
+                tinted.set_color(device, layout, sampler, SELECTION_TINT);
+                tinted
+            })
+            .collect()
+    }
+
+    /// The scene draws with a transparent material, farthest from `camera`
+    /// first, so each blends over the ones behind it. Objects are ordered by
+    /// the centers of their bounds, which is right as long as they don't
+    /// intersect.
+    fn transparent_draws(
+        &self,
+        camera: &Camera,
+    ) -> Vec<(&mesh_builder::Mesh, &Material, Range<u32>)> {
+        let model = *self.model_transform.matrix();
+        let instances = self.transforms.instances();
+        let mut draws: Vec<(f32, (&mesh_builder::Mesh, &Material, Range<u32>))> = self
+            .scene_draws()
+            .into_iter()
+            .filter(|(_, material, _)| material.transparent())
+            .map(|draw| {
+                let world = model * instances[draw.2.start as usize].matrix();
+                let center = (world * draw.0.bounds.center().extend(1.0)).truncate(3);
+                (camera.view_depth(center), draw)
+            })
+            .collect();
+        draws.sort_by(|a, b| b.0.total_cmp(&a.0));
+        draws.into_iter().map(|(_, draw)| draw).collect()
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    /// Nodes added, removed or changed here are drawn from the next `update`.
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    /// Gives every scene node with a mesh an object, reusing those of nodes
+    /// that went away or lost their mesh, and copies the world transforms
+    /// over.
+    fn sync_scene_objects(&mut self) {
+        let scene = &self.scene;
+        let mut freed = Vec::new();
+        self.scene_objects.retain(|&(node, object)| {
+            let keep = scene.contains(node) && scene.mesh(node).is_some();
+            if !keep {
+                freed.push(object);
+            }
+            keep
+        });
+        if self
+            .selected
+            .is_some_and(|selected| freed.contains(&selected))
+        {
+            self.selected = None;
+        }
+        self.free_objects.extend(freed);
+
+        for (node, _, world) in scene.meshes() {
+            if self.scene_objects.iter().any(|&(other, _)| other == node) {
+                continue;
+            }
+            let object = match self.free_objects.pop() {
+                Some(object) => object,
+                None => self.transforms.push(*world),
+            };
+            self.scene_objects.push((node, object));
+        }
+        for &(node, object) in &self.scene_objects {
+            self.transforms.update(object, scene.world_transform(node));
+        }
+    }
+
+    /// Each opaque mesh in draw order, with its material and the objects
+    /// drawn with it.
+    fn drawables(&self) -> Vec<(&mesh_builder::Mesh, &Material, Range<u32>)> {
+        let mut drawables = vec![
+            (
+                &self.quad_mesh,
+                &self.quad_material,
+                self.quad_object.instances(),
+            ),
+            (
+                &self.cube_mesh,
+                &self.triangle_material,
+                self.cube_object.instances(),
+            ),
+        ];
+        if self.wave {
+            drawables.push((
+                &self.wave_mesh,
+                &self.quad_material,
+                self.wave_object.instances(),
+            ));
+        }
+        for (part, object) in &self.models {
+            drawables.push((&part.mesh, &part.material, object.instances()));
+        }
+        drawables.extend(
+            self.scene_draws()
+                .into_iter()
+                .filter(|(mesh, material, _)| {
+                    mesh.topology() == wgpu::PrimitiveTopology::TriangleList
+                        && !material.transparent()
+                }),
+        );
+        drawables.push((
+            &self.triangle_mesh,
+            &self.triangle_material,
+            self.grid_objects(),
+        ));
+        drawables
+    }
+
+    /// Outlines the selected object: the mask pipeline marks its pixels in
+    /// the stencil buffer, then the outline pipeline draws it slightly
+    /// scaled up wherever it didn't already cover. Records nothing when
+    /// nothing is selected or there's no stencil buffer.
+    fn encode_outline(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        camera_bind_group: &wgpu::BindGroup,
+    ) {
+        let (Some(selected), Some((mask_pipeline, outline_pipeline))) =
+            (self.selected, &self.pipelines.outline)
+        else {
+            return;
+        };
+        let Some((mesh, material, _)) = self
+            .drawables()
+            .into_iter()
+            .chain(self.scene_draws())
+            .filter(|(mesh, _, _)| mesh.topology() == wgpu::PrimitiveTopology::TriangleList)
+            .find(|(_, _, objects)| objects.contains(&selected.index()))
+        else {
+            return;
+        };
+
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Outline Pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        targets.apply_viewport(&mut renderpass);
+        renderpass.set_stencil_reference(1);
+        for pipeline in [mask_pipeline, outline_pipeline] {
+            renderpass.set_pipeline(pipeline);
+            renderpass.set_bind_group(0, camera_bind_group, &[]);
+            self.model_transform.bind(&mut renderpass);
+            renderpass.set_bind_group(1, &material.bind_group, &[]);
+            self.draw_objects(&mut renderpass, mesh, selected.instances());
+        }
+    }
+
+    /// Draws the ids of the objects `take_pick` can answer with, then copies
+    /// out the one at `pixel`. The wireframe leaves only lines in the depth
+    /// buffer, so picks in it can land on hidden objects.
+    fn encode_pick(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        pixel: (u32, u32),
+    ) {
+        let (Some(picker), Some(pipeline)) = (&self.picker, &self.pipelines.pick) else {
+            return;
+        };
+        {
+            let mut renderpass = picker.begin_pass(command_encoder, targets.depth);
+            targets.apply_viewport(&mut renderpass);
+            renderpass.set_pipeline(pipeline);
+            renderpass.set_bind_group(0, &self.camera_bind_group, &[]);
+            self.model_transform.bind(&mut renderpass);
+            let draws = self
+                .drawables()
+                .into_iter()
+                .chain(self.transparent_draws(&self.camera));
+            for (mesh, material, objects) in draws {
+                renderpass.set_bind_group(1, &material.bind_group, &[]);
+                self.draw_objects(&mut renderpass, mesh, objects);
+            }
+        }
+        picker.copy_pixel(command_encoder, pixel);
+    }
+
+    /// Every opaque mesh around where the scene is, in world space, for the
+    /// light's view to fit.
+    fn shadow_caster_bounds(&self) -> Aabb {
+        let model = *self.model_transform.matrix();
+        let instances = self.transforms.instances();
+        let corners = self
+            .drawables()
+            .into_iter()
+            .flat_map(|(mesh, _, objects)| {
+                objects.map(move |object| (mesh.bounds, instances[object as usize].matrix()))
+            })
+            .flat_map(|(bounds, matrix)| {
+                let world = bounds.transformed(&(model * matrix));
+                [world.min, world.max]
+            })
+            .collect::<Vec<_>>();
+        Aabb::from_points(corners)
+    }
+
+    fn prepare_shadows(&mut self) {
+        let bounds = self.shadow_caster_bounds();
+        self.shadow_pass
+            .prepare(&mut self.uploader, self.light_direction, &bounds);
+    }
+
+    /// Draws the opaque meshes' depth from the light into the shadow map.
+    /// Camera culling doesn't apply: what's out of view can still throw a
+    /// shadow into it.
+    fn encode_shadows(&self, command_encoder: &mut wgpu::CommandEncoder) {
+        if !self.shadow_pass.settings().enabled {
+            return;
+        }
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Shadow Pass"),
+            color_attachments: &[],
+            depth_stencil_attachment: Some(self.shadow_pass.depth_attachment()),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        renderpass.set_pipeline(&self.pipelines.shadow);
+        renderpass.set_bind_group(0, self.shadow_pass.caster_bind_group(), &[]);
+        self.model_transform.bind(&mut renderpass);
+        for (mesh, material, objects) in self.drawables() {
+            renderpass.set_bind_group(1, &material.bind_group, &[]);
+            self.draw_runs(&mut renderpass, mesh, vec![objects]);
+        }
+    }
+
+    pub fn shadow_settings(&self) -> ShadowSettings {
+        self.shadow_pass.settings()
+    }
+
+    pub fn set_shadow_settings(&mut self, settings: ShadowSettings) {
+        let resolution = self.shadow_pass.settings().resolution;
+        self.shadow_pass.set_settings(&self.device, settings);
+        if self.shadow_pass.settings().resolution != resolution {
+            self.camera_bind_group = Self::build_camera_bind_group(
+                &self.device,
+                &self.camera_bind_group_layout,
+                &self.camera_buffer,
+                &self.light_buffer,
+                &self.globals_buffer,
+                &self.shadow_pass,
+                "Camera Bind Group",
+            );
+            println!(
+                "Shadow map: {0}x{0}",
+                self.shadow_pass.settings().resolution
+            );
+        }
+    }
+
+    /// The view-projection the shadow map was last drawn with.
+    pub fn light_view_projection(&self) -> glm::Mat4 {
+        self.shadow_pass.light_view_projection()
+    }
+
+    /// Keeps the shadow map drawn from the light's view as it is now, shown
+    /// in debug lines, while the light and the scene move on. Toggles back.
+    pub fn toggle_light_freeze(&mut self) {
+        self.shadow_pass.toggle_freeze();
+        println!(
+            "Light view: {}",
+            if self.shadow_pass.frozen() {
+                "frozen"
+            } else {
+                "following the light"
+            }
+        );
+    }
+
+    /// The first pass of the frame; clears the targets the others load. With
+    /// the depth pre-pass on, that's two passes: depth for the opaque meshes,
+    /// then color over it. Timestamps then span both. Transparent meshes
+    /// follow the opaque ones, sorted for `camera`.
+    fn encode_scene(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        targets: &FrameTargets,
+        camera: &Camera,
+        camera_bind_group: &wgpu::BindGroup,
+        timestamp_writes: Option<wgpu::RenderPassTimestampWrites>,
+    ) {
+        let wireframe_pipeline = self.pipelines.wireframe.as_ref().filter(|_| self.wireframe);
+        // The wireframe pipeline tests depth itself, so it goes without.
+        let depth_prepass_pipeline = self
+            .pipelines
+            .depth_prepass
+            .as_ref()
+            .filter(|_| wireframe_pipeline.is_none());
+
+        let (prepass_timestamps, timestamp_writes) =
+            match (depth_prepass_pipeline, timestamp_writes) {
+                (Some(_), Some(writes)) => (
+                    Some(wgpu::RenderPassTimestampWrites {
+                        query_set: writes.query_set,
+                        beginning_of_pass_write_index: writes.beginning_of_pass_write_index,
+                        end_of_pass_write_index: None,
+                    }),
+                    Some(wgpu::RenderPassTimestampWrites {
+                        query_set: writes.query_set,
+                        beginning_of_pass_write_index: None,
+                        end_of_pass_write_index: writes.end_of_pass_write_index,
+                    }),
+                ),
+                (_, writes) => (None, writes),
+            };
+        if let Some(depth_prepass_pipeline) = depth_prepass_pipeline {
+            let render_pass_descriptor = wgpu::RenderPassDescriptor {
+                label: Some("Depth Pre-pass"),
+                color_attachments: &[],
+                depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Clear(1.0))),
+                timestamp_writes: prepass_timestamps,
+                occlusion_query_set: None,
+            };
+            let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+            targets.apply_viewport(&mut renderpass);
+            renderpass.set_pipeline(depth_prepass_pipeline);
+            self.draw_meshes(&mut renderpass, camera_bind_group);
+        }
+
+        let clear_color = self.colors.to_linear_color(self.clear_color);
+        let color_attachment = targets.color_attachment(wgpu::LoadOp::Clear(clear_color));
+        let depth_load = match depth_prepass_pipeline {
+            Some(_) => wgpu::LoadOp::Load,
+            None => wgpu::LoadOp::Clear(1.0),
+        };
+
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Render Pass"),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: Some(targets.depth_attachment(depth_load)),
+            timestamp_writes: timestamp_writes,
+            occlusion_query_set: None,
+        };
+
+        {
+            let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+            targets.apply_viewport(&mut renderpass);
+            renderpass.set_pipeline(wireframe_pipeline.unwrap_or(&self.pipelines.render));
+            self.draw_meshes(&mut renderpass, camera_bind_group);
+            self.draw_unlit(&mut renderpass, camera_bind_group);
+            self.draw_transparent(
+                &mut renderpass,
+                camera,
+                camera_bind_group,
+                wireframe_pipeline.unwrap_or(&self.pipelines.transparent),
+            );
+
+            let blend = self.particles.emitter().blend;
+            renderpass.set_pipeline(self.particle_pipelines.get(blend));
+            renderpass.set_bind_group(0, camera_bind_group, &[]);
+            self.particles.draw(&mut renderpass);
+        }
+    }
+
+    /// Renders the scene into an offscreen copy of the render target and
+    /// returns its pixels as tightly packed RGBA8 rows.
+    pub fn render_offscreen(&mut self) -> Result<Vec<u8>, ScreenshotError> {
+        let target = Texture::create_readback_target(
+            &self.device,
+            self.config.width,
+            self.config.height,
+            self.config.format,
+            "Offscreen Target",
+        );
+        self.render(&target.view);
+        screenshot::read_texture(&self.device, &self.queue, &target.texture)
+    }
+
+    /// Renders the scene offscreen and writes it to `path` as a PNG.
+    pub fn capture_frame(&mut self, path: &Path) -> Result<(), ScreenshotError> {
+        let rgba = self.render_offscreen()?;
+        screenshot::save_png(path, self.config.width, self.config.height, rgba)
+    }
+
+    /// Resizes the render targets; the caller reconfigures the surface, if any.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.config.width = width.max(1);
+        self.config.height = height.max(1);
+        self.update_viewport();
+        self.create_render_targets();
+    }
+
+    pub fn fixed_aspect(&self) -> Option<f32> {
+        self.fixed_aspect
+    }
+
+    /// Keeps the scene at `aspect`, width over height, in the largest
+    /// centered viewport that fits, with black bars around it. `None` draws
+    /// over the whole target again.
+    pub fn set_fixed_aspect(&mut self, aspect: Option<f32>) {
+        self.fixed_aspect = aspect.filter(|aspect| aspect.is_finite() && *aspect > 0.0);
+        self.update_viewport();
+    }
+
+    /// The part of the target the scene is drawn to, when letterboxed.
+    pub fn viewport(&self) -> Option<Viewport> {
+        self.viewport
+    }
+
+    /// The camera follows the viewport's shape rather than the target's.
+    fn update_viewport(&mut self) {
+        self.viewport = self
+            .fixed_aspect
+            .map(|aspect| Viewport::letterbox(self.config.width, self.config.height, aspect));
+        match &self.viewport {
+            Some(viewport) => self.camera.set_viewport(viewport.width, viewport.height),
+            None => self
+                .camera
+                .set_viewport(self.config.width, self.config.height),
+        }
+    }
+
+    fn view_camera_bind_group(&self, camera_buffer: &wgpu::Buffer) -> wgpu::BindGroup {
+        Self::build_camera_bind_group(
+            &self.device,
+            &self.camera_bind_group_layout,
+            camera_buffer,
+            &self.light_buffer,
+            &self.globals_buffer,
+            &self.shadow_pass,
+            "View Camera Bind Group",
+        )
+    }
+
+    /// A view with its own camera drawing into `width` by `height` targets
+    /// of the renderer's format.
+    pub fn create_view(&self, camera: Camera, width: u32, height: u32) -> SceneView {
+        let camera_uniform = uniform::CameraUniform::new(&camera.build_view_projection());
+        let camera_buffer = uniform::make_uniform_buffer(
+            &self.device,
+            "View Camera Uniform Buffer",
+            bytemuck::bytes_of(&camera_uniform),
+        );
+        let camera_bind_group = self.view_camera_bind_group(&camera_buffer);
+
+        let mut config = self.config.clone();
+        config.width = width.max(1);
+        config.height = height.max(1);
+        let (msaa_texture, depth_texture) =
+            Self::create_targets(&self.device, &config, self.depth_format, self.sample_count);
+        SceneView {
+            camera: camera,
+            camera_buffer: camera_buffer,
+            camera_bind_group: camera_bind_group,
+            shadow_resolution: self.shadow_pass.settings().resolution,
+            config: config,
+            depth_format: self.depth_format,
+            sample_count: self.sample_count,
+            msaa_texture: msaa_texture,
+            depth_texture: depth_texture,
+        }
+    }
+
+    pub fn resize_view(&self, view: &mut SceneView, width: u32, height: u32) {
+        view.config.width = width.max(1);
+        view.config.height = height.max(1);
+        view.camera
+            .set_viewport(view.config.width, view.config.height);
+        (view.msaa_texture, view.depth_texture) = Self::create_targets(
+            &self.device,
+            &view.config,
+            self.depth_format,
+            self.sample_count,
+        );
+        view.depth_format = self.depth_format;
+        view.sample_count = self.sample_count;
+    }
+
+    /// Draws the scene as `view` sees it into `target`. Only the scene pass
+    /// is recorded; the passes from `add_pass` draw with the main camera,
+    /// and objects are culled against the main camera's frustum, so the view
+    /// shows what culling leaves out.
+    pub fn encode_view(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        view: &mut SceneView,
+    ) {
+        if view.sample_count != self.sample_count || view.depth_format != self.depth_format {
+            self.resize_view(view, view.config.width, view.config.height);
+        }
+        if view.shadow_resolution != self.shadow_pass.settings().resolution {
+            view.camera_bind_group = self.view_camera_bind_group(&view.camera_buffer);
+            view.shadow_resolution = self.shadow_pass.settings().resolution;
+        }
+        let camera_uniform = uniform::CameraUniform::new(&view.camera.build_view_projection());
+        self.uploader
+            .write(&view.camera_buffer, 0, &[camera_uniform]);
+        self.upload_object_uniforms();
+        self.prepare_indirect(command_encoder);
+        self.uploader.record(&self.device, command_encoder);
+        self.encode_gpu_cull(command_encoder);
+
+        // Whichever of `encode` and `encode_view` comes first in a frame
+        // runs the particle step, so the view keeps animating on its own.
+        self.particles.encode_compute(command_encoder, None);
+
+        let targets = FrameTargets {
+            surface: target,
+            color: match &view.msaa_texture {
+                Some(msaa_texture) => &msaa_texture.view,
+                None => target,
+            },
+            resolve: view.msaa_texture.as_ref().map(|_| target),
+            depth: &view.depth_texture.view,
+            stencil: self.stencil(),
+            viewport: None,
+        };
+        self.encode_scene(
+            command_encoder,
+            &targets,
+            &view.camera,
+            &view.camera_bind_group,
+            None,
+        );
+        self.encode_outline(command_encoder, &targets, &view.camera_bind_group);
+    }
+}
@@ -0,0 +1,277 @@
+use std::fs;
+use std::path::PathBuf;
+
+use super::error::RendererError;
+
+const BACKEND_NAMES: [(&str, wgpu::Backends); 5] = [
+    ("vulkan", wgpu::Backends::VULKAN),
+    ("dx12", wgpu::Backends::DX12),
+    ("metal", wgpu::Backends::METAL),
+    ("gl", wgpu::Backends::GL),
+    ("auto", wgpu::Backends::all()),
+];
+
+const POWER_NAMES: [(&str, wgpu::PowerPreference); 3] = [
+    ("low", wgpu::PowerPreference::LowPower),
+    ("high", wgpu::PowerPreference::HighPerformance),
+    ("default", wgpu::PowerPreference::None),
+];
+
+/// Whether the wgpu this is built against writes API traces. wgpu 24 takes
+/// a trace directory but tracing itself is switched off for the time being,
+/// see https://github.com/gfx-rs/wgpu/issues/5974.
+pub const TRACE_SUPPORTED: bool = false;
+
+/// Which backends to create the instance with and, optionally, a
+/// case-insensitive substring of the adapter name to insist on.
+pub struct AdapterOptions {
+    pub backends: wgpu::Backends,
+    pub name: Option<String>,
+    /// What wgpu picks by when there's no `name`. `HighPerformance` falls
+    /// back to the default preference if it finds nothing.
+    pub power_preference: wgpu::PowerPreference,
+    /// Insist on a software adapter such as lavapipe or WARP.
+    pub force_fallback: bool,
+    /// Where the device writes an API trace, for bug reports.
+    pub trace: Option<PathBuf>,
+}
+
+impl Default for AdapterOptions {
+    fn default() -> Self {
+        AdapterOptions {
+            backends: wgpu::Backends::all(),
+            name: None,
+            power_preference: wgpu::PowerPreference::HighPerformance,
+            force_fallback: false,
+            trace: None,
+        }
+    }
+}
+
+/// Creates `dir`, if needed, for a trace to be written into.
+pub fn trace_dir(dir: &str) -> Result<PathBuf, RendererError> {
+    let dir = PathBuf::from(dir);
+    fs::create_dir_all(&dir).map_err(|error| RendererError::Output {
+        path: dir.clone(),
+        error: error,
+    })?;
+    Ok(dir)
+}
+
+pub fn parse_backends(name: &str) -> Result<wgpu::Backends, RendererError> {
+    BACKEND_NAMES
+        .iter()
+        .find(|(backend_name, _)| backend_name.eq_ignore_ascii_case(name))
+        .map(|&(_, backends)| backends)
+        .ok_or_else(|| {
+            let names: Vec<&str> = BACKEND_NAMES.iter().map(|(name, _)| *name).collect();
+            RendererError::InvalidArgument(format!(
+                "unknown backend \"{}\", expected one of {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
+
+pub fn parse_power_preference(name: &str) -> Result<wgpu::PowerPreference, RendererError> {
+    POWER_NAMES
+        .iter()
+        .find(|(power_name, _)| power_name.eq_ignore_ascii_case(name))
+        .map(|&(_, power)| power)
+        .ok_or_else(|| {
+            let names: Vec<&str> = POWER_NAMES.iter().map(|(name, _)| *name).collect();
+            RendererError::InvalidArgument(format!(
+                "unknown power preference \"{}\", expected one of {}",
+                name,
+                names.join(", ")
+            ))
+        })
+}
+
+fn power_label(power: wgpu::PowerPreference) -> &'static str {
+    match POWER_NAMES.iter().find(|&&(_, p)| p == power) {
+        Some((name, _)) => name,
+        None => "default",
+    }
+}
+
+fn backends_label(backends: wgpu::Backends) -> String {
+    match BACKEND_NAMES.iter().find(|&&(_, b)| b == backends) {
+        Some((name, _)) => name.to_string(),
+        None => format!("{:?}", backends),
+    }
+}
+
+pub fn create_instance(options: &AdapterOptions) -> wgpu::Instance {
+    wgpu::Instance::new(&instance_descriptor(options))
+}
+
+/// Like `create_instance`, but in the browser it leaves WebGPU out where
+/// the browser doesn't support it, which only an async check can tell, so
+/// the `webgl` feature's WebGL2 is used instead.
+pub async fn create_window_instance(options: &AdapterOptions) -> wgpu::Instance {
+    #[cfg(target_arch = "wasm32")]
+    {
+        wgpu::util::new_instance_with_webgpu_detection(&instance_descriptor(options)).await
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        create_instance(options)
+    }
+}
+
+fn instance_descriptor(options: &AdapterOptions) -> wgpu::InstanceDescriptor {
+    wgpu::InstanceDescriptor {
+        backends: options.backends,
+        ..Default::default()
+    }
+}
+
+pub fn log_info(adapter: &wgpu::Adapter, options: &AdapterOptions) {
+    let info = adapter.get_info();
+    let requested = match (&options.name, options.force_fallback) {
+        (Some(name), _) => format!("adapter \"{}\"", name),
+        (None, true) => "fallback adapter".to_string(),
+        (None, false) => format!("power preference {}", power_label(options.power_preference)),
+    };
+    println!(
+        "Adapter: {} ({:?}, {:?}, driver {} {}), requested backend: {}, {}",
+        info.name,
+        info.backend,
+        info.device_type,
+        info.driver,
+        info.driver_info,
+        backends_label(options.backends),
+        requested
+    );
+}
+
+/// Picks the adapter whose name contains `options.name` (case-insensitive)
+/// when one is given, otherwise lets wgpu choose with
+/// `options.power_preference`. Adapters that can't present to
+/// `compatible_surface` are skipped.
+pub async fn request(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+    options: &AdapterOptions,
+) -> Result<wgpu::Adapter, RendererError> {
+    let adapter = match &options.name {
+        Some(name) => find_by_name(instance, compatible_surface, name)?,
+        None => {
+            let mut adapter_descriptor = wgpu::RequestAdapterOptionsBase {
+                power_preference: options.power_preference,
+                compatible_surface: compatible_surface,
+                force_fallback_adapter: options.force_fallback,
+            };
+            let mut adapter = instance.request_adapter(&adapter_descriptor).await;
+            if adapter.is_none()
+                && options.power_preference == wgpu::PowerPreference::HighPerformance
+            {
+                println!("No high-performance adapter, retrying with the default power preference");
+                adapter_descriptor.power_preference = wgpu::PowerPreference::None;
+                adapter = instance.request_adapter(&adapter_descriptor).await;
+            }
+            match adapter {
+                Some(adapter) => adapter,
+                None if options.backends != wgpu::Backends::all() => {
+                    return Err(RendererError::NoAdapterOnBackend {
+                        backend: backends_label(options.backends),
+                        available: available_backends(),
+                    });
+                }
+                None => return Err(RendererError::NoAdapter),
+            }
+        }
+    };
+    log_info(&adapter, options);
+    Ok(adapter)
+}
+
+/// Like `request` without a surface, but settles for a software fallback
+/// adapter when there's no real one, as on a CI machine without a GPU. An
+/// adapter asked for by name is never swapped for the fallback.
+pub async fn request_headless(
+    instance: &wgpu::Instance,
+    options: &AdapterOptions,
+) -> Result<wgpu::Adapter, RendererError> {
+    match request(instance, None, options).await {
+        Err(error @ (RendererError::NoAdapter | RendererError::NoAdapterOnBackend { .. }))
+            if options.name.is_none() =>
+        {
+            let adapter_descriptor = wgpu::RequestAdapterOptionsBase {
+                power_preference: wgpu::PowerPreference::default(),
+                compatible_surface: None,
+                force_fallback_adapter: true,
+            };
+            let adapter = instance
+                .request_adapter(&adapter_descriptor)
+                .await
+                .ok_or(error)?;
+            println!("No hardware adapter, using the fallback adapter");
+            log_info(&adapter, options);
+            Ok(adapter)
+        }
+        result => result,
+    }
+}
+
+/// Every backend that has at least one adapter, probed with a fresh
+/// instance since the caller's only has the requested backends enabled.
+#[cfg(not(target_arch = "wasm32"))]
+fn available_backends() -> Vec<String> {
+    let probe = create_instance(&AdapterOptions::default());
+    BACKEND_NAMES
+        .iter()
+        .filter(|&&(_, backends)| backends != wgpu::Backends::all())
+        .filter(|&&(_, backends)| !probe.enumerate_adapters(backends).is_empty())
+        .map(|(name, _)| name.to_string())
+        .collect()
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn find_by_name(
+    instance: &wgpu::Instance,
+    compatible_surface: Option<&wgpu::Surface<'_>>,
+    name: &str,
+) -> Result<wgpu::Adapter, RendererError> {
+    let adapters: Vec<wgpu::Adapter> = instance
+        .enumerate_adapters(wgpu::Backends::all())
+        .into_iter()
+        .filter(|adapter| compatible_surface.map_or(true, |s| adapter.is_surface_supported(s)))
+        .collect();
+
+    let needle = name.to_lowercase();
+    let available: Vec<String> = adapters
+        .iter()
+        .map(|adapter| {
+            let info = adapter.get_info();
+            format!("{} ({:?})", info.name, info.backend)
+        })
+        .collect();
+
+    adapters
+        .into_iter()
+        .find(|adapter| adapter.get_info().name.to_lowercase().contains(&needle))
+        .ok_or_else(|| RendererError::AdapterNotFound {
+            name: name.to_string(),
+            available: available,
+        })
+}
+
+/// Browsers don't list their adapters.
+#[cfg(target_arch = "wasm32")]
+fn available_backends() -> Vec<String> {
+    Vec::new()
+}
+
+#[cfg(target_arch = "wasm32")]
+fn find_by_name(
+    _instance: &wgpu::Instance,
+    _compatible_surface: Option<&wgpu::Surface<'_>>,
+    name: &str,
+) -> Result<wgpu::Adapter, RendererError> {
+    Err(RendererError::AdapterNotFound {
+        name: name.to_string(),
+        available: Vec::new(),
+    })
+}
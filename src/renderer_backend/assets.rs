@@ -0,0 +1,91 @@
+//! Reading the files the app ships with. Natively they're read from disk;
+//! a browser has no file system to read them from, so there they're built
+//! into the binary and looked up by the relative paths the app uses.
+
+use std::io;
+use std::path::Path;
+
+#[cfg(target_arch = "wasm32")]
+macro_rules! embed {
+    ($($dir:literal => [$($path:literal),* $(,)?]),* $(,)?) => {
+        &[$($((
+            $path,
+            include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/", $dir, $path)) as &[u8],
+        ),)*)*]
+    };
+}
+
+/// Shaders are looked up under `src`, everything else from the crate root.
+#[cfg(target_arch = "wasm32")]
+const EMBEDDED: &[(&str, &[u8])] = embed!(
+    "src/" => [
+        "shaders/bloom.wgsl",
+        "shaders/cull.wgsl",
+        "shaders/debug_lines.wgsl",
+        "shaders/grid.wgsl",
+        "shaders/letterbox.wgsl",
+        "shaders/particle_compute.wgsl",
+        "shaders/particles.wgsl",
+        "shaders/post.wgsl",
+        "shaders/scene_common.wgsl",
+        "shaders/shader.wgsl",
+        "shaders/shader_dynamic_offsets.wgsl",
+        "shaders/shader_push_constants.wgsl",
+        "shaders/shadow_map.wgsl",
+        "shaders/skybox.wgsl",
+        "shaders/sprite.wgsl",
+        "shaders/text.wgsl",
+        "shaders/tilemap.wgsl",
+        "shaders/triangle.wgsl",
+    ],
+    "" => [
+        "img/grunge.jpg",
+        "img/grunge_normal.png",
+        "img/satin.jpg",
+        "img/skybox/px.png",
+        "img/skybox/nx.png",
+        "img/skybox/py.png",
+        "img/skybox/ny.png",
+        "img/skybox/pz.png",
+        "img/skybox/nz.png",
+        "maps/sample.csv",
+        "models/cube.obj",
+    ],
+);
+
+#[cfg(target_arch = "wasm32")]
+fn embedded(path: &Path) -> Option<&'static [u8]> {
+    EMBEDDED
+        .iter()
+        .find(|(name, _)| Path::new(name) == path)
+        .map(|&(_, bytes)| bytes)
+}
+
+/// Whether `read` finds anything at `path`.
+pub fn exists(path: &Path) -> bool {
+    #[cfg(target_arch = "wasm32")]
+    {
+        embedded(path).is_some()
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        path.is_file()
+    }
+}
+
+pub fn read(path: &Path) -> io::Result<Vec<u8>> {
+    #[cfg(target_arch = "wasm32")]
+    {
+        embedded(path).map(<[u8]>::to_vec).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "not built into the browser build")
+        })
+    }
+    #[cfg(not(target_arch = "wasm32"))]
+    {
+        std::fs::read(path)
+    }
+}
+
+pub fn read_to_string(path: &Path) -> io::Result<String> {
+    String::from_utf8(read(path)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
@@ -1,6 +1,8 @@
+use super::bind_group_layout::Layout;
+
 pub struct Builder<'a> {
     entries: Vec<wgpu::BindGroupEntry<'a>>,
-    layout: Option<&'a wgpu::BindGroupLayout>,
+    layout: Option<&'a Layout>,
     device: &'a wgpu::Device,
 }
 
@@ -19,28 +21,80 @@ impl<'a> Builder <'a> {
         self.entries.clear();
     }
 
-    pub fn set_layout(&mut self, layout: &'a wgpu::BindGroupLayout) {
+    pub fn set_layout(&mut self, layout: &'a Layout) {
         self.layout = Some(layout);
     }
 
     pub fn add_material(&mut self, view: &'a wgpu::TextureView, sampler: &'a wgpu::Sampler) {
+        self.add_texture(view);
+        self.add_sampler(sampler);
+    }
 
+    pub fn add_texture(&mut self, view: &'a wgpu::TextureView) {
         self.entries.push(wgpu::BindGroupEntry{
             binding: self.entries.len() as u32,
             resource: wgpu::BindingResource::TextureView(view),
         });
+    }
 
+    pub fn add_sampler(&mut self, sampler: &'a wgpu::Sampler) {
         self.entries.push(wgpu::BindGroupEntry{
             binding: self.entries.len() as u32,
             resource: wgpu::BindingResource::Sampler(sampler),
         });
     }
 
+    pub fn add_buffer(&mut self, buffer: &'a wgpu::Buffer) {
+        self.entries.push(wgpu::BindGroupEntry{
+            binding: self.entries.len() as u32,
+            resource: buffer.as_entire_binding(),
+        });
+    }
+
+    /// Binds only the first `size` bytes of `buffer`, e.g. one element of a
+    /// dynamic uniform buffer.
+    pub fn add_buffer_range(&mut self, buffer: &'a wgpu::Buffer, size: u64) {
+        self.entries.push(wgpu::BindGroupEntry{
+            binding: self.entries.len() as u32,
+            resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                buffer: buffer,
+                offset: 0,
+                size: wgpu::BufferSize::new(size),
+            }),
+        });
+    }
+
+    /// Panics if the resources added don't line up with the layout's
+    /// entries, naming the bind group and the offending binding.
+    fn check_layout(&self, label: &str, layout: &Layout) {
+        let expected = layout.entries();
+        assert!(
+            self.entries.len() == expected.len(),
+            "bind group \"{}\" has {} resources but its layout has {} entries",
+            label, self.entries.len(), expected.len()
+        );
+
+        for (entry, layout_entry) in self.entries.iter().zip(expected) {
+            let resource = resource_kind(&entry.resource);
+            let binding = binding_kind(&layout_entry.ty);
+            assert!(
+                resource == binding,
+                "bind group \"{}\" binding {} is a {} but its layout expects a {}",
+                label, entry.binding, resource, binding
+            );
+        }
+    }
+
     pub fn build(&mut self, label: &str) -> wgpu::BindGroup {
+        let layout = match self.layout {
+            Some(layout) => layout,
+            None => panic!("bind group \"{}\" has no layout set", label),
+        };
+        self.check_layout(label, layout);
 
         let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor { 
             label: Some(label),
-            layout: self.layout.unwrap(),
+            layout: layout,
             entries: &self.entries,
 
         });
@@ -48,4 +102,63 @@ impl<'a> Builder <'a> {
 
         return bind_group
     }
-}
\ No newline at end of file
+}
+
+fn resource_kind(resource: &wgpu::BindingResource) -> &'static str {
+    match resource {
+        wgpu::BindingResource::Buffer(_) | wgpu::BindingResource::BufferArray(_) => "buffer",
+        wgpu::BindingResource::TextureView(_) | wgpu::BindingResource::TextureViewArray(_) => "texture",
+        wgpu::BindingResource::Sampler(_) | wgpu::BindingResource::SamplerArray(_) => "sampler",
+        _ => "other resource",
+    }
+}
+
+fn binding_kind(ty: &wgpu::BindingType) -> &'static str {
+    match ty {
+        wgpu::BindingType::Buffer { .. } => "buffer",
+        wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. } => "texture",
+        wgpu::BindingType::Sampler(_) => "sampler",
+        _ => "other resource",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::{bind_group_layout, uniform};
+    use crate::test_support;
+
+    fn texture_layout(device: &wgpu::Device) -> Layout {
+        let mut builder = bind_group_layout::Builder::new(device);
+        builder.add_texture(wgpu::ShaderStages::FRAGMENT);
+        builder.build("Test Layout")
+    }
+
+    /// This and `missing_resources_panic` need an adapter: without one
+    /// nothing is built to panic, and they fail with the reason printed.
+    #[test]
+    #[should_panic(expected = "bind group \"Test\" binding 0 is a buffer but its layout expects a texture")]
+    fn mismatched_resources_panic() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let layout = texture_layout(&device);
+        let buffer = uniform::make_uniform_buffer(&device, "Test Buffer", &[0; 16]);
+        let mut builder = Builder::new(&device);
+        builder.set_layout(&layout);
+        builder.add_buffer(&buffer);
+        builder.build("Test");
+    }
+
+    #[test]
+    #[should_panic(expected = "bind group \"Test\" has 0 resources but its layout has 1 entries")]
+    fn missing_resources_panic() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let layout = texture_layout(&device);
+        let mut builder = Builder::new(&device);
+        builder.set_layout(&layout);
+        builder.build("Test");
+    }
+}
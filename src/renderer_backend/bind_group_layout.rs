@@ -1,3 +1,27 @@
+use std::ops::Deref;
+
+/// A bind group layout that remembers its entries, so bind groups built
+/// against it can be checked before they reach wgpu validation.
+#[derive(Clone)]
+pub struct Layout {
+    layout: wgpu::BindGroupLayout,
+    entries: Vec<wgpu::BindGroupLayoutEntry>,
+}
+
+impl Layout {
+    pub fn entries(&self) -> &[wgpu::BindGroupLayoutEntry] {
+        &self.entries
+    }
+}
+
+impl Deref for Layout {
+    type Target = wgpu::BindGroupLayout;
+
+    fn deref(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+}
+
 pub struct Builder<'a> {
     entries: Vec<wgpu::BindGroupLayoutEntry>,
     device: &'a wgpu::Device,
@@ -18,9 +42,14 @@ impl<'a> Builder <'a> {
     }
 
     pub fn add_material(&mut self) {
+        self.add_texture(wgpu::ShaderStages::FRAGMENT);
+        self.add_sampler(wgpu::ShaderStages::FRAGMENT);
+    }
+
+    pub fn add_texture(&mut self, visibility: wgpu::ShaderStages) {
         self.entries.push(wgpu::BindGroupLayoutEntry{
             binding: self.entries.len() as u32,
-            visibility: wgpu::ShaderStages::FRAGMENT,
+            visibility: visibility,
             ty: wgpu::BindingType::Texture { 
                 sample_type: wgpu::TextureSampleType::Float { filterable: true },
                 view_dimension: wgpu::TextureViewDimension::D2,
@@ -28,22 +57,123 @@ impl<'a> Builder <'a> {
             },
             count: None,
         });
+    }
+
+    /// A cube texture and its sampler, e.g. for a skybox.
+    pub fn add_cubemap(&mut self, visibility: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                view_dimension: wgpu::TextureViewDimension::Cube,
+                multisampled: false
+            },
+            count: None,
+        });
+        self.add_sampler(visibility);
+    }
+
+    /// A depth texture, read with a comparison sampler or `textureLoad`.
+    pub fn add_depth_texture(&mut self, visibility: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false
+            },
+            count: None,
+        });
+    }
+
+    /// A texture read with `textureLoad` only, which a depth texture can be
+    /// bound as to get at its values.
+    pub fn add_unfilterable_texture(&mut self, visibility: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false
+            },
+            count: None,
+        });
+    }
 
+    /// A sampler that compares against a reference depth, for shadow maps.
+    pub fn add_comparison_sampler(&mut self, visibility: wgpu::ShaderStages) {
         self.entries.push(wgpu::BindGroupLayoutEntry{
             binding: self.entries.len() as u32,
-            visibility: wgpu::ShaderStages::FRAGMENT,
+            visibility: visibility,
+            ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Comparison),
+            count: None,
+        });
+    }
+
+    pub fn add_sampler(&mut self, visibility: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
             ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
             count: None,
         });
     }
 
-    pub fn build(&mut self, label: &str) -> wgpu::BindGroupLayout {
+    pub fn add_uniform_buffer(&mut self, visibility: wgpu::ShaderStages) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+    }
+
+    /// A uniform buffer bound at an offset given per `set_bind_group` call,
+    /// each binding being `size` bytes.
+    pub fn add_dynamic_uniform_buffer(&mut self, visibility: wgpu::ShaderStages, size: u64) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: true,
+                min_binding_size: wgpu::BufferSize::new(size),
+            },
+            count: None,
+        });
+    }
+
+    pub fn add_storage_buffer(&mut self, visibility: wgpu::ShaderStages, read_only: bool) {
+        self.entries.push(wgpu::BindGroupLayoutEntry{
+            binding: self.entries.len() as u32,
+            visibility: visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+    }
+
+    pub fn build(&mut self, label: &str) -> Layout {
         let layout = self.device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor { 
             label: Some(label), 
             entries: &self.entries
         });
+        let entries = self.entries.clone();
         self.reset();
 
-        return layout
+        return Layout {
+            layout: layout,
+            entries: entries,
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,451 @@
+use glm::*;
+
+use super::frustum::Aabb;
+use super::raycast::Ray;
+use super::uniform::identity;
+
+/// Degrees `zoom` keeps a perspective camera's field of view within.
+const MIN_FOV_Y: f32 = 5.0;
+const MAX_FOV_Y: f32 = 120.0;
+
+// glm produces OpenGL clip space with z in -1..1, wgpu expects 0..1.
+#[rustfmt::skip]
+fn opengl_to_wgpu() -> Mat4 {
+    mat4(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 0.5, 0.0,
+        0.0, 0.0, 0.5, 1.0,
+    )
+}
+
+/// Maps the view-space box from `left` to `right`, `bottom` to `top` and
+/// `near` to `far` in front of the camera straight onto wgpu clip space.
+#[rustfmt::skip]
+fn orthographic(left: f32, right: f32, bottom: f32, top: f32, near: f32, far: f32) -> Mat4 {
+    mat4(
+        2.0 / (right - left), 0.0, 0.0, 0.0,
+        0.0, 2.0 / (top - bottom), 0.0, 0.0,
+        0.0, 0.0, -1.0 / (far - near), 0.0,
+        -(right + left) / (right - left), -(top + bottom) / (top - bottom), -near / (far - near), 1.0,
+    )
+}
+
+/// How much of the world an orthographic camera sees.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum OrthographicSize {
+    /// World units from the bottom of the view to the top, centered on the
+    /// camera.
+    Height(f32),
+    /// World units per framebuffer pixel, with the camera at the view's top
+    /// left corner, so the view's size follows the viewport's.
+    PixelScale(f32),
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Projection {
+    /// `fov_y` in degrees.
+    Perspective { fov_y: f32, near: f32, far: f32 },
+    Orthographic {
+        size: OrthographicSize,
+        near: f32,
+        far: f32,
+    },
+}
+
+#[derive(Clone)]
+pub struct Camera {
+    pub position: Vec3,
+    pub target: Vec3,
+    pub up: Vec3,
+    pub projection: Projection,
+    pub aspect: f32,
+    /// Framebuffer pixels the view is tall, for `OrthographicSize::PixelScale`.
+    pub viewport_height: f32,
+}
+
+impl Camera {
+    pub fn new(aspect: f32) -> Self {
+        Camera {
+            position: vec3(0.0, 0.0, 2.5),
+            target: vec3(0.0, 0.0, 0.0),
+            up: vec3(0.0, 1.0, 0.0),
+            projection: Projection::Perspective {
+                fov_y: 45.0,
+                near: 0.1,
+                far: 100.0,
+            },
+            aspect: aspect,
+            viewport_height: 1.0,
+        }
+    }
+
+    /// A 2D camera for a `width` by `height` pixel viewport that maps one
+    /// world unit to one framebuffer pixel, with the origin at the top left
+    /// and y going down. It looks along +z at the z = 0 plane, and keeps
+    /// the mapping when the renderer resizes it.
+    pub fn pixel_space(width: u32, height: u32) -> Self {
+        let mut camera = Camera::new(1.0);
+        camera.position = vec3(0.0, 0.0, -1.0);
+        camera.target = vec3(0.0, 0.0, 0.0);
+        camera.up = vec3(0.0, -1.0, 0.0);
+        camera.projection = Projection::Orthographic {
+            size: OrthographicSize::PixelScale(1.0),
+            near: 0.1,
+            far: 100.0,
+        };
+        camera.set_viewport(width, height);
+        camera
+    }
+
+    /// Fits the projection to a `width` by `height` pixel viewport.
+    pub fn set_viewport(&mut self, width: u32, height: u32) {
+        let (width, height) = ((width as f32).max(1.0), (height as f32).max(1.0));
+        self.aspect = width / height;
+        self.viewport_height = height;
+    }
+
+    /// The field of view, when perspective.
+    pub fn fov_y(&self) -> Option<f32> {
+        match self.projection {
+            Projection::Perspective { fov_y, .. } => Some(fov_y),
+            Projection::Orthographic { .. } => None,
+        }
+    }
+
+    /// Changes the field of view, when perspective.
+    pub fn set_fov_y(&mut self, fov_y: f32) {
+        if let Projection::Perspective { fov_y: current, .. } = &mut self.projection {
+            *current = fov_y;
+        }
+    }
+
+    /// Shows `factor` times as much of the world: a wider field of view in
+    /// perspective, a larger view in orthographic.
+    pub fn zoom(&mut self, factor: f32) {
+        match &mut self.projection {
+            Projection::Perspective { fov_y, .. } => {
+                *fov_y = (*fov_y * factor).clamp(MIN_FOV_Y, MAX_FOV_Y);
+            }
+            Projection::Orthographic {
+                size: OrthographicSize::Height(height) | OrthographicSize::PixelScale(height),
+                ..
+            } => *height *= factor,
+        }
+    }
+
+    /// World units the view is tall, `distance` in front of the camera.
+    fn view_height(&self, distance: f32) -> f32 {
+        match self.projection {
+            Projection::Perspective { fov_y, .. } => 2.0 * distance * (radians(fov_y) * 0.5).tan(),
+            Projection::Orthographic {
+                size: OrthographicSize::Height(height),
+                ..
+            } => height,
+            Projection::Orthographic {
+                size: OrthographicSize::PixelScale(scale),
+                ..
+            } => scale * self.viewport_height,
+        }
+    }
+
+    /// How far away the scene is: the camera's distance to the world
+    /// origin, which is where the scene is built around.
+    fn scene_distance(&self) -> f32 {
+        length(self.position).max(1.0)
+    }
+
+    /// The other kind of projection, showing the scene about as large: an
+    /// orthographic view as tall as this perspective one is at the scene's
+    /// distance, or the other way round.
+    pub fn toggled_projection(&self) -> Projection {
+        let distance = self.scene_distance();
+        let height = self.view_height(distance);
+        match self.projection {
+            Projection::Perspective { near, far, .. } => Projection::Orthographic {
+                size: OrthographicSize::Height(height),
+                near: near,
+                far: far,
+            },
+            Projection::Orthographic { near, far, .. } => Projection::Perspective {
+                fov_y: degrees(2.0 * (height * 0.5 / distance).atan()).clamp(MIN_FOV_Y, MAX_FOV_Y),
+                near: near.max(0.01),
+                far: far,
+            },
+        }
+    }
+
+    /// This camera as perspective, for what's infinitely far away and so
+    /// can't be seen in an orthographic view, like the sky.
+    pub fn as_perspective(&self) -> Camera {
+        let mut camera = self.clone();
+        if let Projection::Orthographic { .. } = self.projection {
+            camera.projection = self.toggled_projection();
+        }
+        camera
+    }
+
+    pub fn build_view(&self) -> Mat4 {
+        if self.position == self.target {
+            return identity();
+        }
+        ext::look_at(self.position, self.target, self.up)
+    }
+
+    /// The projection into wgpu clip space.
+    pub fn build_projection(&self) -> Mat4 {
+        match self.projection {
+            Projection::Perspective { fov_y, near, far } => {
+                let projection = ext::perspective(radians(fov_y), self.aspect, near, far);
+                opengl_to_wgpu() * projection
+            }
+            Projection::Orthographic {
+                size: OrthographicSize::Height(height),
+                near,
+                far,
+            } => {
+                let (half_width, half_height) = (height * self.aspect * 0.5, height * 0.5);
+                orthographic(
+                    -half_width,
+                    half_width,
+                    -half_height,
+                    half_height,
+                    near,
+                    far,
+                )
+            }
+            Projection::Orthographic {
+                size: OrthographicSize::PixelScale(scale),
+                near,
+                far,
+            } => {
+                // The camera's up is the view's top edge, so the view hangs
+                // below and to the right of it.
+                let height = scale * self.viewport_height;
+                orthographic(0.0, height * self.aspect, -height, 0.0, near, far)
+            }
+        }
+    }
+
+    /// How far in front of the camera `point` is, along its view direction.
+    pub fn view_depth(&self, point: Vec3) -> f32 {
+        -(self.build_view() * point.extend(1.0)).z
+    }
+
+    pub fn build_view_projection(&self) -> Mat4 {
+        if self.position == self.target {
+            return identity();
+        }
+        self.build_projection() * self.build_view()
+    }
+
+    /// Moves the camera along its view direction to look at the center of
+    /// `aabb`, in world space, from just far enough away that all of it is
+    /// in view. Orthographic views are resized to fit it instead.
+    pub fn frame(&mut self, aabb: &Aabb) {
+        let center = aabb.center();
+        let radius = length(aabb.half_extents());
+        let forward = match self.position == self.target {
+            true => vec3(0.0, 0.0, -1.0),
+            false => normalize(self.target - self.position),
+        };
+        let aspect = self.aspect;
+        let viewport_height = self.viewport_height;
+        match &mut self.projection {
+            Projection::Perspective { fov_y, near, .. } => {
+                let half_y = radians(*fov_y) * 0.5;
+                let half_x = (half_y.tan() * aspect).atan();
+                let distance = radius / half_y.min(half_x).sin();
+                // Even a single point is framed from past the near plane.
+                self.position = center - forward * distance.max(radius + *near * 2.0);
+            }
+            Projection::Orthographic { size, near, .. } => {
+                // Tall enough for the sphere around the box both ways.
+                let height = (radius * 2.0 * (1.0 / aspect).max(1.0)).max(*near);
+                let back = forward * (radius + *near * 2.0);
+                self.position = center - back;
+                match size {
+                    OrthographicSize::Height(current) => *current = height,
+                    OrthographicSize::PixelScale(scale) => {
+                        // The view hangs below and to the right of the
+                        // camera, so it moves up and left by half of it.
+                        *scale = height / viewport_height;
+                        let right = normalize(cross(forward, self.up));
+                        let up = cross(right, forward);
+                        self.position =
+                            self.position - right * (height * aspect * 0.5) + up * (height * 0.5);
+                    }
+                }
+            }
+        }
+        self.target = self.position + forward;
+    }
+
+    /// The world-space ray through `cursor`, in pixels from the top left of
+    /// a `viewport` sized view, starting on the near plane. Unprojecting the
+    /// near and far points works for both kinds of projection.
+    pub fn screen_to_ray(&self, cursor: (f32, f32), viewport: (f32, f32)) -> Ray {
+        let x = cursor.0 / viewport.0 * 2.0 - 1.0;
+        let y = 1.0 - cursor.1 / viewport.1 * 2.0;
+        let inverse_view_projection = inverse(&self.build_view_projection());
+        let unproject = |depth: f32| {
+            let point = inverse_view_projection * vec4(x, y, depth, 1.0);
+            point.truncate(3) / point.w
+        };
+        let near = unproject(0.0);
+        Ray {
+            origin: near,
+            direction: normalize(unproject(1.0) - near),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::frustum::Frustum;
+
+    fn ndc(camera: &Camera, point: Vec3) -> Vec3 {
+        let clip = camera.build_view_projection() * point.extend(1.0);
+        clip.truncate(3) / clip.w
+    }
+
+    /// Transparent draws are sorted by `view_depth`, farthest first, so it
+    /// has to grow away from the camera whichever way it faces.
+    #[test]
+    fn view_depth_grows_away_from_the_camera() {
+        let mut camera = Camera::new(1.0);
+        for (position, target) in [
+            (vec3(0.0, 0.0, 2.5), vec3(0.0, 0.0, 0.0)),
+            (vec3(3.0, 1.0, -2.0), vec3(-1.0, 0.0, 1.0)),
+        ] {
+            camera.position = position;
+            camera.target = target;
+            let forward = normalize(target - position);
+            assert!((camera.view_depth(position + forward) - 1.0).abs() < 1e-4);
+            assert!((camera.view_depth(position + forward * 4.0) - 4.0).abs() < 1e-4);
+        }
+    }
+
+    /// The 2D camera has to keep world units on pixels, top-left first,
+    /// across a resize.
+    #[test]
+    fn pixel_space_maps_pixels_to_the_viewport() {
+        let mut camera = Camera::pixel_space(200, 100);
+        for (width, height) in [(200.0, 100.0), (400.0, 300.0)] {
+            camera.set_viewport(width as u32, height as u32);
+            for (x, y, expected) in [
+                (0.0, 0.0, (-1.0, 1.0)),
+                (width, height, (1.0, -1.0)),
+                (width / 2.0, height / 2.0, (0.0, 0.0)),
+            ] {
+                let point = ndc(&camera, vec3(x, y, 0.0));
+                assert!(
+                    (point.x - expected.0).abs() < 1e-4
+                        && (point.y - expected.1).abs() < 1e-4
+                        && (0.0..=1.0).contains(&point.z),
+                    "pixel ({}, {}) of {}x{} lands on {:?}",
+                    x,
+                    y,
+                    width,
+                    height,
+                    point.as_array()
+                );
+            }
+        }
+    }
+
+    /// Toggling the projection twice has to come back to the same field of
+    /// view.
+    #[test]
+    fn toggling_the_projection_twice_keeps_the_fov() {
+        let mut camera = Camera::new(1.5);
+        let fov_y = camera.fov_y().unwrap();
+        camera.projection = camera.toggled_projection();
+        let center = ndc(&camera, vec3(0.0, 0.0, 0.0));
+        assert!(
+            center.x.abs() < 1e-4 && center.y.abs() < 1e-4,
+            "the target lands on {:?}",
+            center.as_array()
+        );
+        camera.projection = camera.toggled_projection();
+        match camera.projection {
+            Projection::Perspective { fov_y: back, .. } => assert!((back - fov_y).abs() < 1e-3),
+            projection => panic!("toggled twice to {:?}", projection),
+        }
+    }
+
+    /// The middle of the viewport looks at the target in either projection,
+    /// and a corner of an orthographic view starts at the view's edge.
+    #[test]
+    fn screen_rays_start_where_the_pixel_is() {
+        let mut camera = Camera::new(2.0);
+        camera.position = vec3(3.0, 1.0, -2.0);
+        camera.target = vec3(-1.0, 0.0, 1.0);
+        let forward = normalize(camera.target - camera.position);
+        for _ in 0..2 {
+            let ray = camera.screen_to_ray((100.0, 50.0), (200.0, 100.0));
+            assert!(
+                length(ray.direction - forward) < 1e-4,
+                "the middle of a {:?} view looks along {:?}",
+                camera.projection,
+                ray.direction.as_array()
+            );
+            camera.projection = camera.toggled_projection();
+        }
+        let camera = Camera::pixel_space(200, 100);
+        let ray = camera.screen_to_ray((200.0, 100.0), (200.0, 100.0));
+        assert!(
+            (ray.origin.x - 200.0).abs() < 1e-3 && (ray.origin.y - 100.0).abs() < 1e-3,
+            "the bottom right pixel of a pixel space camera is at {:?}",
+            ray.origin.as_array()
+        );
+    }
+
+    /// Framing something with no size still has it in front of the camera.
+    #[test]
+    fn framing_a_point() {
+        let point = vec3(1.0, 2.0, 3.0);
+        let aabb = Aabb {
+            min: point,
+            max: point,
+        };
+        for mut camera in [Camera::new(1.5), Camera::pixel_space(200, 100)] {
+            camera.frame(&aabb);
+            assert!(
+                camera.position.as_array().iter().all(|x| x.is_finite())
+                    && camera.view_depth(point) > 0.0,
+                "framing a point puts a {:?} camera at {:?}",
+                camera.projection,
+                camera.position.as_array()
+            );
+        }
+    }
+
+    /// Framed, all of a box is in view of either projection.
+    #[test]
+    fn framing_a_box_keeps_it_in_view() {
+        let aabb = Aabb {
+            min: vec3(2.0, -1.0, -6.0),
+            max: vec3(4.0, 3.0, -5.0),
+        };
+        let mut camera = Camera::new(0.5);
+        for _ in 0..2 {
+            camera.frame(&aabb);
+            let frustum = Frustum::from_matrix(&camera.build_view_projection());
+            for corner in [aabb.min, aabb.max, vec3(aabb.min.x, aabb.max.y, aabb.max.z)] {
+                let point = Aabb {
+                    min: corner,
+                    max: corner,
+                };
+                assert!(
+                    frustum.intersects(&point, &identity()),
+                    "{:?} is out of view of a {:?} camera",
+                    corner.as_array(),
+                    camera.projection
+                );
+            }
+            camera.projection = camera.toggled_projection();
+        }
+    }
+}
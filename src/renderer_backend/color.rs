@@ -0,0 +1,75 @@
+use glm::Vec3;
+
+/// How colors written in code, vertex colors and clear colors, are meant.
+/// Shaders output linear values and the sRGB surface encodes them, so
+/// colors picked by eye have to be converted on the way in.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum ColorSpace {
+    /// Authored in sRGB, like values from a color picker, and converted to
+    /// linear before they reach the GPU.
+    Srgb,
+    /// Passed through as they are. sRGB-authored colors come out too dark.
+    Linear,
+}
+
+/// The exact piecewise sRGB transfer function, not the 2.2 gamma
+/// approximation.
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+impl ColorSpace {
+    pub fn toggled(self) -> Self {
+        match self {
+            ColorSpace::Srgb => ColorSpace::Linear,
+            ColorSpace::Linear => ColorSpace::Srgb,
+        }
+    }
+
+    pub fn to_linear(self, color: Vec3) -> Vec3 {
+        match self {
+            ColorSpace::Srgb => glm::vec3(
+                srgb_to_linear(color.x),
+                srgb_to_linear(color.y),
+                srgb_to_linear(color.z),
+            ),
+            ColorSpace::Linear => color,
+        }
+    }
+
+    /// Converts the color channels; alpha is always linear.
+    pub fn to_linear_color(self, color: wgpu::Color) -> wgpu::Color {
+        match self {
+            ColorSpace::Srgb => wgpu::Color {
+                r: srgb_to_linear(color.r as f32) as f64,
+                g: srgb_to_linear(color.g as f32) as f64,
+                b: srgb_to_linear(color.b as f32) as f64,
+                a: color.a,
+            },
+            ColorSpace::Linear => color,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_to_linear_at_the_ends_and_knee() {
+        assert_eq!(srgb_to_linear(0.0), 0.0);
+        assert!((srgb_to_linear(1.0) - 1.0).abs() < 1e-6);
+        // Both pieces meet at the knee, at about 0.0031308.
+        let knee = 0.04045;
+        assert!((srgb_to_linear(knee) - knee / 12.92).abs() < 1e-7);
+        let above = ((knee + 0.055) / 1.055_f32).powf(2.4);
+        assert!((above - srgb_to_linear(knee)).abs() < 1e-6);
+        assert!(srgb_to_linear(knee + 1e-4) > srgb_to_linear(knee));
+        // Darker than the 2.2 gamma approximation would make it.
+        assert!((srgb_to_linear(0.5) - 0.214).abs() < 1e-3);
+    }
+}
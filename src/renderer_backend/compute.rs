@@ -0,0 +1,82 @@
+use std::collections::HashMap;
+use std::path::Path;
+
+use super::shader::{self, ShaderError};
+use super::validation;
+
+pub struct Builder<'a> {
+    shader_filename: String,
+    entry: String,
+    constants: HashMap<String, f64>,
+    bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    device: &'a wgpu::Device,
+}
+
+impl<'a> Builder<'a> {
+    pub fn new(device: &'a wgpu::Device) -> Self {
+        Builder {
+            shader_filename: "dummy".to_string(),
+            entry: "dummy".to_string(),
+            constants: HashMap::new(),
+            bind_group_layouts: Vec::new(),
+            device: device,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.constants.clear();
+        self.bind_group_layouts.clear();
+    }
+
+    pub fn add_bind_group_layout(&mut self, layout: &'a wgpu::BindGroupLayout) {
+        self.bind_group_layouts.push(layout);
+    }
+
+    pub fn set_shader_module(&mut self, shader_filename: &str, entry: &str) {
+        self.shader_filename = shader_filename.to_string();
+        self.entry = entry.to_string();
+    }
+
+    /// Overrides a WGSL `override` declaration, e.g. the workgroup size.
+    pub fn set_constant(&mut self, name: &str, value: f64) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    #[track_caller]
+    pub fn build_pipeline(&mut self, label: &str) -> Result<wgpu::ComputePipeline, ShaderError> {
+        let device = self.device;
+        let compute_pipeline =
+            validation::with_validation(device, label, || self.create_pipeline(label));
+        self.reset();
+        compute_pipeline?
+    }
+
+    fn create_pipeline(&self, label: &str) -> Result<wgpu::ComputePipeline, ShaderError> {
+        let shader_module = shader::load_wgsl(self.device, Path::new(&self.shader_filename))?;
+
+        let pipeline_layout_descriptor = wgpu::PipelineLayoutDescriptor {
+            label: Some(label),
+            bind_group_layouts: &self.bind_group_layouts,
+            push_constant_ranges: &[],
+        };
+        let pipeline_layout = self
+            .device
+            .create_pipeline_layout(&pipeline_layout_descriptor);
+
+        let compute_pipeline_descriptor = wgpu::ComputePipelineDescriptor {
+            label: Some(label),
+            layout: Some(&pipeline_layout),
+            module: &shader_module,
+            entry_point: Some(&self.entry),
+            compilation_options: wgpu::PipelineCompilationOptions {
+                constants: &self.constants,
+                ..Default::default()
+            },
+            cache: None,
+        };
+
+        Ok(self
+            .device
+            .create_compute_pipeline(&compute_pipeline_descriptor))
+    }
+}
@@ -0,0 +1,142 @@
+use std::marker::PhantomData;
+
+use super::bind_group;
+use super::bind_group_layout::Layout;
+use super::frame_uploader::FrameUploader;
+
+/// One uniform buffer holding a `T` per draw at aligned offsets, for devices
+/// that can't read storage buffers from the vertex shader. Values are
+/// allocated on the CPU each frame, uploaded together by `flush` and bound
+/// with `set_bind_group(.., &[offset])`.
+pub struct DynamicUniformBuffer<T> {
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    data: Vec<u8>,
+    stride: u32,
+    capacity: u32,
+    _marker: PhantomData<T>,
+}
+
+impl<T: bytemuck::Pod> DynamicUniformBuffer<T> {
+    /// `layout` must hold a single dynamic uniform buffer of `size_of::<T>()`
+    /// bytes, see `bind_group_layout::Builder::add_dynamic_uniform_buffer`.
+    pub fn new(device: &wgpu::Device, layout: &Layout, capacity: u32) -> Self {
+        let stride = wgpu::util::align_to(
+            std::mem::size_of::<T>() as u32,
+            device.limits().min_uniform_buffer_offset_alignment,
+        );
+        let capacity = capacity.max(1);
+        let buffer = Self::create_buffer(device, capacity * stride);
+        let bind_group = Self::create_bind_group(device, layout, &buffer);
+        DynamicUniformBuffer {
+            buffer: buffer,
+            bind_group: bind_group,
+            data: Vec::with_capacity((capacity * stride) as usize),
+            stride: stride,
+            capacity: capacity,
+            _marker: PhantomData,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, size: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Dynamic Uniform Buffer"),
+            size: size as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &Layout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_buffer_range(buffer, std::mem::size_of::<T>() as u64);
+        builder.build("Dynamic Uniform Bind Group")
+    }
+
+    /// Queues `data` for this frame and returns its offset in the buffer.
+    pub fn alloc(&mut self, data: &T) -> u32 {
+        let offset = self.data.len() as u32;
+        self.data.extend_from_slice(bytemuck::bytes_of(data));
+        self.data.resize((offset + self.stride) as usize, 0);
+        offset
+    }
+
+    /// Forgets this frame's allocations; offsets handed out so far are no
+    /// longer valid.
+    pub fn reset(&mut self) {
+        self.data.clear();
+    }
+
+    /// Queues this frame's allocations for upload, first reallocating the buffer at the
+    /// next power of two if they no longer fit. Nothing is ever written past
+    /// the end of the buffer.
+    pub fn flush(&mut self, device: &wgpu::Device, uploader: &mut FrameUploader, layout: &Layout) {
+        let count = self.data.len() as u32 / self.stride;
+        if count > self.capacity {
+            self.capacity = count.next_power_of_two();
+            self.buffer = Self::create_buffer(device, self.capacity * self.stride);
+            self.bind_group = Self::create_bind_group(device, layout, &self.buffer);
+        }
+        uploader.write(&self.buffer, 0, &self.data);
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::{bind_group_layout, validation};
+    use crate::test_support;
+
+    /// Three allocations into room for two: the offsets should step by the
+    /// device's alignment, and the flush should grow the buffer to fit them
+    /// without tripping validation.
+    #[test]
+    fn offsets_are_aligned_and_flush_grows() {
+        type Value = [f32; 4];
+        let Some((device, queue)) = test_support::headless_device() else {
+            return;
+        };
+        let alignment = device.limits().min_uniform_buffer_offset_alignment;
+        let mut builder = bind_group_layout::Builder::new(&device);
+        builder.add_dynamic_uniform_buffer(
+            wgpu::ShaderStages::VERTEX,
+            std::mem::size_of::<Value>() as u64,
+        );
+        let layout = builder.build("Test Layout");
+        let mut uniforms = DynamicUniformBuffer::<Value>::new(&device, &layout, 2);
+        let mut uploader = FrameUploader::new();
+
+        let offsets: Vec<u32> = (0..3).map(|i| uniforms.alloc(&[i as f32; 4])).collect();
+        for offset in &offsets {
+            assert_eq!(offset % alignment, 0, "unaligned offset {}", offset);
+        }
+        assert_eq!(offsets, [0, uniforms.stride, 2 * uniforms.stride]);
+        assert_eq!(uniforms.capacity, 2);
+
+        validation::with_validation(&device, "dynamic uniform test", || {
+            uniforms.flush(&device, &mut uploader, &layout);
+            let mut command_encoder = device.create_command_encoder(&Default::default());
+            uploader.record(&device, &mut command_encoder);
+            queue.submit([command_encoder.finish()]);
+        })
+        .unwrap_or_else(|error| panic!("{}", error));
+        uploader.recall();
+        assert_eq!(uniforms.capacity, 4);
+        assert!(uniforms.buffer.size() >= 3 * uniforms.stride as u64);
+
+        // Back under capacity the next frame keeps the buffer it has.
+        uniforms.reset();
+        assert_eq!(uniforms.alloc(&[0.0; 4]), 0);
+        uniforms.flush(&device, &mut uploader, &layout);
+        assert_eq!(uniforms.capacity, 4);
+    }
+}
@@ -0,0 +1,175 @@
+use std::fmt;
+use std::path::PathBuf;
+
+use crate::clear_color::ColorParseError;
+use crate::settings::SettingsError;
+
+use super::gltf_loader::GltfError;
+use super::obj_loader::ObjError;
+use super::screenshot::ScreenshotError;
+use super::shader::ShaderError;
+use super::texture::TextureError;
+
+#[derive(Debug)]
+pub enum RendererError {
+    /// GLFW or winit's event loop wouldn't start.
+    Windowing(String),
+    CreateWindow,
+    #[cfg(feature = "glfw")]
+    WindowHandle(String),
+    CreateSurface(wgpu::CreateSurfaceError),
+    NoAdapter,
+    AdapterNotFound {
+        name: String,
+        available: Vec<String>,
+    },
+    NoAdapterOnBackend {
+        backend: String,
+        available: Vec<String>,
+    },
+    RequestDevice(wgpu::RequestDeviceError),
+    /// The device failed again after being recreated once.
+    DeviceFailed(String),
+    UnsupportedSurface,
+    Surface(wgpu::SurfaceError),
+    Shader(ShaderError),
+    Texture(TextureError),
+    Model(ObjError),
+    Gltf(GltfError),
+    ClearColor(ColorParseError),
+    Settings(SettingsError),
+    InvalidArgument(String),
+    Screenshot(ScreenshotError),
+    Output {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for RendererError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RendererError::Windowing(error) => {
+                write!(f, "can't start the windowing library: {}", error)
+            }
+            RendererError::CreateWindow => write!(f, "can't create window"),
+            #[cfg(feature = "glfw")]
+            RendererError::WindowHandle(error) => write!(f, "can't get window handle: {}", error),
+            RendererError::CreateSurface(error) => write!(f, "can't create surface: {}", error),
+            RendererError::NoAdapter => write!(f, "no compatible graphics adapter found"),
+            RendererError::AdapterNotFound { name, available } => {
+                write!(f, "no adapter matching \"{}\", available:", name)?;
+                if available.is_empty() {
+                    write!(f, " none")?;
+                }
+                for adapter in available {
+                    write!(f, "\n  {}", adapter)?;
+                }
+                Ok(())
+            }
+            RendererError::NoAdapterOnBackend { backend, available } => {
+                write!(f, "no adapter found for backend {}", backend)?;
+                match available.is_empty() {
+                    true => write!(f, ", no other backend has adapters either"),
+                    false => write!(f, ", adapters found on: {}", available.join(", ")),
+                }
+            }
+            RendererError::RequestDevice(error) => write!(f, "can't create device: {}", error),
+            RendererError::DeviceFailed(error) => {
+                write!(
+                    f,
+                    "GPU device failed again after being recreated: {}",
+                    error
+                )
+            }
+            RendererError::UnsupportedSurface => {
+                write!(f, "surface is not supported by the adapter")
+            }
+            RendererError::Surface(error) => write!(f, "surface error: {}", error),
+            RendererError::Shader(error) => write!(f, "{}", error),
+            RendererError::Texture(error) => write!(f, "{}", error),
+            RendererError::Model(error) => write!(f, "{}", error),
+            RendererError::Gltf(error) => write!(f, "{}", error),
+            RendererError::ClearColor(error) => write!(f, "{}", error),
+            RendererError::Settings(error) => write!(f, "{}", error),
+            RendererError::InvalidArgument(message) => write!(f, "{}", message),
+            RendererError::Screenshot(error) => write!(f, "{}", error),
+            RendererError::Output { path, error } => {
+                write!(f, "can't write to {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for RendererError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RendererError::CreateSurface(error) => Some(error),
+            RendererError::RequestDevice(error) => Some(error),
+            RendererError::Surface(error) => Some(error),
+            RendererError::Shader(error) => Some(error),
+            RendererError::Texture(error) => Some(error),
+            RendererError::Model(error) => Some(error),
+            RendererError::Gltf(error) => Some(error),
+            RendererError::ClearColor(error) => Some(error),
+            RendererError::Settings(error) => Some(error),
+            RendererError::Screenshot(error) => Some(error),
+            RendererError::Output { error, .. } => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<wgpu::CreateSurfaceError> for RendererError {
+    fn from(error: wgpu::CreateSurfaceError) -> Self {
+        RendererError::CreateSurface(error)
+    }
+}
+
+impl From<wgpu::RequestDeviceError> for RendererError {
+    fn from(error: wgpu::RequestDeviceError) -> Self {
+        RendererError::RequestDevice(error)
+    }
+}
+
+impl From<ShaderError> for RendererError {
+    fn from(error: ShaderError) -> Self {
+        RendererError::Shader(error)
+    }
+}
+
+impl From<TextureError> for RendererError {
+    fn from(error: TextureError) -> Self {
+        RendererError::Texture(error)
+    }
+}
+
+impl From<ObjError> for RendererError {
+    fn from(error: ObjError) -> Self {
+        RendererError::Model(error)
+    }
+}
+
+impl From<GltfError> for RendererError {
+    fn from(error: GltfError) -> Self {
+        RendererError::Gltf(error)
+    }
+}
+
+impl From<ColorParseError> for RendererError {
+    fn from(error: ColorParseError) -> Self {
+        RendererError::ClearColor(error)
+    }
+}
+
+impl From<SettingsError> for RendererError {
+    fn from(error: SettingsError) -> Self {
+        RendererError::Settings(error)
+    }
+}
+
+impl From<ScreenshotError> for RendererError {
+    fn from(error: ScreenshotError) -> Self {
+        RendererError::Screenshot(error)
+    }
+}
@@ -0,0 +1,171 @@
+use std::ops::Range;
+
+use bytemuck::Pod;
+
+/// Staging buffers are allocated in chunks of this size and reused once the
+/// GPU is done copying out of them.
+pub const CHUNK_SIZE: u64 = 256 * 1024;
+
+/// How much the last recorded frame uploaded.
+#[derive(Clone, Copy, Default)]
+pub struct UploadStats {
+    pub writes: u32,
+    /// Writes that landed right after the previous one into the same buffer
+    /// are merged into a single copy.
+    pub copies: u32,
+    pub bytes: u64,
+}
+
+struct PendingWrite {
+    target: wgpu::Buffer,
+    offset: u64,
+    data: Range<usize>,
+}
+
+/// Collects the frame's buffer updates on the CPU and records them as
+/// copies out of a `StagingBelt` at the start of the frame's encoder, in
+/// place of one `queue.write_buffer` per update.
+pub struct FrameUploader {
+    belt: wgpu::util::StagingBelt,
+    data: Vec<u8>,
+    writes: Vec<PendingWrite>,
+    write_count: u32,
+    stats: UploadStats,
+}
+
+impl FrameUploader {
+    pub fn new() -> Self {
+        FrameUploader {
+            belt: wgpu::util::StagingBelt::new(CHUNK_SIZE),
+            data: Vec::new(),
+            writes: Vec::new(),
+            write_count: 0,
+            stats: UploadStats::default(),
+        }
+    }
+
+    /// Queues `data` to be written to `target` at `offset` when the next
+    /// frame is recorded. Like `queue.write_buffer`, the offset and size
+    /// have to be multiples of `wgpu::COPY_BUFFER_ALIGNMENT` and `target`
+    /// needs `COPY_DST` usage.
+    pub fn write<T: Pod>(&mut self, target: &wgpu::Buffer, offset: u64, data: &[T]) {
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        if bytes.is_empty() {
+            return;
+        }
+        debug_assert!(
+            offset % wgpu::COPY_BUFFER_ALIGNMENT == 0
+                && bytes.len() as u64 % wgpu::COPY_BUFFER_ALIGNMENT == 0,
+            "unaligned upload of {} bytes at {}",
+            bytes.len(),
+            offset
+        );
+
+        let start = self.data.len();
+        self.data.extend_from_slice(bytes);
+        self.write_count += 1;
+        if let Some(last) = self.writes.last_mut() {
+            let last_end = last.offset + last.data.len() as u64;
+            if last.target == *target && last_end == offset {
+                last.data.end = self.data.len();
+                return;
+            }
+        }
+        self.writes.push(PendingWrite {
+            target: target.clone(),
+            offset: offset,
+            data: start..self.data.len(),
+        });
+    }
+
+    /// Records every queued write into `command_encoder`, ahead of whatever
+    /// reads the buffers, and closes the staging buffers for submission.
+    pub fn record(&mut self, device: &wgpu::Device, command_encoder: &mut wgpu::CommandEncoder) {
+        self.stats = UploadStats {
+            writes: self.write_count,
+            copies: self.writes.len() as u32,
+            bytes: self.data.len() as u64,
+        };
+        if self.writes.is_empty() {
+            return;
+        }
+        for write in self.writes.drain(..) {
+            let bytes = &self.data[write.data];
+            let size = wgpu::BufferSize::new(bytes.len() as u64).unwrap();
+            self.belt
+                .write_buffer(command_encoder, &write.target, write.offset, size, device)
+                .copy_from_slice(bytes);
+        }
+        self.data.clear();
+        self.write_count = 0;
+        self.belt.finish();
+    }
+
+    /// Hands the staging buffers back for reuse once the GPU is done with
+    /// them. Call after submitting the encoder passed to `record`.
+    pub fn recall(&mut self) {
+        self.belt.recall();
+    }
+
+    pub fn stats(&self) -> UploadStats {
+        self.stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::validation;
+    use crate::test_support;
+
+    /// Several frames of a few thousand small writes that can't be merged:
+    /// after the first frame neither the CPU side nor the staging belt
+    /// should grow.
+    #[test]
+    fn many_small_writes_reuse_their_memory() {
+        const WRITES: u64 = 3000;
+        const SIZE: u64 = 64;
+        let Some((device, queue)) = test_support::headless_device() else {
+            return;
+        };
+        let target = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Upload Target"),
+            size: WRITES * SIZE * 2,
+            usage: wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut uploader = FrameUploader::new();
+
+        let mut steady = None;
+        for frame in 0..4 {
+            for i in 0..WRITES {
+                uploader.write(&target, i * SIZE * 2, &[frame as f32; SIZE as usize / 4]);
+            }
+            let capacities = (uploader.data.capacity(), uploader.writes.capacity());
+            validation::with_validation(&device, "upload test", || {
+                let mut command_encoder = device.create_command_encoder(&Default::default());
+                uploader.record(&device, &mut command_encoder);
+                queue.submit([command_encoder.finish()]);
+            })
+            .unwrap_or_else(|error| panic!("{}", error));
+            uploader.recall();
+            let _ = device.poll(wgpu::Maintain::Wait);
+            // Picks up the chunks the GPU handed back while polling.
+            uploader.recall();
+
+            let stats = uploader.stats();
+            assert_eq!(
+                (stats.writes, stats.copies, stats.bytes),
+                (WRITES as u32, WRITES as u32, WRITES * SIZE),
+                "frame {}",
+                frame
+            );
+            // Only the chunk counts are visible, through `Debug`.
+            let state = (capacities, format!("{:?}", uploader.belt));
+            match &steady {
+                None => steady = Some(state),
+                Some(steady) => assert_eq!(&state, steady, "frame {} allocated more", frame),
+            }
+        }
+    }
+}
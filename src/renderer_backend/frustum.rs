@@ -0,0 +1,276 @@
+use glm::*;
+
+/// An axis-aligned bounding box in a mesh's local space.
+#[derive(Clone, Copy, Debug)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// The smallest box around `points`; a degenerate box at the origin
+    /// when there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3>) -> Self {
+        let mut points = points.into_iter();
+        let Some(first) = points.next() else {
+            return Aabb {
+                min: vec3(0.0, 0.0, 0.0),
+                max: vec3(0.0, 0.0, 0.0),
+            };
+        };
+        let mut aabb = Aabb {
+            min: first,
+            max: first,
+        };
+        for point in points {
+            aabb.min = min(aabb.min, point);
+            aabb.max = max(aabb.max, point);
+        }
+        aabb
+    }
+
+    pub fn center(&self) -> Vec3 {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn half_extents(&self) -> Vec3 {
+        (self.max - self.min) * 0.5
+    }
+
+    /// The smallest axis-aligned box around this one placed by `matrix`:
+    /// the center moves with the matrix, the extents by its absolute
+    /// values. Never smaller than the transformed box, which it can be
+    /// larger than.
+    pub fn transformed(&self, matrix: &Mat4) -> Aabb {
+        let center = (*matrix * self.center().extend(1.0)).truncate(3);
+        let extents = self.half_extents();
+        let axis = |i: usize| abs(matrix[i].truncate(3)) * extents[i];
+        let extents = axis(0) + axis(1) + axis(2);
+        Aabb {
+            min: center - extents,
+            max: center + extents,
+        }
+    }
+}
+
+/// A sphere around a mesh's vertices, in its local space.
+#[derive(Clone, Copy, Debug)]
+pub struct BoundingSphere {
+    pub center: Vec3,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere around `points` centered on their bounds, which is close to
+    /// the smallest for the usual meshes; zero across at the origin when
+    /// there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Vec3> + Clone) -> Self {
+        let center = Aabb::from_points(points.clone()).center();
+        let radius = points
+            .into_iter()
+            .map(|point| length(point - center))
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: center,
+            radius: radius,
+        }
+    }
+
+    /// Placed by `matrix`, and grown by its largest scale so a sphere still
+    /// covers whatever this one did.
+    pub fn transformed(&self, matrix: &Mat4) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|i| length(matrix[i].truncate(3)))
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: (*matrix * self.center.extend(1.0)).truncate(3),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// The six planes bounding what a view-projection matrix maps into wgpu's
+/// clip volume, each as `(normal, distance)` with the normal pointing in.
+#[derive(Clone, Copy, Debug)]
+pub struct Frustum {
+    planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the planes from the rows of `view_projection` (Gribb and
+    /// Hartmann). Clip space z runs 0..w, so the near plane is row 2 alone.
+    pub fn from_matrix(view_projection: &Mat4) -> Self {
+        let m = view_projection;
+        let row = |i: usize| vec4(m[0][i], m[1][i], m[2][i], m[3][i]);
+        let (x, y, z, w) = (row(0), row(1), row(2), row(3));
+        let planes = [w + x, w - x, w + y, w - y, z, w - z].map(|plane| {
+            let length = length(plane.truncate(3));
+            match length > 0.0 {
+                true => plane / length,
+                false => plane,
+            }
+        });
+        Frustum { planes: planes }
+    }
+
+    /// In the order left, right, bottom, top, near, far.
+    pub fn planes(&self) -> [Vec4; 6] {
+        self.planes
+    }
+
+    /// Where the planes meet: the near corners, then the far ones, each
+    /// bottom left, bottom right, top right, top left.
+    pub fn corners(&self) -> [Vec3; 8] {
+        let meet = |a: Vec4, b: Vec4, c: Vec4| {
+            let (na, nb, nc) = (a.truncate(3), b.truncate(3), c.truncate(3));
+            let point = cross(nb, nc) * -a.w + cross(nc, na) * -b.w + cross(na, nb) * -c.w;
+            point / dot(na, cross(nb, nc))
+        };
+        let [left, right, bottom, top, near, far] = self.planes;
+        let face = |depth: Vec4| {
+            [(left, bottom), (right, bottom), (right, top), (left, top)]
+                .map(|(side, height)| meet(side, height, depth))
+        };
+        let [n0, n1, n2, n3] = face(near);
+        let [f0, f1, f2, f3] = face(far);
+        [n0, n1, n2, n3, f0, f1, f2, f3]
+    }
+
+    /// Whether `aabb`, placed in the world by `model`, is at least partly
+    /// inside. Boxes near a corner of the frustum can pass without being
+    /// visible, but nothing visible is ever rejected.
+    pub fn intersects(&self, aabb: &Aabb, model: &Mat4) -> bool {
+        let world = aabb.transformed(model);
+        let center = world.center();
+        let world_extents = world.half_extents();
+
+        self.planes.iter().all(|plane| {
+            let normal = plane.truncate(3);
+            let distance = dot(normal, center) + plane.w;
+            let radius = dot(abs(normal), world_extents);
+            distance + radius >= 0.0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::camera::{Camera, Projection};
+    use crate::renderer_backend::mesh_builder;
+    use crate::renderer_backend::uniform::identity;
+
+    /// The debug lines draw a frozen frustum from its corners, which have
+    /// to land on the corners of clip space.
+    #[test]
+    fn corners_land_on_clip_space_corners() {
+        let mut camera = Camera::new(1.5);
+        camera.position = vec3(3.0, 1.0, -2.0);
+        camera.target = vec3(-1.0, 0.0, 1.0);
+        let view_projection = camera.build_view_projection();
+        let corners = Frustum::from_matrix(&view_projection).corners();
+        let expected = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)];
+        for (i, corner) in corners.iter().enumerate() {
+            let clip = view_projection * corner.extend(1.0);
+            let ndc = clip.truncate(3) / clip.w;
+            let (x, y) = expected[i % 4];
+            let z = if i < 4 { 0.0 } else { 1.0 };
+            assert!(
+                (ndc.x - x).abs() < 1e-3 && (ndc.y - y).abs() < 1e-3 && (ndc.z - z).abs() < 1e-3,
+                "corner {} lands on {:?} in clip space",
+                i,
+                ndc.as_array()
+            );
+        }
+    }
+
+    /// A cube of side 1 turned 45 degrees about y and scaled 2 fills a box
+    /// 2 * sqrt(2) across in x and z.
+    #[test]
+    fn transformed_boxes_bound_the_turned_mesh() {
+        let turned = ext::rotate(&identity(), 45f32.to_radians(), vec3(0.0, 1.0, 0.0));
+        let turned = ext::scale(&turned, vec3(2.0, 2.0, 2.0));
+        let world = mesh_builder::cube_data(1.0).bounds().transformed(&turned);
+        let half = 2f32.sqrt();
+        assert!(
+            length(world.max - vec3(half, 1.0, half)) < 1e-4
+                && length(world.min + vec3(half, 1.0, half)) < 1e-4,
+            "the turned cube is bounded by {:?}",
+            world
+        );
+    }
+
+    /// Stretched 3 times in y, a sphere grows to 3 times its radius.
+    #[test]
+    fn transformed_spheres_take_the_largest_scale() {
+        let stretched = ext::scale(&identity(), vec3(1.0, 3.0, 1.0));
+        let sphere = mesh_builder::cube_data(1.0).bounding_sphere();
+        let BoundingSphere { center, radius } = sphere.transformed(&stretched);
+        assert!(length(center) < 1e-4);
+        assert!((radius - sphere.radius * 3.0).abs() < 1e-4);
+    }
+
+    /// At the origin looking down -z, 90 degrees wide and tall, from 1 to
+    /// 10, so the side planes lean out at 45 degrees.
+    fn right_angled_frustum() -> Frustum {
+        let mut camera = Camera::new(1.0);
+        camera.position = vec3(0.0, 0.0, 0.0);
+        camera.target = vec3(0.0, 0.0, -1.0);
+        camera.projection = Projection::Perspective {
+            fov_y: 90.0,
+            near: 1.0,
+            far: 10.0,
+        };
+        Frustum::from_matrix(&camera.build_view_projection())
+    }
+
+    #[test]
+    fn planes_are_normalized_and_point_inward() {
+        let side = 0.5f32.sqrt();
+        let expected = [
+            vec4(side, 0.0, -side, 0.0),
+            vec4(-side, 0.0, -side, 0.0),
+            vec4(0.0, side, -side, 0.0),
+            vec4(0.0, -side, -side, 0.0),
+            vec4(0.0, 0.0, -1.0, -1.0),
+            vec4(0.0, 0.0, 1.0, 10.0),
+        ];
+        let inside = vec3(0.0, 0.0, -5.0);
+        let names = ["left", "right", "bottom", "top", "near", "far"];
+        let planes = right_angled_frustum().planes();
+        for ((plane, expected), name) in planes.iter().zip(expected).zip(names) {
+            assert!(
+                length(*plane - expected) < 1e-4,
+                "the {} plane is {:?}",
+                name,
+                plane.as_array()
+            );
+            assert!((length(plane.truncate(3)) - 1.0).abs() < 1e-4);
+            assert!(dot(plane.truncate(3), inside) + plane.w > 0.0);
+        }
+    }
+
+    #[test]
+    fn intersects_boxes_inside_outside_and_crossing() {
+        let frustum = right_angled_frustum();
+        // A cube reaching `half` out from its center at (x, 0, z).
+        let cube = |x: f32, z: f32, half: f32| Aabb {
+            min: vec3(x - half, -half, z - half),
+            max: vec3(x + half, half, z + half),
+        };
+        for (name, aabb, expected) in [
+            ("inside", cube(0.0, -5.0, 1.0), true),
+            ("behind", cube(0.0, 2.0, 1.0), false),
+            ("past the far plane", cube(0.0, -17.0, 2.0), false),
+            ("off to the left", cube(-10.0, -5.0, 2.0), false),
+            ("across the near plane", cube(0.0, -1.0, 0.5), true),
+            ("across the left plane", cube(-5.0, -5.0, 1.0), true),
+            ("around the frustum", cube(0.0, 0.0, 20.0), true),
+        ] {
+            assert_eq!(frustum.intersects(&aabb, &identity()), expected, "{}", name);
+        }
+        // Moved behind the camera by its model matrix.
+        let behind = ext::translate(&identity(), vec3(0.0, 0.0, 10.0));
+        assert!(!frustum.intersects(&cube(0.0, -5.0, 1.0), &behind));
+    }
+}
@@ -0,0 +1,233 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use glm::*;
+
+use super::mesh_builder::{self, Vertex};
+use super::uniform;
+use super::validation::ValidationError;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Import {
+        path: PathBuf,
+        error: gltf::Error,
+    },
+    NoScene {
+        path: PathBuf,
+    },
+    MissingPositions {
+        mesh: usize,
+    },
+    UnsupportedImageFormat {
+        image: usize,
+        format: gltf::image::Format,
+    },
+    /// wgpu rejected a mesh's buffers or a texture.
+    Validation(ValidationError),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GltfError::Import { path, error } => {
+                write!(f, "can't import model {}: {}", path.display(), error)
+            }
+            GltfError::NoScene { path } => write!(f, "{} has no scene", path.display()),
+            GltfError::MissingPositions { mesh } => {
+                write!(f, "mesh {} has a primitive without positions", mesh)
+            }
+            GltfError::UnsupportedImageFormat { image, format } => {
+                write!(f, "image {} has unsupported format {:?}", image, format)
+            }
+            GltfError::Validation(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for GltfError {}
+
+impl From<ValidationError> for GltfError {
+    fn from(error: ValidationError) -> Self {
+        GltfError::Validation(error)
+    }
+}
+
+/// One triangle primitive with its vertices already in `Vertex` layout.
+pub struct Primitive {
+    pub label: String,
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+    /// Index into `Scene::images`, `None` for untextured materials.
+    pub base_color_texture: Option<usize>,
+    /// Index into `Scene::images` of the tangent-space normal map.
+    pub normal_texture: Option<usize>,
+    /// The node's transform combined with all of its parents'.
+    pub transform: Mat4,
+}
+
+pub struct Scene {
+    pub primitives: Vec<Primitive>,
+    pub images: Vec<gltf::image::Data>,
+}
+
+/// Reads the default scene of a .gltf or .glb file, or its first scene if
+/// none is marked default. Sparse accessors and other scenes are ignored.
+pub fn import(path: &Path) -> Result<Scene, GltfError> {
+    let (document, buffers, images) = gltf::import(path).map_err(|error| GltfError::Import {
+        path: path.to_path_buf(),
+        error: error,
+    })?;
+    let scene = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .ok_or_else(|| GltfError::NoScene {
+            path: path.to_path_buf(),
+        })?;
+
+    let mut primitives = Vec::new();
+    for node in scene.nodes() {
+        read_node(&node, &uniform::identity(), &buffers, &mut primitives)?;
+    }
+    Ok(Scene {
+        primitives: primitives,
+        images: images,
+    })
+}
+
+fn read_node(
+    node: &gltf::Node,
+    parent: &Mat4,
+    buffers: &[gltf::buffer::Data],
+    primitives: &mut Vec<Primitive>,
+) -> Result<(), GltfError> {
+    let [c0, c1, c2, c3] = node.transform().matrix();
+    let local = Mat4::new(
+        *Vec4::from_array(&c0),
+        *Vec4::from_array(&c1),
+        *Vec4::from_array(&c2),
+        *Vec4::from_array(&c3),
+    );
+    let transform = *parent * local;
+
+    if let Some(mesh) = node.mesh() {
+        for primitive in mesh.primitives() {
+            if primitive.mode() != gltf::mesh::Mode::Triangles {
+                eprintln!(
+                    "Skipping mesh {} primitive {}: {:?} is not supported",
+                    mesh.index(),
+                    primitive.index(),
+                    primitive.mode()
+                );
+                continue;
+            }
+            primitives.push(read_primitive(&mesh, &primitive, &transform, buffers)?);
+        }
+    }
+    for child in node.children() {
+        read_node(&child, &transform, buffers, primitives)?;
+    }
+    Ok(())
+}
+
+fn read_primitive(
+    mesh: &gltf::Mesh,
+    primitive: &gltf::Primitive,
+    transform: &Mat4,
+    buffers: &[gltf::buffer::Data],
+) -> Result<Primitive, GltfError> {
+    let reader = primitive.reader(|buffer| Some(&buffers[buffer.index()]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .ok_or(GltfError::MissingPositions { mesh: mesh.index() })?
+        .collect();
+    let count = positions.len();
+    let normals: Option<Vec<[f32; 3]>> = reader.read_normals().map(|normals| normals.collect());
+    let tangents: Option<Vec<[f32; 4]>> = reader.read_tangents().map(|tangents| tangents.collect());
+    let uvs: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+        Some(uvs) => uvs.into_f32().collect(),
+        None => vec![[0.0, 0.0]; count],
+    };
+    let colors: Vec<[f32; 3]> = match reader.read_colors(0) {
+        Some(colors) => colors.into_rgb_f32().collect(),
+        None => vec![[1.0, 1.0, 1.0]; count],
+    };
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..count as u32).collect(),
+    };
+
+    // The base color factor is baked into the vertex colors, which the
+    // shader multiplies with the texture anyway. glTF colors are linear.
+    let pbr = primitive.material().pbr_metallic_roughness();
+    let [r, g, b, _] = pbr.base_color_factor();
+    let mut vertices: Vec<Vertex> = (0..count)
+        .map(|i| {
+            let [x, y, z] = positions[i];
+            let [u, v] = uvs[i];
+            let color = colors[i];
+            let normal = normals.as_ref().map_or(vec3(0.0, 0.0, 0.0), |normals| {
+                *Vec3::from_array(&normals[i])
+            });
+            Vertex::new(
+                vec3(x, y, z),
+                vec3(color[0] * r, color[1] * g, color[2] * b),
+                vec2(u, v),
+                normal,
+            )
+        })
+        .collect();
+    if normals.is_none() {
+        mesh_builder::compute_normals(&mut vertices, &indices);
+    }
+    // glTF's bitangent is `cross(normal, tangent) * w` too, pointing up
+    // its images.
+    match &tangents {
+        Some(tangents) if normals.is_some() => {
+            for (vertex, tangent) in vertices.iter_mut().zip(tangents) {
+                vertex.set_tangent(*Vec4::from_array(tangent));
+            }
+        }
+        _ => mesh_builder::compute_tangents(&mut vertices, &indices),
+    }
+
+    Ok(Primitive {
+        label: format!("{} {}", mesh.name().unwrap_or("Mesh"), primitive.index()),
+        vertices: vertices,
+        indices: indices,
+        base_color_texture: pbr
+            .base_color_texture()
+            .map(|info| info.texture().source().index()),
+        normal_texture: primitive
+            .material()
+            .normal_texture()
+            .map(|info| info.texture().source().index()),
+        transform: *transform,
+    })
+}
+
+/// Converts one of `Scene::images` for `Texture::from_image`. Only 8-bit
+/// formats are supported.
+pub fn decode_image(
+    index: usize,
+    data: &gltf::image::Data,
+) -> Result<image::DynamicImage, GltfError> {
+    use gltf::image::Format;
+
+    let pixels = data.pixels.clone();
+    let decoded = match data.format {
+        Format::R8 => image::GrayImage::from_raw(data.width, data.height, pixels)
+            .map(image::DynamicImage::ImageLuma8),
+        Format::R8G8 => image::GrayAlphaImage::from_raw(data.width, data.height, pixels)
+            .map(image::DynamicImage::ImageLumaA8),
+        Format::R8G8B8 => image::RgbImage::from_raw(data.width, data.height, pixels)
+            .map(image::DynamicImage::ImageRgb8),
+        Format::R8G8B8A8 => image::RgbaImage::from_raw(data.width, data.height, pixels)
+            .map(image::DynamicImage::ImageRgba8),
+        _ => None,
+    };
+    decoded.ok_or(GltfError::UnsupportedImageFormat {
+        image: index,
+        format: data.format,
+    })
+}
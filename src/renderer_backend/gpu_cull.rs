@@ -0,0 +1,586 @@
+use std::sync::mpsc;
+
+use glm::Mat4;
+use wgpu::util::DrawIndexedIndirectArgs;
+
+use super::bind_group;
+use super::bind_group_layout::{self, Layout};
+use super::compute;
+use super::frame_uploader::FrameUploader;
+use super::frustum::{Aabb, Frustum};
+use super::indirect::IndirectBatch;
+use super::mesh_builder;
+use super::mesh_pool::PooledMesh;
+use super::shader::ShaderError;
+use super::uniform;
+
+pub const SHADER_FILENAME: &str = "shaders/cull.wgsl";
+
+const WORKGROUP_SIZE: u32 = 64;
+const ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+const COUNT_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct CullParams {
+    planes: [[f32; 4]; 6],
+    model: [[f32; 4]; 4],
+    candidate_count: u32,
+    _padding: [u32; 3],
+}
+
+/// One object to test, and the draw to append when it passes.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct Candidate {
+    aabb_min: [f32; 3],
+    object: u32,
+    aabb_max: [f32; 3],
+    batch: u32,
+    /// First draw slot of the batch; it has one per candidate.
+    slot_base: u32,
+    index_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+}
+
+// Both have to match the WGSL structs byte for byte.
+const _: () = assert!(std::mem::size_of::<CullParams>() == 176);
+const _: () = assert!(std::mem::size_of::<Candidate>() == 48);
+const _: () = assert!(std::mem::offset_of!(Candidate, aabb_max) == 16);
+const _: () = assert!(ARGS_SIZE == 20);
+
+/// What the GPU culler made of a recent frame, read back a few frames late.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct GpuCullStats {
+    /// Draws the compute pass kept, summed over the batches.
+    pub visible: u32,
+    /// Objects the compute pass and the CPU culler disagreed on.
+    pub mismatches: u32,
+}
+
+enum Readback {
+    Free,
+    /// Copied into the readback buffer this frame, `batches` counts first;
+    /// `expected` is the CPU culler's verdict for the same frame.
+    Recorded {
+        batches: usize,
+        expected: Vec<bool>,
+    },
+    Mapping {
+        batches: usize,
+        expected: Vec<bool>,
+        receiver: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    },
+}
+
+/// Frustum culling in a compute pass. Candidates, one per object with the
+/// pooled mesh it's drawn with, are grouped into batches like
+/// `IndirectDraws`; the shader appends each visible one to its batch's
+/// slots and counts them. With `Features::MULTI_DRAW_INDIRECT_COUNT` those
+/// counts drive the draws directly. Without it every slot is drawn and the
+/// ones left over are cleared beforehand, so they draw no instances.
+///
+/// The clears, the compute pass and the render passes are recorded into one
+/// encoder in that order, which is all the synchronization wgpu needs.
+pub struct GpuCuller {
+    pipeline: wgpu::ComputePipeline,
+    bind_group_layout: Layout,
+    /// Rebuilt whenever one of the buffers it binds is replaced.
+    bind_group: Option<wgpu::BindGroup>,
+    transform_buffer: Option<wgpu::Buffer>,
+    params_buffer: wgpu::Buffer,
+    candidate_buffer: Option<wgpu::Buffer>,
+    draw_buffer: Option<wgpu::Buffer>,
+    count_buffer: Option<wgpu::Buffer>,
+    visibility_buffer: Option<wgpu::Buffer>,
+    readback_buffer: Option<wgpu::Buffer>,
+    readback: Readback,
+    candidates: Vec<Candidate>,
+    batches: Vec<IndirectBatch>,
+    object_count: u32,
+    count_draws: bool,
+    multi_draw: bool,
+    latest: Option<GpuCullStats>,
+}
+
+impl GpuCuller {
+    pub fn new(device: &wgpu::Device) -> Result<Self, ShaderError> {
+        let bind_group_layout: Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::COMPUTE);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, true);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, true);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, false);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, false);
+            builder.add_storage_buffer(wgpu::ShaderStages::COMPUTE, false);
+            bind_group_layout = builder.build("Cull Bind Group Layout");
+        }
+
+        let pipeline: wgpu::ComputePipeline;
+        {
+            let mut builder = compute::Builder::new(device);
+            builder.set_shader_module(SHADER_FILENAME, "cs_main");
+            builder.set_constant("workgroup_size", WORKGROUP_SIZE as f64);
+            builder.add_bind_group_layout(&bind_group_layout);
+            pipeline = builder.build_pipeline("Cull Pipeline")?;
+        }
+
+        let params = CullParams {
+            planes: [[0.0; 4]; 6],
+            model: [[0.0; 4]; 4],
+            candidate_count: 0,
+            _padding: [0; 3],
+        };
+        let params_buffer =
+            uniform::make_uniform_buffer(device, "Cull Params Buffer", bytemuck::bytes_of(&params));
+
+        Ok(GpuCuller {
+            pipeline: pipeline,
+            bind_group_layout: bind_group_layout,
+            bind_group: None,
+            transform_buffer: None,
+            params_buffer: params_buffer,
+            candidate_buffer: None,
+            draw_buffer: None,
+            count_buffer: None,
+            visibility_buffer: None,
+            readback_buffer: None,
+            readback: Readback::Free,
+            candidates: Vec::new(),
+            batches: Vec::new(),
+            object_count: 0,
+            count_draws: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT_COUNT),
+            multi_draw: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+            latest: None,
+        })
+    }
+
+    /// Whether the draw counts come straight from the compute pass.
+    pub fn count_draws(&self) -> bool {
+        self.count_draws
+    }
+
+    pub fn latest(&self) -> Option<GpuCullStats> {
+        self.latest
+    }
+
+    pub fn clear(&mut self) {
+        self.candidates.clear();
+        self.batches.clear();
+    }
+
+    /// Like `IndirectDraws::begin_batch`, with one draw slot per candidate.
+    pub fn begin_batch(&mut self, key: usize) {
+        if self.batches.last().is_some_and(|batch| batch.key == key) {
+            return;
+        }
+        let start = self.candidates.len() as u32;
+        self.batches.push(IndirectBatch {
+            key: key,
+            entries: start..start,
+        });
+    }
+
+    /// `object` is to be drawn with `mesh` if `bounds`, placed by its
+    /// transform, touches the frustum.
+    pub fn push(&mut self, mesh: PooledMesh, object: u32, bounds: &Aabb) {
+        let batch_index = self.batches.len() as u32;
+        let Some(batch) = self.batches.last_mut() else {
+            return;
+        };
+        self.candidates.push(Candidate {
+            aabb_min: *bounds.min.as_array(),
+            object: object,
+            aabb_max: *bounds.max.as_array(),
+            batch: batch_index - 1,
+            slot_base: batch.entries.start,
+            index_count: mesh.index_count,
+            first_index: mesh.first_index,
+            base_vertex: mesh.base_vertex,
+        });
+        batch.entries.end = self.candidates.len() as u32;
+    }
+
+    pub fn batches(&self) -> &[IndirectBatch] {
+        &self.batches
+    }
+
+    /// Queues this frame's candidates and parameters. `transform_buffer`
+    /// holds the `object_count` transforms the scene shader reads.
+    pub fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        uploader: &mut FrameUploader,
+        frustum: &Frustum,
+        model: &Mat4,
+        transform_buffer: &wgpu::Buffer,
+        object_count: u32,
+    ) {
+        let candidate_count = self.candidates.len() as u64;
+        let mut replaced = self.transform_buffer.as_ref() != Some(transform_buffer);
+        replaced |= mesh_builder::grow_buffer(
+            device,
+            &mut self.candidate_buffer,
+            wgpu::BufferUsages::STORAGE,
+            "Cull Candidate Buffer",
+            candidate_count * std::mem::size_of::<Candidate>() as u64,
+        );
+        replaced |= mesh_builder::grow_buffer(
+            device,
+            &mut self.draw_buffer,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_SRC,
+            "Culled Draw Buffer",
+            candidate_count * ARGS_SIZE,
+        );
+        replaced |= mesh_builder::grow_buffer(
+            device,
+            &mut self.count_buffer,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::INDIRECT
+                | wgpu::BufferUsages::COPY_SRC,
+            "Culled Draw Count Buffer",
+            self.batches.len() as u64 * COUNT_SIZE,
+        );
+        replaced |= mesh_builder::grow_buffer(
+            device,
+            &mut self.visibility_buffer,
+            wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+            "Cull Visibility Buffer",
+            object_count as u64 * COUNT_SIZE,
+        );
+        self.object_count = object_count;
+        if replaced {
+            self.transform_buffer = Some(transform_buffer.clone());
+            self.bind_group = self.create_bind_group(device);
+        }
+
+        let planes = frustum.planes().map(|plane| *plane.as_array());
+        let params = CullParams {
+            planes: planes,
+            model: uniform::mat4_to_array(model),
+            candidate_count: self.candidates.len() as u32,
+            _padding: [0; 3],
+        };
+        uploader.write(&self.params_buffer, 0, &[params]);
+        if let Some(candidate_buffer) = &self.candidate_buffer {
+            uploader.write(candidate_buffer, 0, &self.candidates);
+        }
+    }
+
+    fn create_bind_group(&self, device: &wgpu::Device) -> Option<wgpu::BindGroup> {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(&self.bind_group_layout);
+        builder.add_buffer(&self.params_buffer);
+        builder.add_buffer(self.candidate_buffer.as_ref()?);
+        builder.add_buffer(self.transform_buffer.as_ref()?);
+        builder.add_buffer(self.draw_buffer.as_ref()?);
+        builder.add_buffer(self.count_buffer.as_ref()?);
+        builder.add_buffer(self.visibility_buffer.as_ref()?);
+        Some(builder.build("Cull Bind Group"))
+    }
+
+    /// Records the cull. Goes after the uploads from `prepare` and before
+    /// the passes drawing with `draw`. `expected` is which objects the CPU
+    /// culler kept, to compare the GPU's verdict with once it's read back.
+    pub fn encode(
+        &mut self,
+        device: &wgpu::Device,
+        command_encoder: &mut wgpu::CommandEncoder,
+        expected: &[bool],
+    ) {
+        let (Some(bind_group), Some(draw_buffer), Some(count_buffer), Some(visibility_buffer)) = (
+            &self.bind_group,
+            &self.draw_buffer,
+            &self.count_buffer,
+            &self.visibility_buffer,
+        ) else {
+            return;
+        };
+        command_encoder.clear_buffer(count_buffer, 0, None);
+        command_encoder.clear_buffer(visibility_buffer, 0, None);
+        if !self.count_draws {
+            command_encoder.clear_buffer(draw_buffer, 0, None);
+        }
+        if !self.candidates.is_empty() {
+            let mut compute_pass =
+                command_encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+                    label: Some("Cull Compute Pass"),
+                    timestamp_writes: None,
+                });
+            compute_pass.set_pipeline(&self.pipeline);
+            compute_pass.set_bind_group(0, bind_group, &[]);
+            let workgroups = (self.candidates.len() as u32).div_ceil(WORKGROUP_SIZE);
+            compute_pass.dispatch_workgroups(workgroups, 1, 1);
+        }
+
+        // Read back whenever the last readback is done with the buffer.
+        if !matches!(self.readback, Readback::Free) {
+            return;
+        }
+        let counts_size = self.batches.len() as u64 * COUNT_SIZE;
+        let visibility_size = self.object_count as u64 * COUNT_SIZE;
+        mesh_builder::grow_buffer(
+            device,
+            &mut self.readback_buffer,
+            wgpu::BufferUsages::MAP_READ,
+            "Cull Readback Buffer",
+            counts_size + visibility_size,
+        );
+        let Some(readback_buffer) = &self.readback_buffer else {
+            return;
+        };
+        command_encoder.copy_buffer_to_buffer(count_buffer, 0, readback_buffer, 0, counts_size);
+        command_encoder.copy_buffer_to_buffer(
+            visibility_buffer,
+            0,
+            readback_buffer,
+            counts_size,
+            visibility_size,
+        );
+        self.readback = Readback::Recorded {
+            batches: self.batches.len(),
+            expected: expected.to_vec(),
+        };
+    }
+
+    /// Records the draws of `batch` with whatever pipeline, bind groups and
+    /// pool buffers are set. Returns how many draw calls that took.
+    pub fn draw(&self, renderpass: &mut wgpu::RenderPass, index: usize) -> u32 {
+        let (Some(draw_buffer), Some(count_buffer), Some(batch)) = (
+            &self.draw_buffer,
+            &self.count_buffer,
+            self.batches.get(index),
+        ) else {
+            return 0;
+        };
+        let entries = &batch.entries;
+        if entries.is_empty() {
+            return 0;
+        }
+        let offset = entries.start as u64 * ARGS_SIZE;
+        match (self.count_draws, self.multi_draw) {
+            (true, _) => {
+                renderpass.multi_draw_indexed_indirect_count(
+                    draw_buffer,
+                    offset,
+                    count_buffer,
+                    index as u64 * COUNT_SIZE,
+                    entries.len() as u32,
+                );
+                1
+            }
+            (false, true) => {
+                renderpass.multi_draw_indexed_indirect(draw_buffer, offset, entries.len() as u32);
+                1
+            }
+            (false, false) => {
+                for slot in entries.clone() {
+                    renderpass.draw_indexed_indirect(draw_buffer, slot as u64 * ARGS_SIZE);
+                }
+                entries.len() as u32
+            }
+        }
+    }
+
+    /// Starts mapping the readback recorded by `encode`. Call once its
+    /// command buffer has been submitted.
+    pub fn frame_submitted(&mut self) {
+        let Readback::Recorded { batches, expected } =
+            std::mem::replace(&mut self.readback, Readback::Free)
+        else {
+            return;
+        };
+        let Some(readback_buffer) = &self.readback_buffer else {
+            return;
+        };
+        let (sender, receiver) = mpsc::channel();
+        readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Readback::Mapping {
+            batches: batches,
+            expected: expected,
+            receiver: receiver,
+        };
+    }
+
+    /// Picks up a finished readback, if there is one, into `latest`.
+    pub fn poll(&mut self, device: &wgpu::Device) {
+        let _ = device.poll(wgpu::Maintain::Poll);
+        let Readback::Mapping {
+            batches,
+            expected,
+            receiver,
+        } = &self.readback
+        else {
+            return;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.readback = Readback::Free;
+                return;
+            }
+        };
+        let Some(readback_buffer) = &self.readback_buffer else {
+            return;
+        };
+        if result.is_ok() {
+            let stats = {
+                let bytes = readback_buffer.slice(..).get_mapped_range();
+                let words: &[u32] = bytemuck::cast_slice(&bytes);
+                let (counts, visibility) = words.split_at((*batches).min(words.len()));
+                GpuCullStats {
+                    visible: counts.iter().sum(),
+                    mismatches: expected
+                        .iter()
+                        .zip(visibility)
+                        .filter(|&(&expected, &visible)| expected != (visible != 0))
+                        .count() as u32,
+                }
+            };
+            self.latest = Some(stats);
+        }
+        readback_buffer.unmap();
+        self.readback = Readback::Free;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::camera::Camera;
+    use crate::renderer_backend::instance;
+    use crate::renderer_backend::uniform::identity;
+    use crate::renderer_backend::validation;
+    use crate::test_support;
+    use wgpu::util::DeviceExt;
+
+    /// Copies the first `size` bytes of `buffer` out and waits for them.
+    fn read_words(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        buffer: &wgpu::Buffer,
+        size: u64,
+    ) -> Vec<u32> {
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Test Readback Buffer"),
+            size: size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let mut command_encoder = device.create_command_encoder(&Default::default());
+        command_encoder.copy_buffer_to_buffer(buffer, 0, &readback, 0, size);
+        queue.submit([command_encoder.finish()]);
+        let (sender, receiver) = mpsc::channel();
+        readback
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        let _ = device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .expect("map_async callback dropped without running")
+            .unwrap_or_else(|error| panic!("{}", error));
+        let words = bytemuck::cast_slice(&readback.slice(..).get_mapped_range()).to_vec();
+        words
+    }
+
+    /// Two batches of two unit cubes, one of each in front of the camera
+    /// and one behind or off to the side: each batch should get the draw of
+    /// its visible cube in its first slot, and nothing else.
+    #[test]
+    fn draws_only_what_is_in_the_frustum() {
+        let Some(renderer) = test_support::headless_renderer(64, 64) else {
+            return;
+        };
+        if !renderer.particle_stats().on_gpu {
+            println!("skipped, no compute shaders");
+            return;
+        }
+        let (device, queue) = (&renderer.device, &renderer.queue);
+        let mut camera = Camera::new(1.0);
+        camera.position = glm::vec3(0.0, 0.0, 5.0);
+        camera.target = glm::vec3(0.0, 0.0, 0.0);
+        let frustum = Frustum::from_matrix(&camera.build_view_projection());
+        let bounds = Aabb {
+            min: glm::vec3(-0.5, -0.5, -0.5),
+            max: glm::vec3(0.5, 0.5, 0.5),
+        };
+        let positions = [
+            glm::vec3(0.0, 0.0, 0.0),
+            glm::vec3(0.0, 0.0, 20.0),
+            glm::vec3(100.0, 0.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+        ];
+        let transforms = positions.map(|position| instance::transform(position, 0.0, 1.0));
+        let expected = transforms.map(|transform| frustum.intersects(&bounds, &transform));
+        assert_eq!(expected, [true, false, false, true]);
+        let transform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Test Transform Buffer"),
+            contents: bytemuck::cast_slice(&transforms.map(|m| uniform::mat4_to_array(&m))),
+            usage: wgpu::BufferUsages::STORAGE,
+        });
+
+        let mut culler = GpuCuller::new(device).unwrap_or_else(|error| panic!("{}", error));
+        let mut uploader = FrameUploader::new();
+        for object in 0..4 {
+            culler.begin_batch(object as usize / 2);
+            let mesh = PooledMesh {
+                base_vertex: 10 * object as i32,
+                first_index: 100 * object,
+                index_count: 6 * (object + 1),
+            };
+            culler.push(mesh, object, &bounds);
+        }
+        culler.prepare(
+            device,
+            &mut uploader,
+            &frustum,
+            &identity(),
+            &transform_buffer,
+            4,
+        );
+        validation::with_validation(device, "gpu cull test", || {
+            let mut command_encoder = device.create_command_encoder(&Default::default());
+            uploader.record(device, &mut command_encoder);
+            culler.encode(device, &mut command_encoder, &expected);
+            queue.submit([command_encoder.finish()]);
+        })
+        .unwrap_or_else(|error| panic!("{}", error));
+        uploader.recall();
+        culler.frame_submitted();
+
+        let count_buffer = culler.count_buffer.as_ref().unwrap();
+        assert_eq!(
+            read_words(device, queue, count_buffer, 2 * COUNT_SIZE),
+            [1, 1]
+        );
+        let draw_buffer = culler.draw_buffer.as_ref().unwrap();
+        let args = read_words(device, queue, draw_buffer, 4 * ARGS_SIZE);
+        let slots: Vec<&[u32]> = args.chunks_exact(5).collect();
+        assert_eq!(slots[0], [6, 1, 0, 0, 0]);
+        assert_eq!(slots[2], [24, 1, 300, 30, 3]);
+        if !culler.count_draws() {
+            // Every slot gets drawn, so the spare ones must draw nothing.
+            assert_eq!(slots[1][1], 0);
+            assert_eq!(slots[3][1], 0);
+        }
+
+        culler.poll(device);
+        let stats = culler.latest().expect("the readback hasn't finished");
+        assert_eq!((stats.visible, stats.mismatches), (2, 0));
+    }
+}
@@ -0,0 +1,235 @@
+use std::sync::mpsc;
+
+/// Readback buffers in rotation. Results are read this many frames after
+/// they were recorded, by which point the GPU is long done with them, so
+/// mapping never stalls the frame.
+const READBACK_FRAMES: usize = 3;
+
+const RENDER_BEGIN: u32 = 0;
+const RENDER_END: u32 = 1;
+const COMPUTE_BEGIN: u32 = 2;
+const COMPUTE_END: u32 = 3;
+const QUERY_COUNT: u32 = 4;
+const RESOLVE_SIZE: u64 = QUERY_COUNT as u64 * std::mem::size_of::<u64>() as u64;
+
+/// How long the GPU spent in the timed passes of a recent frame.
+#[derive(Clone, Copy, Debug)]
+pub struct GpuTimings {
+    /// Which frame was timed, counting the `begin_frame` calls from 0.
+    pub frame: u64,
+    pub render_ms: f32,
+    /// `None` when the frame had no compute pass.
+    pub compute_ms: Option<f32>,
+}
+
+enum SlotState {
+    Free,
+    Recorded {
+        compute: bool,
+        frame: u64,
+    },
+    Mapping {
+        compute: bool,
+        frame: u64,
+        receiver: mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>,
+    },
+}
+
+struct Slot {
+    buffer: wgpu::Buffer,
+    state: SlotState,
+}
+
+/// Times the main render pass and the particle compute pass with timestamp
+/// queries. Only exists when the device has `Features::TIMESTAMP_QUERY`.
+pub struct GpuTimer {
+    query_set: wgpu::QuerySet,
+    resolve_buffer: wgpu::Buffer,
+    slots: Vec<Slot>,
+    next_slot: usize,
+    current: Option<usize>,
+    /// Frames started so far.
+    frames: u64,
+    period: f32,
+    latest: Option<GpuTimings>,
+}
+
+impl GpuTimer {
+    /// Returns `None` when timestamp queries aren't supported.
+    pub fn new(device: &wgpu::Device, queue: &wgpu::Queue) -> Option<Self> {
+        if !device.features().contains(wgpu::Features::TIMESTAMP_QUERY) {
+            return None;
+        }
+
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: Some("Timestamp Query Set"),
+            ty: wgpu::QueryType::Timestamp,
+            count: QUERY_COUNT,
+        });
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Timestamp Resolve Buffer"),
+            size: RESOLVE_SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let slots = (0..READBACK_FRAMES)
+            .map(|_| Slot {
+                buffer: device.create_buffer(&wgpu::BufferDescriptor {
+                    label: Some("Timestamp Readback Buffer"),
+                    size: RESOLVE_SIZE,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                }),
+                state: SlotState::Free,
+            })
+            .collect();
+
+        Some(GpuTimer {
+            query_set: query_set,
+            resolve_buffer: resolve_buffer,
+            slots: slots,
+            next_slot: 0,
+            current: None,
+            period: queue.get_timestamp_period(),
+            frames: 0,
+            latest: None,
+        })
+    }
+
+    /// The most recent timings that have made it back from the GPU.
+    pub fn latest(&self) -> Option<GpuTimings> {
+        self.latest
+    }
+
+    /// Collects any finished readbacks and picks the buffer this frame's
+    /// timestamps go to. The frame goes untimed if that buffer is still
+    /// being mapped.
+    pub fn begin_frame(&mut self, device: &wgpu::Device) {
+        let _ = device.poll(wgpu::Maintain::Poll);
+        for i in 0..READBACK_FRAMES {
+            // Oldest first, so `latest` ends up with the newest result.
+            let index = (self.next_slot + i) % READBACK_FRAMES;
+            if let Some(timings) = self.read_slot(index) {
+                self.latest = Some(timings);
+            }
+        }
+
+        self.frames += 1;
+        let index = self.next_slot;
+        self.current = match self.slots[index].state {
+            SlotState::Mapping { .. } => None,
+            _ => Some(index),
+        };
+        if self.current.is_some() {
+            self.next_slot = (index + 1) % READBACK_FRAMES;
+        }
+    }
+
+    fn read_slot(&mut self, index: usize) -> Option<GpuTimings> {
+        let slot = &mut self.slots[index];
+        let (compute, frame, result) = match &slot.state {
+            SlotState::Mapping {
+                compute,
+                frame,
+                receiver,
+            } => match receiver.try_recv() {
+                Ok(result) => (*compute, *frame, result),
+                Err(mpsc::TryRecvError::Empty) => return None,
+                Err(mpsc::TryRecvError::Disconnected) => {
+                    slot.state = SlotState::Free;
+                    return None;
+                }
+            },
+            _ => return None,
+        };
+        slot.state = SlotState::Free;
+        if result.is_err() {
+            return None;
+        }
+
+        let timings = {
+            let bytes = slot.buffer.slice(..).get_mapped_range();
+            let ticks: &[u64] = bytemuck::cast_slice(&bytes);
+            let to_ms = |begin: u32, end: u32| {
+                let elapsed = ticks[end as usize].wrapping_sub(ticks[begin as usize]);
+                elapsed as f32 * self.period / 1_000_000.0
+            };
+            GpuTimings {
+                frame: frame,
+                render_ms: to_ms(RENDER_BEGIN, RENDER_END),
+                compute_ms: compute.then(|| to_ms(COMPUTE_BEGIN, COMPUTE_END)),
+            }
+        };
+        slot.buffer.unmap();
+        Some(timings)
+    }
+
+    pub fn render_pass_writes(&self) -> Option<wgpu::RenderPassTimestampWrites<'_>> {
+        self.current?;
+        Some(wgpu::RenderPassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(RENDER_BEGIN),
+            end_of_pass_write_index: Some(RENDER_END),
+        })
+    }
+
+    pub fn compute_pass_writes(&self) -> Option<wgpu::ComputePassTimestampWrites<'_>> {
+        self.current?;
+        Some(wgpu::ComputePassTimestampWrites {
+            query_set: &self.query_set,
+            beginning_of_pass_write_index: Some(COMPUTE_BEGIN),
+            end_of_pass_write_index: Some(COMPUTE_END),
+        })
+    }
+
+    /// Resolves this frame's timestamps into its readback buffer. Goes after
+    /// the timed passes; `compute` says whether the compute pass ran.
+    pub fn resolve(&mut self, command_encoder: &mut wgpu::CommandEncoder, compute: bool) {
+        let Some(index) = self.current.take() else {
+            return;
+        };
+        // Only resolve queries that were written this frame.
+        let end = match compute {
+            true => COMPUTE_END + 1,
+            false => RENDER_END + 1,
+        };
+        command_encoder.resolve_query_set(
+            &self.query_set,
+            RENDER_BEGIN..end,
+            &self.resolve_buffer,
+            0,
+        );
+        command_encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.slots[index].buffer,
+            0,
+            end as u64 * std::mem::size_of::<u64>() as u64,
+        );
+        self.slots[index].state = SlotState::Recorded {
+            compute: compute,
+            frame: self.frames - 1,
+        };
+    }
+
+    /// Starts mapping the readback buffer resolved into this frame. Call
+    /// once the command buffer from `resolve` has been submitted.
+    pub fn frame_submitted(&mut self) {
+        for slot in &mut self.slots {
+            let SlotState::Recorded { compute, frame } = slot.state else {
+                continue;
+            };
+            let (sender, receiver) = mpsc::channel();
+            slot.buffer
+                .slice(..)
+                .map_async(wgpu::MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+            slot.state = SlotState::Mapping {
+                compute: compute,
+                frame: frame,
+                receiver: receiver,
+            };
+        }
+    }
+}
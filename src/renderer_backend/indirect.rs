@@ -0,0 +1,130 @@
+use std::ops::Range;
+
+use wgpu::util::DrawIndexedIndirectArgs;
+
+use super::frame_uploader::FrameUploader;
+use super::mesh_builder;
+use super::mesh_pool::PooledMesh;
+
+const ARGS_SIZE: u64 = std::mem::size_of::<DrawIndexedIndirectArgs>() as u64;
+
+/// A run of entries drawn with the same bind groups, tagged with whatever
+/// the caller needs to set those up again.
+pub struct IndirectBatch {
+    pub key: usize,
+    pub entries: Range<u32>,
+}
+
+/// Indexed draws of `MeshPool` meshes, built on the CPU each frame and read
+/// by the GPU from an indirect buffer. With `Features::MULTI_DRAW_INDIRECT`
+/// a batch is a single draw call; without it, one call per entry.
+pub struct IndirectDraws {
+    buffer: Option<wgpu::Buffer>,
+    args: Vec<DrawIndexedIndirectArgs>,
+    batches: Vec<IndirectBatch>,
+    multi_draw: bool,
+}
+
+impl IndirectDraws {
+    pub fn new(device: &wgpu::Device) -> Self {
+        IndirectDraws {
+            buffer: None,
+            args: Vec::new(),
+            batches: Vec::new(),
+            multi_draw: device
+                .features()
+                .contains(wgpu::Features::MULTI_DRAW_INDIRECT),
+        }
+    }
+
+    pub fn multi_draw(&self) -> bool {
+        self.multi_draw
+    }
+
+    pub fn clear(&mut self) {
+        self.args.clear();
+        self.batches.clear();
+    }
+
+    /// Entries pushed from now on go into a new batch tagged `key`, unless
+    /// the last batch already has that key.
+    pub fn begin_batch(&mut self, key: usize) {
+        if self.batches.last().is_some_and(|batch| batch.key == key) {
+            return;
+        }
+        let start = self.args.len() as u32;
+        self.batches.push(IndirectBatch {
+            key: key,
+            entries: start..start,
+        });
+    }
+
+    /// Draws `mesh` for `instances`, which the scene shader reads
+    /// transforms with.
+    pub fn push(&mut self, mesh: PooledMesh, instances: Range<u32>) {
+        if instances.is_empty() {
+            return;
+        }
+        self.args.push(DrawIndexedIndirectArgs {
+            index_count: mesh.index_count,
+            instance_count: instances.len() as u32,
+            first_index: mesh.first_index,
+            base_vertex: mesh.base_vertex,
+            first_instance: instances.start,
+        });
+        if let Some(batch) = self.batches.last_mut() {
+            batch.entries.end = self.args.len() as u32;
+        }
+    }
+
+    pub fn batches(&self) -> &[IndirectBatch] {
+        &self.batches
+    }
+
+    /// Writes the entries to the indirect buffer, growing it first if they
+    /// don't fit.
+    pub fn upload(&mut self, device: &wgpu::Device, uploader: &mut FrameUploader) {
+        mesh_builder::grow_buffer(
+            device,
+            &mut self.buffer,
+            wgpu::BufferUsages::INDIRECT,
+            "Indirect Draw Buffer",
+            self.args.len() as u64 * ARGS_SIZE,
+        );
+        let bytes: Vec<u8> = self
+            .args
+            .iter()
+            .flat_map(|args| args.as_bytes().iter().copied())
+            .collect();
+        if let Some(buffer) = &self.buffer {
+            uploader.write(buffer, 0, &bytes);
+        }
+    }
+
+    /// Records `entries` with whatever pipeline, bind groups and pool
+    /// buffers are set. Returns how many draw calls that took.
+    pub fn draw(&self, renderpass: &mut wgpu::RenderPass, entries: &Range<u32>) -> u32 {
+        let Some(buffer) = &self.buffer else {
+            return 0;
+        };
+        if entries.is_empty() {
+            return 0;
+        }
+        match self.multi_draw {
+            true => {
+                renderpass.multi_draw_indexed_indirect(
+                    buffer,
+                    entries.start as u64 * ARGS_SIZE,
+                    entries.len() as u32,
+                );
+                1
+            }
+            false => {
+                for entry in entries.clone() {
+                    renderpass.draw_indexed_indirect(buffer, entry as u64 * ARGS_SIZE);
+                }
+                entries.len() as u32
+            }
+        }
+    }
+}
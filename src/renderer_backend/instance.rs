@@ -0,0 +1,33 @@
+use glm::*;
+
+use super::uniform::{identity, mat4_to_array};
+
+/// One object's model matrix as laid out in a `TransformBuffer`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Instance {
+    model: [[f32; 4]; 4],
+}
+
+// Read as `array<mat4x4<f32>>` in the shaders, which has a 64 byte stride.
+const _: () = assert!(std::mem::size_of::<Instance>() == 64);
+
+/// Translation, rotation about Y and a uniform scale, in that order.
+pub fn transform(position: Vec3, rotation_y: f32, scale: f32) -> Mat4 {
+    let mut model = ext::translate(&identity(), position);
+    model = ext::rotate(&model, rotation_y, vec3(0.0, 1.0, 0.0));
+    ext::scale(&model, vec3(scale, scale, scale))
+}
+
+impl Instance {
+    pub fn from_matrix(model: &Mat4) -> Self {
+        Instance {
+            model: mat4_to_array(model),
+        }
+    }
+
+    pub fn matrix(&self) -> Mat4 {
+        let [c0, c1, c2, c3] = self.model.map(|column| *Vec4::from_array(&column));
+        Mat4::new(c0, c1, c2, c3)
+    }
+}
@@ -1,11 +1,20 @@
-use std::{env::current_dir, fs};
-
-use image::GenericImageView;
+use std::env::current_dir;
 
 use super::bind_group;
+use super::bind_group_layout::Layout;
+use super::texture::{Texture, TextureError};
+use super::uniform;
 
+#[derive(Clone)]
 pub struct Material {
     pub bind_group: wgpu::BindGroup,
+    view: wgpu::TextureView,
+    normal_view: wgpu::TextureView,
+    /// Multiplies the vertex colors, white unless `set_color` changed it.
+    color: [f32; 4],
+    color_buffer: wgpu::Buffer,
+    label: String,
+    transparent: bool,
 }
 
 impl Material {
@@ -13,71 +22,139 @@ impl Material {
         filename: &str,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        layout: &wgpu::BindGroupLayout,
-    ) -> Material {
-        let mut filepath = current_dir().unwrap();
+        layout: &Layout,
+        sampler: &wgpu::Sampler,
+    ) -> Result<Material, TextureError> {
+        let texture = Texture::from_file(device, queue, &Self::path(filename), true)?;
+        let normal_map = Texture::flat_normal(device, queue);
+        Ok(Self::from_texture(
+            device,
+            &texture,
+            &normal_map,
+            layout,
+            sampler,
+            filename,
+        ))
+    }
+
+    fn path(filename: &str) -> std::path::PathBuf {
+        let mut filepath = current_dir().unwrap_or_default();
         filepath.push(filename);
-        let filepath = filepath.into_os_string().into_string().unwrap();
-        let bytes = fs::read(filepath).unwrap();
-        let loaded_image = image::load_from_memory(&bytes).unwrap();
-        let converted = loaded_image.to_rgba8();
-        let size = loaded_image.dimensions();
+        filepath
+    }
+
+    /// Samples `texture` and `normal_map` with `sampler` rather than the
+    /// textures' own. `Texture::flat_normal` leaves the normals as they are.
+    pub fn from_texture(
+        device: &wgpu::Device,
+        texture: &Texture,
+        normal_map: &Texture,
+        layout: &Layout,
+        sampler: &wgpu::Sampler,
+        label: &str,
+    ) -> Material {
+        let color = [1.0; 4];
+        let color_buffer = Self::make_color_buffer(device, color, label);
+        Material {
+            bind_group: Self::build_bind_group(
+                device,
+                &texture.view,
+                &normal_map.view,
+                &color_buffer,
+                layout,
+                sampler,
+                label,
+            ),
+            view: texture.view.clone(),
+            normal_view: normal_map.view.clone(),
+            color: color,
+            color_buffer: color_buffer,
+            label: label.to_string(),
+            transparent: false,
+        }
+    }
 
-        let texture_size = wgpu::Extent3d {
-            width: size.0,
-            height: size.1,
-            depth_or_array_layers: 1,
-        };
+    /// Loads a tangent-space normal map, green up, for the lighting to bend
+    /// the normals by.
+    pub fn load_normal_map(
+        &mut self,
+        filename: &str,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &Layout,
+        sampler: &wgpu::Sampler,
+    ) -> Result<(), TextureError> {
+        let normal_map = Texture::from_file(device, queue, &Self::path(filename), true)?;
+        self.normal_view = normal_map.view.clone();
+        self.set_sampler(device, layout, sampler);
+        Ok(())
+    }
 
-        let texture_descriptor = wgpu::TextureDescriptor {
-            label: Some(filename),
-            mip_level_count: 1,
-            dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8Unorm,
-            size: texture_size,
-            sample_count: 1,
-            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
-            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
-        };
+    /// Transparent materials are blended over what's behind them by their
+    /// alpha, after everything opaque, and don't write depth.
+    pub fn transparent(&self) -> bool {
+        self.transparent
+    }
 
-        let texture = device.create_texture(&texture_descriptor);
+    pub fn set_transparent(&mut self, transparent: bool) {
+        self.transparent = transparent;
+    }
 
-        queue.write_texture(
-            wgpu::TexelCopyTextureInfoBase {
-                texture: &texture,
-                mip_level: 0,
-                origin: wgpu::Origin3d::ZERO,
-                aspect: wgpu::TextureAspect::All,
-            },
-            &converted,
-            wgpu::TexelCopyBufferLayout {
-                offset: 0,
-                bytes_per_row: Some(size.0 * 4),
-                rows_per_image: Some(size.1),
-            },
-            texture_size,
-        );
+    pub fn color(&self) -> [f32; 4] {
+        self.color
+    }
 
-        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    /// Gives the material a buffer of its own holding `color`, so clones
+    /// made before keep the color they had.
+    pub fn set_color(
+        &mut self,
+        device: &wgpu::Device,
+        layout: &Layout,
+        sampler: &wgpu::Sampler,
+        color: [f32; 4],
+    ) {
+        self.color = color;
+        self.color_buffer = Self::make_color_buffer(device, color, &self.label);
+        self.set_sampler(device, layout, sampler);
+    }
 
-        let sampler_descriptor = wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::Repeat,
-            address_mode_v: wgpu::AddressMode::Repeat,
-            address_mode_w: wgpu::AddressMode::Repeat,
-            min_filter: wgpu::FilterMode::Nearest,
-            mag_filter: wgpu::FilterMode::Linear,
-            ..Default::default()
-        };
+    /// Rebuilds the bind group to sample with `sampler` from now on.
+    pub fn set_sampler(&mut self, device: &wgpu::Device, layout: &Layout, sampler: &wgpu::Sampler) {
+        self.bind_group = Self::build_bind_group(
+            device,
+            &self.view,
+            &self.normal_view,
+            &self.color_buffer,
+            layout,
+            sampler,
+            &self.label,
+        );
+    }
 
-        let sampler = device.create_sampler(&sampler_descriptor);
+    fn make_color_buffer(device: &wgpu::Device, color: [f32; 4], label: &str) -> wgpu::Buffer {
+        uniform::make_uniform_buffer(
+            device,
+            &format!("{} Color Buffer", label),
+            bytemuck::cast_slice(&color),
+        )
+    }
 
+    /// The base color texture and sampler, then the normal map and sampler,
+    /// then the color.
+    fn build_bind_group(
+        device: &wgpu::Device,
+        view: &wgpu::TextureView,
+        normal_view: &wgpu::TextureView,
+        color_buffer: &wgpu::Buffer,
+        layout: &Layout,
+        sampler: &wgpu::Sampler,
+        label: &str,
+    ) -> wgpu::BindGroup {
         let mut builder = bind_group::Builder::new(device);
         builder.set_layout(layout);
-        builder.add_material(&view, &sampler);
-        let bind_group = builder.build(&filename);
-
-        Material {
-            bind_group: bind_group,
-        }
+        builder.add_material(view, sampler);
+        builder.add_material(normal_view, sampler);
+        builder.add_buffer(color_buffer);
+        builder.build(label)
     }
 }
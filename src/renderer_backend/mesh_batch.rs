@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+use glm::*;
+
+use super::frustum::Aabb;
+use super::mesh_builder::{self, Mesh, MeshData};
+
+/// Where one merged mesh's triangles sit in a `MeshBatch`.
+#[derive(Clone, Debug)]
+pub struct BatchItem {
+    pub indices: Range<u32>,
+    /// Bounds in world space, since the vertices already are.
+    pub bounds: Aabb,
+}
+
+/// Meshes that never move, transformed into world space on the CPU and
+/// merged into one vertex buffer and one index buffer, so the whole lot
+/// draws without rebinding buffers. Every item's indices stay together, so
+/// items can still be drawn or skipped one at a time.
+pub struct MeshBatch {
+    mesh: Mesh,
+    items: Vec<BatchItem>,
+}
+
+/// Whether `model` mirrors what it transforms, which turns triangles'
+/// winding around.
+fn mirrors(model: &Mat4) -> bool {
+    let [x, y, z] = [0, 1, 2].map(|i| model[i].truncate(3));
+    dot(x, cross(y, z)) < 0.0
+}
+
+impl MeshBatch {
+    /// Merges triangle list `items`, each placed by its model matrix. An
+    /// item's id is its position in `items`. Indices are rebased onto where
+    /// the item's vertices land; the batch uses 16-bit indices if all of
+    /// the rebased ones still fit. Mirrored items have their triangles
+    /// flipped back to counter-clockwise.
+    pub fn build(device: &wgpu::Device, items: &[(&MeshData, Mat4)]) -> Self {
+        let vertex_count: usize = items.iter().map(|(data, _)| data.vertices.len()).sum();
+        let index_count: usize = items.iter().map(|(data, _)| data.indices.len()).sum();
+        let mut vertices = Vec::with_capacity(vertex_count);
+        let mut indices = Vec::with_capacity(index_count);
+        let mut batch_items = Vec::with_capacity(items.len());
+
+        for (data, model) in items {
+            let base_vertex = vertices.len();
+            let first_index = indices.len() as u32;
+            vertices.extend(data.vertices.iter().map(|vertex| vertex.transformed(model)));
+
+            let mirrored = mirrors(model);
+            for triangle in data.indices.chunks_exact(3) {
+                let [a, b, c] =
+                    [triangle[0], triangle[1], triangle[2]].map(|index| base_vertex as u32 + index);
+                match mirrored {
+                    true => indices.extend([a, c, b]),
+                    false => indices.extend([a, b, c]),
+                }
+            }
+
+            batch_items.push(BatchItem {
+                indices: first_index..indices.len() as u32,
+                bounds: Aabb::from_points(vertices[base_vertex..].iter().map(|v| v.position())),
+            });
+        }
+
+        MeshBatch {
+            mesh: mesh_builder::make_mesh_u32(device, "Mesh Batch", &vertices, &indices),
+            items: batch_items,
+        }
+    }
+
+    pub fn items(&self) -> &[BatchItem] {
+        &self.items
+    }
+
+    /// The merged mesh, with its indices kept on the CPU too.
+    pub fn mesh(&self) -> &Mesh {
+        &self.mesh
+    }
+
+    pub fn draw_all<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        self.mesh.draw(pass);
+    }
+
+    /// Draws the item with id `item` alone.
+    pub fn draw_range<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, item: usize) {
+        let Some(index_buffer) = &self.mesh.index_buffer else {
+            return;
+        };
+        pass.set_vertex_buffer(0, self.mesh.vertex_buffer.slice(..));
+        pass.set_index_buffer(index_buffer.slice(..), self.mesh.index_format);
+        pass.draw_indexed(self.items[item].indices.clone(), 0, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::mesh_builder::Vertex;
+    use crate::renderer_backend::uniform::identity;
+    use crate::test_support::{self, assert_mesh};
+
+    /// Checks every item of `batch` reaches only its own vertices, and that
+    /// its triangles still face the way its normals point.
+    fn assert_items(batch: &MeshBatch, items: &[(&MeshData, Mat4)]) {
+        let mesh = batch.mesh();
+        let vertices: Vec<Vertex> = items
+            .iter()
+            .flat_map(|(data, model)| data.vertices.iter().map(|vertex| vertex.transformed(model)))
+            .collect();
+        assert_mesh(mesh, vertices.len() as u32, mesh.indices().len() as u32);
+        assert_eq!(batch.items().len(), items.len());
+
+        let mut base_vertex = 0;
+        let mut next_index = 0;
+        for (id, ((data, _), item)) in items.iter().zip(batch.items()).enumerate() {
+            assert_eq!(
+                item.indices,
+                next_index..next_index + data.indices.len() as u32,
+                "item {}",
+                id
+            );
+            let own_vertices = base_vertex..base_vertex + data.vertices.len() as u32;
+            let indices = &mesh.indices()[item.indices.start as usize..item.indices.end as usize];
+            for triangle in indices.chunks_exact(3) {
+                if let Some(index) = triangle.iter().find(|index| !own_vertices.contains(index)) {
+                    panic!(
+                        "item {} reaches vertex {}, outside its {:?}",
+                        id, index, own_vertices
+                    );
+                }
+                let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i] as usize].position());
+                let face = cross(b - a, c - a);
+                assert!(
+                    dot(face, vertices[triangle[0] as usize].normal()) >= 0.0,
+                    "item {} has a triangle wound backwards",
+                    id
+                );
+            }
+            base_vertex = own_vertices.end;
+            next_index = item.indices.end;
+        }
+    }
+
+    #[test]
+    fn items_keep_their_own_vertices() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let cube = mesh_builder::cube_data(1.0);
+        let sphere = mesh_builder::uv_sphere_data(1.0, 8, 12);
+        // Moved aside and mirrored, with a non-uniform scale for the normals.
+        let mirrored = ext::translate(&identity(), vec3(3.0, 0.0, 0.0));
+        let mirrored = ext::scale(&mirrored, vec3(-2.0, 1.0, 0.5));
+        let items = [(&cube, identity()), (&sphere, mirrored), (&cube, mirrored)];
+        assert_items(&MeshBatch::build(&device, &items), &items);
+    }
+
+    /// Two planes together need more vertices than 16-bit indices reach.
+    #[test]
+    fn large_batches_widen_their_indices() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let plane = mesh_builder::plane_data(1.0, 1.0, 181);
+        let mirrored = ext::translate(&identity(), vec3(3.0, 0.0, 0.0));
+        let mirrored = ext::scale(&mirrored, vec3(-2.0, 1.0, 0.5));
+        let items = [(&plane, identity()), (&plane, mirrored)];
+        let batch = MeshBatch::build(&device, &items);
+        assert_eq!(batch.mesh().index_format, wgpu::IndexFormat::Uint32);
+        assert_items(&batch, &items);
+    }
+}
@@ -1,100 +1,1333 @@
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+
 use glm::*;
 use wgpu::util::DeviceExt;
 
+use super::assets;
+use super::bind_group_layout::Layout;
+use super::color::ColorSpace;
+use super::frustum::{Aabb, BoundingSphere};
+use super::gltf_loader::{self, GltfError};
+use super::material::Material;
+use super::obj_loader::{self, ObjError};
+use super::texture::Texture;
+use super::texture_atlas::AtlasRegion;
+use super::validation;
+
 #[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex {
-    position: Vec3,
-    color: Vec3,
+    position: [f32; 3],
+    /// Linear RGB and opacity.
+    color: [f32; 4],
+    uv: [f32; 2],
+    normal: [f32; 3],
+    /// Along the surface where u grows, with w the sign that turns
+    /// `cross(normal, tangent)` toward where v shrinks, up the image.
+    /// Normal maps are read in this frame. Zero until `compute_tangents`.
+    tangent: [f32; 4],
 }
 
+/// Vertex and index buffers can be larger than the counts they are drawn
+/// with, so `update_vertices` and `update_indices` only reallocate when the
+/// new data doesn't fit.
 pub struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer
+    pub index_buffer: Option<wgpu::Buffer>,
+    pub vertex_count: u32,
+    pub index_count: u32,
+    pub index_format: wgpu::IndexFormat,
+    /// Bounds of the vertex positions, for frustum culling, picking and
+    /// framing with `Camera::frame`.
+    pub bounds: Aabb,
+    pub bounding_sphere: BoundingSphere,
+    topology: wgpu::PrimitiveTopology,
+    label: String,
+    id: u64,
+    revision: u64,
+    /// The indices widened to 32 bits, or empty when not indexed, for
+    /// packing into a `MeshPool`.
+    indices: Vec<u32>,
+    /// The vertex positions, for ray casting.
+    positions: Vec<Vec3>,
+}
+
+static NEXT_MESH_ID: AtomicU64 = AtomicU64::new(0);
+
+/// Size in bytes of a buffer replacing one of `capacity` bytes that `needed`
+/// bytes no longer fit: 1.5 times larger, or just large enough if that's
+/// still too small, and a whole number of copy blocks.
+pub const fn grown_capacity(capacity: u64, needed: u64) -> u64 {
+    let grown = capacity + capacity / 2;
+    let size = if grown > needed { grown } else { needed };
+    size.div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT
+}
+
+/// Replaces `buffer` with a larger one, contents not kept, when `needed`
+/// bytes don't fit, and creates it if there's none yet. Even an empty one
+/// gets a few bytes so there's something to bind. Returns whether `buffer`
+/// was replaced.
+pub fn grow_buffer(
+    device: &wgpu::Device,
+    buffer: &mut Option<wgpu::Buffer>,
+    usage: wgpu::BufferUsages,
+    label: &str,
+    needed: u64,
+) -> bool {
+    let capacity = buffer.as_ref().map_or(0, wgpu::Buffer::size);
+    if needed <= capacity && buffer.is_some() {
+        return false;
+    }
+    let needed = needed.max(wgpu::COPY_BUFFER_ALIGNMENT);
+    *buffer = Some(device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some(label),
+        size: grown_capacity(capacity, needed),
+        usage: usage | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    }));
+    true
 }
 
+const _: () = {
+    assert!(grown_capacity(400, 404) == 600);
+    assert!(grown_capacity(400, 1000) == 1000);
+    assert!(grown_capacity(0, 6) == 8);
+    assert!(grown_capacity(10, 12) == 16);
+};
+
 impl Vertex {
-    pub fn get_layout() -> wgpu::VertexBufferLayout<'static> {
-        const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
-            wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3];
+    const ATTRIBUTES: [wgpu::VertexAttribute; 5] = wgpu::vertex_attr_array![
+        0 => Float32x3, 1 => Float32x4, 2 => Float32x2, 3 => Float32x3, 4 => Float32x4
+    ];
+
+    /// An opaque vertex; see `set_color` for translucent ones.
+    pub fn new(position: Vec3, color: Vec3, uv: Vec2, normal: Vec3) -> Self {
+        Vertex {
+            position: *position.as_array(),
+            color: *color.extend(1.0).as_array(),
+            uv: *uv.as_array(),
+            normal: *normal.as_array(),
+            tangent: [0.0; 4],
+        }
+    }
 
+    pub fn position(&self) -> Vec3 {
+        *Vec3::from_array(&self.position)
+    }
+
+    pub fn normal(&self) -> Vec3 {
+        *Vec3::from_array(&self.normal)
+    }
+
+    pub fn uv(&self) -> Vec2 {
+        *Vec2::from_array(&self.uv)
+    }
+
+    pub fn tangent(&self) -> Vec4 {
+        *Vec4::from_array(&self.tangent)
+    }
+
+    pub fn set_tangent(&mut self, tangent: Vec4) {
+        self.tangent = *tangent.as_array();
+    }
+
+    /// Only meshes drawn with a transparent material blend by `alpha`.
+    pub fn set_color(&mut self, color: Vec3, alpha: f32) {
+        self.color = *color.extend(alpha).as_array();
+    }
+
+    /// The vertex placed by `model`. The normal goes through the inverse
+    /// transpose of the upper 3x3, so it stays perpendicular to the surface
+    /// under non-uniform scale.
+    pub fn transformed(&self, model: &Mat4) -> Self {
+        let position = (*model * self.position().extend(1.0)).truncate(3);
+        let [x, y, z] = [0, 1, 2].map(|i| model[i].truncate(3));
+        // The cofactor matrix is the inverse transpose scaled by the
+        // determinant, so only the determinant's sign needs putting back.
+        let [nx, ny, nz] = self.normal;
+        let normal =
+            (cross(y, z) * nx + cross(z, x) * ny + cross(x, y) * nz) * dot(x, cross(y, z)).signum();
+        let normal = match length(normal) > 0.0 {
+            true => normalize(normal),
+            false => normal,
+        };
+        // Tangents lie along the surface, so they move with it. Mirroring
+        // turns the bitangent around, which the sign puts back.
+        let [tx, ty, tz, handedness] = self.tangent;
+        let tangent = x * tx + y * ty + z * tz;
+        let tangent = match length(tangent) > 0.0 {
+            true => normalize(tangent),
+            false => tangent,
+        };
+        let handedness = handedness * dot(x, cross(y, z)).signum();
+        Vertex {
+            position: *position.as_array(),
+            color: self.color,
+            uv: self.uv,
+            normal: *normal.as_array(),
+            tangent: *tangent.extend(handedness).as_array(),
+        }
+    }
+
+    pub fn get_layout() -> wgpu::VertexBufferLayout<'static> {
         wgpu::VertexBufferLayout {
             array_stride: std::mem::size_of::<Vertex>() as u64,
             step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &ATTRIBUTES,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+// The attribute table above must describe the struct exactly, otherwise the
+// shader reads garbage. Adding a field without updating it fails the build.
+const _: () = {
+    let attributes = &Vertex::ATTRIBUTES;
+    assert!(attributes[0].offset == std::mem::offset_of!(Vertex, position) as u64);
+    assert!(attributes[1].offset == std::mem::offset_of!(Vertex, color) as u64);
+    assert!(attributes[2].offset == std::mem::offset_of!(Vertex, uv) as u64);
+    assert!(attributes[3].offset == std::mem::offset_of!(Vertex, normal) as u64);
+    assert!(attributes[4].offset == std::mem::offset_of!(Vertex, tangent) as u64);
+    let last = &attributes[attributes.len() - 1];
+    assert!(last.offset + last.format.size() == std::mem::size_of::<Vertex>() as u64);
+    assert!(std::mem::size_of::<Vertex>() == 64);
+    assert!(std::mem::align_of::<Vertex>() == 4);
+};
+
+impl Mesh {
+    pub fn draw<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>) {
+        self.draw_instanced(pass, 0..1);
+    }
+
+    pub fn label(&self) -> &str {
+        &self.label
+    }
+
+    pub fn topology(&self) -> wgpu::PrimitiveTopology {
+        self.topology
+    }
+
+    /// What the vertices, or indices, make up. Meshes that aren't triangle
+    /// lists are drawn unlit in their vertex colors. Points are always one
+    /// pixel across, since WGSL has no point size. Strips can't be indexed.
+    pub fn set_topology(&mut self, topology: wgpu::PrimitiveTopology) {
+        assert!(
+            !topology.is_strip() || self.index_buffer.is_none(),
+            "{} is indexed and can't be drawn as a strip",
+            self.label
+        );
+        self.topology = topology;
+    }
+
+    /// Tells meshes apart for as long as they live, wherever they're moved.
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Goes up with every `update_vertices` and `update_indices`.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn indices(&self) -> &[u32] {
+        &self.indices
+    }
+
+    pub fn positions(&self) -> &[Vec3] {
+        &self.positions
+    }
+
+    /// The corners of each triangle, when the mesh is a triangle list;
+    /// nothing otherwise.
+    pub fn triangles(&self) -> impl Iterator<Item = [Vec3; 3]> + '_ {
+        let count = match self.topology {
+            wgpu::PrimitiveTopology::TriangleList => match self.indices.is_empty() {
+                true => self.positions.len(),
+                false => self.indices.len(),
+            },
+            _ => 0,
+        };
+        (0..count / 3).map(move |triangle| {
+            [0, 1, 2].map(|corner| {
+                let i = triangle * 3 + corner;
+                match self.indices.is_empty() {
+                    true => self.positions[i],
+                    false => self.positions[self.indices[i] as usize],
+                }
+            })
+        })
+    }
+
+    /// Replaces the vertices, drawing `vertices.len()` from now on. They are
+    /// written in place when they fit the vertex buffer and into a new,
+    /// larger one otherwise; wgpu keeps the old buffer alive for frames
+    /// already submitted.
+    pub fn update_vertices(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        vertices: &[Vertex],
+    ) {
+        let label = format!("{} Vertex Buffer", self.label);
+        write_or_grow(
+            device,
+            queue,
+            &mut self.vertex_buffer,
+            VERTEX_USAGE,
+            &label,
+            bytemuck::cast_slice(vertices),
+        );
+        self.vertex_count = vertices.len() as u32;
+        self.bounds = bounds(vertices);
+        self.bounding_sphere = bounding_sphere(vertices);
+        self.positions = vertices.iter().map(Vertex::position).collect();
+        self.revision += 1;
+    }
+
+    /// Like `update_vertices`, for the indices. The mesh is indexed with
+    /// 16-bit indices from now on, even if it wasn't indexed before.
+    pub fn update_indices(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, indices: &[u16]) {
+        self.write_indices(
+            device,
+            queue,
+            bytemuck::cast_slice(indices),
+            indices.len() as u32,
+            wgpu::IndexFormat::Uint16,
+        );
+        self.indices = indices.iter().map(|&index| index as u32).collect();
+    }
+
+    /// `update_indices` with 32-bit indices, which are narrowed to 16 bits
+    /// when they all fit, like `make_mesh_u32`'s.
+    pub fn update_indices_u32(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indices: &[u32],
+    ) {
+        if let Some(narrow) = narrow_indices(indices) {
+            return self.update_indices(device, queue, &narrow);
+        }
+        self.write_indices(
+            device,
+            queue,
+            bytemuck::cast_slice(indices),
+            indices.len() as u32,
+            wgpu::IndexFormat::Uint32,
+        );
+        self.indices = indices.to_vec();
+    }
+
+    fn write_indices(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        indices: &[u8],
+        count: u32,
+        format: wgpu::IndexFormat,
+    ) {
+        let label = format!("{} Index Buffer", self.label);
+        match &mut self.index_buffer {
+            Some(index_buffer) => write_or_grow(
+                device,
+                queue,
+                index_buffer,
+                wgpu::BufferUsages::INDEX,
+                &label,
+                indices,
+            ),
+            None => {
+                self.index_buffer = Some(create_buffer(
+                    device,
+                    wgpu::BufferUsages::INDEX,
+                    &label,
+                    indices,
+                ))
+            }
+        }
+        self.index_count = count;
+        self.index_format = format;
+        self.revision += 1;
+    }
+
+    pub fn draw_instanced<'a>(&'a self, pass: &mut wgpu::RenderPass<'a>, instances: Range<u32>) {
+        pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        match &self.index_buffer {
+            Some(index_buffer) => {
+                pass.set_index_buffer(index_buffer.slice(..), self.index_format);
+                pass.draw_indexed(0..self.index_count, 0, instances);
+            }
+            None => pass.draw(0..self.vertex_count, instances),
         }
     }
 }
 
-unsafe fn any_as_u8_slice<T: Sized>(p: &T) -> &[u8] {
-    ::core::slice::from_raw_parts((p as *const T) as *const u8, ::core::mem::size_of::<T>())
+/// One mesh of an imported model, to be drawn with `material` at
+/// `transform`.
+pub struct ModelPart {
+    pub mesh: Mesh,
+    pub material: Material,
+    pub transform: Mat4,
 }
 
-pub fn make_triangle(device: &wgpu::Device) -> wgpu::Buffer {
-    let vertices: [Vertex; 3] = [
-        Vertex {
-            position: Vec3::new(-0.75, -0.75, 0.0),
-            color: Vec3::new(1.0, 0.0, 0.0),
-        },
-        Vertex {
-            position: Vec3::new(0.75, -0.75, 0.0),
-            color: Vec3::new(0.0, 1.0, 0.0),
-        },
-        Vertex {
-            position: Vec3::new(0.0, 0.75, 0.0),
-            color: Vec3::new(0.0, 0.0, 1.0),
-        },
-    ];
-    let bytes: &[u8] = unsafe { any_as_u8_slice(&vertices) };
+fn bounds(vertices: &[Vertex]) -> Aabb {
+    Aabb::from_points(vertices.iter().map(Vertex::position))
+}
+
+fn bounding_sphere(vertices: &[Vertex]) -> BoundingSphere {
+    BoundingSphere::from_points(vertices.iter().map(Vertex::position))
+}
 
+/// A buffer holding `contents`, padded to a whole number of copy blocks
+/// so it can be written again later.
+fn create_buffer(
+    device: &wgpu::Device,
+    usage: wgpu::BufferUsages,
+    label: &str,
+    contents: &[u8],
+) -> wgpu::Buffer {
     let buffer_descriptor = wgpu::util::BufferInitDescriptor {
-        label: Some("Triangle Vertex Buffer"),
-        contents: bytes,
-        usage: wgpu::BufferUsages::VERTEX,
+        label: Some(label),
+        contents: contents,
+        usage: usage | wgpu::BufferUsages::COPY_DST,
     };
+    device.create_buffer_init(&buffer_descriptor)
+}
 
-    let buffer = device.create_buffer_init(&buffer_descriptor);
-    return buffer;
+/// Writes `contents` to the start of `buffer`, first swapping in a larger
+/// buffer if they don't fit.
+fn write_or_grow(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    buffer: &mut wgpu::Buffer,
+    usage: wgpu::BufferUsages,
+    label: &str,
+    contents: &[u8],
+) {
+    // Writes have to be whole copy blocks; the padding lands past the
+    // logical end, which is never drawn.
+    let padded =
+        (contents.len() as u64).div_ceil(wgpu::COPY_BUFFER_ALIGNMENT) * wgpu::COPY_BUFFER_ALIGNMENT;
+    if padded > buffer.size() {
+        *buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some(label),
+            size: grown_capacity(buffer.size(), padded),
+            usage: usage | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+    }
+    if padded as usize == contents.len() {
+        queue.write_buffer(buffer, 0, contents);
+    } else {
+        let mut bytes = contents.to_vec();
+        bytes.resize(padded as usize, 0);
+        queue.write_buffer(buffer, 0, &bytes);
+    }
 }
 
-pub fn make_quad(device: &wgpu::Device) -> Mesh {
-    let vertices: [Vertex; 4] = [
-        Vertex {
-            position: Vec3::new(-0.75, -0.75, 0.0),
-            color: Vec3::new(1.0, 0.0, 0.0),
-        },
-        Vertex {
-            position: Vec3::new(0.75, -0.75, 0.0),
-            color: Vec3::new(0.0, 1.0, 0.0),
-        },
-        Vertex {
-            position: Vec3::new(0.75, 0.75, 0.0),
-            color: Vec3::new(0.0, 0.0, 1.0),
-        },
-        Vertex {
-            position: Vec3::new(-0.75, 0.75, 0.0),
-            color: Vec3::new(0.0, 1.0, 0.0),
+/// Vertex buffers can be copied from, into a `MeshPool`.
+const VERTEX_USAGE: wgpu::BufferUsages =
+    wgpu::BufferUsages::VERTEX.union(wgpu::BufferUsages::COPY_SRC);
+
+fn build_mesh(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[Vertex],
+    indices: Option<(&[u8], u32)>,
+    index_format: wgpu::IndexFormat,
+) -> Mesh {
+    let vertex_buffer = create_buffer(
+        device,
+        VERTEX_USAGE,
+        &format!("{} Vertex Buffer", label),
+        bytemuck::cast_slice(vertices),
+    );
+    let index_buffer = indices.map(|(indices, _)| {
+        create_buffer(
+            device,
+            wgpu::BufferUsages::INDEX,
+            &format!("{} Index Buffer", label),
+            indices,
+        )
+    });
+
+    Mesh {
+        vertex_buffer: vertex_buffer,
+        index_buffer: index_buffer,
+        vertex_count: vertices.len() as u32,
+        index_count: indices.map_or(0, |(_, count)| count),
+        index_format: index_format,
+        bounds: bounds(vertices),
+        bounding_sphere: bounding_sphere(vertices),
+        topology: wgpu::PrimitiveTopology::TriangleList,
+        label: label.to_string(),
+        id: NEXT_MESH_ID.fetch_add(1, Ordering::Relaxed),
+        revision: 0,
+        indices: match indices {
+            Some((indices, _)) => match index_format {
+                wgpu::IndexFormat::Uint16 => bytemuck::cast_slice::<u8, u16>(indices)
+                    .iter()
+                    .map(|&index| index as u32)
+                    .collect(),
+                wgpu::IndexFormat::Uint32 => bytemuck::cast_slice(indices).to_vec(),
+            },
+            None => Vec::new(),
         },
+        positions: vertices.iter().map(Vertex::position).collect(),
+    }
+}
+
+pub fn make_mesh(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[Vertex],
+    indices: Option<&[u16]>,
+) -> Mesh {
+    let indices = indices.map(|indices| (bytemuck::cast_slice(indices), indices.len() as u32));
+    build_mesh(device, label, vertices, indices, wgpu::IndexFormat::Uint16)
+}
+
+/// `indices` as 16 bits, if every one of them fits.
+pub fn narrow_indices(indices: &[u32]) -> Option<Vec<u16>> {
+    indices
+        .iter()
+        .map(|&index| u16::try_from(index).ok())
+        .collect()
+}
+
+/// Like `make_mesh`, with indices given as 32 bits. They're stored as 16
+/// bits when they all fit, so only meshes with more vertices than 16-bit
+/// indices reach pay for the larger format.
+pub fn make_mesh_u32(
+    device: &wgpu::Device,
+    label: &str,
+    vertices: &[Vertex],
+    indices: &[u32],
+) -> Mesh {
+    if let Some(narrow) = narrow_indices(indices) {
+        return make_mesh(device, label, vertices, Some(&narrow));
+    }
+    let indices = Some((bytemuck::cast_slice(indices), indices.len() as u32));
+    build_mesh(device, label, vertices, indices, wgpu::IndexFormat::Uint32)
+}
+
+/// A mesh's vertices and indices kept on the CPU, so they can be uploaded
+/// on their own with `to_mesh` or merged with others into a `MeshBatch`.
+pub struct MeshData {
+    pub vertices: Vec<Vertex>,
+    pub indices: Vec<u32>,
+}
+
+impl MeshData {
+    pub fn to_mesh(&self, device: &wgpu::Device, label: &str) -> Mesh {
+        make_mesh_u32(device, label, &self.vertices, &self.indices)
+    }
+
+    /// What the mesh's `bounds` will be.
+    pub fn bounds(&self) -> Aabb {
+        bounds(&self.vertices)
+    }
+
+    /// What the mesh's `bounding_sphere` will be.
+    pub fn bounding_sphere(&self) -> BoundingSphere {
+        bounding_sphere(&self.vertices)
+    }
+
+    /// Gives every vertex `color` at `alpha` opacity.
+    pub fn set_color(&mut self, color: Vec3, alpha: f32) {
+        for vertex in &mut self.vertices {
+            vertex.set_color(color, alpha);
+        }
+    }
+}
+
+/// Sets every vertex normal to the area-weighted average of the faces
+/// around it. Vertices shared between faces come out smooth; split them to
+/// get hard edges.
+pub fn compute_normals<I: Copy + Into<u32>>(vertices: &mut [Vertex], indices: &[I]) {
+    let mut normals = vec![vec3(0.0, 0.0, 0.0); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| {
+            let [x, y, z] = vertices[triangle[i].into() as usize].position;
+            vec3(x, y, z)
+        });
+        // Not normalized: the cross product's length weights by area.
+        let face_normal = cross(b - a, c - a);
+        for &index in triangle {
+            let index = index.into() as usize;
+            normals[index] = normals[index] + face_normal;
+        }
+    }
+    for (vertex, normal) in vertices.iter_mut().zip(normals) {
+        let normal = match length(normal) > 0.0 {
+            true => normalize(normal),
+            false => vec3(0.0, 1.0, 0.0),
+        };
+        vertex.normal = *normal.as_array();
+    }
+}
+
+/// Sets every vertex tangent from how the UVs run across the faces around
+/// it, made perpendicular to the vertex normal, which has to be set first.
+/// Faces whose UVs don't span an area don't count, and a vertex with
+/// nothing to go by gets any tangent perpendicular to its normal.
+pub fn compute_tangents<I: Copy + Into<u32>>(vertices: &mut [Vertex], indices: &[I]) {
+    let mut tangents = vec![vec3(0.0, 0.0, 0.0); vertices.len()];
+    let mut bitangents = vec![vec3(0.0, 0.0, 0.0); vertices.len()];
+    for triangle in indices.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| vertices[triangle[i].into() as usize]);
+        let (edge1, edge2) = (b.position() - a.position(), c.position() - a.position());
+        // v is flipped so the bitangent points up the image, the way
+        // normal maps store green.
+        let (du1, dv1) = (b.uv[0] - a.uv[0], a.uv[1] - b.uv[1]);
+        let (du2, dv2) = (c.uv[0] - a.uv[0], a.uv[1] - c.uv[1]);
+        let area = du1 * dv2 - du2 * dv1;
+        if area.abs() < 1e-12 {
+            continue;
+        }
+        let tangent = (edge1 * dv2 - edge2 * dv1) / area;
+        let bitangent = (edge2 * du1 - edge1 * du2) / area;
+        for &index in triangle {
+            let index = index.into() as usize;
+            tangents[index] = tangents[index] + tangent;
+            bitangents[index] = bitangents[index] + bitangent;
+        }
+    }
+    for (vertex, (tangent, bitangent)) in vertices
+        .iter_mut()
+        .zip(tangents.into_iter().zip(bitangents))
+    {
+        let normal = vertex.normal();
+        // Gram-Schmidt: what's left of the tangent once the part along the
+        // normal is taken out.
+        let mut tangent = tangent - normal * dot(normal, tangent);
+        if length(tangent) < 1e-6 {
+            let axis = match normal.x.abs() < 0.9 {
+                true => vec3(1.0, 0.0, 0.0),
+                false => vec3(0.0, 1.0, 0.0),
+            };
+            tangent = cross(axis, normal);
+        }
+        let tangent = match length(tangent) > 0.0 {
+            true => normalize(tangent),
+            false => vec3(1.0, 0.0, 0.0),
+        };
+        let handedness = match dot(cross(normal, tangent), bitangent) < 0.0 {
+            true => -1.0,
+            false => 1.0,
+        };
+        vertex.tangent = *tangent.extend(handedness).as_array();
+    }
+}
+
+/// `colors` says how the vertex colors below are meant; they're stored
+/// converted to linear.
+pub fn make_triangle(device: &wgpu::Device, colors: ColorSpace) -> Mesh {
+    let red = colors.to_linear(vec3(1.0, 0.0, 0.0));
+    let green = colors.to_linear(vec3(0.0, 1.0, 0.0));
+    let blue = colors.to_linear(vec3(0.0, 0.0, 1.0));
+    // Normals are filled in by `compute_normals`.
+    let normal = vec3(0.0, 0.0, 0.0);
+    let mut vertices: [Vertex; 3] = [
+        Vertex::new(vec3(-0.75, -0.75, 0.0), red, vec2(-0.75, 0.75), normal),
+        Vertex::new(vec3(0.75, -0.75, 0.0), green, vec2(0.75, 0.75), normal),
+        Vertex::new(vec3(0.0, 0.75, 0.0), blue, vec2(0.0, -0.75), normal),
     ];
-    let mut bytes: &[u8] = unsafe { any_as_u8_slice(&vertices) };
+    compute_normals(&mut vertices, &[0u16, 1, 2]);
+    compute_tangents(&mut vertices, &[0u16, 1, 2]);
 
-    let mut buffer_descriptor = wgpu::util::BufferInitDescriptor {
-        label: Some("Quad Vertex Buffer"),
-        contents: bytes,
-        usage: wgpu::BufferUsages::VERTEX,
-    };
+    make_mesh(device, "Triangle", &vertices, None)
+}
 
-    let vertex_buffer = device.create_buffer_init(&buffer_descriptor);
+/// Quads per side of a wave grid are capped so its vertices stay within
+/// 16-bit index range.
+pub const MAX_WAVE_RESOLUTION: u32 = 128;
 
-    let indices: [u16; 6] = [ 0, 1, 2, 2, 3, 0];
-    bytes = unsafe { any_as_u8_slice(&indices) };
+const WAVE_AMPLITUDE: f32 = 0.25;
+const WAVE_FREQUENCY: f32 = 1.5;
 
-    buffer_descriptor = wgpu::util::BufferInitDescriptor {
-        label: Some("Quad Index Buffer"),
-        contents: bytes,
-        usage: wgpu::BufferUsages::INDEX,
-    };
+/// A `size` by `size` sheet in the XZ plane centered on the origin, split
+/// into `resolution` quads per side and displaced into a sine wave that
+/// moves along X with `time`.
+pub fn wave_vertices(resolution: u32, size: f32, time: f32) -> Vec<Vertex> {
+    let resolution = resolution.clamp(1, MAX_WAVE_RESOLUTION);
+    let white = vec3(1.0, 1.0, 1.0);
+    let mut vertices = Vec::with_capacity(((resolution + 1) * (resolution + 1)) as usize);
+    for row in 0..=resolution {
+        for column in 0..=resolution {
+            let uv = vec2(
+                column as f32 / resolution as f32,
+                row as f32 / resolution as f32,
+            );
+            let x = (uv.x - 0.5) * size;
+            let z = (uv.y - 0.5) * size;
+            let phase = WAVE_FREQUENCY * x + time;
+            let ripple = (WAVE_FREQUENCY * z).cos();
+            let y = WAVE_AMPLITUDE * phase.sin() * ripple;
+            // The normal follows from the height function's slopes.
+            let dx = WAVE_AMPLITUDE * WAVE_FREQUENCY * phase.cos() * ripple;
+            let dz = -WAVE_AMPLITUDE * WAVE_FREQUENCY * phase.sin() * (WAVE_FREQUENCY * z).sin();
+            let normal = normalize(vec3(-dx, 1.0, -dz));
+            // u runs along x and v along z, so the tangent is the slope
+            // along x and the bitangent, up the image, points to -z.
+            let mut vertex = Vertex::new(vec3(x, y, z), white, uv, normal);
+            vertex.set_tangent(normalize(vec3(1.0, dx, 0.0)).extend(1.0));
+            vertices.push(vertex);
+        }
+    }
+    vertices
+}
+
+/// Two counter-clockwise triangles, seen from above, per quad of a
+/// `wave_vertices` grid.
+pub fn wave_indices(resolution: u32) -> Vec<u16> {
+    let resolution = resolution.clamp(1, MAX_WAVE_RESOLUTION);
+    grid_indices(resolution, resolution)
+        .into_iter()
+        .map(|index| index as u16)
+        .collect()
+}
+
+/// Indices for `rows + 1` rows of `columns + 1` vertices each, row by row.
+/// Counter-clockwise when the columns run left to right and the rows top to
+/// bottom as seen from the front.
+fn grid_indices(columns: u32, rows: u32) -> Vec<u32> {
+    let stride = columns + 1;
+    let mut indices = Vec::with_capacity((columns * rows * 6) as usize);
+    for row in 0..rows {
+        for column in 0..columns {
+            let a = row * stride + column;
+            let b = a + 1;
+            let c = a + stride;
+            let d = c + 1;
+            indices.extend([a, c, b, b, c, d]);
+        }
+    }
+    indices
+}
+
+const _: () = assert!((MAX_WAVE_RESOLUTION + 1) * (MAX_WAVE_RESOLUTION + 1) <= u16::MAX as u32 + 1);
+
+/// A quad sampling `region` of its texture, `AtlasRegion::FULL` for all of it.
+pub fn make_textured_quad(device: &wgpu::Device, colors: ColorSpace, region: &AtlasRegion) -> Mesh {
+    let white = colors.to_linear(vec3(1.0, 1.0, 1.0));
+    let normal = vec3(0.0, 0.0, 0.0);
+    let mut vertices: [Vertex; 4] = [
+        Vertex::new(
+            vec3(-0.75, -0.75, 0.0),
+            white,
+            region.map(vec2(0.0, 1.0)),
+            normal,
+        ),
+        Vertex::new(
+            vec3(0.75, -0.75, 0.0),
+            white,
+            region.map(vec2(1.0, 1.0)),
+            normal,
+        ),
+        Vertex::new(
+            vec3(0.75, 0.75, 0.0),
+            white,
+            region.map(vec2(1.0, 0.0)),
+            normal,
+        ),
+        Vertex::new(
+            vec3(-0.75, 0.75, 0.0),
+            white,
+            region.map(vec2(0.0, 0.0)),
+            normal,
+        ),
+    ];
+    let indices: [u16; 6] = [0, 1, 2, 2, 3, 0];
+    compute_normals(&mut vertices, &indices);
+    compute_tangents(&mut vertices, &indices);
+
+    make_mesh(device, "Textured Quad", &vertices, Some(&indices))
+}
+
+// The builders below make white, indexed meshes centered on the origin,
+// wound counter-clockwise seen from outside. Negative sizes are taken as
+// their magnitude and counts below the documented minimum are raised to it.
+
+pub const MIN_CIRCLE_SEGMENTS: u32 = 3;
+pub const MIN_SPHERE_RINGS: u32 = 2;
+pub const MIN_SPHERE_SECTORS: u32 = 3;
+pub const MIN_PLANE_SUBDIVISIONS: u32 = 1;
+
+/// A `size` by `size` square in the XY plane facing +Z.
+pub fn quad_data(size: f32) -> MeshData {
+    let half = size.abs() / 2.0;
+    let white = vec3(1.0, 1.0, 1.0);
+    let normal = vec3(0.0, 0.0, 1.0);
+    let mut vertices = vec![
+        Vertex::new(vec3(-half, -half, 0.0), white, vec2(0.0, 1.0), normal),
+        Vertex::new(vec3(half, -half, 0.0), white, vec2(1.0, 1.0), normal),
+        Vertex::new(vec3(half, half, 0.0), white, vec2(1.0, 0.0), normal),
+        Vertex::new(vec3(-half, half, 0.0), white, vec2(0.0, 0.0), normal),
+    ];
+    let indices = vec![0, 1, 2, 2, 3, 0];
+    compute_tangents(&mut vertices, &indices);
+    MeshData {
+        vertices: vertices,
+        indices: indices,
+    }
+}
+
+/// `quad_data` in GPU buffers.
+pub fn make_quad(device: &wgpu::Device, size: f32) -> Mesh {
+    quad_data(size).to_mesh(device, "Quad")
+}
+
+/// A disc in the XY plane facing +Z: a center vertex and `segments` on the
+/// rim, at least `MIN_CIRCLE_SEGMENTS`. `segments + 1` vertices and
+/// `3 * segments` indices.
+pub fn circle_data(radius: f32, segments: u32) -> MeshData {
+    let radius = radius.abs();
+    let segments = std::cmp::max(segments, MIN_CIRCLE_SEGMENTS);
+    let white = vec3(1.0, 1.0, 1.0);
+    let normal = vec3(0.0, 0.0, 1.0);
+
+    let mut vertices = Vec::with_capacity(segments as usize + 1);
+    vertices.push(Vertex::new(
+        vec3(0.0, 0.0, 0.0),
+        white,
+        vec2(0.5, 0.5),
+        normal,
+    ));
+    for segment in 0..segments {
+        let angle = std::f32::consts::TAU * segment as f32 / segments as f32;
+        let (sin, cos) = angle.sin_cos();
+        vertices.push(Vertex::new(
+            vec3(radius * cos, radius * sin, 0.0),
+            white,
+            vec2(0.5 + 0.5 * cos, 0.5 - 0.5 * sin),
+            normal,
+        ));
+    }
+    let mut indices = Vec::with_capacity(segments as usize * 3);
+    for segment in 0..segments {
+        indices.extend([0, segment + 1, (segment + 1) % segments + 1]);
+    }
+    compute_tangents(&mut vertices, &indices);
+    MeshData {
+        vertices: vertices,
+        indices: indices,
+    }
+}
+
+/// `circle_data` in GPU buffers.
+pub fn make_circle(device: &wgpu::Device, radius: f32, segments: u32) -> Mesh {
+    circle_data(radius, segments).to_mesh(device, "Circle")
+}
+
+/// A cube with edges `size` long. Each face has vertices of its own so
+/// normals and UVs don't bleed across edges: 24 vertices and 36 indices.
+pub fn cube_data(size: f32) -> MeshData {
+    let half = size.abs() / 2.0;
+    let white = vec3(1.0, 1.0, 1.0);
+    // Each face's normal and the axes its UVs run along, with u x v = n.
+    let faces = [
+        (
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+            vec3(0.0, 1.0, 0.0),
+        ),
+        (
+            vec3(-1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+            vec3(0.0, 1.0, 0.0),
+        ),
+        (
+            vec3(0.0, 1.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, -1.0),
+        ),
+        (
+            vec3(0.0, -1.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        ),
+        (
+            vec3(0.0, 0.0, 1.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ),
+        (
+            vec3(0.0, 0.0, -1.0),
+            vec3(-1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ),
+    ];
+
+    let mut vertices = Vec::with_capacity(24);
+    let mut indices = Vec::with_capacity(36);
+    for (normal, u, v) in faces {
+        let first = vertices.len() as u32;
+        for (s, t) in [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)] {
+            let position = (normal + u * s + v * t) * half;
+            let uv = vec2((s + 1.0) / 2.0, (1.0 - t) / 2.0);
+            vertices.push(Vertex::new(position, white, uv, normal));
+        }
+        indices.extend([0, 1, 2, 2, 3, 0].map(|index| first + index));
+    }
+    compute_tangents(&mut vertices, &indices);
+    MeshData {
+        vertices: vertices,
+        indices: indices,
+    }
+}
+
+/// `cube_data` in GPU buffers.
+pub fn make_cube(device: &wgpu::Device, size: f32) -> Mesh {
+    cube_data(size).to_mesh(device, "Cube")
+}
+
+/// A sphere of `rings` bands from pole to pole, at least
+/// `MIN_SPHERE_RINGS`, and `sectors` slices around the Y axis, at least
+/// `MIN_SPHERE_SECTORS`. The poles and the seam are repeated for every
+/// sector so each gets its own UV: `(rings + 1) * (sectors + 1)` vertices
+/// and `6 * sectors * (rings - 1)` indices, the triangles that would
+/// collapse at the poles left out.
+pub fn uv_sphere_data(radius: f32, rings: u32, sectors: u32) -> MeshData {
+    let radius = radius.abs();
+    let rings = std::cmp::max(rings, MIN_SPHERE_RINGS);
+    let sectors = std::cmp::max(sectors, MIN_SPHERE_SECTORS);
+    let white = vec3(1.0, 1.0, 1.0);
+
+    let mut vertices = Vec::with_capacity(((rings + 1) * (sectors + 1)) as usize);
+    for ring in 0..=rings {
+        let polar = std::f32::consts::PI * ring as f32 / rings as f32;
+        let (ring_radius, y) = polar.sin_cos();
+        for sector in 0..=sectors {
+            let azimuth = std::f32::consts::TAU * sector as f32 / sectors as f32;
+            let (sin, cos) = azimuth.sin_cos();
+            // Already unit length, so the poles get a normal without
+            // normalizing a zero vector.
+            let normal = vec3(ring_radius * sin, y, ring_radius * cos);
+            let uv = vec2(sector as f32 / sectors as f32, ring as f32 / rings as f32);
+            vertices.push(Vertex::new(normal * radius, white, uv, normal));
+        }
+    }
+
+    let stride = sectors + 1;
+    let mut indices = Vec::with_capacity((6 * sectors * (rings - 1)) as usize);
+    for ring in 0..rings {
+        for sector in 0..sectors {
+            let a = ring * stride + sector;
+            let b = a + 1;
+            let c = a + stride;
+            let d = c + 1;
+            if ring != 0 {
+                indices.extend([a, c, b]);
+            }
+            if ring != rings - 1 {
+                indices.extend([b, c, d]);
+            }
+        }
+    }
+    compute_tangents(&mut vertices, &indices);
+    MeshData {
+        vertices: vertices,
+        indices: indices,
+    }
+}
+
+/// `uv_sphere_data` in GPU buffers.
+pub fn make_uv_sphere(device: &wgpu::Device, radius: f32, rings: u32, sectors: u32) -> Mesh {
+    uv_sphere_data(radius, rings, sectors).to_mesh(device, "UV Sphere")
+}
+
+/// A `width` by `depth` sheet in the XZ plane facing +Y, split into
+/// `subdivisions` quads along each side, at least `MIN_PLANE_SUBDIVISIONS`.
+/// `(subdivisions + 1)^2` vertices and `6 * subdivisions^2` indices.
+pub fn plane_data(width: f32, depth: f32, subdivisions: u32) -> MeshData {
+    let (width, depth) = (width.abs(), depth.abs());
+    let subdivisions = std::cmp::max(subdivisions, MIN_PLANE_SUBDIVISIONS);
+    let white = vec3(1.0, 1.0, 1.0);
+    let normal = vec3(0.0, 1.0, 0.0);
+
+    let mut vertices = Vec::with_capacity(((subdivisions + 1) * (subdivisions + 1)) as usize);
+    for row in 0..=subdivisions {
+        for column in 0..=subdivisions {
+            let uv = vec2(
+                column as f32 / subdivisions as f32,
+                row as f32 / subdivisions as f32,
+            );
+            let position = vec3((uv.x - 0.5) * width, 0.0, (uv.y - 0.5) * depth);
+            vertices.push(Vertex::new(position, white, uv, normal));
+        }
+    }
+    let indices = grid_indices(subdivisions, subdivisions);
+    compute_tangents(&mut vertices, &indices);
+    MeshData {
+        vertices: vertices,
+        indices: indices,
+    }
+}
+
+/// `plane_data` in GPU buffers.
+pub fn make_plane(device: &wgpu::Device, width: f32, depth: f32, subdivisions: u32) -> Mesh {
+    plane_data(width, depth, subdivisions).to_mesh(device, "Plane")
+}
+
+/// Two vertices per line, for a line list. Cheap enough to redo every
+/// frame and hand to `Mesh::update_vertices`.
+pub fn line_list_vertices(lines: &[(Vec3, Vec3)], color: Vec3) -> Vec<Vertex> {
+    let normal = vec3(0.0, 0.0, 0.0);
+    lines
+        .iter()
+        .flat_map(|&(start, end)| [start, end])
+        .map(|position| Vertex::new(position, color, vec2(0.0, 0.0), normal))
+        .collect()
+}
+
+/// `line_list_vertices` in an unindexed line list mesh.
+pub fn make_line_list(device: &wgpu::Device, lines: &[(Vec3, Vec3)], color: Vec3) -> Mesh {
+    let vertices = line_list_vertices(lines, color);
+    let mut mesh = make_mesh(device, "Line List", &vertices, None);
+    mesh.set_topology(wgpu::PrimitiveTopology::LineList);
+    mesh
+}
 
-    let index_buffer = device.create_buffer_init(&buffer_descriptor);
+/// The X, Y and Z axes from the origin, `length` long, in red, green and
+/// blue.
+pub fn make_axes(device: &wgpu::Device, length: f32) -> Mesh {
+    let origin = vec3(0.0, 0.0, 0.0);
+    let mut vertices = Vec::with_capacity(6);
+    for axis in [
+        vec3(1.0, 0.0, 0.0),
+        vec3(0.0, 1.0, 0.0),
+        vec3(0.0, 0.0, 1.0),
+    ] {
+        vertices.extend(line_list_vertices(&[(origin, axis * length)], axis));
+    }
+    let mut mesh = make_mesh(device, "Axes", &vertices, None);
+    mesh.set_topology(wgpu::PrimitiveTopology::LineList);
+    mesh
+}
+
+#[track_caller]
+pub fn load_obj(device: &wgpu::Device, path: &Path) -> Result<Mesh, ObjError> {
+    let source = assets::read_to_string(path).map_err(|error| ObjError::Io {
+        path: path.to_path_buf(),
+        error: error,
+    })?;
+    let (vertices, indices) = obj_loader::parse_obj(&source)?;
+    let label = path.to_string_lossy();
+    let mesh = validation::with_validation(device, &label, || {
+        make_mesh_u32(device, &label, &vertices, &indices)
+    })?;
+    Ok(mesh)
+}
+
+/// Loads every triangle primitive of a .gltf or .glb file as its own mesh,
+/// placed by its node's world transform. Materials are built against
+/// `layout` and sample with `sampler`; ones without a base color texture get
+/// a 1x1 white one, and ones without a normal map a flat one.
+#[track_caller]
+pub fn load_gltf(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    layout: &Layout,
+    sampler: &wgpu::Sampler,
+    path: &Path,
+) -> Result<Vec<ModelPart>, GltfError> {
+    let scene = gltf_loader::import(path)?;
+
+    // Primitives sharing textures share their material.
+    let mut materials: HashMap<(Option<usize>, Option<usize>), Material> = HashMap::new();
+    let mut parts = Vec::with_capacity(scene.primitives.len());
+    for primitive in scene.primitives {
+        let key = (primitive.base_color_texture, primitive.normal_texture);
+        let material = match materials.get(&key) {
+            Some(material) => material.clone(),
+            None => {
+                let load = |index: usize| -> Result<Texture, GltfError> {
+                    let image = gltf_loader::decode_image(index, &scene.images[index])?;
+                    Ok(validation::with_validation(
+                        device,
+                        &primitive.label,
+                        || Texture::from_image(device, queue, &image, &primitive.label, true),
+                    )?)
+                };
+                let texture = match primitive.base_color_texture {
+                    Some(index) => load(index)?,
+                    None => Texture::white(device, queue),
+                };
+                let normal_map = match primitive.normal_texture {
+                    Some(index) => load(index)?,
+                    None => Texture::flat_normal(device, queue),
+                };
+                let material = Material::from_texture(
+                    device,
+                    &texture,
+                    &normal_map,
+                    layout,
+                    sampler,
+                    &primitive.label,
+                );
+                materials.insert(key, material.clone());
+                material
+            }
+        };
+        let mesh = validation::with_validation(device, &primitive.label, || {
+            make_mesh_u32(
+                device,
+                &primitive.label,
+                &primitive.vertices,
+                &primitive.indices,
+            )
+        })?;
+        parts.push(ModelPart {
+            mesh: mesh,
+            material: material,
+            transform: primitive.transform,
+        });
+    }
+    Ok(parts)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::uniform::identity;
+    use crate::test_support::{self, assert_mesh};
+
+    #[test]
+    fn vertex_layout_covers_the_vertex() {
+        let layout = Vertex::get_layout();
+        assert_eq!(layout.array_stride, std::mem::size_of::<Vertex>() as u64);
+        let mut end = 0;
+        for (location, attribute) in layout.attributes.iter().enumerate() {
+            assert_eq!(attribute.shader_location, location as u32);
+            assert!(
+                attribute.offset >= end,
+                "attribute {} at offset {} overlaps the one before, which ends at {}",
+                location,
+                attribute.offset,
+                end
+            );
+            end = attribute.offset + attribute.format.size();
+        }
+        assert_eq!(end, layout.array_stride);
+    }
+
+    /// Twice the area of each triangle seen from +Z, positive where its
+    /// corners run counter-clockwise.
+    fn signed_areas(positions: &[Vec3], indices: &[u32]) -> Vec<f32> {
+        indices
+            .chunks_exact(3)
+            .map(|triangle| {
+                let [a, b, c] = [0, 1, 2].map(|i| positions[triangle[i] as usize]);
+                cross(b - a, c - a).z
+            })
+            .collect()
+    }
 
-    Mesh { vertex_buffer: vertex_buffer, index_buffer: index_buffer }
-}
\ No newline at end of file
+    #[test]
+    fn quad_triangles_wind_counter_clockwise() {
+        let data = quad_data(2.0);
+        let positions: Vec<Vec3> = data.vertices.iter().map(Vertex::position).collect();
+        // Each triangle is half of the 2 by 2 quad.
+        assert_eq!(signed_areas(&positions, &data.indices), [4.0, 4.0]);
+    }
+
+    #[test]
+    fn make_quad_keeps_the_winding() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let mesh = make_quad(&device, 2.0);
+        assert_eq!(signed_areas(mesh.positions(), mesh.indices()), [4.0, 4.0]);
+    }
+
+    /// Shrinks, refits and then outgrows a quad's buffers. Buffers only
+    /// change when the data no longer fits, and the odd numbers of 16-bit
+    /// indices have to be padded for validation to let the writes through.
+    #[test]
+    fn updates_reuse_buffers_until_they_outgrow_them() {
+        let Some((device, queue)) = test_support::headless_device() else {
+            return;
+        };
+        let mut mesh = make_quad(&device, 1.0);
+        let data = quad_data(1.0);
+        let (vertex_buffer, index_buffer) = (mesh.vertex_buffer.clone(), mesh.index_buffer.clone());
+        let steps: [(&[Vertex], &[u16], bool); 3] = [
+            (&data.vertices[..3], &[0, 1, 2], false),
+            (&data.vertices, &[0, 1, 2, 2, 3, 0], false),
+            (&[data.vertices[0]; 5], &[0, 1, 2, 2, 3, 0, 4], true),
+        ];
+        validation::with_validation(&device, "mesh update test", || {
+            for (vertices, indices, grows) in steps {
+                let revision = mesh.revision();
+                mesh.update_vertices(&device, &queue, vertices);
+                mesh.update_indices(&device, &queue, indices);
+                assert_eq!(mesh.revision(), revision + 2);
+                let indices: Vec<u32> = indices.iter().map(|&index| index as u32).collect();
+                assert_mesh(&mesh, vertices.len() as u32, indices.len() as u32);
+                assert_eq!(mesh.indices(), indices);
+                assert_eq!(mesh.index_format, wgpu::IndexFormat::Uint16);
+                assert_eq!(
+                    (
+                        mesh.vertex_buffer != vertex_buffer,
+                        mesh.index_buffer != index_buffer
+                    ),
+                    (grows, grows),
+                    "{} vertices and {} indices",
+                    vertices.len(),
+                    indices.len()
+                );
+            }
+        })
+        .unwrap_or_else(|error| panic!("{}", error));
+        // 14 bytes of indices, padded to 16, into a buffer half as large again.
+        assert_eq!(mesh.index_buffer.map(|buffer| buffer.size()), Some(20));
+    }
+
+    #[test]
+    fn cube_faces_show_the_whole_texture() {
+        let vertices = cube_data(2.0).vertices;
+        for face in vertices.chunks(4) {
+            let normal = face[0].normal();
+            let corner = |u: f32, v: f32| {
+                face.iter()
+                    .find(|vertex| vertex.uv() == vec2(u, v))
+                    .unwrap_or_else(|| {
+                        panic!("face {:?} has no corner at uv ({}, {})", normal, u, v)
+                    })
+                    .position()
+            };
+            corner(1.0, 1.0);
+            // u runs right and v down the image; seen from outside, right
+            // then up turns counter-clockwise unless the face is mirrored.
+            let right = corner(1.0, 0.0) - corner(0.0, 0.0);
+            let up = corner(0.0, 0.0) - corner(0.0, 1.0);
+            assert!(
+                dot(cross(right, up), normal) > 0.0,
+                "face {:?} shows the texture mirrored",
+                normal
+            );
+        }
+    }
+
+    /// Tangents run along u, perpendicular to the normal, with w turning
+    /// the bitangent up the image.
+    fn assert_tangents(name: &str, data: &MeshData, expected: Option<Vec4>) {
+        for (i, vertex) in data.vertices.iter().enumerate() {
+            let tangent = vertex.tangent();
+            let along = tangent.truncate(3);
+            let well_formed = (length(along) - 1.0).abs() < 1e-4
+                && dot(along, vertex.normal()).abs() < 1e-4
+                && tangent.w.abs() == 1.0;
+            let matches = expected.map_or(true, |expected| length(tangent - expected) < 1e-4);
+            assert!(
+                well_formed && matches,
+                "{} vertex {} has tangent {:?} for normal {:?}, expected {:?}",
+                name,
+                i,
+                tangent.as_array(),
+                vertex.normal().as_array(),
+                expected.map(|expected| *expected.as_array())
+            );
+        }
+    }
+
+    /// `data` with every vertex's UV replaced by `uv` of its position.
+    fn with_uvs(mut data: MeshData, uv: impl Fn(Vec3) -> Vec2) -> MeshData {
+        for vertex in &mut data.vertices {
+            *vertex = Vertex::new(
+                vertex.position(),
+                vec3(1.0, 1.0, 1.0),
+                uv(vertex.position()),
+                vertex.normal(),
+            );
+        }
+        compute_tangents(&mut data.vertices, &data.indices);
+        data
+    }
+
+    #[test]
+    fn tangents_follow_u() {
+        assert_tangents("quad", &quad_data(1.0), Some(vec4(1.0, 0.0, 0.0, 1.0)));
+        assert_tangents("cube", &cube_data(1.0), None);
+        assert_tangents("sphere", &uv_sphere_data(1.0, 8, 12), None);
+        assert_tangents("plane", &plane_data(1.0, 1.0, 2), None);
+    }
+
+    #[test]
+    fn tangents_of_mirrored_uvs_flip() {
+        let mirrored = with_uvs(quad_data(1.0), |position| {
+            vec2(-position.x + 0.5, 0.5 - position.y)
+        });
+        assert_tangents("mirrored quad", &mirrored, Some(vec4(-1.0, 0.0, 0.0, -1.0)));
+        // UVs that don't span anything still give a tangent to go by.
+        let flat = with_uvs(quad_data(1.0), |_| vec2(0.5, 0.5));
+        assert_tangents("quad without UVs", &flat, None);
+    }
+
+    #[test]
+    fn tangents_flip_under_mirroring_transforms() {
+        let mirror = ext::scale(&identity(), vec3(-1.0, 1.0, 1.0));
+        let transformed = quad_data(1.0).vertices[0].transformed(&mirror).tangent();
+        assert!(
+            length(transformed - vec4(-1.0, 0.0, 0.0, -1.0)) < 1e-4,
+            "the quad's tangent mirrored in x is {:?}",
+            transformed.as_array()
+        );
+    }
+
+    /// Bounds have to stay finite for meshes with no size.
+    #[test]
+    fn bounds_of_meshes_without_size() {
+        let zero = vec3(0.0, 0.0, 0.0);
+        let point = vec3(1.0, 2.0, 3.0);
+        let at = |position: Vec3| Vertex::new(position, zero, vec2(0.0, 0.0), zero);
+        for (name, vertices) in [
+            ("no vertices", vec![]),
+            ("one vertex", vec![at(point)]),
+            ("identical vertices", vec![at(point); 3]),
+        ] {
+            let data = MeshData {
+                vertices: vertices,
+                indices: vec![],
+            };
+            let (aabb, sphere) = (data.bounds(), data.bounding_sphere());
+            let expected = data.vertices.first().map_or(zero, Vertex::position);
+            assert!(
+                aabb.min == expected
+                    && aabb.max == expected
+                    && sphere.center == expected
+                    && sphere.radius == 0.0,
+                "{} are bounded by {:?} and {:?}",
+                name,
+                aabb,
+                sphere
+            );
+        }
+    }
+
+    /// Checks `make_mesh_u32` picks `expected` for a mesh whose indices
+    /// reach the last of `vertex_count` vertices.
+    fn assert_index_format(device: &wgpu::Device, vertex_count: u32, expected: wgpu::IndexFormat) {
+        let zero = vec3(0.0, 0.0, 0.0);
+        let vertex = Vertex::new(zero, zero, vec2(0.0, 0.0), zero);
+        let vertices = vec![vertex; vertex_count as usize];
+        let indices = [0, vertex_count - 1, 1];
+        let mesh = make_mesh_u32(device, "Index Format Test", &vertices, &indices);
+        assert_mesh(&mesh, vertex_count, 3);
+        assert_eq!(mesh.index_format, expected, "{} vertices", vertex_count);
+        assert_eq!(mesh.indices(), indices);
+    }
+
+    #[test]
+    fn indices_narrow_to_16_bits_when_they_fit() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        // The most vertices 16-bit indices reach, and one more.
+        assert_index_format(&device, u16::MAX as u32 + 1, wgpu::IndexFormat::Uint16);
+        assert_index_format(&device, u16::MAX as u32 + 2, wgpu::IndexFormat::Uint32);
+    }
+}
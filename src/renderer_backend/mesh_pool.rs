@@ -0,0 +1,151 @@
+use super::mesh_builder::{self, Mesh, Vertex};
+
+const VERTEX_SIZE: u64 = std::mem::size_of::<Vertex>() as u64;
+const INDEX_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+/// Where a mesh's geometry sits in a `MeshPool`.
+#[derive(Clone, Copy, Debug)]
+pub struct PooledMesh {
+    pub base_vertex: i32,
+    pub first_index: u32,
+    pub index_count: u32,
+}
+
+struct Slot {
+    mesh_id: u64,
+    revision: u64,
+    vertex_count: u32,
+    pooled: PooledMesh,
+}
+
+/// Several meshes packed one after another into a shared vertex buffer and
+/// a shared 32-bit index buffer, so one indirect draw can reach any of them.
+/// Indices stay relative to their own mesh; `base_vertex` offsets them.
+/// Meshes drawn without indices get `0..vertex_count`.
+pub struct MeshPool {
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    slots: Vec<Slot>,
+}
+
+impl MeshPool {
+    pub fn new() -> Self {
+        MeshPool {
+            vertex_buffer: None,
+            index_buffer: None,
+            slots: Vec::new(),
+        }
+    }
+
+    /// Packs `meshes`, in order. When they're the same meshes at the same
+    /// sizes as last time only those updated since get copied again.
+    /// Vertices are copied on the GPU, so this records into
+    /// `command_encoder` ahead of the passes drawing from the pool.
+    pub fn update(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        command_encoder: &mut wgpu::CommandEncoder,
+        meshes: &[&Mesh],
+    ) {
+        let same_layout = self.slots.len() == meshes.len()
+            && self.slots.iter().zip(meshes).all(|(slot, mesh)| {
+                slot.mesh_id == mesh.id()
+                    && slot.vertex_count == mesh.vertex_count
+                    && slot.pooled.index_count == pooled_index_count(mesh)
+            });
+        if !same_layout {
+            self.layout(device, meshes);
+        }
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            return;
+        };
+        for (slot, mesh) in self.slots.iter_mut().zip(meshes) {
+            if same_layout && slot.revision == mesh.revision() {
+                continue;
+            }
+            command_encoder.copy_buffer_to_buffer(
+                &mesh.vertex_buffer,
+                0,
+                vertex_buffer,
+                slot.pooled.base_vertex as u64 * VERTEX_SIZE,
+                mesh.vertex_count as u64 * VERTEX_SIZE,
+            );
+            let indices: Vec<u32> = match mesh.index_buffer {
+                Some(_) => mesh.indices().to_vec(),
+                None => (0..mesh.vertex_count).collect(),
+            };
+            queue.write_buffer(
+                index_buffer,
+                slot.pooled.first_index as u64 * INDEX_SIZE,
+                bytemuck::cast_slice(&indices),
+            );
+            slot.revision = mesh.revision();
+        }
+    }
+
+    /// Assigns every mesh its place, growing the buffers if they're too
+    /// small. Contents are filled in by `update`.
+    fn layout(&mut self, device: &wgpu::Device, meshes: &[&Mesh]) {
+        self.slots.clear();
+        let (mut vertex_count, mut index_count) = (0, 0);
+        for mesh in meshes {
+            let pooled = PooledMesh {
+                base_vertex: vertex_count as i32,
+                first_index: index_count,
+                index_count: pooled_index_count(mesh),
+            };
+            self.slots.push(Slot {
+                mesh_id: mesh.id(),
+                revision: mesh.revision(),
+                vertex_count: mesh.vertex_count,
+                pooled: pooled,
+            });
+            vertex_count += mesh.vertex_count;
+            index_count += pooled.index_count;
+        }
+
+        mesh_builder::grow_buffer(
+            device,
+            &mut self.vertex_buffer,
+            wgpu::BufferUsages::VERTEX,
+            "Mesh Pool Vertex Buffer",
+            vertex_count as u64 * VERTEX_SIZE,
+        );
+        mesh_builder::grow_buffer(
+            device,
+            &mut self.index_buffer,
+            wgpu::BufferUsages::INDEX,
+            "Mesh Pool Index Buffer",
+            index_count as u64 * INDEX_SIZE,
+        );
+    }
+
+    /// Where `mesh` went in the last `update`, if it was among the meshes.
+    pub fn get(&self, mesh: &Mesh) -> Option<PooledMesh> {
+        self.slots
+            .iter()
+            .find(|slot| slot.mesh_id == mesh.id())
+            .map(|slot| slot.pooled)
+    }
+
+    /// Binds the shared buffers for draws from the pool. `false`, binding
+    /// nothing, while the pool is still empty.
+    pub fn bind(&self, renderpass: &mut wgpu::RenderPass) -> bool {
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            return false;
+        };
+        renderpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        renderpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        true
+    }
+}
+
+fn pooled_index_count(mesh: &Mesh) -> u32 {
+    match mesh.index_buffer {
+        Some(_) => mesh.index_count,
+        None => mesh.vertex_count,
+    }
+}
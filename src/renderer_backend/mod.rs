@@ -2,4 +2,38 @@ pub mod pipeline;
 pub mod mesh_builder;
 pub mod bind_group_layout;
 pub mod bind_group;
-pub mod material;
\ No newline at end of file
+pub mod material;
+pub mod shader;
+pub mod uniform;
+pub mod camera;
+pub mod texture;
+pub mod instance;
+pub mod obj_loader;
+pub mod error;
+pub mod screenshot;
+pub mod compute;
+pub mod adapter;
+pub mod per_draw;
+pub mod transform_buffer;
+pub mod dynamic_uniform;
+pub mod render_pass;
+pub mod text;
+pub mod gpu_timer;
+pub mod color;
+pub mod gltf_loader;
+pub mod frustum;
+pub mod sampler;
+pub mod frame_uploader;
+pub mod viewport;
+pub mod mesh_pool;
+pub mod indirect;
+pub mod gpu_cull;
+pub mod validation;
+pub mod mesh_batch;
+pub mod scene;
+pub mod picker;
+pub mod assets;
+pub mod texture_atlas;
+pub mod sprite_batch;
+pub mod tilemap;
+pub mod raycast;
\ No newline at end of file
@@ -0,0 +1,285 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+use glm::*;
+
+use super::mesh_builder::{self, Vertex};
+use super::validation::ValidationError;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Parse {
+        line: usize,
+        message: String,
+    },
+    /// wgpu rejected the mesh's buffers.
+    Validation(ValidationError),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ObjError::Io { path, error } => {
+                write!(f, "can't read model {}: {}", path.display(), error)
+            }
+            ObjError::Parse { line, message } => write!(f, "line {}: {}", line, message),
+            ObjError::Validation(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+impl From<ValidationError> for ObjError {
+    fn from(error: ValidationError) -> Self {
+        ObjError::Validation(error)
+    }
+}
+
+fn parse_error(line: usize, message: String) -> ObjError {
+    ObjError::Parse {
+        line: line,
+        message: message,
+    }
+}
+
+fn parse_floats<const N: usize>(
+    line: usize,
+    fields: &[&str],
+    min: usize,
+) -> Result<[f32; N], ObjError> {
+    if fields.len() < min {
+        return Err(parse_error(line, format!("expected {} numbers", min)));
+    }
+    let mut values = [0.0; N];
+    for (value, field) in values.iter_mut().zip(fields) {
+        *value = field
+            .parse()
+            .map_err(|_| parse_error(line, format!("invalid number '{}'", field)))?;
+    }
+    Ok(values)
+}
+
+/// Turns a 1-based (or negative, relative to the end) OBJ index into a 0-based one.
+fn resolve_index(line: usize, field: &str, count: usize) -> Result<usize, ObjError> {
+    let index: i64 = field
+        .parse()
+        .map_err(|_| parse_error(line, format!("invalid index '{}'", field)))?;
+    let resolved = match index {
+        i if i > 0 => i - 1,
+        i if i < 0 => count as i64 + i,
+        _ => -1,
+    };
+    if resolved < 0 || resolved >= count as i64 {
+        return Err(parse_error(line, format!("index {} out of range", index)));
+    }
+    Ok(resolved as usize)
+}
+
+type Corner = (usize, Option<usize>, Option<usize>);
+
+fn parse_corner(
+    line: usize,
+    field: &str,
+    counts: (usize, usize, usize),
+) -> Result<Corner, ObjError> {
+    let mut parts = field.split('/');
+    let position = resolve_index(line, parts.next().unwrap_or(""), counts.0)?;
+    let uv = match parts.next() {
+        Some(uv) if !uv.is_empty() => Some(resolve_index(line, uv, counts.1)?),
+        _ => None,
+    };
+    let normal = match parts.next() {
+        Some(normal) if !normal.is_empty() => Some(resolve_index(line, normal, counts.2)?),
+        _ => None,
+    };
+    Ok((position, uv, normal))
+}
+
+pub fn parse_obj(source: &str) -> Result<(Vec<Vertex>, Vec<u32>), ObjError> {
+    let mut positions: Vec<Vec3> = Vec::new();
+    let mut uvs: Vec<Vec2> = Vec::new();
+    let mut normals: Vec<Vec3> = Vec::new();
+
+    let mut corners: Vec<Corner> = Vec::new();
+    let mut corner_lookup: HashMap<Corner, u32> = HashMap::new();
+    let mut computed_normals: Vec<Vec3> = Vec::new();
+    let mut indices: Vec<u32> = Vec::new();
+
+    for (line_index, line) in source.lines().enumerate() {
+        let line_number = line_index + 1;
+        let line = line.split('#').next().unwrap_or("");
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let Some((&keyword, fields)) = fields.split_first() else {
+            continue;
+        };
+
+        match keyword {
+            "v" => {
+                let [x, y, z] = parse_floats(line_number, fields, 3)?;
+                positions.push(vec3(x, y, z));
+            }
+            "vt" => {
+                let [u, v] = parse_floats(line_number, fields, 1)?;
+                uvs.push(vec2(u, v));
+            }
+            "vn" => {
+                let [x, y, z] = parse_floats(line_number, fields, 3)?;
+                normals.push(vec3(x, y, z));
+            }
+            "f" => {
+                if fields.len() < 3 {
+                    return Err(parse_error(
+                        line_number,
+                        "face needs at least 3 vertices".to_string(),
+                    ));
+                }
+                let counts = (positions.len(), uvs.len(), normals.len());
+                let face = fields
+                    .iter()
+                    .map(|field| parse_corner(line_number, field, counts))
+                    .collect::<Result<Vec<Corner>, ObjError>>()?;
+
+                let face_normal = cross(
+                    positions[face[1].0] - positions[face[0].0],
+                    positions[face[2].0] - positions[face[0].0],
+                );
+
+                let mut face_indices = Vec::with_capacity(face.len());
+                for corner in face {
+                    let index = *corner_lookup.entry(corner).or_insert_with(|| {
+                        corners.push(corner);
+                        computed_normals.push(vec3(0.0, 0.0, 0.0));
+                        (corners.len() - 1) as u32
+                    });
+                    computed_normals[index as usize] =
+                        computed_normals[index as usize] + face_normal;
+                    face_indices.push(index);
+                }
+
+                // Fan triangulation around the first corner.
+                for i in 1..face_indices.len() - 1 {
+                    indices.extend([face_indices[0], face_indices[i], face_indices[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut vertices: Vec<Vertex> = corners
+        .iter()
+        .zip(&computed_normals)
+        .map(|(&(position, uv, normal), &computed_normal)| {
+            let normal = match normal {
+                Some(normal) => normalize(normals[normal]),
+                None if length(computed_normal) > 0.0 => normalize(computed_normal),
+                None => vec3(0.0, 1.0, 0.0),
+            };
+            // OBJ puts the UV origin at the bottom left, wgpu at the top left.
+            let uv = uv.map_or(vec2(0.0, 0.0), |uv| vec2(uvs[uv].x, 1.0 - uvs[uv].y));
+            let color = normal * 0.5 + vec3(0.5, 0.5, 0.5);
+            Vertex::new(positions[position], color, uv, normal)
+        })
+        .collect();
+    mesh_builder::compute_tangents(&mut vertices, &indices);
+
+    Ok((vertices, indices))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uvs_pass_through_with_the_origin_at_the_top() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+vt 0 0
+vt 1 0
+vt 0.25 0.75
+f 1/1 2/2 3/3
+f 1 2 3
+";
+        let (vertices, indices) = parse_obj(source).unwrap();
+        let uvs: Vec<Vec2> = indices
+            .iter()
+            .map(|&index| vertices[index as usize].uv())
+            .collect();
+        let expected = [
+            // OBJ's v runs up the image, wgpu's down it.
+            vec2(0.0, 1.0),
+            vec2(1.0, 1.0),
+            vec2(0.25, 0.25),
+            // Corners without one get the origin.
+            vec2(0.0, 0.0),
+            vec2(0.0, 0.0),
+            vec2(0.0, 0.0),
+        ];
+        assert_eq!(uvs, expected);
+    }
+
+    /// Where each triangle's corners are.
+    fn triangles(source: &str) -> Vec<[Vec3; 3]> {
+        let (vertices, indices) = parse_obj(source).unwrap();
+        indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|i| vertices[triangle[i] as usize].position()))
+            .collect()
+    }
+
+    /// Corners repeat where faces share a position and a UV, so there are
+    /// fewer than four a face.
+    #[test]
+    fn cube_model_has_every_face() {
+        let source = std::fs::read_to_string("models/cube.obj").unwrap();
+        let (vertices, indices) = parse_obj(&source).unwrap();
+        assert_eq!((vertices.len(), indices.len()), (20, 36));
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_vertex() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 0 1 0
+f -3 -2 -1
+v 0 0 1
+f 1 -3 -1
+";
+        let (a, b, c, d) = (
+            vec3(0.0, 0.0, 0.0),
+            vec3(1.0, 0.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            vec3(0.0, 0.0, 1.0),
+        );
+        assert_eq!(triangles(source), [[a, b, c], [a, b, d]]);
+        assert!(matches!(
+            parse_obj("v 0 0 0\nf -2 1 1\n"),
+            Err(ObjError::Parse { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn quads_fan_into_two_triangles() {
+        let source = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+f 1 2 3 4
+";
+        let (vertices, indices) = parse_obj(source).unwrap();
+        assert_eq!(vertices.len(), 4);
+        assert_eq!(indices, [0, 1, 2, 0, 2, 3]);
+        for vertex in &vertices {
+            assert_eq!(vertex.normal(), vec3(0.0, 0.0, 1.0));
+        }
+    }
+}
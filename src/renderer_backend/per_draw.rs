@@ -0,0 +1,106 @@
+use glm::Mat4;
+
+use super::bind_group;
+use super::bind_group_layout::Layout;
+use super::uniform::{self, ModelUniform};
+
+/// Push constant space the scene shader's model matrix takes up.
+pub const PUSH_CONSTANT_SIZE: u32 = std::mem::size_of::<ModelUniform>() as u32;
+
+/// Bind group index of the model uniform when push constants aren't used.
+/// It comes last so the push constant pipeline can simply leave it out.
+pub const MODEL_GROUP: u32 = 3;
+
+/// How the per-draw model matrix reaches the scene shader.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Mode {
+    PushConstants,
+    UniformBuffer,
+}
+
+impl Mode {
+    /// Push constants when the adapter has the feature and enough space for
+    /// a model matrix, otherwise a uniform buffer per object.
+    pub fn for_adapter(adapter: &wgpu::Adapter) -> Self {
+        let supported = adapter.features().contains(wgpu::Features::PUSH_CONSTANTS)
+            && adapter.limits().max_push_constant_size >= PUSH_CONSTANT_SIZE;
+        match supported {
+            true => Mode::PushConstants,
+            false => Mode::UniformBuffer,
+        }
+    }
+
+    /// The mode a device created by `renderer::request_device` ended up with.
+    pub fn for_device(device: &wgpu::Device) -> Self {
+        match device.features().contains(wgpu::Features::PUSH_CONSTANTS) {
+            true => Mode::PushConstants,
+            false => Mode::UniformBuffer,
+        }
+    }
+}
+
+/// A model matrix applied on top of the per-object transforms, pushed right
+/// before the draws it's for or, without push constants, kept in its own
+/// uniform buffer and bound at `MODEL_GROUP`.
+pub struct ModelTransform {
+    matrix: Mat4,
+    model: ModelUniform,
+    binding: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+}
+
+impl ModelTransform {
+    /// `layout` is the uniform buffer layout; it's only used in
+    /// `Mode::UniformBuffer`.
+    pub fn new(
+        device: &wgpu::Device,
+        mode: Mode,
+        layout: &Layout,
+        model: &Mat4,
+        label: &str,
+    ) -> Self {
+        let matrix = *model;
+        let model = ModelUniform::new(model);
+        let binding = match mode {
+            Mode::PushConstants => None,
+            Mode::UniformBuffer => {
+                let buffer =
+                    uniform::make_uniform_buffer(device, label, bytemuck::bytes_of(&model));
+                let mut builder = bind_group::Builder::new(device);
+                builder.set_layout(layout);
+                builder.add_buffer(&buffer);
+                let bind_group = builder.build(label);
+                Some((buffer, bind_group))
+            }
+        };
+
+        ModelTransform {
+            matrix: matrix,
+            model: model,
+            binding: binding,
+        }
+    }
+
+    pub fn matrix(&self) -> &Mat4 {
+        &self.matrix
+    }
+
+    pub fn set(&mut self, queue: &wgpu::Queue, model: &Mat4) {
+        self.matrix = *model;
+        self.model = ModelUniform::new(model);
+        if let Some((buffer, _)) = &self.binding {
+            queue.write_buffer(buffer, 0, bytemuck::bytes_of(&self.model));
+        }
+    }
+
+    /// Makes this the model matrix for the following draws in `renderpass`.
+    pub fn bind<'a>(&'a self, renderpass: &mut wgpu::RenderPass<'a>) {
+        match &self.binding {
+            Some((_, bind_group)) => renderpass.set_bind_group(MODEL_GROUP, bind_group, &[]),
+            None => renderpass.set_push_constants(
+                wgpu::ShaderStages::VERTEX,
+                0,
+                bytemuck::bytes_of(&self.model),
+            ),
+        }
+    }
+}
@@ -0,0 +1,205 @@
+use std::sync::mpsc;
+
+use super::texture::Texture;
+
+/// What the id pass draws into: each object's index plus one, 0 where
+/// nothing was drawn.
+pub const ID_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+const ID_SIZE: u64 = std::mem::size_of::<u32>() as u64;
+
+/// Where the pick in flight is.
+enum Readback {
+    Free,
+    /// The id pass and the copy are recorded, but not yet submitted.
+    Recorded,
+    Mapping(mpsc::Receiver<Result<(), wgpu::BufferAsyncError>>),
+}
+
+/// Finds the object drawn at a pixel by drawing object ids into an offscreen
+/// target and copying that one pixel back. The id pass only runs on frames
+/// with a pick waiting, and the answer comes back a frame or two later.
+pub struct Picker {
+    target: Texture,
+    /// For the ids to test against when the scene's depth buffer is
+    /// multisampled and can't be shared with them.
+    depth: Option<Texture>,
+    readback_buffer: wgpu::Buffer,
+    requested: Option<(u32, u32)>,
+    readback: Readback,
+}
+
+impl Picker {
+    /// `config` gives the size of the scene's targets. The scene's depth
+    /// buffer is shared when `sample_count` is 1.
+    pub fn new(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> Self {
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Pick Readback Buffer"),
+            size: ID_SIZE,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        let (target, depth) = Self::create_targets(device, config, depth_format, sample_count);
+        Picker {
+            target: target,
+            depth: depth,
+            readback_buffer: readback_buffer,
+            requested: None,
+            readback: Readback::Free,
+        }
+    }
+
+    fn create_targets(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) -> (Texture, Option<Texture>) {
+        let target = Texture::create_readback_target(
+            device,
+            config.width.max(1),
+            config.height.max(1),
+            ID_FORMAT,
+            "Pick Target",
+        );
+        let depth = (sample_count > 1).then(|| {
+            Texture::create_depth_texture(device, config, depth_format, 1, "Pick Depth Texture")
+        });
+        (target, depth)
+    }
+
+    pub fn resize(
+        &mut self,
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        depth_format: wgpu::TextureFormat,
+        sample_count: u32,
+    ) {
+        (self.target, self.depth) =
+            Self::create_targets(device, config, depth_format, sample_count);
+    }
+
+    /// Asks for the id at pixel `x`, `y` of the target. Replaces a request
+    /// that hasn't been drawn yet.
+    pub fn request(&mut self, x: u32, y: u32) {
+        self.requested = Some((x, y));
+    }
+
+    /// The pixel to draw ids for this frame, if a pick is waiting and the
+    /// last one is back. The caller has to record `begin_pass` and
+    /// `copy_pixel` for it.
+    pub fn take_request(&mut self) -> Option<(u32, u32)> {
+        if !matches!(self.readback, Readback::Free) {
+            return None;
+        }
+        let (x, y) = self.requested.take()?;
+        self.readback = Readback::Recorded;
+        let size = self.target.texture.size();
+        Some((x.min(size.width - 1), y.min(size.height - 1)))
+    }
+
+    /// Starts the pass the ids are drawn in, cleared to 0. It tests depth
+    /// against `scene_depth`, which the scene has already been drawn into,
+    /// or has a depth buffer of its own when the scene's is multisampled.
+    pub fn begin_pass<'e>(
+        &self,
+        command_encoder: &'e mut wgpu::CommandEncoder,
+        scene_depth: &wgpu::TextureView,
+    ) -> wgpu::RenderPass<'e> {
+        let (depth, load) = match &self.depth {
+            Some(depth) => (&depth.view, wgpu::LoadOp::Clear(1.0)),
+            None => (scene_depth, wgpu::LoadOp::Load),
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Pick Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: &self.target.view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                view: depth,
+                depth_ops: Some(wgpu::Operations {
+                    load: load,
+                    store: wgpu::StoreOp::Store,
+                }),
+                stencil_ops: None,
+            }),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        command_encoder.begin_render_pass(&render_pass_descriptor)
+    }
+
+    /// Copies the id at `x`, `y` out, after the pass `begin_pass` started.
+    pub fn copy_pixel(&self, command_encoder: &mut wgpu::CommandEncoder, (x, y): (u32, u32)) {
+        command_encoder.copy_texture_to_buffer(
+            wgpu::TexelCopyTextureInfo {
+                texture: &self.target.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d { x: x, y: y, z: 0 },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::TexelCopyBufferInfo {
+                buffer: &self.readback_buffer,
+                layout: wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: None,
+                    rows_per_image: None,
+                },
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+    }
+
+    /// Starts mapping the pixel `encode` copied. Call once its command
+    /// buffer has been submitted.
+    pub fn frame_submitted(&mut self) {
+        if !matches!(self.readback, Readback::Recorded) {
+            return;
+        }
+        let (sender, receiver) = mpsc::channel();
+        self.readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |result| {
+                let _ = sender.send(result);
+            });
+        self.readback = Readback::Mapping(receiver);
+    }
+
+    /// The id the last pick found, 0 for none, once it's been read back.
+    pub fn poll(&mut self, device: &wgpu::Device) -> Option<u32> {
+        let _ = device.poll(wgpu::Maintain::Poll);
+        let Readback::Mapping(receiver) = &self.readback else {
+            return None;
+        };
+        let result = match receiver.try_recv() {
+            Ok(result) => result,
+            Err(mpsc::TryRecvError::Empty) => return None,
+            Err(mpsc::TryRecvError::Disconnected) => {
+                self.readback = Readback::Free;
+                return None;
+            }
+        };
+        self.readback = Readback::Free;
+        result.ok()?;
+        let id = {
+            let bytes = self.readback_buffer.slice(..).get_mapped_range();
+            bytemuck::cast_slice::<u8, u32>(&bytes)[0]
+        };
+        self.readback_buffer.unmap();
+        Some(id)
+    }
+}
@@ -1,13 +1,34 @@
-use std::env::current_dir;
-use std::fs;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+use super::shader::{self, ShaderError};
+use super::validation;
 
 pub struct Builder<'a> {
     shader_filename: String,
+    /// Compiled in place of the file's contents when set.
+    shader_source: Option<String>,
     vertex_entry: String,
     fragment_entry: String,
-    pixel_format: wgpu::TextureFormat,
+    constants: HashMap<String, f64>,
+    pixel_format: Option<wgpu::TextureFormat>,
+    depth_format: Option<wgpu::TextureFormat>,
+    sample_count: u32,
+    polygon_mode: wgpu::PolygonMode,
+    topology: wgpu::PrimitiveTopology,
+    strip_index_format: Option<wgpu::IndexFormat>,
+    front_face: wgpu::FrontFace,
+    cull_mode: Option<wgpu::Face>,
+    blend: Option<wgpu::BlendState>,
+    write_mask: wgpu::ColorWrites,
+    depth_write_enabled: bool,
+    depth_compare: wgpu::CompareFunction,
+    stencil: wgpu::StencilState,
+    depth_only: bool,
     vertex_buffer_layouts: Vec<wgpu::VertexBufferLayout<'static>>,
     bind_group_layouts: Vec<&'a wgpu::BindGroupLayout>,
+    push_constant_ranges: Vec<wgpu::PushConstantRange>,
     device: &'a wgpu::Device,
 }
 
@@ -15,18 +36,36 @@ impl<'a> Builder<'a> {
     pub fn new(device: &'a wgpu::Device) -> Self {
         Builder {
             shader_filename: "dummy".to_string(),
+            shader_source: None,
             vertex_entry: "dummy".to_string(),
             fragment_entry: "dummy".to_string(),
-            pixel_format: wgpu::TextureFormat::Rgba8Unorm,
+            constants: HashMap::new(),
+            pixel_format: None,
+            depth_format: None,
+            sample_count: 1,
+            polygon_mode: wgpu::PolygonMode::Fill,
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            strip_index_format: None,
+            front_face: wgpu::FrontFace::Ccw,
+            cull_mode: Some(wgpu::Face::Back),
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrites::ALL,
+            depth_write_enabled: true,
+            depth_compare: wgpu::CompareFunction::LessEqual,
+            stencil: wgpu::StencilState::default(),
+            depth_only: false,
             vertex_buffer_layouts: Vec::new(),
             bind_group_layouts: Vec::new(),
+            push_constant_ranges: Vec::new(),
             device: device,
         }
     }
 
     pub fn reset(&mut self) {
+        self.constants.clear();
         self.vertex_buffer_layouts.clear();
         self.bind_group_layouts.clear();
+        self.push_constant_ranges.clear();
     }
 
     pub fn add_vertex_buffer_layout(&mut self, layout: wgpu::VertexBufferLayout<'static>){
@@ -37,6 +76,12 @@ impl<'a> Builder<'a> {
         self.bind_group_layouts.push(layout);
     }
 
+    /// Needs `Features::PUSH_CONSTANTS` and a big enough
+    /// `max_push_constant_size` on the device.
+    pub fn add_push_constant_range(&mut self, stages: wgpu::ShaderStages, range: Range<u32>){
+        self.push_constant_ranges.push(wgpu::PushConstantRange { stages: stages, range: range });
+    }
+
     pub fn set_shader_module(
         &mut self,
         shader_filename: &str,
@@ -44,39 +89,147 @@ impl<'a> Builder<'a> {
         fragment_entry: &str,
     ) {
         self.shader_filename = shader_filename.to_string();
+        self.shader_source = None;
         self.vertex_entry = vertex_entry.to_string();
         self.fragment_entry = fragment_entry.to_string();
     }
 
+    /// Like `set_shader_module`, with WGSL put together by the caller;
+    /// `label` names it in errors.
+    pub fn set_shader_source(
+        &mut self,
+        label: &str,
+        source: String,
+        vertex_entry: &str,
+        fragment_entry: &str,
+    ) {
+        self.set_shader_module(label, vertex_entry, fragment_entry);
+        self.shader_source = Some(source);
+    }
+
+    /// Overrides a WGSL `override` declaration in either stage.
+    pub fn set_constant(&mut self, name: &str, value: f64) {
+        self.constants.insert(name.to_string(), value);
+    }
+
+    /// Has to match the target the pipeline draws into; there's no default.
     pub fn set_pixel_format(&mut self, pixel_format: wgpu::TextureFormat) {
-        self.pixel_format = pixel_format;
+        self.pixel_format = Some(pixel_format);
+    }
+
+    pub fn set_depth_format(&mut self, depth_format: wgpu::TextureFormat) {
+        self.depth_format = Some(depth_format);
+    }
+
+    pub fn set_sample_count(&mut self, sample_count: u32) {
+        self.sample_count = sample_count;
+    }
+
+    pub fn set_polygon_mode(&mut self, polygon_mode: wgpu::PolygonMode) {
+        self.polygon_mode = polygon_mode;
+    }
+
+    /// `strip_index_format` must be set for indexed strip topologies and
+    /// `None` otherwise.
+    pub fn set_topology(
+        &mut self,
+        topology: wgpu::PrimitiveTopology,
+        strip_index_format: Option<wgpu::IndexFormat>,
+    ) {
+        self.topology = topology;
+        self.strip_index_format = strip_index_format;
+    }
+
+    pub fn set_front_face(&mut self, front_face: wgpu::FrontFace) {
+        self.front_face = front_face;
+    }
+
+    pub fn set_cull_mode(&mut self, cull_mode: Option<wgpu::Face>) {
+        self.cull_mode = cull_mode;
+    }
+
+    pub fn set_blend_state(&mut self, blend: Option<wgpu::BlendState>) {
+        self.blend = blend;
+    }
+
+    pub fn set_write_mask(&mut self, write_mask: wgpu::ColorWrites) {
+        self.write_mask = write_mask;
+    }
+
+    /// Only used when a depth format is set.
+    pub fn set_depth_test(&mut self, depth_write_enabled: bool, depth_compare: wgpu::CompareFunction) {
+        self.depth_write_enabled = depth_write_enabled;
+        self.depth_compare = depth_compare;
+    }
+
+    pub fn set_stencil_state(&mut self, stencil: wgpu::StencilState) {
+        self.stencil = stencil;
+    }
+
+    /// The same stencil test for front and back faces. The reference value
+    /// is set on the render pass.
+    pub fn set_stencil(
+        &mut self,
+        compare: wgpu::CompareFunction,
+        fail_op: wgpu::StencilOperation,
+        pass_op: wgpu::StencilOperation,
+        read_mask: u32,
+        write_mask: u32,
+    ) {
+        let face = wgpu::StencilFaceState {
+            compare: compare,
+            fail_op: fail_op,
+            depth_fail_op: fail_op,
+            pass_op: pass_op,
+        };
+        self.stencil = wgpu::StencilState {
+            front: face,
+            back: face,
+            read_mask: read_mask,
+            write_mask: write_mask,
+        };
     }
 
-    pub fn build_pipeline(&mut self, label: &str) -> wgpu::RenderPipeline {
-        let mut filepath = current_dir().unwrap();
-        filepath.push("src/");
-        filepath.push(self.shader_filename.as_str());
-        let filepath = filepath.into_os_string().into_string().unwrap();
-        let source_code = fs::read_to_string(filepath).expect("Can't read source code!");
+    /// Leaves out the fragment stage and color target, for pipelines that
+    /// only write depth. No pixel format is needed then.
+    pub fn set_depth_only(&mut self, depth_only: bool) {
+        self.depth_only = depth_only;
+    }
 
-        let shader_module_descriptor = wgpu::ShaderModuleDescriptor {
-            label: Some("Shader Module"),
-            source: wgpu::ShaderSource::Wgsl(source_code.into()),
+    #[track_caller]
+    pub fn build_pipeline(&mut self, label: &str) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let device = self.device;
+        let render_pipeline =
+            validation::with_validation(device, label, || self.create_pipeline(label));
+        self.reset();
+        render_pipeline?
+    }
+
+    fn create_pipeline(&self, label: &str) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let shader_module = match &self.shader_source {
+            Some(source) => shader::create_wgsl(self.device, &self.shader_filename, source),
+            None => shader::load_wgsl(self.device, Path::new(&self.shader_filename))?,
         };
-        let shader_module = self.device.create_shader_module(shader_module_descriptor);
 
         let pipeline_layout_descriptor  = wgpu::PipelineLayoutDescriptor{
             label: Some(label),
             bind_group_layouts: &self.bind_group_layouts,
-            push_constant_ranges: &[],
+            push_constant_ranges: &self.push_constant_ranges,
         };
         let pipeline_layout = self.device.create_pipeline_layout(&pipeline_layout_descriptor);
 
-        let render_targets = [Some(wgpu::ColorTargetState{
-            format: self.pixel_format,
-            blend: Some(wgpu::BlendState::REPLACE),
-            write_mask: wgpu::ColorWrites::ALL,
-        })];
+        let render_targets = match self.depth_only {
+            true => Vec::new(),
+            false => {
+                let pixel_format = self.pixel_format
+                    .expect("set_pixel_format must be called before build_pipeline");
+                vec![Some(wgpu::ColorTargetState{
+                    format: pixel_format,
+                    blend: self.blend,
+                    write_mask: self.write_mask,
+                })]
+            }
+        };
 
         let render_pipeline_descriptor = wgpu::RenderPipelineDescriptor{
             label: Some(label),
@@ -85,30 +238,45 @@ impl<'a> Builder<'a> {
             vertex: wgpu::VertexState { 
                 module: &shader_module, 
                 entry_point: Some(&self.vertex_entry),
-                compilation_options: Default::default(),
+                compilation_options: wgpu::PipelineCompilationOptions {
+                    constants: &self.constants,
+                    ..Default::default()
+                },
                 buffers: &self.vertex_buffer_layouts,
             },
 
             primitive: wgpu::PrimitiveState{
-                topology: wgpu::PrimitiveTopology::TriangleList,
-                strip_index_format: None,
-                front_face: wgpu::FrontFace::Ccw,
-                cull_mode: Some(wgpu::Face::Back),
-                polygon_mode: wgpu::PolygonMode::Fill,
+                topology: self.topology,
+                strip_index_format: self.strip_index_format,
+                front_face: self.front_face,
+                cull_mode: self.cull_mode,
+                polygon_mode: self.polygon_mode,
                 unclipped_depth: false,
                 conservative: false
             },
 
-            fragment: Some(wgpu::FragmentState{
-                module: &shader_module,
-                entry_point: Some(&self.fragment_entry),
-                targets: &render_targets,
-                compilation_options: Default::default(),
-            }),
+            fragment: match self.depth_only {
+                true => None,
+                false => Some(wgpu::FragmentState{
+                    module: &shader_module,
+                    entry_point: Some(&self.fragment_entry),
+                    targets: &render_targets,
+                    compilation_options: wgpu::PipelineCompilationOptions {
+                        constants: &self.constants,
+                        ..Default::default()
+                    },
+                }),
+            },
 
-            depth_stencil: None,
+            depth_stencil: self.depth_format.map(|format| wgpu::DepthStencilState {
+                format: format,
+                depth_write_enabled: self.depth_write_enabled,
+                depth_compare: self.depth_compare,
+                stencil: self.stencil.clone(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
             multisample: wgpu::MultisampleState {
-                count: 1,
+                count: self.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
@@ -116,8 +284,6 @@ impl<'a> Builder<'a> {
             cache: None,
         };
 
-        let render_pipeline = self.device.create_render_pipeline(&render_pipeline_descriptor);
-        self.reset();
-        return render_pipeline;
+        return Ok(self.device.create_render_pipeline(&render_pipeline_descriptor));
     }
 }
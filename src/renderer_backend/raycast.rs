@@ -0,0 +1,162 @@
+use glm::*;
+
+use super::frustum::Aabb;
+
+/// Below this, a ray counts as parallel to a triangle's plane.
+const PARALLEL_EPSILON: f32 = 1e-7;
+
+/// A half line from `origin` along `direction`. Distances along it are in
+/// multiples of `direction`, which are world units when it's unit length.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Vec3,
+    pub direction: Vec3,
+}
+
+impl Ray {
+    pub fn at(&self, distance: f32) -> Vec3 {
+        self.origin + self.direction * distance
+    }
+
+    /// The same ray in the space `matrix` maps to. The direction isn't
+    /// normalized again, so distances along both rays are the same.
+    pub fn transformed(&self, matrix: &Mat4) -> Ray {
+        Ray {
+            origin: (*matrix * self.origin.extend(1.0)).truncate(3),
+            direction: (*matrix * self.direction.extend(0.0)).truncate(3),
+        }
+    }
+}
+
+/// How far along `ray` it enters `aabb`, or 0 when it starts inside; `None`
+/// when it misses. Slab test: the ray is inside the box where it's between
+/// all three pairs of planes at once.
+pub fn intersect_aabb(ray: &Ray, aabb: &Aabb) -> Option<f32> {
+    let mut near = 0.0f32;
+    let mut far = f32::INFINITY;
+    for axis in 0..3 {
+        let (origin, direction) = (ray.origin[axis], ray.direction[axis]);
+        if direction == 0.0 {
+            if origin < aabb.min[axis] || origin > aabb.max[axis] {
+                return None;
+            }
+            continue;
+        }
+        let t0 = (aabb.min[axis] - origin) / direction;
+        let t1 = (aabb.max[axis] - origin) / direction;
+        near = near.max(t0.min(t1));
+        far = far.min(t0.max(t1));
+        if near > far {
+            return None;
+        }
+    }
+    Some(near)
+}
+
+/// How far along `ray` it hits the triangle `a`, `b`, `c`, with
+/// Möller–Trumbore. Only the front, where the corners run counter-clockwise,
+/// is hit unless `two_sided`, the same as what back-face culling draws.
+pub fn intersect_triangle(ray: &Ray, [a, b, c]: [Vec3; 3], two_sided: bool) -> Option<f32> {
+    let edge1 = b - a;
+    let edge2 = c - a;
+    let p = cross(ray.direction, edge2);
+    let determinant = dot(edge1, p);
+    // Negative when the ray comes at the back.
+    let facing = match two_sided {
+        true => determinant.abs(),
+        false => determinant,
+    };
+    if facing < PARALLEL_EPSILON {
+        return None;
+    }
+    let inverse_determinant = 1.0 / determinant;
+    let s = ray.origin - a;
+    let u = dot(s, p) * inverse_determinant;
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+    let q = cross(s, edge1);
+    let v = dot(ray.direction, q) * inverse_determinant;
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+    let distance = dot(edge2, q) * inverse_determinant;
+    (distance > 0.0).then_some(distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: *Vec3::from_array(&origin),
+            direction: *Vec3::from_array(&direction),
+        }
+    }
+
+    fn same(a: Option<f32>, b: Option<f32>) -> bool {
+        match (a, b) {
+            (Some(a), Some(b)) => (a - b).abs() < 1e-4,
+            (a, b) => a.is_none() && b.is_none(),
+        }
+    }
+
+    #[test]
+    fn rays_enter_boxes() {
+        let aabb = Aabb {
+            min: vec3(-1.0, -1.0, -1.0),
+            max: vec3(1.0, 1.0, 1.0),
+        };
+        for (ray, expected) in [
+            (ray([0.0, 0.0, 5.0], [0.0, 0.0, -1.0]), Some(4.0)),
+            (ray([0.0, 0.0, 5.0], [0.0, 0.0, 1.0]), None),
+            (ray([0.0, 0.0, 0.0], [1.0, 0.0, 0.0]), Some(0.0)),
+            // Parallel to the x and y slabs, inside one and outside the other.
+            (ray([0.5, 0.5, 5.0], [0.0, 0.0, -1.0]), Some(4.0)),
+            (ray([2.0, 0.5, 5.0], [0.0, 0.0, -1.0]), None),
+            (ray([3.0, 3.0, 0.0], [-1.0, 0.0, 0.0]), None),
+        ] {
+            let distance = intersect_aabb(&ray, &aabb);
+            assert!(
+                same(distance, expected),
+                "a ray from {:?} along {:?} enters the box at {:?}, expected {:?}",
+                ray.origin.as_array(),
+                ray.direction.as_array(),
+                distance,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rays_hit_the_front_of_triangles() {
+        // Counter-clockwise seen from +z.
+        let triangle = [
+            vec3(-1.0, -1.0, 0.0),
+            vec3(1.0, -1.0, 0.0),
+            vec3(0.0, 1.0, 0.0),
+        ];
+        for (ray, two_sided, expected) in [
+            (ray([0.0, 0.0, 2.0], [0.0, 0.0, -1.0]), false, Some(2.0)),
+            (ray([0.0, 0.0, 2.0], [0.0, 0.0, 1.0]), false, None),
+            (ray([0.9, 0.9, 2.0], [0.0, 0.0, -1.0]), false, None),
+            // From behind.
+            (ray([0.0, 0.0, -2.0], [0.0, 0.0, 1.0]), false, None),
+            (ray([0.0, 0.0, -2.0], [0.0, 0.0, 1.0]), true, Some(2.0)),
+            // In the triangle's plane.
+            (ray([-3.0, 0.0, 0.0], [1.0, 0.0, 0.0]), true, None),
+            (ray([0.0, 0.0, 1.0], [1.0, 0.0, 0.0]), true, None),
+        ] {
+            let distance = intersect_triangle(&ray, triangle, two_sided);
+            assert!(
+                same(distance, expected),
+                "a ray from {:?} along {:?} hits the triangle at {:?}, expected {:?}",
+                ray.origin.as_array(),
+                ray.direction.as_array(),
+                distance,
+                expected
+            );
+        }
+    }
+}
@@ -0,0 +1,99 @@
+use super::camera::Camera;
+use super::color::ColorSpace;
+use super::frame_uploader::FrameUploader;
+use super::sampler::CachedSampler;
+use super::viewport::Viewport;
+
+/// The views one frame is drawn into.
+pub struct FrameTargets<'a> {
+    /// Where the finished frame ends up: the surface texture, or the
+    /// offscreen target when running headless.
+    pub surface: &'a wgpu::TextureView,
+    /// What passes draw color into; the MSAA texture when multisampling.
+    pub color: &'a wgpu::TextureView,
+    /// `surface` when `color` is multisampled and has to be resolved into it.
+    pub resolve: Option<&'a wgpu::TextureView>,
+    pub depth: &'a wgpu::TextureView,
+    /// Whether `depth` has a stencil aspect, which is cleared to 0 along
+    /// with depth.
+    pub stencil: bool,
+    /// Where scene passes draw, when that's less than the whole target.
+    pub viewport: Option<Viewport>,
+}
+
+impl<'a> FrameTargets<'a> {
+    /// Limits `renderpass` to `viewport`, if there is one.
+    pub fn apply_viewport(&self, renderpass: &mut wgpu::RenderPass) {
+        if let Some(viewport) = &self.viewport {
+            viewport.apply(renderpass);
+        }
+    }
+
+    pub fn color_attachment(
+        &self,
+        load: wgpu::LoadOp<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'a> {
+        wgpu::RenderPassColorAttachment {
+            view: self.color,
+            resolve_target: self.resolve,
+            ops: wgpu::Operations {
+                load: load,
+                store: wgpu::StoreOp::Store,
+            },
+        }
+    }
+
+    pub fn depth_attachment(
+        &self,
+        load: wgpu::LoadOp<f32>,
+    ) -> wgpu::RenderPassDepthStencilAttachment<'a> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: self.depth,
+            depth_ops: Some(wgpu::Operations {
+                load: load,
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: self.stencil.then(|| wgpu::Operations {
+                load: match load {
+                    wgpu::LoadOp::Clear(_) => wgpu::LoadOp::Clear(0),
+                    wgpu::LoadOp::Load => wgpu::LoadOp::Load,
+                },
+                store: wgpu::StoreOp::Store,
+            }),
+        }
+    }
+}
+
+/// What the target-dependent resources of a pass have to match.
+pub struct TargetInfo {
+    pub width: u32,
+    pub height: u32,
+    pub format: wgpu::TextureFormat,
+    pub depth_format: wgpu::TextureFormat,
+    pub sample_count: u32,
+    /// How colors authored in code are meant, for passes that bake them.
+    pub colors: ColorSpace,
+    /// The sampler materials currently sample with.
+    pub sampler: CachedSampler,
+}
+
+/// One stage of the frame. Passes are recorded in order after the scene,
+/// so they load the color and depth targets rather than clearing them.
+pub trait RenderPass {
+    /// Called before each frame is recorded, for passes that follow the
+    /// camera.
+    fn prepare(&mut self, _uploader: &mut FrameUploader, _camera: &Camera) {}
+
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets);
+
+    /// For post-processing passes: the texture everything before the pass
+    /// draws into in place of the frame. The pass reads it and writes to
+    /// `FrameTargets::surface`.
+    fn input(&self) -> Option<&wgpu::TextureView> {
+        None
+    }
+
+    /// Called whenever the targets change size or sample count, or the
+    /// color space changes.
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo);
+}
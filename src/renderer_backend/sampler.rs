@@ -0,0 +1,142 @@
+use std::collections::HashMap;
+
+/// The sampler settings materials can be switched between. The same
+/// address mode is used along every axis.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct SamplerDesc {
+    pub min_filter: wgpu::FilterMode,
+    pub mag_filter: wgpu::FilterMode,
+    pub mipmap_filter: wgpu::FilterMode,
+    pub address_mode: wgpu::AddressMode,
+    /// 1 turns anisotropic filtering off; at most 16.
+    pub anisotropy_clamp: u16,
+}
+
+impl SamplerDesc {
+    pub const MAX_ANISOTROPY: u16 = 16;
+
+    /// Every filter linear, with the given anisotropy.
+    pub const fn linear(anisotropy_clamp: u16) -> Self {
+        SamplerDesc {
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            address_mode: wgpu::AddressMode::Repeat,
+            anisotropy_clamp: anisotropy_clamp,
+        }
+    }
+
+    pub const TRILINEAR: SamplerDesc = SamplerDesc::linear(1);
+
+    /// Named settings to compare in the debug window.
+    pub const PRESETS: [(&'static str, SamplerDesc); 5] = [
+        (
+            "Nearest",
+            SamplerDesc {
+                min_filter: wgpu::FilterMode::Nearest,
+                mag_filter: wgpu::FilterMode::Nearest,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                address_mode: wgpu::AddressMode::Repeat,
+                anisotropy_clamp: 1,
+            },
+        ),
+        (
+            "Bilinear",
+            SamplerDesc {
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..SamplerDesc::TRILINEAR
+            },
+        ),
+        ("Trilinear", SamplerDesc::TRILINEAR),
+        ("Anisotropic 4x", SamplerDesc::linear(4)),
+        ("Anisotropic 16x", SamplerDesc::linear(16)),
+    ];
+
+    /// The preset this matches, ignoring the address mode.
+    pub fn preset_name(&self) -> &'static str {
+        let filters = SamplerDesc {
+            address_mode: wgpu::AddressMode::Repeat,
+            ..*self
+        };
+        SamplerDesc::PRESETS
+            .iter()
+            .find(|(_, preset)| *preset == filters)
+            .map_or("Custom", |(name, _)| name)
+    }
+
+    fn is_linear(&self) -> bool {
+        [self.min_filter, self.mag_filter, self.mipmap_filter]
+            .iter()
+            .all(|&filter| filter == wgpu::FilterMode::Linear)
+    }
+}
+
+/// A sampler along with the settings it was actually created with.
+#[derive(Clone)]
+pub struct CachedSampler {
+    pub desc: SamplerDesc,
+    pub sampler: wgpu::Sampler,
+}
+
+/// Hands out one `wgpu::Sampler` per distinct `SamplerDesc`.
+pub struct SamplerCache {
+    anisotropic: bool,
+    samplers: HashMap<SamplerDesc, wgpu::Sampler>,
+}
+
+impl SamplerCache {
+    pub fn new(adapter: &wgpu::Adapter) -> Self {
+        let anisotropic = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::ANISOTROPIC_FILTERING);
+        SamplerCache {
+            anisotropic: anisotropic,
+            samplers: HashMap::new(),
+        }
+    }
+
+    /// Lowers the anisotropy of `desc` to what the device can do, with a
+    /// warning, rather than failing validation.
+    fn supported(&self, desc: &SamplerDesc) -> SamplerDesc {
+        let mut desc = *desc;
+        let max_anisotropy = match self.anisotropic {
+            true => SamplerDesc::MAX_ANISOTROPY,
+            false => 1,
+        };
+        if desc.anisotropy_clamp > max_anisotropy {
+            eprintln!(
+                "Warning: {}x anisotropic filtering is not supported, clamping to {}x",
+                desc.anisotropy_clamp, max_anisotropy
+            );
+            desc.anisotropy_clamp = max_anisotropy;
+        }
+        if desc.anisotropy_clamp > 1 && !desc.is_linear() {
+            eprintln!("Warning: anisotropic filtering needs linear filters, turning it off");
+            desc.anisotropy_clamp = 1;
+        }
+        desc.anisotropy_clamp = desc.anisotropy_clamp.max(1);
+        desc
+    }
+
+    pub fn get(&mut self, device: &wgpu::Device, desc: &SamplerDesc) -> CachedSampler {
+        let desc = self.supported(desc);
+        let sampler = self.samplers.entry(desc).or_insert_with(|| {
+            device.create_sampler(&wgpu::SamplerDescriptor {
+                label: Some(desc.preset_name()),
+                address_mode_u: desc.address_mode,
+                address_mode_v: desc.address_mode,
+                address_mode_w: desc.address_mode,
+                min_filter: desc.min_filter,
+                mag_filter: desc.mag_filter,
+                mipmap_filter: desc.mipmap_filter,
+                anisotropy_clamp: desc.anisotropy_clamp,
+                ..Default::default()
+            })
+        });
+        CachedSampler {
+            desc: desc,
+            sampler: sampler.clone(),
+        }
+    }
+}
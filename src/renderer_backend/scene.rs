@@ -0,0 +1,454 @@
+use std::fmt;
+
+use glm::*;
+
+use super::mesh_builder::Mesh;
+use super::raycast::{self, Ray};
+use super::uniform;
+
+/// Where a ray first hits a node's mesh, `distance` along the ray.
+#[derive(Clone, Copy, Debug)]
+pub struct Hit {
+    pub entity: NodeId,
+    pub distance: f32,
+    pub point: Vec3,
+}
+
+/// A node's placement relative to its parent.
+#[derive(Clone, Copy, Debug)]
+pub struct Transform {
+    pub translation: Vec3,
+    /// Euler angles in radians, applied about Z, then X, then Y.
+    pub rotation: Vec3,
+    pub scale: Vec3,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Transform {
+            translation: vec3(0.0, 0.0, 0.0),
+            rotation: vec3(0.0, 0.0, 0.0),
+            scale: vec3(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Transform {
+    /// Scales, then rotates, then translates.
+    pub fn matrix(&self) -> Mat4 {
+        let mut model = ext::translate(&uniform::identity(), self.translation);
+        model = ext::rotate(&model, self.rotation.y, vec3(0.0, 1.0, 0.0));
+        model = ext::rotate(&model, self.rotation.x, vec3(1.0, 0.0, 0.0));
+        model = ext::rotate(&model, self.rotation.z, vec3(0.0, 0.0, 1.0));
+        ext::scale(&model, self.scale)
+    }
+}
+
+/// Refers to a node of a `Scene`. Once the node is removed its id stays
+/// dead, even after the slot is reused for a new node.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct NodeId {
+    index: u32,
+    generation: u32,
+}
+
+/// What `Scene::remove` does with the removed node's children.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Children {
+    /// They become roots, keeping their local transforms.
+    Orphan,
+    /// They're removed too, and theirs, all the way down.
+    Remove,
+}
+
+#[derive(Debug)]
+pub enum SceneError {
+    /// `parent` is `node` itself or one of its descendants.
+    Cycle { node: NodeId, parent: NodeId },
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SceneError::Cycle { node, parent } => write!(
+                f,
+                "can't parent node {} to node {}, which is itself or below it",
+                node.index, parent.index
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SceneError {}
+
+struct Node {
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+    local: Transform,
+    world: Mat4,
+    mesh: Option<usize>,
+    material: usize,
+    visible: bool,
+}
+
+struct Slot {
+    generation: u32,
+    node: Option<Node>,
+}
+
+/// A hierarchy of nodes, each placed relative to its parent, kept in an
+/// arena. Nodes can only be parented to nodes that aren't below them, so
+/// the hierarchy stays a forest. Nodes with a mesh are what gets drawn,
+/// with the mesh and material they refer to, at their world transform.
+/// Removing a node never disturbs the ids of the others.
+pub struct Scene {
+    slots: Vec<Slot>,
+    free: Vec<u32>,
+    roots: Vec<NodeId>,
+}
+
+impl Scene {
+    pub fn new() -> Self {
+        Scene {
+            slots: Vec::new(),
+            free: Vec::new(),
+            roots: Vec::new(),
+        }
+    }
+
+    /// Whether `id` still refers to a node. Every other method panics when
+    /// it doesn't.
+    pub fn contains(&self, id: NodeId) -> bool {
+        self.slots
+            .get(id.index as usize)
+            .is_some_and(|slot| slot.generation == id.generation && slot.node.is_some())
+    }
+
+    fn node(&self, id: NodeId) -> &Node {
+        assert!(self.contains(id), "node {} was removed", id.index);
+        self.slots[id.index as usize].node.as_ref().unwrap()
+    }
+
+    fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        assert!(self.contains(id), "node {} was removed", id.index);
+        self.slots[id.index as usize].node.as_mut().unwrap()
+    }
+
+    /// Adds a visible node with an identity transform and no mesh, under
+    /// `parent` or as a root.
+    pub fn add_node(&mut self, parent: Option<NodeId>) -> NodeId {
+        let node = Node {
+            parent: parent,
+            children: Vec::new(),
+            local: Transform::default(),
+            world: uniform::identity(),
+            mesh: None,
+            material: 0,
+            visible: true,
+        };
+        let id = match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.node = Some(node);
+                NodeId {
+                    index: index,
+                    generation: slot.generation,
+                }
+            }
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    node: Some(node),
+                });
+                NodeId {
+                    index: self.slots.len() as u32 - 1,
+                    generation: 0,
+                }
+            }
+        };
+        self.attach(id, parent);
+        id
+    }
+
+    fn attach(&mut self, id: NodeId, parent: Option<NodeId>) {
+        match parent {
+            Some(parent) => self.node_mut(parent).children.push(id),
+            None => self.roots.push(id),
+        }
+        self.node_mut(id).parent = parent;
+    }
+
+    fn detach(&mut self, id: NodeId) {
+        let siblings = match self.node(id).parent {
+            Some(parent) => &mut self.node_mut(parent).children,
+            None => &mut self.roots,
+        };
+        siblings.retain(|&sibling| sibling != id);
+    }
+
+    pub fn parent(&self, id: NodeId) -> Option<NodeId> {
+        self.node(id).parent
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        &self.node(id).children
+    }
+
+    /// Moves `id`, with everything below it, under `parent` or to the roots.
+    /// Fails, changing nothing, when that would make a cycle.
+    pub fn reparent(&mut self, id: NodeId, parent: Option<NodeId>) -> Result<(), SceneError> {
+        let mut ancestor = parent;
+        while let Some(node) = ancestor {
+            if node == id {
+                return Err(SceneError::Cycle {
+                    node: id,
+                    parent: parent.unwrap(),
+                });
+            }
+            ancestor = self.node(node).parent;
+        }
+        self.detach(id);
+        self.attach(id, parent);
+        Ok(())
+    }
+
+    /// Removes `id`, and with `Children::Remove` everything below it.
+    pub fn remove(&mut self, id: NodeId, children: Children) {
+        self.detach(id);
+        let mut removed = vec![id];
+        while let Some(id) = removed.pop() {
+            let slot = &mut self.slots[id.index as usize];
+            let node = slot.node.take().unwrap();
+            slot.generation += 1;
+            self.free.push(id.index);
+            match children {
+                Children::Orphan => {
+                    for child in node.children {
+                        self.attach(child, None);
+                    }
+                }
+                Children::Remove => removed.extend(node.children),
+            }
+        }
+    }
+
+    pub fn local_transform(&self, id: NodeId) -> Transform {
+        self.node(id).local
+    }
+
+    /// Takes effect on the node's world transform, and its descendants',
+    /// at the next `update_world_transforms`.
+    pub fn set_local_transform(&mut self, id: NodeId, transform: Transform) {
+        self.node_mut(id).local = transform;
+    }
+
+    /// The mesh drawn at the node: an index into whatever list of meshes the
+    /// scene is drawn with.
+    pub fn mesh(&self, id: NodeId) -> Option<usize> {
+        self.node(id).mesh
+    }
+
+    pub fn set_mesh(&mut self, id: NodeId, mesh: Option<usize>) {
+        self.node_mut(id).mesh = mesh;
+    }
+
+    /// The material the node's mesh is drawn with, an index like `mesh`.
+    pub fn material(&self, id: NodeId) -> usize {
+        self.node(id).material
+    }
+
+    pub fn set_material(&mut self, id: NodeId, material: usize) {
+        self.node_mut(id).material = material;
+    }
+
+    /// Hidden nodes aren't drawn, though their children still are.
+    pub fn visible(&self, id: NodeId) -> bool {
+        self.node(id).visible
+    }
+
+    pub fn set_visible(&mut self, id: NodeId, visible: bool) {
+        self.node_mut(id).visible = visible;
+    }
+
+    /// The node's transform combined with all of its ancestors', as of the
+    /// last `update_world_transforms`.
+    pub fn world_transform(&self, id: NodeId) -> Mat4 {
+        self.node(id).world
+    }
+
+    /// Recomputes every world transform, parents before their children.
+    pub fn update_world_transforms(&mut self) {
+        let mut pending: Vec<(NodeId, Mat4)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, uniform::identity()))
+            .collect();
+        while let Some((id, parent_world)) = pending.pop() {
+            let node = self.node_mut(id);
+            node.world = parent_world * node.local.matrix();
+            let world = node.world;
+            pending.extend(node.children.iter().map(|&child| (child, world)));
+        }
+    }
+
+    /// Every node with a mesh, with the mesh and its world transform.
+    pub fn meshes(&self) -> impl Iterator<Item = (NodeId, usize, &Mat4)> {
+        self.slots.iter().enumerate().filter_map(|(index, slot)| {
+            let node = slot.node.as_ref()?;
+            let id = NodeId {
+                index: index as u32,
+                generation: slot.generation,
+            };
+            node.mesh.map(|mesh| (id, mesh, &node.world))
+        })
+    }
+
+    /// The visible node whose mesh `ray` hits first, with `meshes` being
+    /// what the nodes' mesh indices index. Each mesh's bounds are tried
+    /// before its triangles, and only their front faces are hit.
+    pub fn raycast(&self, ray: &Ray, meshes: &[Mesh]) -> Option<Hit> {
+        let mut nearest: Option<Hit> = None;
+        for (id, mesh, world) in self.meshes() {
+            let Some(mesh) = meshes.get(mesh) else {
+                continue;
+            };
+            if !self.visible(id) {
+                continue;
+            }
+            let local = ray.transformed(&inverse(world));
+            let Some(enter) = raycast::intersect_aabb(&local, &mesh.bounds) else {
+                continue;
+            };
+            if nearest.is_some_and(|hit| hit.distance <= enter) {
+                continue;
+            }
+            let distance = mesh
+                .triangles()
+                .filter_map(|triangle| raycast::intersect_triangle(&local, triangle, false))
+                .min_by(f32::total_cmp);
+            let Some(distance) = distance else {
+                continue;
+            };
+            if nearest.is_none_or(|hit| distance < hit.distance) {
+                nearest = Some(Hit {
+                    entity: id,
+                    distance: distance,
+                    point: ray.at(distance),
+                });
+            }
+        }
+        nearest
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer_backend::mesh_builder;
+    use crate::test_support;
+
+    #[test]
+    fn world_transforms_follow_the_hierarchy() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None);
+        let child = scene.add_node(Some(root));
+        let grandchild = scene.add_node(Some(child));
+        scene.set_local_transform(
+            root,
+            Transform {
+                translation: vec3(1.0, 0.0, 0.0),
+                ..Transform::default()
+            },
+        );
+        scene.set_local_transform(
+            child,
+            Transform {
+                rotation: vec3(0.0, std::f32::consts::FRAC_PI_2, 0.0),
+                ..Transform::default()
+            },
+        );
+        scene.set_local_transform(
+            grandchild,
+            Transform {
+                translation: vec3(1.0, 0.0, 0.0),
+                scale: vec3(2.0, 2.0, 2.0),
+                ..Transform::default()
+            },
+        );
+        scene.update_world_transforms();
+
+        // Scaled to 3 along X by the grandchild, turned onto -Z by the child
+        // and moved along X by the root.
+        let point = scene.world_transform(grandchild) * vec4(1.0, 0.0, 0.0, 1.0);
+        let expected = vec3(1.0, 0.0, -3.0);
+        assert!(
+            length(point.truncate(3) - expected) < 1e-5,
+            "grandchild maps (1, 0, 0) to {:?}",
+            point.truncate(3)
+        );
+    }
+
+    #[test]
+    fn removing_and_reparenting() {
+        let mut scene = Scene::new();
+        let root = scene.add_node(None);
+        let child = scene.add_node(Some(root));
+        let grandchild = scene.add_node(Some(child));
+        assert!(scene.reparent(root, Some(grandchild)).is_err());
+        assert!(scene.reparent(root, Some(root)).is_err());
+
+        scene.remove(child, Children::Orphan);
+        assert!(!scene.contains(child));
+        assert_eq!(scene.parent(grandchild), None);
+        scene.reparent(grandchild, Some(root)).unwrap();
+        scene.remove(root, Children::Remove);
+        assert!(!scene.contains(grandchild));
+    }
+
+    /// Two cubes in a row along the ray and a hidden one in front of them:
+    /// the ray hits the nearer of the visible two, on its face.
+    #[test]
+    fn raycast_hits_the_nearest_visible_mesh() {
+        let Some((device, _queue)) = test_support::headless_device() else {
+            return;
+        };
+        let meshes = [mesh_builder::make_cube(&device, 1.0)];
+        let mut scene = Scene::new();
+        let mut add_cube = |z: f32, scale: f32| {
+            let node = scene.add_node(None);
+            scene.set_mesh(node, Some(0));
+            scene.set_local_transform(
+                node,
+                Transform {
+                    translation: vec3(0.0, 0.0, z),
+                    scale: vec3(scale, scale, scale),
+                    ..Transform::default()
+                },
+            );
+            node
+        };
+        let far = add_cube(-4.0, 1.0);
+        let near = add_cube(0.0, 2.0);
+        let hidden = add_cube(3.0, 1.0);
+        scene.set_visible(hidden, false);
+        scene.update_world_transforms();
+
+        let ray = Ray {
+            origin: vec3(0.2, 0.3, 10.0),
+            direction: vec3(0.0, 0.0, -1.0),
+        };
+        // The cube is 1 across, so twice that reaches 1 out.
+        let hit = scene.raycast(&ray, &meshes).expect("the near cube");
+        assert_eq!(hit.entity, near);
+        assert!((hit.distance - 9.0).abs() < 1e-4, "hit {:?}", hit);
+        scene.set_visible(near, false);
+        let hit = scene.raycast(&ray, &meshes).expect("the far cube");
+        assert_eq!(hit.entity, far);
+        assert!((hit.point.z + 3.5).abs() < 1e-4, "hit {:?}", hit);
+        let miss = Ray {
+            origin: vec3(5.0, 0.0, 10.0),
+            direction: vec3(0.0, 0.0, -1.0),
+        };
+        assert!(scene.raycast(&miss, &meshes).is_none());
+    }
+}
@@ -0,0 +1,198 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+
+use web_time::{SystemTime, UNIX_EPOCH};
+
+use super::texture::unpad_rows;
+
+#[derive(Debug)]
+pub enum ScreenshotError {
+    UnsupportedFormat(wgpu::TextureFormat),
+    Map(wgpu::BufferAsyncError),
+    Save {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+}
+
+impl fmt::Display for ScreenshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ScreenshotError::UnsupportedFormat(format) => {
+                write!(f, "can't read back {:?} textures", format)
+            }
+            ScreenshotError::Map(error) => write!(f, "can't map readback buffer: {}", error),
+            ScreenshotError::Save { path, error } => {
+                write!(f, "can't save {}: {}", path.display(), error)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScreenshotError {}
+
+/// Whether `format` reads back as RGBA8 once red and blue are swapped,
+/// rather than as it is.
+pub fn swaps_red_blue(format: wgpu::TextureFormat) -> Result<bool, ScreenshotError> {
+    match format {
+        wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => Ok(false),
+        wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => Ok(true),
+        _ => Err(ScreenshotError::UnsupportedFormat(format)),
+    }
+}
+
+/// Bytes between the rows of a `width` pixel wide copy into a buffer.
+pub fn padded_row_size(width: u32) -> u32 {
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    (width * 4).div_ceil(alignment) * alignment
+}
+
+/// A mappable buffer for a `width` by `height` copy.
+pub fn create_readback_buffer(device: &wgpu::Device, width: u32, height: u32) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Readback Buffer"),
+        size: (padded_row_size(width) * height) as wgpu::BufferAddress,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    })
+}
+
+/// Records a copy of all of `texture` into `buffer`, made for its size by
+/// `create_readback_buffer`.
+pub fn copy_to_buffer(
+    command_encoder: &mut wgpu::CommandEncoder,
+    texture: &wgpu::Texture,
+    buffer: &wgpu::Buffer,
+) {
+    command_encoder.copy_texture_to_buffer(
+        wgpu::TexelCopyTextureInfo {
+            texture: texture,
+            mip_level: 0,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        wgpu::TexelCopyBufferInfo {
+            buffer: buffer,
+            layout: wgpu::TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(padded_row_size(texture.width())),
+                rows_per_image: Some(texture.height()),
+            },
+        },
+        texture.size(),
+    );
+}
+
+/// Tightly packed RGBA8 rows from the mapped `bytes` of a copy
+/// `copy_to_buffer` made.
+pub fn to_rgba(bytes: &[u8], width: u32, height: u32, swap_red_blue: bool) -> Vec<u8> {
+    let mut rgba = unpad_rows(bytes, width, height, padded_row_size(width));
+    if swap_red_blue {
+        for pixel in rgba.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+    rgba
+}
+
+/// Copies `texture` into a mapped buffer and returns tightly packed RGBA8
+/// rows. Blocks on `device.poll` until the copy has finished.
+pub fn read_texture(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    texture: &wgpu::Texture,
+) -> Result<Vec<u8>, ScreenshotError> {
+    let swap_red_blue = swaps_red_blue(texture.format())?;
+    let buffer = create_readback_buffer(device, texture.width(), texture.height());
+
+    let mut command_encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+        label: Some("Readback Encoder"),
+    });
+    copy_to_buffer(&mut command_encoder, texture, &buffer);
+    queue.submit(std::iter::once(command_encoder.finish()));
+
+    let slice = buffer.slice(..);
+    let (sender, receiver) = mpsc::channel();
+    slice.map_async(wgpu::MapMode::Read, move |result| {
+        let _ = sender.send(result);
+    });
+    let _ = device.poll(wgpu::Maintain::Wait);
+    receiver
+        .recv()
+        .expect("map_async callback dropped without running")
+        .map_err(ScreenshotError::Map)?;
+
+    let rgba = to_rgba(
+        &slice.get_mapped_range(),
+        texture.width(),
+        texture.height(),
+        swap_red_blue,
+    );
+    buffer.unmap();
+    Ok(rgba)
+}
+
+pub fn save_png(
+    path: &Path,
+    width: u32,
+    height: u32,
+    rgba: Vec<u8>,
+) -> Result<(), ScreenshotError> {
+    let image = image::RgbaImage::from_raw(width, height, rgba)
+        .expect("pixel buffer doesn't match the image size");
+    image.save(path).map_err(|error| ScreenshotError::Save {
+        path: path.to_path_buf(),
+        error: error,
+    })
+}
+
+/// Puts two `width` by `height` RGBA8 images next to each other, `left`
+/// first, in one image twice as wide.
+pub fn side_by_side(width: u32, height: u32, left: &[u8], right: &[u8]) -> Vec<u8> {
+    let row_size = width as usize * 4;
+    let mut rgba = Vec::with_capacity(row_size * 2 * height as usize);
+    for (left_row, right_row) in left
+        .chunks_exact(row_size)
+        .zip(right.chunks_exact(row_size))
+    {
+        rgba.extend_from_slice(left_row);
+        rgba.extend_from_slice(right_row);
+    }
+    rgba
+}
+
+/// `screenshot_YYYY-MM-DD_HHMMSS.png` for the current UTC time.
+pub fn timestamped_filename() -> String {
+    let seconds = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    let (year, month, day) = civil_from_days((seconds / 86400) as i64);
+    let time_of_day = seconds % 86400;
+    format!(
+        "screenshot_{:04}-{:02}-{:02}_{:02}{:02}{:02}.png",
+        year,
+        month,
+        day,
+        time_of_day / 3600,
+        time_of_day / 60 % 60,
+        time_of_day % 60
+    )
+}
+
+/// Days since 1970-01-01 to a proleptic Gregorian (year, month, day), after
+/// Howard Hinnant's `civil_from_days`.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,155 @@
+use std::env::{current_dir, current_exe};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+use web_time::Instant;
+
+use super::assets;
+use super::validation::ValidationError;
+
+#[derive(Debug)]
+pub enum ShaderError {
+    NotFound { path: PathBuf, searched: Vec<PathBuf> },
+    Io { path: PathBuf, error: std::io::Error },
+    InvalidUtf8 { path: PathBuf },
+    /// Creating the module or the pipeline from it failed validation.
+    Compilation(ValidationError),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ShaderError::NotFound { path, searched } => {
+                write!(f, "shader {} not found, searched:", path.display())?;
+                for dir in searched {
+                    write!(f, " {}", dir.display())?;
+                }
+                Ok(())
+            }
+            ShaderError::Io { path, error } => {
+                write!(f, "can't read shader {}: {}", path.display(), error)
+            }
+            ShaderError::InvalidUtf8 { path } => {
+                write!(f, "shader {} is not valid UTF-8", path.display())
+            }
+            ShaderError::Compilation(error) => {
+                write!(f, "{} failed to compile:\n{}", error.label, error.message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ShaderError {}
+
+impl From<ValidationError> for ShaderError {
+    fn from(error: ValidationError) -> Self {
+        ShaderError::Compilation(error)
+    }
+}
+
+/// Directories a relative shader path is looked up in, in order: the working
+/// directory (`cargo run`), next to the executable (shipped binary) and the
+/// crate root the binary was built from. In the browser they're built in and
+/// found by the path as given.
+fn search_dirs() -> Vec<PathBuf> {
+    if cfg!(target_arch = "wasm32") {
+        return vec![PathBuf::new()];
+    }
+    let mut dirs = Vec::new();
+    if let Ok(dir) = current_dir() {
+        dirs.push(dir.join("src"));
+        dirs.push(dir);
+    }
+    if let Some(dir) = current_exe().ok().and_then(|exe| exe.parent().map(Path::to_path_buf)) {
+        dirs.push(dir);
+    }
+    dirs.push(Path::new(env!("CARGO_MANIFEST_DIR")).join("src"));
+    dirs
+}
+
+pub fn resolve(path: &Path) -> Result<PathBuf, ShaderError> {
+    if path.is_absolute() {
+        return match assets::exists(path) {
+            true => Ok(path.to_path_buf()),
+            false => Err(ShaderError::NotFound {
+                path: path.to_path_buf(),
+                searched: Vec::new(),
+            }),
+        };
+    }
+
+    let searched = search_dirs();
+    searched
+        .iter()
+        .map(|dir| dir.join(path))
+        .find(|candidate| assets::exists(candidate))
+        .ok_or_else(|| ShaderError::NotFound {
+            path: path.to_path_buf(),
+            searched: searched.clone(),
+        })
+}
+
+pub fn read_wgsl(path: &Path) -> Result<String, ShaderError> {
+    let filepath = resolve(path)?;
+    let bytes = assets::read(&filepath).map_err(|error| ShaderError::Io {
+        path: filepath.clone(),
+        error: error,
+    })?;
+    String::from_utf8(bytes).map_err(|_| ShaderError::InvalidUtf8 { path: filepath })
+}
+
+pub fn load_wgsl(device: &wgpu::Device, path: &Path) -> Result<wgpu::ShaderModule, ShaderError> {
+    let source_code = read_wgsl(path)?;
+    Ok(create_wgsl(device, &path.to_string_lossy(), &source_code))
+}
+
+pub fn create_wgsl(device: &wgpu::Device, label: &str, source_code: &str) -> wgpu::ShaderModule {
+    let shader_module_descriptor = wgpu::ShaderModuleDescriptor {
+        label: Some(label),
+        source: wgpu::ShaderSource::Wgsl(source_code.into()),
+    };
+    device.create_shader_module(shader_module_descriptor)
+}
+
+pub struct Watcher {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    last_check: Instant,
+}
+
+impl Watcher {
+    const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+    pub fn new(path: &Path) -> Self {
+        let mut watcher = Watcher {
+            path: path.to_path_buf(),
+            modified: None,
+            last_check: Instant::now(),
+        };
+        watcher.modified = watcher.modified_time();
+        watcher
+    }
+
+    fn modified_time(&self) -> Option<SystemTime> {
+        let filepath = resolve(&self.path).ok()?;
+        fs::metadata(filepath).and_then(|m| m.modified()).ok()
+    }
+
+    /// Polls the file's mtime at most once per `POLL_INTERVAL` and reports
+    /// whether it changed since the last call.
+    pub fn changed(&mut self) -> bool {
+        if self.last_check.elapsed() < Self::POLL_INTERVAL {
+            return false;
+        }
+        self.last_check = Instant::now();
+
+        let modified = self.modified_time();
+        if modified.is_some() && modified != self.modified {
+            self.modified = modified;
+            return true;
+        }
+        false
+    }
+}
@@ -0,0 +1,339 @@
+use std::ops::Range;
+
+use glm::{vec2, Vec2};
+
+use super::{
+    bind_group, bind_group_layout, mesh_builder, pipeline, shader::ShaderError, text,
+    texture::Texture, texture_atlas::AtlasRegion, uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/sprite.wgsl";
+
+const INDICES_PER_SPRITE: usize = 6;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct SpriteVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl SpriteVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn get_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<SpriteVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// A texture added to a `SpriteBatch` with `add_texture`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct SpriteTexture(usize);
+
+impl SpriteTexture {
+    /// Part of the texture, e.g. one image of an atlas.
+    pub fn region(self, region: AtlasRegion) -> SpriteImage {
+        SpriteImage {
+            texture: self,
+            region: region,
+        }
+    }
+}
+
+/// What a sprite shows: a texture, or a region of one.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct SpriteImage {
+    pub texture: SpriteTexture,
+    pub region: AtlasRegion,
+}
+
+impl From<SpriteTexture> for SpriteImage {
+    fn from(texture: SpriteTexture) -> Self {
+        texture.region(AtlasRegion::FULL)
+    }
+}
+
+/// What the last `draw` did. `reallocations` counts up over the batch's
+/// life; the others are for the last frame only.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct SpriteStats {
+    pub sprites: u32,
+    /// Draw calls, one for each texture used.
+    pub batches: u32,
+    /// Sprites the buffers hold before they have to grow.
+    pub capacity: u32,
+    pub reallocations: u32,
+}
+
+struct QueuedSprite {
+    texture: SpriteTexture,
+    vertices: [SpriteVertex; 4],
+}
+
+/// Textured, tinted and rotated quads in pixel coordinates, origin
+/// top-left. Sprites are queued during the frame and drawn together after
+/// the scene, alpha blended, with one draw call per texture. Sprites that
+/// share a texture draw in the order they were queued; across textures,
+/// in the order the textures were added.
+pub struct SpriteBatch {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    projection_buffer: wgpu::Buffer,
+    projection_bind_group: wgpu::BindGroup,
+    texture_layout: bind_group_layout::Layout,
+    texture_bind_groups: Vec<wgpu::BindGroup>,
+    white: SpriteTexture,
+    sprites: Vec<QueuedSprite>,
+    vertices: Vec<SpriteVertex>,
+    /// What `draw` uploaded: each texture with its range of indices.
+    batches: Vec<(SpriteTexture, Range<u32>)>,
+    /// Kept from frame to frame and only replaced to grow.
+    vertex_buffer: Option<wgpu::Buffer>,
+    index_buffer: Option<wgpu::Buffer>,
+    stats: SpriteStats,
+}
+
+impl SpriteBatch {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ShaderError> {
+        let projection_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            projection_layout = builder.build("Sprite Projection Bind Group Layout");
+        }
+        let texture_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_material();
+            texture_layout = builder.build("Sprite Texture Bind Group Layout");
+        }
+
+        let projection = uniform::CameraUniform::new(&text::orthographic(width, height));
+        let projection_buffer = uniform::make_uniform_buffer(
+            device,
+            "Sprite Projection Buffer",
+            bytemuck::bytes_of(&projection),
+        );
+        let projection_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&projection_layout);
+            builder.add_buffer(&projection_buffer);
+            projection_bind_group = builder.build("Sprite Projection Bind Group");
+        }
+
+        let pipeline: wgpu::RenderPipeline;
+        {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+            builder.set_pixel_format(format);
+            builder.set_cull_mode(None);
+            builder.set_blend_state(Some(wgpu::BlendState::ALPHA_BLENDING));
+            builder.add_vertex_buffer_layout(SpriteVertex::get_layout());
+            builder.add_bind_group_layout(&projection_layout);
+            builder.add_bind_group_layout(&texture_layout);
+            pipeline = builder.build_pipeline("Sprite Pipeline")?;
+        }
+
+        let mut batch = SpriteBatch {
+            device: device.clone(),
+            queue: queue.clone(),
+            pipeline: pipeline,
+            projection_buffer: projection_buffer,
+            projection_bind_group: projection_bind_group,
+            texture_layout: texture_layout,
+            texture_bind_groups: Vec::new(),
+            white: SpriteTexture(0),
+            sprites: Vec::new(),
+            vertices: Vec::new(),
+            batches: Vec::new(),
+            vertex_buffer: None,
+            index_buffer: None,
+            stats: SpriteStats::default(),
+        };
+        batch.white = batch.add_texture(&Texture::white(device, queue));
+        Ok(batch)
+    }
+
+    /// Makes `texture`, which must have a sampler, available to sprites.
+    pub fn add_texture(&mut self, texture: &Texture) -> SpriteTexture {
+        let bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(&self.device);
+            builder.set_layout(&self.texture_layout);
+            builder.add_material(&texture.view, texture.sampler.as_ref().unwrap());
+            bind_group = builder.build("Sprite Texture Bind Group");
+        }
+        self.texture_bind_groups.push(bind_group);
+        SpriteTexture(self.texture_bind_groups.len() - 1)
+    }
+
+    /// A plain white texture, for sprites that are only their color.
+    pub fn white(&self) -> SpriteTexture {
+        self.white
+    }
+
+    /// Keeps one unit equal to one framebuffer pixel.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let projection = uniform::CameraUniform::new(&text::orthographic(width, height));
+        self.queue
+            .write_buffer(&self.projection_buffer, 0, bytemuck::bytes_of(&projection));
+    }
+
+    /// Queues a `size` pixel sprite centered on pixel `position`, turned
+    /// `rotation` radians clockwise about its center. `color` multiplies
+    /// the texture.
+    pub fn draw_sprite(
+        &mut self,
+        image: impl Into<SpriteImage>,
+        position: Vec2,
+        size: Vec2,
+        rotation: f32,
+        color: [f32; 4],
+    ) {
+        let image = image.into();
+        let (sin, cos) = rotation.sin_cos();
+        let corner = |x: f32, y: f32| {
+            let offset = vec2(x * size.x, y * size.y) * 0.5;
+            let uv = image.region.map(vec2(x + 1.0, y + 1.0) * 0.5);
+            SpriteVertex {
+                position: [
+                    position.x + offset.x * cos - offset.y * sin,
+                    position.y + offset.x * sin + offset.y * cos,
+                ],
+                uv: [uv.x, uv.y],
+                color: color,
+            }
+        };
+        self.sprites.push(QueuedSprite {
+            texture: image.texture,
+            vertices: [
+                corner(-1.0, -1.0),
+                corner(-1.0, 1.0),
+                corner(1.0, 1.0),
+                corner(1.0, -1.0),
+            ],
+        });
+    }
+
+    pub fn stats(&self) -> SpriteStats {
+        self.stats
+    }
+
+    /// Uploads the sprites queued since the last call, grouped by texture,
+    /// growing the buffers if they don't fit.
+    fn upload(&mut self) {
+        self.sprites.sort_by_key(|sprite| sprite.texture);
+        self.vertices.clear();
+        self.batches.clear();
+        for (i, sprite) in self.sprites.iter().enumerate() {
+            self.vertices.extend_from_slice(&sprite.vertices);
+            let end = ((i + 1) * INDICES_PER_SPRITE) as u32;
+            match self.batches.last_mut() {
+                Some((texture, indices)) if *texture == sprite.texture => indices.end = end,
+                _ => self
+                    .batches
+                    .push((sprite.texture, end - INDICES_PER_SPRITE as u32..end)),
+            }
+        }
+        self.stats.sprites = self.sprites.len() as u32;
+        self.stats.batches = self.batches.len() as u32;
+        self.sprites.clear();
+        if self.vertices.is_empty() {
+            return;
+        }
+
+        let vertex_bytes = std::mem::size_of_val(self.vertices.as_slice()) as u64;
+        if mesh_builder::grow_buffer(
+            &self.device,
+            &mut self.vertex_buffer,
+            wgpu::BufferUsages::VERTEX,
+            "Sprite Vertex Buffer",
+            vertex_bytes,
+        ) {
+            self.stats.reallocations += 1;
+        }
+        let index_bytes = (self.stats.sprites as usize * INDICES_PER_SPRITE * 4) as u64;
+        let grown = mesh_builder::grow_buffer(
+            &self.device,
+            &mut self.index_buffer,
+            wgpu::BufferUsages::INDEX,
+            "Sprite Index Buffer",
+            index_bytes,
+        );
+        // The indices are the same six for every quad, so they're only
+        // written when the buffer is new, for as many as it holds.
+        if let Some(index_buffer) = self.index_buffer.as_ref().filter(|_| grown) {
+            self.stats.reallocations += 1;
+            let quads = index_buffer.size() as usize / (INDICES_PER_SPRITE * 4);
+            let indices: Vec<u32> = (0..quads as u32)
+                .flat_map(|quad| [0, 1, 2, 0, 2, 3].map(|corner| quad * 4 + corner))
+                .collect();
+            self.queue
+                .write_buffer(index_buffer, 0, bytemuck::cast_slice(&indices));
+        }
+        if let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        {
+            self.queue
+                .write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+            let vertex_capacity =
+                vertex_buffer.size() / std::mem::size_of::<[SpriteVertex; 4]>() as u64;
+            let index_capacity = index_buffer.size() / (INDICES_PER_SPRITE * 4) as u64;
+            self.stats.capacity = vertex_capacity.min(index_capacity) as u32;
+        }
+    }
+
+    /// Uploads the sprites queued since the last call and draws them into
+    /// `renderpass`, which must target the format given to `new`.
+    pub fn draw<'a>(&'a mut self, renderpass: &mut wgpu::RenderPass<'a>) {
+        self.upload();
+        let (Some(vertex_buffer), Some(index_buffer)) = (&self.vertex_buffer, &self.index_buffer)
+        else {
+            return;
+        };
+        if self.batches.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.projection_bind_group, &[]);
+        renderpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        renderpass.set_index_buffer(index_buffer.slice(..), wgpu::IndexFormat::Uint32);
+        for (texture, indices) in &self.batches {
+            renderpass.set_bind_group(1, &self.texture_bind_groups[texture.0], &[]);
+            renderpass.draw_indexed(indices.clone(), 0, 0..1);
+        }
+    }
+
+    /// Draws the queued sprites over `view` in a pass of its own.
+    pub fn encode(&mut self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Sprite Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        self.draw(&mut renderpass);
+    }
+}
@@ -0,0 +1,393 @@
+use super::{
+    bind_group, bind_group_layout, pipeline, shader::ShaderError, texture::Texture, uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/text.wgsl";
+
+/// Glyphs are 8x8 pixels in the atlas and drawn `SCALE` times as large,
+/// times the window's content scale.
+const GLYPH_SIZE: u32 = 8;
+const SCALE: f32 = 2.0;
+const ATLAS_COLUMNS: u32 = 16;
+const FIRST_CHAR: char = ' ';
+const MIN_VERTEX_CAPACITY: usize = 1024;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TextVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+    color: [f32; 4],
+}
+
+impl TextVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 3] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2, 2 => Float32x4];
+
+    fn get_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TextVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+/// Screen-space text from a baked monospace bitmap font. Strings are queued
+/// in pixel coordinates during the frame and drawn in one go, alpha blended
+/// over whatever is already in the target.
+pub struct TextRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    projection_buffer: wgpu::Buffer,
+    projection_bind_group: wgpu::BindGroup,
+    atlas_bind_group: wgpu::BindGroup,
+    vertex_buffer: wgpu::Buffer,
+    vertex_capacity: usize,
+    vertex_count: u32,
+    vertices: Vec<TextVertex>,
+    scale: f32,
+}
+
+impl TextRenderer {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, ShaderError> {
+        let projection_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            projection_layout = builder.build("Text Projection Bind Group Layout");
+        }
+        let atlas_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_material();
+            atlas_layout = builder.build("Font Atlas Bind Group Layout");
+        }
+
+        let projection = uniform::CameraUniform::new(&orthographic(width, height));
+        let projection_buffer = uniform::make_uniform_buffer(
+            device,
+            "Text Projection Buffer",
+            bytemuck::bytes_of(&projection),
+        );
+        let projection_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&projection_layout);
+            builder.add_buffer(&projection_buffer);
+            projection_bind_group = builder.build("Text Projection Bind Group");
+        }
+
+        let atlas = font_atlas(device, queue);
+        let atlas_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&atlas_layout);
+            builder.add_material(&atlas.view, atlas.sampler.as_ref().unwrap());
+            atlas_bind_group = builder.build("Font Atlas Bind Group");
+        }
+
+        let pipeline: wgpu::RenderPipeline;
+        {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+            builder.set_pixel_format(format);
+            builder.set_cull_mode(None);
+            builder.set_blend_state(Some(wgpu::BlendState::ALPHA_BLENDING));
+            builder.add_vertex_buffer_layout(TextVertex::get_layout());
+            builder.add_bind_group_layout(&projection_layout);
+            builder.add_bind_group_layout(&atlas_layout);
+            pipeline = builder.build_pipeline("Text Pipeline")?;
+        }
+
+        Ok(TextRenderer {
+            device: device.clone(),
+            queue: queue.clone(),
+            pipeline: pipeline,
+            projection_buffer: projection_buffer,
+            projection_bind_group: projection_bind_group,
+            atlas_bind_group: atlas_bind_group,
+            vertex_buffer: create_vertex_buffer(device, MIN_VERTEX_CAPACITY),
+            vertex_capacity: MIN_VERTEX_CAPACITY,
+            vertex_count: 0,
+            vertices: Vec::with_capacity(MIN_VERTEX_CAPACITY),
+            scale: SCALE,
+        })
+    }
+
+    /// Keeps one unit equal to one framebuffer pixel.
+    pub fn resize(&mut self, width: u32, height: u32) {
+        let projection = uniform::CameraUniform::new(&orthographic(width, height));
+        self.queue
+            .write_buffer(&self.projection_buffer, 0, bytemuck::bytes_of(&projection));
+    }
+
+    /// Rounded to whole pixels so the nearest-filtered glyphs stay sharp.
+    pub fn set_content_scale(&mut self, content_scale: f32) {
+        self.scale = (SCALE * content_scale).round().max(1.0);
+    }
+
+    pub fn line_height(&self) -> f32 {
+        GLYPH_SIZE as f32 * self.scale
+    }
+
+    /// Queues `text` with its top-left corner at pixel `(x, y)`. Newlines
+    /// start a new line below; characters outside printable ASCII show as
+    /// `?`.
+    pub fn queue_text(&mut self, x: f32, y: f32, text: &str, color: [f32; 4]) {
+        let advance = GLYPH_SIZE as f32 * self.scale;
+        let (mut pen_x, mut pen_y) = (x, y);
+        for character in text.chars() {
+            if character == '\n' {
+                pen_x = x;
+                pen_y += self.line_height();
+                continue;
+            }
+            if character != ' ' {
+                self.queue_glyph(pen_x, pen_y, character, color);
+            }
+            pen_x += advance;
+        }
+    }
+
+    fn queue_glyph(&mut self, x: f32, y: f32, character: char, color: [f32; 4]) {
+        let index = glyph_index(character).unwrap_or_else(|| glyph_index('?').unwrap());
+        let atlas_rows = (FONT.len() as u32).div_ceil(ATLAS_COLUMNS);
+        let u0 = (index % ATLAS_COLUMNS) as f32 / ATLAS_COLUMNS as f32;
+        let v0 = (index / ATLAS_COLUMNS) as f32 / atlas_rows as f32;
+        let u1 = u0 + 1.0 / ATLAS_COLUMNS as f32;
+        let v1 = v0 + 1.0 / atlas_rows as f32;
+
+        let size = GLYPH_SIZE as f32 * self.scale;
+        let (x1, y1) = (x + size, y + size);
+        let vertex = |position: [f32; 2], uv: [f32; 2]| TextVertex {
+            position: position,
+            uv: uv,
+            color: color,
+        };
+        self.vertices.extend_from_slice(&[
+            vertex([x, y], [u0, v0]),
+            vertex([x, y1], [u0, v1]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x, y], [u0, v0]),
+            vertex([x1, y1], [u1, v1]),
+            vertex([x1, y], [u1, v0]),
+        ]);
+    }
+
+    /// Uploads the text queued since the last call and draws it into
+    /// `renderpass`, which must target the format given to `new`.
+    pub fn draw<'a>(&'a mut self, renderpass: &mut wgpu::RenderPass<'a>) {
+        self.vertex_count = self.vertices.len() as u32;
+        if self.vertex_count == 0 {
+            return;
+        }
+        if self.vertices.len() > self.vertex_capacity {
+            self.vertex_capacity = self.vertices.len().next_power_of_two();
+            self.vertex_buffer = create_vertex_buffer(&self.device, self.vertex_capacity);
+        }
+        self.queue
+            .write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&self.vertices));
+        self.vertices.clear();
+
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.projection_bind_group, &[]);
+        renderpass.set_bind_group(1, &self.atlas_bind_group, &[]);
+        renderpass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        renderpass.draw(0..self.vertex_count, 0..1);
+    }
+
+    /// Draws the queued text over `view` in a pass of its own.
+    pub fn encode(&mut self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Text Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        self.draw(&mut renderpass);
+    }
+}
+
+fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("Text Vertex Buffer"),
+        size: (capacity * std::mem::size_of::<TextVertex>()) as u64,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
+
+/// Maps pixel coordinates, origin top-left and y down, to clip space.
+#[rustfmt::skip]
+pub fn orthographic(width: u32, height: u32) -> glm::Mat4 {
+    let (width, height) = (width.max(1) as f32, height.max(1) as f32);
+    glm::mat4(
+        2.0 / width, 0.0, 0.0, 0.0,
+        0.0, -2.0 / height, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        -1.0, 1.0, 0.0, 1.0,
+    )
+}
+
+fn glyph_index(character: char) -> Option<u32> {
+    let index = (character as u32).checked_sub(FIRST_CHAR as u32)?;
+    match (index as usize) < FONT.len() {
+        true => Some(index),
+        false => None,
+    }
+}
+
+/// Lays the font out `ATLAS_COLUMNS` glyphs wide as white pixels with the
+/// glyph coverage in alpha.
+fn font_atlas(device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+    let rows = (FONT.len() as u32).div_ceil(ATLAS_COLUMNS);
+    let mut atlas = image::RgbaImage::new(ATLAS_COLUMNS * GLYPH_SIZE, rows * GLYPH_SIZE);
+    for (index, glyph) in FONT.iter().enumerate() {
+        let origin_x = (index as u32 % ATLAS_COLUMNS) * GLYPH_SIZE;
+        let origin_y = (index as u32 / ATLAS_COLUMNS) * GLYPH_SIZE;
+        for (y, bits) in glyph.iter().enumerate() {
+            for x in 0..GLYPH_SIZE {
+                let alpha = if bits & (1 << x) != 0 { 255 } else { 0 };
+                atlas.put_pixel(
+                    origin_x + x,
+                    origin_y + y as u32,
+                    image::Rgba([255, 255, 255, alpha]),
+                );
+            }
+        }
+    }
+
+    let mut texture = Texture::from_image(
+        device,
+        queue,
+        &image::DynamicImage::ImageRgba8(atlas),
+        "Font Atlas",
+        false,
+    );
+    // Nearest filtering keeps the scaled-up pixels crisp.
+    texture.sampler = Some(device.create_sampler(&wgpu::SamplerDescriptor {
+        label: Some("Font Atlas Sampler"),
+        mag_filter: wgpu::FilterMode::Nearest,
+        min_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    }));
+    texture
+}
+
+/// The public domain font8x8_basic, printable ASCII only. One byte per row,
+/// top to bottom, least significant bit leftmost.
+#[rustfmt::skip]
+const FONT: [[u8; 8]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x18, 0x3C, 0x3C, 0x18, 0x18, 0x00, 0x18, 0x00], // !
+    [0x36, 0x36, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // "
+    [0x36, 0x36, 0x7F, 0x36, 0x7F, 0x36, 0x36, 0x00], // #
+    [0x0C, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x0C, 0x00], // $
+    [0x00, 0x63, 0x33, 0x18, 0x0C, 0x66, 0x63, 0x00], // %
+    [0x1C, 0x36, 0x1C, 0x6E, 0x3B, 0x33, 0x6E, 0x00], // &
+    [0x06, 0x06, 0x03, 0x00, 0x00, 0x00, 0x00, 0x00], // '
+    [0x18, 0x0C, 0x06, 0x06, 0x06, 0x0C, 0x18, 0x00], // (
+    [0x06, 0x0C, 0x18, 0x18, 0x18, 0x0C, 0x06, 0x00], // )
+    [0x00, 0x66, 0x3C, 0xFF, 0x3C, 0x66, 0x00, 0x00], // *
+    [0x00, 0x0C, 0x0C, 0x3F, 0x0C, 0x0C, 0x00, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ,
+    [0x00, 0x00, 0x00, 0x3F, 0x00, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C, 0x00], // .
+    [0x60, 0x30, 0x18, 0x0C, 0x06, 0x03, 0x01, 0x00], // /
+    [0x3E, 0x63, 0x73, 0x7B, 0x6F, 0x67, 0x3E, 0x00], // 0
+    [0x0C, 0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x3F, 0x00], // 1
+    [0x1E, 0x33, 0x30, 0x1C, 0x06, 0x33, 0x3F, 0x00], // 2
+    [0x1E, 0x33, 0x30, 0x1C, 0x30, 0x33, 0x1E, 0x00], // 3
+    [0x38, 0x3C, 0x36, 0x33, 0x7F, 0x30, 0x78, 0x00], // 4
+    [0x3F, 0x03, 0x1F, 0x30, 0x30, 0x33, 0x1E, 0x00], // 5
+    [0x1C, 0x06, 0x03, 0x1F, 0x33, 0x33, 0x1E, 0x00], // 6
+    [0x3F, 0x33, 0x30, 0x18, 0x0C, 0x0C, 0x0C, 0x00], // 7
+    [0x1E, 0x33, 0x33, 0x1E, 0x33, 0x33, 0x1E, 0x00], // 8
+    [0x1E, 0x33, 0x33, 0x3E, 0x30, 0x18, 0x0E, 0x00], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x00, 0x0C, 0x0C, 0x06], // ;
+    [0x18, 0x0C, 0x06, 0x03, 0x06, 0x0C, 0x18, 0x00], // <
+    [0x00, 0x00, 0x3F, 0x00, 0x00, 0x3F, 0x00, 0x00], // =
+    [0x06, 0x0C, 0x18, 0x30, 0x18, 0x0C, 0x06, 0x00], // >
+    [0x1E, 0x33, 0x30, 0x18, 0x0C, 0x00, 0x0C, 0x00], // ?
+    [0x3E, 0x63, 0x7B, 0x7B, 0x7B, 0x03, 0x1E, 0x00], // @
+    [0x0C, 0x1E, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x00], // A
+    [0x3F, 0x66, 0x66, 0x3E, 0x66, 0x66, 0x3F, 0x00], // B
+    [0x3C, 0x66, 0x03, 0x03, 0x03, 0x66, 0x3C, 0x00], // C
+    [0x1F, 0x36, 0x66, 0x66, 0x66, 0x36, 0x1F, 0x00], // D
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x46, 0x7F, 0x00], // E
+    [0x7F, 0x46, 0x16, 0x1E, 0x16, 0x06, 0x0F, 0x00], // F
+    [0x3C, 0x66, 0x03, 0x03, 0x73, 0x66, 0x7C, 0x00], // G
+    [0x33, 0x33, 0x33, 0x3F, 0x33, 0x33, 0x33, 0x00], // H
+    [0x1E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // I
+    [0x78, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E, 0x00], // J
+    [0x67, 0x66, 0x36, 0x1E, 0x36, 0x66, 0x67, 0x00], // K
+    [0x0F, 0x06, 0x06, 0x06, 0x46, 0x66, 0x7F, 0x00], // L
+    [0x63, 0x77, 0x7F, 0x7F, 0x6B, 0x63, 0x63, 0x00], // M
+    [0x63, 0x67, 0x6F, 0x7B, 0x73, 0x63, 0x63, 0x00], // N
+    [0x1C, 0x36, 0x63, 0x63, 0x63, 0x36, 0x1C, 0x00], // O
+    [0x3F, 0x66, 0x66, 0x3E, 0x06, 0x06, 0x0F, 0x00], // P
+    [0x1E, 0x33, 0x33, 0x33, 0x3B, 0x1E, 0x38, 0x00], // Q
+    [0x3F, 0x66, 0x66, 0x3E, 0x36, 0x66, 0x67, 0x00], // R
+    [0x1E, 0x33, 0x07, 0x0E, 0x38, 0x33, 0x1E, 0x00], // S
+    [0x3F, 0x2D, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // T
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x33, 0x3F, 0x00], // U
+    [0x33, 0x33, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // V
+    [0x63, 0x63, 0x63, 0x6B, 0x7F, 0x77, 0x63, 0x00], // W
+    [0x63, 0x63, 0x36, 0x1C, 0x1C, 0x36, 0x63, 0x00], // X
+    [0x33, 0x33, 0x33, 0x1E, 0x0C, 0x0C, 0x1E, 0x00], // Y
+    [0x7F, 0x63, 0x31, 0x18, 0x4C, 0x66, 0x7F, 0x00], // Z
+    [0x1E, 0x06, 0x06, 0x06, 0x06, 0x06, 0x1E, 0x00], // [
+    [0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x40, 0x00], // \
+    [0x1E, 0x18, 0x18, 0x18, 0x18, 0x18, 0x1E, 0x00], // ]
+    [0x08, 0x1C, 0x36, 0x63, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0xFF], // _
+    [0x0C, 0x0C, 0x18, 0x00, 0x00, 0x00, 0x00, 0x00], // `
+    [0x00, 0x00, 0x1E, 0x30, 0x3E, 0x33, 0x6E, 0x00], // a
+    [0x07, 0x06, 0x06, 0x3E, 0x66, 0x66, 0x3B, 0x00], // b
+    [0x00, 0x00, 0x1E, 0x33, 0x03, 0x33, 0x1E, 0x00], // c
+    [0x38, 0x30, 0x30, 0x3E, 0x33, 0x33, 0x6E, 0x00], // d
+    [0x00, 0x00, 0x1E, 0x33, 0x3F, 0x03, 0x1E, 0x00], // e
+    [0x1C, 0x36, 0x06, 0x0F, 0x06, 0x06, 0x0F, 0x00], // f
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x1F], // g
+    [0x07, 0x06, 0x36, 0x6E, 0x66, 0x66, 0x67, 0x00], // h
+    [0x0C, 0x00, 0x0E, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // i
+    [0x30, 0x00, 0x30, 0x30, 0x30, 0x33, 0x33, 0x1E], // j
+    [0x07, 0x06, 0x66, 0x36, 0x1E, 0x36, 0x67, 0x00], // k
+    [0x0E, 0x0C, 0x0C, 0x0C, 0x0C, 0x0C, 0x1E, 0x00], // l
+    [0x00, 0x00, 0x33, 0x7F, 0x7F, 0x6B, 0x63, 0x00], // m
+    [0x00, 0x00, 0x1F, 0x33, 0x33, 0x33, 0x33, 0x00], // n
+    [0x00, 0x00, 0x1E, 0x33, 0x33, 0x33, 0x1E, 0x00], // o
+    [0x00, 0x00, 0x3B, 0x66, 0x66, 0x3E, 0x06, 0x0F], // p
+    [0x00, 0x00, 0x6E, 0x33, 0x33, 0x3E, 0x30, 0x78], // q
+    [0x00, 0x00, 0x3B, 0x6E, 0x66, 0x06, 0x0F, 0x00], // r
+    [0x00, 0x00, 0x3E, 0x03, 0x1E, 0x30, 0x1F, 0x00], // s
+    [0x08, 0x0C, 0x3E, 0x0C, 0x0C, 0x2C, 0x18, 0x00], // t
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x33, 0x6E, 0x00], // u
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x1E, 0x0C, 0x00], // v
+    [0x00, 0x00, 0x63, 0x6B, 0x7F, 0x7F, 0x36, 0x00], // w
+    [0x00, 0x00, 0x63, 0x36, 0x1C, 0x36, 0x63, 0x00], // x
+    [0x00, 0x00, 0x33, 0x33, 0x33, 0x3E, 0x30, 0x1F], // y
+    [0x00, 0x00, 0x3F, 0x19, 0x0C, 0x26, 0x3F, 0x00], // z
+    [0x38, 0x0C, 0x0C, 0x07, 0x0C, 0x0C, 0x38, 0x00], // {
+    [0x18, 0x18, 0x18, 0x00, 0x18, 0x18, 0x18, 0x00], // |
+    [0x07, 0x0C, 0x0C, 0x38, 0x0C, 0x0C, 0x07, 0x00], // }
+    [0x6E, 0x3B, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // ~
+];
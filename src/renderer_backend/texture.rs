@@ -0,0 +1,519 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+use image::GenericImageView;
+
+use super::assets;
+use super::validation::{self, ValidationError};
+
+#[derive(Debug)]
+pub enum TextureError {
+    Io {
+        path: PathBuf,
+        error: std::io::Error,
+    },
+    Decode {
+        path: PathBuf,
+        error: image::ImageError,
+    },
+    /// A cubemap face that isn't square or doesn't match the first face.
+    FaceSize {
+        path: PathBuf,
+        size: (u32, u32),
+        expected: (u32, u32),
+    },
+    /// Two atlas images whose file names only differ in the extension.
+    DuplicateAtlasName { path: PathBuf },
+    /// Atlas images that don't fit in the largest texture the device allows.
+    AtlasOverflow {
+        max_size: u32,
+        unplaced: Vec<PathBuf>,
+    },
+    /// wgpu rejected the texture, e.g. for being larger than the device allows.
+    Validation(ValidationError),
+}
+
+impl fmt::Display for TextureError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TextureError::Io { path, error } => {
+                write!(f, "can't read texture {}: {}", path.display(), error)
+            }
+            TextureError::Decode { path, error } => {
+                write!(f, "can't decode texture {}: {}", path.display(), error)
+            }
+            TextureError::FaceSize {
+                path,
+                size,
+                expected,
+            } => write!(
+                f,
+                "cubemap face {} is {}x{}, expected {}x{}",
+                path.display(),
+                size.0,
+                size.1,
+                expected.0,
+                expected.1
+            ),
+            TextureError::DuplicateAtlasName { path } => write!(
+                f,
+                "atlas image {} has the same name as another one",
+                path.display()
+            ),
+            TextureError::AtlasOverflow { max_size, unplaced } => {
+                write!(f, "atlas images don't fit in {}x{}:", max_size, max_size)?;
+                for path in unplaced {
+                    write!(f, " {}", path.display())?;
+                }
+                Ok(())
+            }
+            TextureError::Validation(error) => write!(f, "{}", error),
+        }
+    }
+}
+
+impl std::error::Error for TextureError {}
+
+impl From<ValidationError> for TextureError {
+    fn from(error: ValidationError) -> Self {
+        TextureError::Validation(error)
+    }
+}
+
+pub struct Texture {
+    pub texture: wgpu::Texture,
+    pub view: wgpu::TextureView,
+    pub sampler: Option<wgpu::Sampler>,
+}
+
+/// Pads every row of tightly packed `rgba` pixels to `COPY_BYTES_PER_ROW_ALIGNMENT`.
+/// Returns the padded bytes and the padded row pitch.
+pub fn pad_rows(rgba: &[u8], width: u32, height: u32) -> (Vec<u8>, u32) {
+    let unpadded = width * 4;
+    let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    let padded = unpadded.div_ceil(alignment) * alignment;
+
+    let mut bytes = vec![0u8; (padded * height) as usize];
+    for row in 0..height as usize {
+        let src = &rgba[row * unpadded as usize..(row + 1) * unpadded as usize];
+        let dst_start = row * padded as usize;
+        bytes[dst_start..dst_start + unpadded as usize].copy_from_slice(src);
+    }
+    (bytes, padded)
+}
+
+/// Inverse of `pad_rows`: drops the padding from rows `padded` bytes apart.
+pub fn unpad_rows(bytes: &[u8], width: u32, height: u32, padded: u32) -> Vec<u8> {
+    let unpadded = (width * 4) as usize;
+    let mut rgba = Vec::with_capacity(unpadded * height as usize);
+    for row in 0..height as usize {
+        let start = row * padded as usize;
+        rgba.extend_from_slice(&bytes[start..start + unpadded]);
+    }
+    rgba
+}
+
+/// Levels in a full mip chain for a `width` by `height` texture, down to 1x1.
+pub const fn mip_level_count(width: u32, height: u32) -> u32 {
+    let largest = if width > height { width } else { height };
+    u32::BITS - (largest | 1).leading_zeros()
+}
+
+/// One side of mip `level`: halved per level, rounding down, but never 0.
+pub const fn mip_size(size: u32, level: u32) -> u32 {
+    let size = size >> level;
+    if size > 1 {
+        size
+    } else {
+        1
+    }
+}
+
+// Odd sizes round down: 257 goes 128, 64, ..., 2, 1 over 9 levels.
+const _: () = {
+    assert!(mip_level_count(1, 1) == 1);
+    assert!(mip_level_count(256, 256) == 9);
+    assert!(mip_level_count(257, 100) == 9);
+    assert!(mip_size(257, 1) == 128);
+    assert!(mip_size(100, 6) == 1);
+    assert!(mip_size(100, 8) == 1);
+};
+
+fn write_level(queue: &wgpu::Queue, texture: &wgpu::Texture, level: u32, rgba: &image::RgbaImage) {
+    let (width, height) = rgba.dimensions();
+    let (bytes, bytes_per_row) = pad_rows(rgba, width, height);
+    queue.write_texture(
+        wgpu::TexelCopyTextureInfoBase {
+            texture: texture,
+            mip_level: level,
+            origin: wgpu::Origin3d::ZERO,
+            aspect: wgpu::TextureAspect::All,
+        },
+        &bytes,
+        wgpu::TexelCopyBufferLayout {
+            offset: 0,
+            bytes_per_row: Some(bytes_per_row),
+            rows_per_image: Some(height),
+        },
+        wgpu::Extent3d {
+            width: width,
+            height: height,
+            depth_or_array_layers: 1,
+        },
+    );
+}
+
+impl Texture {
+    pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+    /// The depth format with a stencil aspect, for effects that mask pixels.
+    pub const DEPTH_STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+    /// `format` is `DEPTH_FORMAT` or `DEPTH_STENCIL_FORMAT`.
+    pub fn create_depth_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Texture {
+        Self::create_render_target(device, config, format, sample_count, label)
+    }
+
+    pub fn create_msaa_texture(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        sample_count: u32,
+        label: &str,
+    ) -> Texture {
+        Self::create_render_target(device, config, config.format, sample_count, label)
+    }
+
+    pub fn create_render_target(
+        device: &wgpu::Device,
+        config: &wgpu::SurfaceConfiguration,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: &str,
+    ) -> Texture {
+        let size = wgpu::Extent3d {
+            width: config.width.max(1),
+            height: config.height.max(1),
+            depth_or_array_layers: 1,
+        };
+
+        let texture_descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: size,
+            mip_level_count: 1,
+            sample_count: sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+
+        let texture = device.create_texture(&texture_descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Texture {
+            texture: texture,
+            view: view,
+            sampler: None,
+        }
+    }
+
+    /// A single-sampled color target that later passes can read from.
+    pub fn create_sampled_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Texture {
+        let texture_descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width.max(1),
+                height: height.max(1),
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+            view_formats: &[],
+        };
+
+        let texture = device.create_texture(&texture_descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Texture {
+            texture: texture,
+            view: view,
+            sampler: None,
+        }
+    }
+
+    /// A single-sampled color target that can be copied out of after rendering.
+    pub fn create_readback_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: &str,
+    ) -> Texture {
+        let texture_descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            size: wgpu::Extent3d {
+                width: width,
+                height: height,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        };
+
+        let texture = device.create_texture(&texture_descriptor);
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        Texture {
+            texture: texture,
+            view: view,
+            sampler: None,
+        }
+    }
+
+    pub fn load_image(path: &Path) -> Result<image::DynamicImage, TextureError> {
+        let bytes = assets::read(path).map_err(|error| TextureError::Io {
+            path: path.to_path_buf(),
+            error: error,
+        })?;
+        image::load_from_memory(&bytes).map_err(|error| TextureError::Decode {
+            path: path.to_path_buf(),
+            error: error,
+        })
+    }
+
+    #[track_caller]
+    pub fn from_file(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        path: &Path,
+        generate_mips: bool,
+    ) -> Result<Texture, TextureError> {
+        let loaded_image = Self::load_image(path)?;
+        let label = path.to_string_lossy();
+        let texture = validation::with_validation(device, &label, || {
+            Self::from_image(device, queue, &loaded_image, &label, generate_mips)
+        })?;
+        Ok(texture)
+    }
+
+    /// A 1x1 white texture, for materials that have no texture of their own.
+    pub fn white(device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba([255, 255, 255, 255]));
+        let white = image::DynamicImage::ImageRgba8(pixel);
+        Self::from_image(device, queue, &white, "White Texture", false)
+    }
+
+    /// A 1x1 normal map pointing straight out of the surface, for materials
+    /// that have no normal map of their own.
+    pub fn flat_normal(device: &wgpu::Device, queue: &wgpu::Queue) -> Texture {
+        let pixel = image::RgbaImage::from_pixel(1, 1, image::Rgba([128, 128, 255, 255]));
+        let flat = image::DynamicImage::ImageRgba8(pixel);
+        Self::from_image(device, queue, &flat, "Flat Normal Texture", false)
+    }
+
+    /// Loads six square faces of the same size into a cube texture, in
+    /// wgpu's layer order: +X, -X, +Y, -Y, +Z, -Z. The faces are taken to
+    /// be sRGB images.
+    #[track_caller]
+    pub fn cubemap_from_files(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: [&Path; 6],
+    ) -> Result<Texture, TextureError> {
+        let mut faces = Vec::with_capacity(paths.len());
+        for path in paths {
+            let face = Self::load_image(path)?;
+            let size = face.dimensions();
+            let expected = match faces.first() {
+                Some((_, first_size)) => *first_size,
+                None => (size.0, size.0),
+            };
+            if size != expected {
+                return Err(TextureError::FaceSize {
+                    path: path.to_path_buf(),
+                    size: size,
+                    expected: expected,
+                });
+            }
+            faces.push((face.to_rgba8(), size));
+        }
+        let size = faces[0].1;
+
+        let texture_descriptor = wgpu::TextureDescriptor {
+            label: Some("Cubemap"),
+            mip_level_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            size: wgpu::Extent3d {
+                width: size.0,
+                height: size.1,
+                depth_or_array_layers: 6,
+            },
+            sample_count: 1,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        };
+        let texture = validation::with_validation(device, "Cubemap", || {
+            device.create_texture(&texture_descriptor)
+        })?;
+
+        for (layer, (rgba, _)) in faces.iter().enumerate() {
+            let (bytes, bytes_per_row) = pad_rows(rgba, size.0, size.1);
+            queue.write_texture(
+                wgpu::TexelCopyTextureInfoBase {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &bytes,
+                wgpu::TexelCopyBufferLayout {
+                    offset: 0,
+                    bytes_per_row: Some(bytes_per_row),
+                    rows_per_image: Some(size.1),
+                },
+                wgpu::Extent3d {
+                    width: size.0,
+                    height: size.1,
+                    depth_or_array_layers: 1,
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            label: Some("Cubemap View"),
+            dimension: Some(wgpu::TextureViewDimension::Cube),
+            ..Default::default()
+        });
+
+        let sampler_descriptor = wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        };
+        let sampler = device.create_sampler(&sampler_descriptor);
+
+        Ok(Texture {
+            texture: texture,
+            view: view,
+            sampler: Some(sampler),
+        })
+    }
+
+    /// With `generate_mips` the image is uploaded with a full mip chain,
+    /// each level filtered down on the CPU from the one above it.
+    pub fn from_image(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        loaded_image: &image::DynamicImage,
+        label: &str,
+        generate_mips: bool,
+    ) -> Texture {
+        let converted = loaded_image.to_rgba8();
+        let size = loaded_image.dimensions();
+        let mip_level_count = match generate_mips {
+            true => mip_level_count(size.0, size.1),
+            false => 1,
+        };
+
+        let texture_size = wgpu::Extent3d {
+            width: size.0,
+            height: size.1,
+            depth_or_array_layers: 1,
+        };
+
+        let texture_descriptor = wgpu::TextureDescriptor {
+            label: Some(label),
+            mip_level_count: mip_level_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8Unorm,
+            size: texture_size,
+            sample_count: 1,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[wgpu::TextureFormat::Rgba8Unorm],
+        };
+
+        let texture = device.create_texture(&texture_descriptor);
+
+        write_level(queue, &texture, 0, &converted);
+        let mut level_image = converted;
+        for level in 1..mip_level_count {
+            level_image = image::imageops::resize(
+                &level_image,
+                mip_size(size.0, level),
+                mip_size(size.1, level),
+                image::imageops::FilterType::Triangle,
+            );
+            write_level(queue, &texture, level, &level_image);
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+
+        // Trilinear: blends between texels and between mip levels.
+        let sampler_descriptor = wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::Repeat,
+            address_mode_v: wgpu::AddressMode::Repeat,
+            address_mode_w: wgpu::AddressMode::Repeat,
+            min_filter: wgpu::FilterMode::Linear,
+            mag_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        };
+
+        let sampler = device.create_sampler(&sampler_descriptor);
+
+        Texture {
+            texture: texture,
+            view: view,
+            sampler: Some(sampler),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows_survive_padding() {
+        let alignment = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let height = 3;
+        for width in [1, 63, 64, 65, 300] {
+            let rgba: Vec<u8> = (0..width * height * 4).map(|i| i as u8).collect();
+            let (bytes, padded) = pad_rows(&rgba, width, height);
+            assert!(
+                padded % alignment == 0 && padded >= width * 4 && padded - width * 4 < alignment,
+                "{} pixel rows padded to {} bytes",
+                width,
+                padded
+            );
+            assert_eq!(bytes.len(), (padded * height) as usize);
+            assert_eq!(unpad_rows(&bytes, width, height, padded), rgba);
+        }
+    }
+}
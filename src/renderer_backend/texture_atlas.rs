@@ -0,0 +1,281 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::path::Path;
+
+use glm::{vec2, Vec2};
+
+use super::texture::{Texture, TextureError};
+use super::validation;
+
+/// Texels left empty between packed images, so filtering at a region's
+/// edge doesn't pick up its neighbours.
+pub const ATLAS_PADDING: u32 = 1;
+
+/// Where an image landed in an atlas, in texels from the top left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct PackedRect {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl PackedRect {
+    pub fn overlaps(&self, other: &PackedRect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+/// An atlas size and a rect for each of the sizes packed, in their order.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Packing {
+    pub width: u32,
+    pub height: u32,
+    pub rects: Vec<PackedRect>,
+}
+
+/// Packs `sizes` onto shelves, tallest first, in the smallest power-of-two
+/// atlas up to `max_size` square that holds them all, `padding` texels
+/// apart. Returns the indices of the sizes that don't fit even at
+/// `max_size`, if any.
+pub fn pack(sizes: &[(u32, u32)], max_size: u32, padding: u32) -> Result<Packing, Vec<usize>> {
+    let mut order: Vec<usize> = (0..sizes.len()).collect();
+    order.sort_by_key(|&i| Reverse((sizes[i].1, sizes[i].0)));
+
+    let area: u64 = sizes
+        .iter()
+        .map(|&(width, height)| (width + padding) as u64 * (height + padding) as u64)
+        .sum();
+    let widest = sizes.iter().map(|&(width, _)| width).max().unwrap_or(1);
+    let tallest = sizes.iter().map(|&(_, height)| height).max().unwrap_or(1);
+    let start = (area as f64).sqrt().ceil() as u32;
+    let mut width = start.max(widest).next_power_of_two().min(max_size);
+    let mut height = start.max(tallest).next_power_of_two().min(max_size);
+    loop {
+        let placed = pack_shelves(sizes, &order, width, height, padding);
+        if placed.iter().all(Option::is_some) {
+            return Ok(Packing {
+                width: width,
+                height: height,
+                rects: placed.into_iter().flatten().collect(),
+            });
+        }
+        match (width < max_size, height < max_size) {
+            (false, false) => {
+                return Err((0..sizes.len()).filter(|&i| placed[i].is_none()).collect());
+            }
+            // Grow the shorter side, keeping the atlas close to square.
+            (true, true) if height < width => height = (height * 2).min(max_size),
+            (true, _) => width = (width * 2).min(max_size),
+            (false, true) => height = (height * 2).min(max_size),
+        }
+    }
+}
+
+/// One pass over `order` at a fixed atlas size: left to right along a
+/// shelf as tall as its first image, then a new shelf below. An image that
+/// doesn't fit is skipped, and shorter ones after it may still fit.
+fn pack_shelves(
+    sizes: &[(u32, u32)],
+    order: &[usize],
+    width: u32,
+    height: u32,
+    padding: u32,
+) -> Vec<Option<PackedRect>> {
+    let mut placed = vec![None; sizes.len()];
+    let (mut x, mut y, mut shelf_height) = (0, 0, 0);
+    for &i in order {
+        let (image_width, image_height) = sizes[i];
+        if x > 0 && x + image_width > width {
+            x = 0;
+            y += shelf_height + padding;
+            shelf_height = 0;
+        }
+        if image_width > width || y + image_height > height {
+            continue;
+        }
+        placed[i] = Some(PackedRect {
+            x: x,
+            y: y,
+            width: image_width,
+            height: image_height,
+        });
+        x += image_width + padding;
+        shelf_height = shelf_height.max(image_height);
+    }
+    placed
+}
+
+/// A sub-rectangle of a texture in UV coordinates, (0, 0) at the top left.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct AtlasRegion {
+    pub min: Vec2,
+    pub max: Vec2,
+}
+
+impl AtlasRegion {
+    /// The whole texture.
+    pub const FULL: AtlasRegion = AtlasRegion {
+        min: Vec2 { x: 0.0, y: 0.0 },
+        max: Vec2 { x: 1.0, y: 1.0 },
+    };
+
+    /// Maps `uv` over a whole texture onto the region.
+    pub fn map(&self, uv: Vec2) -> Vec2 {
+        vec2(
+            self.min.x + (self.max.x - self.min.x) * uv.x,
+            self.min.y + (self.max.y - self.min.y) * uv.y,
+        )
+    }
+}
+
+/// Many small images packed into one texture, uploaded once, with the
+/// region each one ended up in keyed by its file name without extension.
+pub struct TextureAtlas {
+    pub texture: Texture,
+    rects: HashMap<String, PackedRect>,
+    width: u32,
+    height: u32,
+}
+
+impl TextureAtlas {
+    /// Loads and packs the images in `paths`, no larger than the device's
+    /// largest 2D texture. Packed images share texels at their edges when
+    /// mipmapped, so the atlas has only the one level.
+    #[track_caller]
+    pub fn from_files(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        paths: &[&Path],
+    ) -> Result<TextureAtlas, TextureError> {
+        let mut images = Vec::with_capacity(paths.len());
+        for &path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            if images.iter().any(|(other, _)| *other == name) {
+                return Err(TextureError::DuplicateAtlasName {
+                    path: path.to_path_buf(),
+                });
+            }
+            images.push((name, Texture::load_image(path)?.to_rgba8()));
+        }
+
+        let sizes: Vec<(u32, u32)> = images.iter().map(|(_, image)| image.dimensions()).collect();
+        let max_size = device.limits().max_texture_dimension_2d;
+        let packing = pack(&sizes, max_size, ATLAS_PADDING).map_err(|unplaced| {
+            TextureError::AtlasOverflow {
+                max_size: max_size,
+                unplaced: unplaced
+                    .into_iter()
+                    .map(|i| paths[i].to_path_buf())
+                    .collect(),
+            }
+        })?;
+
+        let mut pixels = image::RgbaImage::new(packing.width, packing.height);
+        for ((_, image), rect) in images.iter().zip(&packing.rects) {
+            image::imageops::replace(&mut pixels, image, rect.x as i64, rect.y as i64);
+        }
+        let pixels = image::DynamicImage::ImageRgba8(pixels);
+        let texture = validation::with_validation(device, "Texture Atlas", || {
+            Texture::from_image(device, queue, &pixels, "Texture Atlas", false)
+        })?;
+
+        let rects = images
+            .into_iter()
+            .map(|(name, _)| name.into_owned())
+            .zip(packing.rects)
+            .collect();
+        Ok(TextureAtlas {
+            texture: texture,
+            rects: rects,
+            width: packing.width,
+            height: packing.height,
+        })
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.rects.keys().map(String::as_str)
+    }
+
+    /// The region `name` was packed into. With `half_texel_inset` its edges
+    /// move half a texel in, onto the outermost texels' centers, so linear
+    /// filtering at the edge never reaches past the image.
+    pub fn region(&self, name: &str, half_texel_inset: bool) -> Option<AtlasRegion> {
+        let rect = self.rects.get(name)?;
+        let inset = match half_texel_inset {
+            true => 0.5,
+            false => 0.0,
+        };
+        let (width, height) = (self.width as f32, self.height as f32);
+        Some(AtlasRegion {
+            min: vec2(
+                (rect.x as f32 + inset) / width,
+                (rect.y as f32 + inset) / height,
+            ),
+            max: vec2(
+                ((rect.x + rect.width) as f32 - inset) / width,
+                ((rect.y + rect.height) as f32 - inset) / height,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_images_fit_without_touching() {
+        let sizes = [
+            (64, 64),
+            (32, 32),
+            (32, 32),
+            (32, 32),
+            (32, 32),
+            (16, 48),
+            (100, 10),
+            (1, 1),
+        ];
+        let packing = pack(&sizes, 256, ATLAS_PADDING).unwrap();
+        assert_eq!((packing.width, packing.height), (128, 128));
+        for (i, (rect, &(width, height))) in packing.rects.iter().zip(&sizes).enumerate() {
+            assert_eq!((rect.width, rect.height), (width, height), "image {}", i);
+            assert!(
+                rect.x + rect.width <= packing.width && rect.y + rect.height <= packing.height,
+                "image {} at {:?} is outside the atlas",
+                i,
+                rect
+            );
+            // Grown by the padding, no two may touch.
+            let padded = PackedRect {
+                width: rect.width + ATLAS_PADDING,
+                height: rect.height + ATLAS_PADDING,
+                ..*rect
+            };
+            if let Some(j) = (i + 1..sizes.len()).find(|&j| padded.overlaps(&packing.rects[j])) {
+                panic!("images {} and {} overlap", i, j);
+            }
+        }
+    }
+
+    /// The second square doesn't fit beside or below the first; the small
+    /// one still goes below it.
+    #[test]
+    fn overflowing_images_are_reported() {
+        let packing = pack(&[(200, 200), (200, 200), (10, 10)], 256, ATLAS_PADDING);
+        assert_eq!(packing.err(), Some(vec![1]));
+    }
+
+    #[test]
+    fn regions_map_uvs_into_the_atlas() {
+        let region = AtlasRegion {
+            min: vec2(0.25, 0.5),
+            max: vec2(0.5, 1.0),
+        };
+        let corner = region.map(vec2(1.0, 0.0));
+        assert_eq!((corner.x, corner.y), (0.5, 0.5));
+    }
+}
@@ -0,0 +1,584 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use wgpu::util::DeviceExt;
+
+use super::{
+    assets, bind_group, bind_group_layout,
+    camera::Camera,
+    frustum::Frustum,
+    mesh_builder, pipeline,
+    shader::ShaderError,
+    texture::TextureError,
+    texture_atlas::{AtlasRegion, TextureAtlas},
+    uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/tilemap.wgsl";
+
+/// Tiles along each side of a chunk. Each chunk has a vertex buffer of its
+/// own, rebuilt when one of its tiles changes.
+pub const CHUNK_SIZE: u32 = 32;
+
+const INDICES_PER_TILE: usize = 6;
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct TileVertex {
+    position: [f32; 2],
+    uv: [f32; 2],
+}
+
+impl TileVertex {
+    const ATTRIBUTES: [wgpu::VertexAttribute; 2] =
+        wgpu::vertex_attr_array![0 => Float32x2, 1 => Float32x2];
+
+    fn get_layout() -> wgpu::VertexBufferLayout<'static> {
+        wgpu::VertexBufferLayout {
+            array_stride: std::mem::size_of::<TileVertex>() as u64,
+            step_mode: wgpu::VertexStepMode::Vertex,
+            attributes: &Self::ATTRIBUTES,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub reason: &'static str,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.reason)
+    }
+}
+
+#[derive(Debug)]
+pub enum TilemapError {
+    Io {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: ParseError,
+    },
+    Texture(TextureError),
+    Shader(ShaderError),
+    /// The map uses a tile past the end of the tile images.
+    UnknownTile {
+        tile: u16,
+        tiles: usize,
+    },
+    /// A tile names an image the atlas wasn't built from.
+    MissingImage {
+        name: String,
+    },
+}
+
+impl fmt::Display for TilemapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TilemapError::Io { path, error } => write!(f, "{}: {}", path.display(), error),
+            TilemapError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            TilemapError::Texture(error) => write!(f, "{}", error),
+            TilemapError::Shader(error) => write!(f, "{}", error),
+            TilemapError::UnknownTile { tile, tiles } => {
+                write!(f, "the map uses tile {} but there are only {}", tile, tiles)
+            }
+            TilemapError::MissingImage { name } => write!(f, "no {} in the tile atlas", name),
+        }
+    }
+}
+
+impl From<TextureError> for TilemapError {
+    fn from(error: TextureError) -> Self {
+        TilemapError::Texture(error)
+    }
+}
+
+impl From<ShaderError> for TilemapError {
+    fn from(error: ShaderError) -> Self {
+        TilemapError::Shader(error)
+    }
+}
+
+/// A map of tile indices, row by row from the top left. Empty cells have
+/// no tile.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TileGrid {
+    pub width: u32,
+    pub height: u32,
+    tiles: Vec<Option<u16>>,
+}
+
+impl TileGrid {
+    pub fn new(width: u32, height: u32) -> Self {
+        TileGrid {
+            width: width,
+            height: height,
+            tiles: vec![None; (width * height) as usize],
+        }
+    }
+
+    /// Reads one row of comma-separated tile indices per line, all rows as
+    /// long as the first. `-1` or nothing at all leaves a cell empty.
+    pub fn parse_csv(text: &str) -> Result<Self, ParseError> {
+        let mut width = None;
+        let mut tiles = Vec::new();
+        let mut height = 0;
+        for (i, line) in text.lines().enumerate() {
+            let line_number = i + 1;
+            let error = |reason| ParseError {
+                line: line_number,
+                reason: reason,
+            };
+            let line = line.trim();
+            if line.is_empty() {
+                continue;
+            }
+            let row_start = tiles.len();
+            for cell in line.split(',').map(str::trim) {
+                let tile = match cell {
+                    "" | "-1" => None,
+                    cell => Some(cell.parse().map_err(|_| error("not a tile index"))?),
+                };
+                tiles.push(tile);
+            }
+            let row_width = (tiles.len() - row_start) as u32;
+            if *width.get_or_insert(row_width) != row_width {
+                return Err(error("row isn't as long as the first"));
+            }
+            height += 1;
+        }
+        match width {
+            Some(width) => Ok(TileGrid {
+                width: width,
+                height: height,
+                tiles: tiles,
+            }),
+            None => Err(ParseError {
+                line: 1,
+                reason: "no rows",
+            }),
+        }
+    }
+
+    pub fn get(&self, x: u32, y: u32) -> Option<u16> {
+        match x < self.width && y < self.height {
+            true => self.tiles[(y * self.width + x) as usize],
+            false => None,
+        }
+    }
+
+    /// Does nothing outside the map.
+    pub fn set(&mut self, x: u32, y: u32, tile: Option<u16>) {
+        if x < self.width && y < self.height {
+            self.tiles[(y * self.width + x) as usize] = tile;
+        }
+    }
+
+    /// How many chunks across and down the map is split into.
+    pub fn chunks(&self) -> (u32, u32) {
+        (
+            self.width.div_ceil(CHUNK_SIZE),
+            self.height.div_ceil(CHUNK_SIZE),
+        )
+    }
+
+    /// The index of the chunk tile `(x, y)` is in, row by row.
+    pub fn chunk_of(&self, x: u32, y: u32) -> usize {
+        let (columns, _) = self.chunks();
+        ((y / CHUNK_SIZE) * columns + x / CHUNK_SIZE) as usize
+    }
+
+    fn highest_tile(&self) -> Option<u16> {
+        self.tiles.iter().flatten().copied().max()
+    }
+}
+
+/// What the last `prepare` did.
+#[derive(Clone, Copy, Default, PartialEq, Eq, Debug)]
+pub struct TilemapStats {
+    pub chunks: u32,
+    /// Chunks in the camera's view, drawn.
+    pub drawn: u32,
+    pub rebuilt: u32,
+}
+
+struct Chunk {
+    vertex_buffer: Option<wgpu::Buffer>,
+    tiles: u32,
+    dirty: bool,
+}
+
+/// A grid of tiles from a `TextureAtlas`, drawn in world space through a
+/// camera, `tile_size` units to a tile with the map's top left corner at
+/// the origin and rows going down +y, as `Camera::pixel_space` sees them.
+/// The map is split into `CHUNK_SIZE` chunks, built once into meshes of
+/// their own; only the ones in view are drawn, and changing a tile only
+/// rebuilds its chunk.
+pub struct Tilemap {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    pipeline: wgpu::RenderPipeline,
+    camera_buffer: wgpu::Buffer,
+    camera_bind_group: wgpu::BindGroup,
+    atlas_bind_group: wgpu::BindGroup,
+    /// The same six indices for every tile, enough for a full chunk.
+    index_buffer: wgpu::Buffer,
+    grid: TileGrid,
+    /// Tile index to where it is in the atlas.
+    regions: Vec<AtlasRegion>,
+    tile_size: f32,
+    chunks: Vec<Chunk>,
+    /// What `prepare` found in view, for `draw`.
+    visible: Vec<usize>,
+    rebuilt: Vec<usize>,
+}
+
+impl Tilemap {
+    /// Loads the map at `map_path`, whose tile indices count into
+    /// `tile_paths`, packing those images into one atlas.
+    pub fn from_files(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        map_path: &Path,
+        tile_paths: &[&Path],
+        tile_size: f32,
+    ) -> Result<Self, TilemapError> {
+        let text = assets::read_to_string(map_path).map_err(|error| TilemapError::Io {
+            path: map_path.to_path_buf(),
+            error: error,
+        })?;
+        let grid = TileGrid::parse_csv(&text).map_err(|error| TilemapError::Parse {
+            path: map_path.to_path_buf(),
+            error: error,
+        })?;
+        let atlas = TextureAtlas::from_files(device, queue, tile_paths)?;
+        let names: Vec<String> = tile_paths
+            .iter()
+            .map(|path| {
+                path.file_stem()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        let names: Vec<&str> = names.iter().map(String::as_str).collect();
+        Self::new(device, queue, format, grid, &atlas, &names, tile_size)
+    }
+
+    /// Draws `grid` with tile `i` showing the atlas image `tile_names[i]`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        grid: TileGrid,
+        atlas: &TextureAtlas,
+        tile_names: &[&str],
+        tile_size: f32,
+    ) -> Result<Self, TilemapError> {
+        if let Some(tile) = grid
+            .highest_tile()
+            .filter(|&tile| tile as usize >= tile_names.len())
+        {
+            return Err(TilemapError::UnknownTile {
+                tile: tile,
+                tiles: tile_names.len(),
+            });
+        }
+        let regions = tile_names
+            .iter()
+            .map(|&name| {
+                atlas
+                    .region(name, true)
+                    .ok_or_else(|| TilemapError::MissingImage {
+                        name: name.to_string(),
+                    })
+            })
+            .collect::<Result<_, _>>()?;
+
+        let camera_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            camera_layout = builder.build("Tilemap Camera Bind Group Layout");
+        }
+        let atlas_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_material();
+            atlas_layout = builder.build("Tilemap Atlas Bind Group Layout");
+        }
+        let camera_uniform = uniform::CameraUniform::new(&uniform::identity());
+        let camera_buffer = uniform::make_uniform_buffer(
+            device,
+            "Tilemap Camera Buffer",
+            bytemuck::bytes_of(&camera_uniform),
+        );
+        let camera_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&camera_layout);
+            builder.add_buffer(&camera_buffer);
+            camera_bind_group = builder.build("Tilemap Camera Bind Group");
+        }
+        let atlas_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&atlas_layout);
+            builder.add_material(&atlas.texture.view, atlas.texture.sampler.as_ref().unwrap());
+            atlas_bind_group = builder.build("Tilemap Atlas Bind Group");
+        }
+
+        let pipeline: wgpu::RenderPipeline;
+        {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+            builder.set_pixel_format(format);
+            builder.set_cull_mode(None);
+            builder.set_blend_state(Some(wgpu::BlendState::ALPHA_BLENDING));
+            builder.add_vertex_buffer_layout(TileVertex::get_layout());
+            builder.add_bind_group_layout(&camera_layout);
+            builder.add_bind_group_layout(&atlas_layout);
+            pipeline = builder.build_pipeline("Tilemap Pipeline")?;
+        }
+
+        let indices: Vec<u16> = (0..(CHUNK_SIZE * CHUNK_SIZE) as u16)
+            .flat_map(|tile| [0, 1, 2, 0, 2, 3].map(|corner| tile * 4 + corner))
+            .collect();
+        let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Tilemap Index Buffer"),
+            contents: bytemuck::cast_slice(&indices),
+            usage: wgpu::BufferUsages::INDEX,
+        });
+
+        let (columns, rows) = grid.chunks();
+        let chunks = (0..columns * rows)
+            .map(|_| Chunk {
+                vertex_buffer: None,
+                tiles: 0,
+                dirty: true,
+            })
+            .collect();
+        Ok(Tilemap {
+            device: device.clone(),
+            queue: queue.clone(),
+            pipeline: pipeline,
+            camera_buffer: camera_buffer,
+            camera_bind_group: camera_bind_group,
+            atlas_bind_group: atlas_bind_group,
+            index_buffer: index_buffer,
+            grid: grid,
+            regions: regions,
+            tile_size: tile_size,
+            chunks: chunks,
+            visible: Vec::new(),
+            rebuilt: Vec::new(),
+        })
+    }
+
+    pub fn grid(&self) -> &TileGrid {
+        &self.grid
+    }
+
+    /// World units across and down the whole map.
+    pub fn size(&self) -> (f32, f32) {
+        (
+            self.grid.width as f32 * self.tile_size,
+            self.grid.height as f32 * self.tile_size,
+        )
+    }
+
+    /// Changes one tile; its chunk is rebuilt by the next `prepare`.
+    /// Returns false, changing nothing, outside the map or for a tile
+    /// there's no image for.
+    pub fn set_tile(&mut self, x: u32, y: u32, tile: Option<u16>) -> bool {
+        let known = tile.is_none_or(|tile| (tile as usize) < self.regions.len());
+        if !known || x >= self.grid.width || y >= self.grid.height {
+            return false;
+        }
+        if self.grid.get(x, y) != tile {
+            self.grid.set(x, y, tile);
+            let chunk = self.grid.chunk_of(x, y);
+            self.chunks[chunk].dirty = true;
+        }
+        true
+    }
+
+    /// The chunks the last `prepare` rebuilt.
+    pub fn rebuilt_chunks(&self) -> &[usize] {
+        &self.rebuilt
+    }
+
+    pub fn stats(&self) -> TilemapStats {
+        TilemapStats {
+            chunks: self.chunks.len() as u32,
+            drawn: self.visible.len() as u32,
+            rebuilt: self.rebuilt.len() as u32,
+        }
+    }
+
+    /// Rebuilds the chunks whose tiles changed and picks out the ones
+    /// `camera` sees, for `draw`.
+    pub fn prepare(&mut self, camera: &Camera) {
+        let view_projection = camera.build_view_projection();
+        let camera_uniform = uniform::CameraUniform::new(&view_projection);
+        self.queue
+            .write_buffer(&self.camera_buffer, 0, bytemuck::bytes_of(&camera_uniform));
+
+        self.rebuilt.clear();
+        for index in 0..self.chunks.len() {
+            if self.chunks[index].dirty {
+                self.rebuild_chunk(index);
+                self.rebuilt.push(index);
+            }
+        }
+
+        // The world rectangle the view covers, from the frustum's corners.
+        let corners = Frustum::from_matrix(&view_projection).corners();
+        let (mut min, mut max) = (corners[0], corners[0]);
+        for corner in &corners[1..] {
+            min = glm::min(min, *corner);
+            max = glm::max(max, *corner);
+        }
+        let (columns, _) = self.grid.chunks();
+        let chunk_size = CHUNK_SIZE as f32 * self.tile_size;
+        self.visible.clear();
+        for (index, chunk) in self.chunks.iter().enumerate() {
+            let x = (index as u32 % columns) as f32 * chunk_size;
+            let y = (index as u32 / columns) as f32 * chunk_size;
+            let overlaps =
+                x < max.x && x + chunk_size > min.x && y < max.y && y + chunk_size > min.y;
+            if overlaps && chunk.tiles > 0 {
+                self.visible.push(index);
+            }
+        }
+    }
+
+    /// Writes the quads of the tiles in chunk `index` into its own buffer,
+    /// growing it if it's too small.
+    fn rebuild_chunk(&mut self, index: usize) {
+        let (columns, _) = self.grid.chunks();
+        let origin_x = (index as u32 % columns) * CHUNK_SIZE;
+        let origin_y = (index as u32 / columns) * CHUNK_SIZE;
+        let mut vertices = Vec::new();
+        for y in origin_y..(origin_y + CHUNK_SIZE).min(self.grid.height) {
+            for x in origin_x..(origin_x + CHUNK_SIZE).min(self.grid.width) {
+                let Some(tile) = self.grid.get(x, y) else {
+                    continue;
+                };
+                let region = &self.regions[tile as usize];
+                let (x0, y0) = (x as f32 * self.tile_size, y as f32 * self.tile_size);
+                let (x1, y1) = (x0 + self.tile_size, y0 + self.tile_size);
+                let vertex = |position: [f32; 2], uv: glm::Vec2| TileVertex {
+                    position: position,
+                    uv: [uv.x, uv.y],
+                };
+                vertices.extend_from_slice(&[
+                    vertex([x0, y0], region.min),
+                    vertex([x0, y1], glm::vec2(region.min.x, region.max.y)),
+                    vertex([x1, y1], region.max),
+                    vertex([x1, y0], glm::vec2(region.max.x, region.min.y)),
+                ]);
+            }
+        }
+
+        let chunk = &mut self.chunks[index];
+        chunk.tiles = (vertices.len() / 4) as u32;
+        chunk.dirty = false;
+        mesh_builder::grow_buffer(
+            &self.device,
+            &mut chunk.vertex_buffer,
+            wgpu::BufferUsages::VERTEX,
+            "Tilemap Chunk Vertex Buffer",
+            std::mem::size_of_val(vertices.as_slice()) as u64,
+        );
+        if let Some(vertex_buffer) = chunk.vertex_buffer.as_ref().filter(|_| chunk.tiles > 0) {
+            self.queue
+                .write_buffer(vertex_buffer, 0, bytemuck::cast_slice(&vertices));
+        }
+    }
+
+    /// Draws the chunks `prepare` found in view into `renderpass`, which
+    /// must target the format given to `new`.
+    pub fn draw<'a>(&'a self, renderpass: &mut wgpu::RenderPass<'a>) {
+        if self.visible.is_empty() {
+            return;
+        }
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.camera_bind_group, &[]);
+        renderpass.set_bind_group(1, &self.atlas_bind_group, &[]);
+        renderpass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+        for &index in &self.visible {
+            let chunk = &self.chunks[index];
+            if let Some(vertex_buffer) = &chunk.vertex_buffer {
+                renderpass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                renderpass.draw_indexed(0..chunk.tiles * INDICES_PER_TILE as u32, 0, 0..1);
+            }
+        }
+    }
+
+    /// Draws the map over `view` in a pass of its own.
+    pub fn encode(&self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Tilemap Pass"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view: view,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: wgpu::StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        self.draw(&mut renderpass);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_grids_parse_with_empty_cells() {
+        let grid = TileGrid::parse_csv("0, 1,2\n-1,,3\n\n4,5,0\n").unwrap();
+        assert_eq!((grid.width, grid.height), (3, 3));
+        let cells: Vec<Option<u16>> = (0..9).map(|i| grid.get(i % 3, i / 3)).collect();
+        let expected = [
+            Some(0),
+            Some(1),
+            Some(2),
+            None,
+            None,
+            Some(3),
+            Some(4),
+            Some(5),
+            Some(0),
+        ];
+        assert_eq!(cells, expected);
+    }
+
+    #[test]
+    fn short_rows_are_errors_on_their_line() {
+        match TileGrid::parse_csv("0,1\n2\n") {
+            Err(error) => assert_eq!(error.line, 2),
+            Ok(grid) => panic!("a short row parsed into {}x{}", grid.width, grid.height),
+        }
+    }
+
+    #[test]
+    fn grids_split_into_chunks() {
+        let grid = TileGrid::new(100, 70);
+        assert_eq!(grid.chunks(), (4, 3));
+        assert_eq!(grid.chunk_of(CHUNK_SIZE * 2 + 1, CHUNK_SIZE + 5), 6);
+    }
+}
@@ -0,0 +1,158 @@
+use std::ops::Range;
+
+use glm::Mat4;
+
+use super::bind_group;
+use super::bind_group_layout::Layout;
+use super::frame_uploader::FrameUploader;
+use super::instance::Instance;
+
+/// Index of an object's model matrix in a `TransformBuffer`. The scene
+/// shader reads it as `@builtin(instance_index)`, so an object is drawn with
+/// `instances()` as the instance range.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct ObjectId(u32);
+
+impl ObjectId {
+    pub fn index(self) -> u32 {
+        self.0
+    }
+
+    pub fn instances(self) -> Range<u32> {
+        self.0..self.0 + 1
+    }
+}
+
+/// Where the scene shader reads per-object transforms from.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Binding {
+    /// A `TransformBuffer` indexed by instance index.
+    Storage,
+    /// A `DynamicUniformBuffer` rebound at each object's offset, for GL and
+    /// other downlevel targets without vertex shader storage buffers.
+    DynamicOffsets,
+}
+
+impl Binding {
+    pub fn for_device(adapter: &wgpu::Adapter, device: &wgpu::Device) -> Self {
+        let vertex_storage = adapter
+            .get_downlevel_capabilities()
+            .flags
+            .contains(wgpu::DownlevelFlags::VERTEX_STORAGE)
+            && device.limits().max_storage_buffers_per_shader_stage > 0;
+        match vertex_storage {
+            true => Binding::Storage,
+            false => Binding::DynamicOffsets,
+        }
+    }
+}
+
+/// The model matrices of every object in the scene in one read-only storage
+/// buffer, so drawing an object needs no bind group of its own. Changes are
+/// kept on the CPU and uploaded by `flush`.
+pub struct TransformBuffer {
+    gpu: Option<(wgpu::Buffer, wgpu::BindGroup)>,
+    transforms: Vec<Instance>,
+    capacity: u32,
+    dirty: Option<Range<usize>>,
+}
+
+impl TransformBuffer {
+    const MIN_CAPACITY: u32 = 64;
+
+    /// `layout` must hold a single read-only storage buffer visible to the
+    /// vertex stage. Without one the transforms are only kept on the CPU,
+    /// for `Binding::DynamicOffsets`.
+    pub fn new(device: &wgpu::Device, layout: Option<&Layout>, capacity: u32) -> Self {
+        let capacity = capacity.max(Self::MIN_CAPACITY);
+        let gpu = layout.map(|layout| {
+            let buffer = Self::create_buffer(device, capacity);
+            let bind_group = Self::create_bind_group(device, layout, &buffer);
+            (buffer, bind_group)
+        });
+        TransformBuffer {
+            gpu: gpu,
+            transforms: Vec::with_capacity(capacity as usize),
+            capacity: capacity,
+            dirty: None,
+        }
+    }
+
+    fn create_buffer(device: &wgpu::Device, capacity: u32) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Transform Buffer"),
+            size: capacity as u64 * std::mem::size_of::<Instance>() as u64,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_bind_group(
+        device: &wgpu::Device,
+        layout: &Layout,
+        buffer: &wgpu::Buffer,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_buffer(buffer);
+        builder.build("Transform Bind Group")
+    }
+
+    fn mark_dirty(&mut self, index: usize) {
+        self.dirty = match self.dirty.take() {
+            Some(range) => Some(range.start.min(index)..range.end.max(index + 1)),
+            None => Some(index..index + 1),
+        };
+    }
+
+    pub fn push(&mut self, transform: Mat4) -> ObjectId {
+        let id = ObjectId(self.transforms.len() as u32);
+        self.transforms.push(Instance::from_matrix(&transform));
+        self.mark_dirty(id.0 as usize);
+        id
+    }
+
+    pub fn update(&mut self, id: ObjectId, transform: Mat4) {
+        self.transforms[id.0 as usize] = Instance::from_matrix(&transform);
+        self.mark_dirty(id.0 as usize);
+    }
+
+    pub fn instances(&self) -> &[Instance] {
+        &self.transforms
+    }
+
+    /// `None` when the buffer is CPU-only.
+    pub fn bind_group(&self) -> Option<&wgpu::BindGroup> {
+        self.gpu.as_ref().map(|(_, bind_group)| bind_group)
+    }
+
+    /// `None` when the buffer is CPU-only. Replaced by `flush` when it grows.
+    pub fn buffer(&self) -> Option<&wgpu::Buffer> {
+        self.gpu.as_ref().map(|(buffer, _)| buffer)
+    }
+
+    /// Queues everything changed since the last flush as one write. When
+    /// objects were pushed past the capacity, the buffer is first reallocated
+    /// at the next power of two and all of it is written again, since writes
+    /// still queued for the old buffer won't reach the new one.
+    pub fn flush(&mut self, device: &wgpu::Device, uploader: &mut FrameUploader, layout: &Layout) {
+        let mut dirty = self.dirty.take();
+        let Some((buffer, bind_group)) = &mut self.gpu else {
+            return;
+        };
+
+        if self.transforms.len() as u32 > self.capacity {
+            let capacity = (self.transforms.len() as u32).next_power_of_two();
+            let grown = Self::create_buffer(device, capacity);
+            *bind_group = Self::create_bind_group(device, layout, &grown);
+            *buffer = grown;
+            self.capacity = capacity;
+            dirty = Some(0..self.transforms.len());
+        }
+
+        if let Some(range) = dirty {
+            let offset = range.start as u64 * std::mem::size_of::<Instance>() as u64;
+            uploader.write(buffer, offset, &self.transforms[range]);
+        }
+    }
+}
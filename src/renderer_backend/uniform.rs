@@ -0,0 +1,224 @@
+use glm::*;
+use wgpu::util::DeviceExt;
+
+#[rustfmt::skip]
+pub fn identity() -> Mat4 {
+    mat4(
+        1.0, 0.0, 0.0, 0.0,
+        0.0, 1.0, 0.0, 0.0,
+        0.0, 0.0, 1.0, 0.0,
+        0.0, 0.0, 0.0, 1.0,
+    )
+}
+
+pub fn mat4_to_array(m: &Mat4) -> [[f32; 4]; 4] {
+    let [c0, c1, c2, c3] = m.as_array();
+    [
+        *c0.as_array(),
+        *c1.as_array(),
+        *c2.as_array(),
+        *c3.as_array(),
+    ]
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ModelUniform {
+    model: [[f32; 4]; 4],
+}
+
+// WGSL requires uniform buffer structs to be sized in multiples of 16 bytes.
+const _: () = assert!(std::mem::size_of::<ModelUniform>() % 16 == 0);
+
+impl ModelUniform {
+    pub fn new(model: &Mat4) -> Self {
+        ModelUniform {
+            model: mat4_to_array(model),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct CameraUniform {
+    view_projection: [[f32; 4]; 4],
+}
+
+const _: () = assert!(std::mem::size_of::<CameraUniform>() % 16 == 0);
+
+impl CameraUniform {
+    pub fn new(view_projection: &Mat4) -> Self {
+        CameraUniform {
+            view_projection: mat4_to_array(view_projection),
+        }
+    }
+}
+
+/// View and projection kept apart so the skybox shader can drop the view's
+/// translation.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct SkyboxUniform {
+    view: [[f32; 4]; 4],
+    projection: [[f32; 4]; 4],
+}
+
+const _: () = assert!(std::mem::size_of::<SkyboxUniform>() % 16 == 0);
+
+impl SkyboxUniform {
+    pub fn new(view: &Mat4, projection: &Mat4) -> Self {
+        SkyboxUniform {
+            view: mat4_to_array(view),
+            projection: mat4_to_array(projection),
+        }
+    }
+}
+
+/// A directional light. `direction` is the way the light travels, so it
+/// points away from the light.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct LightUniform {
+    direction: [f32; 3],
+    ambient: f32,
+    color: [f32; 3],
+    _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<LightUniform>() % 16 == 0);
+
+impl LightUniform {
+    pub fn new(direction: &Vec3, color: &Vec3, ambient: f32) -> Self {
+        LightUniform {
+            direction: *normalize(*direction).as_array(),
+            ambient: ambient,
+            color: *color.as_array(),
+            _padding: 0.0,
+        }
+    }
+}
+
+/// The light's view-projection the shadow map was drawn with, and how the
+/// scene's fragments compare against it: `bias` comes off their depth from
+/// the light first, and the filter's taps are `texel_size` apart in UV.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ShadowUniform {
+    light_view_projection: [[f32; 4]; 4],
+    bias: f32,
+    texel_size: f32,
+    enabled: f32,
+    _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<ShadowUniform>() % 16 == 0);
+
+impl ShadowUniform {
+    pub fn new(light_view_projection: &Mat4, bias: f32, resolution: u32, enabled: bool) -> Self {
+        ShadowUniform {
+            light_view_projection: mat4_to_array(light_view_projection),
+            bias: bias,
+            texel_size: 1.0 / std::cmp::max(resolution, 1) as f32,
+            enabled: if enabled { 1.0 } else { 0.0 },
+            _padding: 0.0,
+        }
+    }
+}
+
+/// Per-frame values for every stage: animation time and the time since the
+/// last frame in seconds, both frozen while paused, and the framebuffer
+/// size in pixels.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GlobalsUniform {
+    time: f32,
+    delta_time: f32,
+    resolution: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<GlobalsUniform>() % 16 == 0);
+
+impl GlobalsUniform {
+    pub fn new(time: f32, delta_time: f32, width: u32, height: u32) -> Self {
+        GlobalsUniform {
+            time: time,
+            delta_time: delta_time,
+            resolution: [width as f32, height as f32],
+        }
+    }
+}
+
+/// What the ground grid shader needs to cast a ray through each pixel, and
+/// the `GridSettings` it draws with.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct GridUniform {
+    view_projection: [[f32; 4]; 4],
+    inverse_view_projection: [[f32; 4]; 4],
+    camera_position: [f32; 3],
+    spacing: f32,
+    minor_color: [f32; 4],
+    major_color: [f32; 4],
+    major_every: f32,
+    fade_distance: f32,
+    _padding: [f32; 2],
+}
+
+const _: () = assert!(std::mem::size_of::<GridUniform>() % 16 == 0);
+
+impl GridUniform {
+    pub fn new(
+        view_projection: &Mat4,
+        camera_position: &Vec3,
+        spacing: f32,
+        major_every: u32,
+        minor_color: [f32; 4],
+        major_color: [f32; 4],
+        fade_distance: f32,
+    ) -> Self {
+        GridUniform {
+            view_projection: mat4_to_array(view_projection),
+            inverse_view_projection: mat4_to_array(&inverse(view_projection)),
+            camera_position: *camera_position.as_array(),
+            spacing: spacing,
+            minor_color: minor_color,
+            major_color: major_color,
+            major_every: major_every as f32,
+            fade_distance: fade_distance,
+            _padding: [0.0; 2],
+        }
+    }
+}
+
+/// The `BloomPass` settings; see `BloomSettings`.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct BloomUniform {
+    threshold: f32,
+    intensity: f32,
+    radius: f32,
+    _padding: f32,
+}
+
+const _: () = assert!(std::mem::size_of::<BloomUniform>() % 16 == 0);
+
+impl BloomUniform {
+    pub fn new(threshold: f32, intensity: f32, radius: f32) -> Self {
+        BloomUniform {
+            threshold: threshold,
+            intensity: intensity,
+            radius: radius,
+            _padding: 0.0,
+        }
+    }
+}
+
+pub fn make_uniform_buffer(device: &wgpu::Device, label: &str, contents: &[u8]) -> wgpu::Buffer {
+    let buffer_descriptor = wgpu::util::BufferInitDescriptor {
+        label: Some(label),
+        contents: contents,
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    };
+
+    device.create_buffer_init(&buffer_descriptor)
+}
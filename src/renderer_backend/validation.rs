@@ -0,0 +1,57 @@
+use std::fmt;
+use std::panic::Location;
+
+/// A validation error wgpu reported while creating a resource.
+#[derive(Debug)]
+pub struct ValidationError {
+    /// The label the resource was created with.
+    pub label: String,
+    /// wgpu's message, which lists everything that went wrong.
+    pub message: String,
+    /// Where the resource was asked for.
+    pub location: &'static Location<'static>,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "creating {} failed:\n{}", self.label, self.message)
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+pub type GpuResult<T> = Result<T, ValidationError>;
+
+/// Runs `f` inside a validation error scope, so a resource it creates that
+/// wgpu rejects comes back as an error instead of failing some later submit.
+/// Debug builds also print the error with the caller's file and line; mark
+/// functions that pass their own caller along with `#[track_caller]`.
+#[track_caller]
+pub fn with_validation<T>(
+    device: &wgpu::Device,
+    label: &str,
+    f: impl FnOnce() -> T,
+) -> GpuResult<T> {
+    let location = Location::caller();
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let value = f();
+    match pollster::block_on(device.pop_error_scope()) {
+        None => Ok(value),
+        Some(error) => {
+            let error = ValidationError {
+                label: label.to_string(),
+                message: error.to_string(),
+                location: location,
+            };
+            if cfg!(debug_assertions) {
+                eprintln!(
+                    "{}:{}: {}",
+                    error.location.file(),
+                    error.location.line(),
+                    error
+                );
+            }
+            Err(error)
+        }
+    }
+}
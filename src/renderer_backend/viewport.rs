@@ -0,0 +1,97 @@
+/// A rectangle of the render target in framebuffer pixels, origin top left.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct Viewport {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// The largest rectangle with `aspect` (width over height) that fits in
+    /// a `width` by `height` target, centered, leaving bars on two sides.
+    pub fn letterbox(width: u32, height: u32, aspect: f32) -> Self {
+        let (width, height) = (width.max(1), height.max(1));
+        let (box_width, box_height) = match width as f32 / height as f32 > aspect {
+            true => (
+                ((height as f32 * aspect).round() as u32).clamp(1, width),
+                height,
+            ),
+            false => (
+                width,
+                ((width as f32 / aspect).round() as u32).clamp(1, height),
+            ),
+        };
+        Viewport {
+            x: (width - box_width) / 2,
+            y: (height - box_height) / 2,
+            width: box_width,
+            height: box_height,
+        }
+    }
+
+    pub fn aspect(&self) -> f32 {
+        self.width as f32 / self.height as f32
+    }
+
+    /// The parts of a `width` by `height` target outside the viewport; none
+    /// of them empty.
+    pub fn bars(&self, width: u32, height: u32) -> Vec<Viewport> {
+        let bars = match self.width < width {
+            true => [
+                Viewport {
+                    x: 0,
+                    y: 0,
+                    width: self.x,
+                    height: height,
+                },
+                Viewport {
+                    x: self.x + self.width,
+                    y: 0,
+                    width: width - self.x - self.width,
+                    height: height,
+                },
+            ],
+            false => [
+                Viewport {
+                    x: 0,
+                    y: 0,
+                    width: width,
+                    height: self.y,
+                },
+                Viewport {
+                    x: 0,
+                    y: self.y + self.height,
+                    width: width,
+                    height: height - self.y - self.height,
+                },
+            ],
+        };
+        bars.into_iter()
+            .filter(|bar| bar.width > 0 && bar.height > 0)
+            .collect()
+    }
+
+    /// Restricts drawing in `renderpass` to the viewport, both where NDC
+    /// maps to and what gets rasterized at all.
+    pub fn apply(&self, renderpass: &mut wgpu::RenderPass) {
+        renderpass.set_viewport(
+            self.x as f32,
+            self.y as f32,
+            self.width as f32,
+            self.height as f32,
+            0.0,
+            1.0,
+        );
+        renderpass.set_scissor_rect(self.x, self.y, self.width, self.height);
+    }
+
+    /// Converts a position on the target, in pixels, to one relative to the
+    /// viewport's corner. `None` when it's in the bars.
+    pub fn to_viewport(&self, x: f32, y: f32) -> Option<(f32, f32)> {
+        let (x, y) = (x - self.x as f32, y - self.y as f32);
+        let inside =
+            (0.0..self.width as f32).contains(&x) && (0.0..self.height as f32).contains(&y);
+        inside.then_some((x, y))
+    }
+}
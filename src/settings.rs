@@ -0,0 +1,430 @@
+use std::env::current_exe;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::args;
+use crate::clear_color;
+use crate::renderer::DEFAULT_CLEAR_COLOR;
+use crate::renderer_backend::{adapter, error::RendererError, shader};
+use crate::shadow_pass::ShadowSettings;
+use crate::window_config::{WindowConfig, SAMPLE_COUNTS};
+
+pub const FILENAME: &str = "settings.toml";
+
+const HEADER: &str = "\
+# Renderer settings, read at startup. Command-line flags override them, see --help.
+# Leave out whatever should keep its default. [gpu] takes adapter = \"NAME\" too,
+# for the adapter whose name contains NAME, and [display] present_mode = \"fifo\",
+# \"mailbox\" or \"immediate\", which takes the place of vsync, and max_fps = FPS.
+# Saving the file while the app runs applies what can change without a restart.
+
+";
+
+#[derive(Debug)]
+pub enum SettingsError {
+    Read {
+        path: PathBuf,
+        error: io::Error,
+    },
+    Parse {
+        path: PathBuf,
+        error: toml::de::Error,
+    },
+    Invalid {
+        path: PathBuf,
+        message: String,
+    },
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SettingsError::Read { path, error } => {
+                write!(f, "can't read {}: {}", path.display(), error)
+            }
+            SettingsError::Parse { path, error } => write!(f, "{}: {}", path.display(), error),
+            SettingsError::Invalid { path, message } => {
+                write!(f, "{}: {}", path.display(), message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SettingsError {}
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum PresentMode {
+    Fifo,
+    Mailbox,
+    Immediate,
+}
+
+impl PresentMode {
+    pub fn to_wgpu(self) -> wgpu::PresentMode {
+        match self {
+            PresentMode::Fifo => wgpu::PresentMode::Fifo,
+            PresentMode::Mailbox => wgpu::PresentMode::Mailbox,
+            PresentMode::Immediate => wgpu::PresentMode::Immediate,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct WindowSettings {
+    pub title: String,
+    /// Size of the content area in screen coordinates.
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Default for WindowSettings {
+    fn default() -> Self {
+        let window = WindowConfig::default();
+        WindowSettings {
+            title: window.title,
+            width: window.width,
+            height: window.height,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct DisplaySettings {
+    pub vsync: bool,
+    /// Overrides `vsync` when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub present_mode: Option<PresentMode>,
+    pub msaa: u32,
+    /// Frame rate cap while presenting doesn't wait for vertical blank.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_fps: Option<u32>,
+    /// `r,g,b` or `r,g,b,a`, as `--clear-color` takes it.
+    pub clear_color: String,
+}
+
+impl Default for DisplaySettings {
+    fn default() -> Self {
+        let color = DEFAULT_CLEAR_COLOR;
+        DisplaySettings {
+            vsync: true,
+            present_mode: None,
+            msaa: 1,
+            max_fps: None,
+            clear_color: format!("{},{},{},{}", color.r, color.g, color.b, color.a),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct CameraSettings {
+    /// World units a second.
+    pub speed: f32,
+    /// Degrees per pixel of cursor movement.
+    pub sensitivity: f32,
+}
+
+impl Default for CameraSettings {
+    fn default() -> Self {
+        CameraSettings {
+            speed: 2.0,
+            sensitivity: 0.1,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct GpuSettings {
+    /// One of the names `--backend` takes.
+    pub backend: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub adapter: Option<String>,
+    /// One of the names `--power` takes.
+    pub power: String,
+    pub fallback_adapter: bool,
+}
+
+impl Default for GpuSettings {
+    fn default() -> Self {
+        GpuSettings {
+            backend: "auto".to_string(),
+            adapter: None,
+            power: "high".to_string(),
+            fallback_adapter: false,
+        }
+    }
+}
+
+/// What the app starts with, from `settings.toml` and then the command
+/// line. New options go here, with a default, a check in `validate` if
+/// they need one, and a flag in `apply_args` if they have one.
+#[derive(Serialize, Deserialize, Clone, Default, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct Settings {
+    pub window: WindowSettings,
+    pub display: DisplaySettings,
+    pub camera: CameraSettings,
+    pub shadows: ShadowSettings,
+    pub gpu: GpuSettings,
+}
+
+impl Settings {
+    /// Reads the file `--config` names, or `FILENAME` next to the
+    /// executable, and returns it with the path it was read from. The
+    /// default file is written out when it doesn't exist yet, for editing.
+    /// In the browser there's no file and the defaults apply.
+    pub fn load() -> Result<(Self, PathBuf), RendererError> {
+        if cfg!(target_arch = "wasm32") {
+            return Ok((Settings::default(), PathBuf::from(FILENAME)));
+        }
+        let explicit = args::flag_value("--config")?.map(PathBuf::from);
+        let path = explicit.clone().unwrap_or_else(|| {
+            current_exe()
+                .ok()
+                .and_then(|exe| exe.parent().map(|dir| dir.join(FILENAME)))
+                .unwrap_or_else(|| PathBuf::from(FILENAME))
+        });
+        if explicit.is_none() && !path.exists() {
+            let settings = Settings::default();
+            settings.write_default(&path);
+            return Ok((settings, path));
+        }
+        // Absolute, so `SettingsWatcher` finds the same file.
+        let path = fs::canonicalize(&path).unwrap_or(path);
+        Ok((Self::read(&path)?, path))
+    }
+
+    /// Reads and checks the settings in `path`.
+    pub fn read(path: &Path) -> Result<Self, SettingsError> {
+        let text = fs::read_to_string(path).map_err(|error| SettingsError::Read {
+            path: path.to_path_buf(),
+            error: error,
+        })?;
+        let settings: Settings = toml::from_str(&text).map_err(|error| SettingsError::Parse {
+            path: path.to_path_buf(),
+            error: error,
+        })?;
+        settings
+            .validate()
+            .map_err(|message| SettingsError::Invalid {
+                path: path.to_path_buf(),
+                message: message,
+            })?;
+        Ok(settings)
+    }
+
+    fn write_default(&self, path: &Path) {
+        let written = toml::to_string_pretty(self)
+            .map_err(|e| e.to_string())
+            .and_then(|text| {
+                fs::write(path, HEADER.to_string() + &text).map_err(|e| e.to_string())
+            });
+        match written {
+            Ok(_) => println!("Wrote default settings to {}", path.display()),
+            Err(e) => eprintln!("Warning: can't write {}: {}", path.display(), e),
+        }
+    }
+
+    /// Checks what the file can't express in its types, naming the key
+    /// that's wrong.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.window.width == 0 || self.window.height == 0 {
+            return Err("window.width and window.height must be above 0".to_string());
+        }
+        if !SAMPLE_COUNTS.contains(&self.display.msaa) {
+            let counts: Vec<String> = SAMPLE_COUNTS.iter().map(u32::to_string).collect();
+            let (last, rest) = counts.split_last().expect("there are sample counts");
+            return Err(format!(
+                "display.msaa must be {} or {}",
+                rest.join(", "),
+                last
+            ));
+        }
+        if self.display.max_fps == Some(0) {
+            return Err("display.max_fps must be above 0".to_string());
+        }
+        if let Err(e) = clear_color::parse(&self.display.clear_color) {
+            return Err(format!("display.clear_color: {}", e));
+        }
+        if self.camera.speed.is_nan() || self.camera.speed <= 0.0 {
+            return Err("camera.speed must be above 0".to_string());
+        }
+        if self.camera.sensitivity.is_nan() || self.camera.sensitivity <= 0.0 {
+            return Err("camera.sensitivity must be above 0".to_string());
+        }
+        if self.shadows.resolution == 0 {
+            return Err("shadows.resolution must be above 0".to_string());
+        }
+        if !self.shadows.bias.is_finite() || self.shadows.bias < 0.0 {
+            return Err("shadows.bias must be 0 or above".to_string());
+        }
+        if let Err(e) = adapter::parse_backends(&self.gpu.backend) {
+            return Err(format!("gpu.backend: {}", e));
+        }
+        if let Err(e) = adapter::parse_power_preference(&self.gpu.power) {
+            return Err(format!("gpu.power: {}", e));
+        }
+        if self.gpu.fallback_adapter && self.gpu.adapter.is_some() {
+            return Err("gpu.adapter and gpu.fallback_adapter can't both be set".to_string());
+        }
+        Ok(())
+    }
+
+    /// Overrides settings with the flags and environment variables given
+    /// for them: `--size`, `--title`, `--vsync`, `--msaa`, `--max-fps`,
+    /// `--shadow-resolution`, `--clear-color` or `$CLEAR_COLOR`, `--backend` or `$WGPU_BACKEND`,
+    /// `--adapter` or `$WGPU_ADAPTER`, `--power` and `--fallback-adapter`.
+    pub fn apply_args(&mut self) -> Result<(), RendererError> {
+        if let Some(size) = args::flag_value("--size")? {
+            (self.window.width, self.window.height) = args::parse_size("--size", &size)?;
+        }
+        if let Some(title) = args::flag_value("--title")? {
+            self.window.title = title;
+        }
+        if let Some(vsync) = args::flag_value("--vsync")? {
+            self.display.vsync = match vsync.as_str() {
+                "on" => true,
+                "off" => false,
+                value => {
+                    return Err(RendererError::InvalidArgument(format!(
+                        "--vsync expects on or off, got \"{}\"",
+                        value
+                    )))
+                }
+            };
+            // Or a present mode from the file would win over the flag.
+            self.display.present_mode = None;
+        }
+        if let Some(value) = args::flag_value("--msaa")? {
+            let count = args::parse_count("--msaa", &value)?;
+            if !SAMPLE_COUNTS.contains(&count) {
+                return Err(RendererError::InvalidArgument(format!(
+                    "--msaa expects one of {:?}, got {}",
+                    SAMPLE_COUNTS, count
+                )));
+            }
+            self.display.msaa = count;
+        }
+        if let Some(value) = args::flag_value("--max-fps")? {
+            match args::parse_count("--max-fps", &value)? {
+                0 => {
+                    return Err(RendererError::InvalidArgument(
+                        "--max-fps needs at least one frame a second".to_string(),
+                    ))
+                }
+                fps => self.display.max_fps = Some(fps),
+            }
+        }
+        if let Some(value) = args::flag_value("--shadow-resolution")? {
+            match args::parse_count("--shadow-resolution", &value)? {
+                0 => {
+                    return Err(RendererError::InvalidArgument(
+                        "--shadow-resolution needs at least one texel".to_string(),
+                    ))
+                }
+                resolution => self.shadows.resolution = resolution,
+            }
+        }
+        if let Some(color) = args::flag_or_env(clear_color::ARG_NAME, clear_color::ENV_NAME)? {
+            clear_color::parse(&color)?;
+            self.display.clear_color = color;
+        }
+        if let Some(backend) = args::flag_or_env("--backend", "WGPU_BACKEND")? {
+            adapter::parse_backends(&backend)?;
+            self.gpu.backend = backend;
+        }
+        if let Some(name) = args::flag_or_env("--adapter", "WGPU_ADAPTER")? {
+            self.gpu.adapter = Some(name);
+        }
+        if let Some(power) = args::flag_value("--power")? {
+            adapter::parse_power_preference(&power)?;
+            self.gpu.power = power;
+        }
+        if args::has_flag("--fallback-adapter") {
+            self.gpu.fallback_adapter = true;
+        }
+        if self.gpu.fallback_adapter && self.gpu.adapter.is_some() {
+            return Err(RendererError::InvalidArgument(
+                "--fallback-adapter can't be combined with --adapter".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    pub fn clear_color(&self) -> wgpu::Color {
+        clear_color::parse(&self.display.clear_color).unwrap_or(DEFAULT_CLEAR_COLOR)
+    }
+
+    /// The adapter `gpu` asks for, tracing into `trace` if set.
+    pub fn adapter_options(
+        &self,
+        trace: Option<PathBuf>,
+    ) -> Result<adapter::AdapterOptions, RendererError> {
+        Ok(adapter::AdapterOptions {
+            backends: adapter::parse_backends(&self.gpu.backend)?,
+            name: self.gpu.adapter.clone(),
+            power_preference: adapter::parse_power_preference(&self.gpu.power)?,
+            force_fallback: self.gpu.fallback_adapter,
+            trace: trace,
+        })
+    }
+
+    /// The main window `window` describes, with every other option at its
+    /// default.
+    pub fn window_config(&self) -> WindowConfig {
+        WindowConfig {
+            title: self.window.title.clone(),
+            width: self.window.width,
+            height: self.window.height,
+            ..WindowConfig::default()
+        }
+    }
+}
+
+/// Rereads the settings file whenever it's saved, for the settings that
+/// can change while the app runs.
+pub struct SettingsWatcher {
+    watcher: shader::Watcher,
+    path: PathBuf,
+    /// What the file said last, to tell what an edit changed. Settings the
+    /// command line overrode stay overridden until the file changes them.
+    settings: Settings,
+}
+
+impl SettingsWatcher {
+    /// Watches `path`, which `settings` were read from.
+    pub fn new(path: PathBuf, settings: Settings) -> Self {
+        SettingsWatcher {
+            watcher: shader::Watcher::new(&path),
+            path: path,
+            settings: settings,
+        }
+    }
+
+    /// The file's settings before and after it was saved, once it has been
+    /// and reads back fine. A file that doesn't keeps the previous settings
+    /// in force, with the error reported.
+    pub fn poll(&mut self) -> Option<(Settings, Settings)> {
+        if !self.watcher.changed() {
+            return None;
+        }
+        match Settings::read(&self.path) {
+            Ok(settings) => {
+                let previous = std::mem::replace(&mut self.settings, settings.clone());
+                Some((previous, settings))
+            }
+            Err(e) => {
+                eprintln!("Keeping the previous settings: {}", e);
+                None
+            }
+        }
+    }
+}
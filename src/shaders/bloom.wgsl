@@ -0,0 +1,82 @@
+// The four passes of `BloomPass`, sharing one fullscreen triangle:
+// fs_extract, fs_blur_horizontal, fs_blur_vertical and fs_composite.
+
+struct Bloom {
+    threshold: f32,
+    intensity: f32,
+    // Texels sampled either side of the center by each blur pass.
+    radius: f32,
+};
+
+// Keep in step with `bloom_pass::MAX_BLUR_RADIUS`.
+const MAX_BLUR_RADIUS: i32 = 16;
+
+@group(0) @binding(0) var<uniform> bloom: Bloom;
+
+@group(1) @binding(0) var source: texture_2d<f32>;
+@group(1) @binding(1) var sourceSampler: sampler;
+
+// Only bound for fs_composite: the blurred bright pixels.
+@group(2) @binding(0) var glow: texture_2d<f32>;
+@group(2) @binding(1) var glowSampler: sampler;
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexPayload {
+    // One triangle that covers the screen: (-1, -1), (3, -1) and (-1, 3).
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexPayload;
+    out.position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+// Drawn at half resolution, so the linear sampler averages 2x2 texels of
+// the scene on the way down.
+@fragment
+fn fs_extract(in: VertexPayload) -> @location(0) vec4<f32> {
+    let color = textureSample(source, sourceSampler, in.uv).rgb;
+    let brightness = max(color.r, max(color.g, color.b));
+    // Scales the whole color, so bright pixels keep their hue.
+    let excess = max(brightness - bloom.threshold, 0.0) / max(brightness, 0.0001);
+    return vec4<f32>(color * excess, 1.0);
+}
+
+fn blur(uv: vec2<f32>, direction: vec2<f32>) -> vec4<f32> {
+    let texel = direction / vec2<f32>(textureDimensions(source));
+    let radius = min(i32(ceil(bloom.radius)), MAX_BLUR_RADIUS);
+    // The kernel reaches about two standard deviations out.
+    let sigma = max(bloom.radius * 0.5, 0.5);
+
+    var sum = vec3<f32>(0.0);
+    var total = 0.0;
+    for (var i = -radius; i <= radius; i++) {
+        let x = f32(i);
+        let weight = exp(-(x * x) / (2.0 * sigma * sigma));
+        sum += textureSample(source, sourceSampler, uv + texel * x).rgb * weight;
+        total += weight;
+    }
+    return vec4<f32>(sum / total, 1.0);
+}
+
+@fragment
+fn fs_blur_horizontal(in: VertexPayload) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(1.0, 0.0));
+}
+
+@fragment
+fn fs_blur_vertical(in: VertexPayload) -> @location(0) vec4<f32> {
+    return blur(in.uv, vec2<f32>(0.0, 1.0));
+}
+
+@fragment
+fn fs_composite(in: VertexPayload) -> @location(0) vec4<f32> {
+    // Same size as the target: read the scene texel for texel.
+    let scene = textureLoad(source, vec2<i32>(in.position.xy), 0);
+    let blurred = textureSample(glow, glowSampler, in.uv).rgb;
+    return vec4<f32>(scene.rgb + blurred * bloom.intensity, scene.a);
+}
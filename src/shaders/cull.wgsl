@@ -0,0 +1,83 @@
+// Frustum culls a list of candidate draws, one object each, and appends the
+// visible ones to their batch's stretch of `draws`. `counts` ends up with how
+// many each batch got and is drawn with `multi_draw_indexed_indirect_count`.
+
+override workgroup_size: u32 = 64;
+
+struct Params {
+    // Left, right, bottom, top, near, far; normals point inside.
+    planes: array<vec4<f32>, 6>,
+    model: mat4x4<f32>,
+    candidate_count: u32,
+}
+
+struct Candidate {
+    aabb_min: vec3<f32>,
+    object: u32,
+    aabb_max: vec3<f32>,
+    batch: u32,
+    slot_base: u32,
+    index_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+}
+
+// Laid out like `wgpu::util::DrawIndexedIndirectArgs`.
+struct DrawArgs {
+    index_count: u32,
+    instance_count: u32,
+    first_index: u32,
+    base_vertex: i32,
+    first_instance: u32,
+}
+
+@group(0) @binding(0) var<uniform> params: Params;
+@group(0) @binding(1) var<storage, read> candidates: array<Candidate>;
+@group(0) @binding(2) var<storage, read> transforms: array<mat4x4<f32>>;
+@group(0) @binding(3) var<storage, read_write> draws: array<DrawArgs>;
+@group(0) @binding(4) var<storage, read_write> counts: array<atomic<u32>>;
+// 1 for every object that passed, 0 for the rest, for checking against the
+// CPU culler.
+@group(0) @binding(5) var<storage, read_write> visibility: array<u32>;
+
+// The same test as `Frustum::intersects`: the world-space box around the
+// transformed one against each plane.
+fn intersects(candidate: Candidate, model: mat4x4<f32>) -> bool {
+    let local_center = (candidate.aabb_min + candidate.aabb_max) * 0.5;
+    let extents = (candidate.aabb_max - candidate.aabb_min) * 0.5;
+    let center = (model * vec4<f32>(local_center, 1.0)).xyz;
+    let world_extents = abs(model[0].xyz) * extents.x
+        + abs(model[1].xyz) * extents.y
+        + abs(model[2].xyz) * extents.z;
+    for (var i = 0u; i < 6u; i++) {
+        let plane = params.planes[i];
+        let distance = dot(plane.xyz, center) + plane.w;
+        let radius = dot(abs(plane.xyz), world_extents);
+        if (distance + radius < 0.0) {
+            return false;
+        }
+    }
+    return true;
+}
+
+@compute @workgroup_size(workgroup_size)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (id.x >= params.candidate_count) {
+        return;
+    }
+    let candidate = candidates[id.x];
+    let model = params.model * transforms[candidate.object];
+    let visible = intersects(candidate, model);
+    visibility[candidate.object] = u32(visible);
+    if (!visible) {
+        return;
+    }
+    let slot = atomicAdd(&counts[candidate.batch], 1u);
+    draws[candidate.slot_base + slot] = DrawArgs(
+        candidate.index_count,
+        1u,
+        candidate.first_index,
+        candidate.base_vertex,
+        candidate.object,
+    );
+}
@@ -0,0 +1,77 @@
+struct Grid {
+    view_projection: mat4x4<f32>,
+    inverse_view_projection: mat4x4<f32>,
+    camera_position: vec3<f32>,
+    spacing: f32,
+    minor_color: vec4<f32>,
+    major_color: vec4<f32>,
+    major_every: f32,
+    fade_distance: f32,
+};
+
+@group(0) @binding(0) var<uniform> grid: Grid;
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+struct GridOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+// One triangle covering the whole screen.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexPayload {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    let ndc = uv * 2.0 - 1.0;
+    var out: VertexPayload;
+    out.position = vec4<f32>(ndc, 0.0, 1.0);
+    out.ndc = ndc;
+    return out;
+}
+
+fn unproject(ndc: vec2<f32>, depth: f32) -> vec3<f32> {
+    let point = grid.inverse_view_projection * vec4<f32>(ndc, depth, 1.0);
+    return point.xyz / point.w;
+}
+
+// 1 on a line of the grid with cells a unit wide in `coord`, falling to 0
+// a pixel away from it.
+fn line_coverage(coord: vec2<f32>) -> f32 {
+    let width = fwidth(coord);
+    let distance = abs(fract(coord - 0.5) - 0.5) / width;
+    return 1.0 - min(min(distance.x, distance.y), 1.0);
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> GridOutput {
+    // Where the ray from the near plane to the far plane crosses y = 0.
+    let near = unproject(in.ndc, 0.0);
+    let far = unproject(in.ndc, 1.0);
+    let ray = far - near;
+    let t = -near.y / ray.y;
+    let point = near + ray * t;
+
+    let coord = point.xz / grid.spacing;
+    let minor = line_coverage(coord);
+    let major = line_coverage(coord / grid.major_every);
+
+    // Lines further away than a pixel apart, and lines seen edge on near
+    // the horizon, would shimmer, so both fade out.
+    let distance = length(point.xz - grid.camera_position.xz);
+    let distance_fade = 1.0 - smoothstep(grid.fade_distance * 0.5, grid.fade_distance, distance);
+    let angle_fade = smoothstep(0.0, 0.2, abs(normalize(ray).y));
+
+    var out: GridOutput;
+    out.color = mix(grid.minor_color, grid.major_color, major);
+    out.color.a *= max(minor, major) * distance_fade * angle_fade;
+    let clip = grid.view_projection * vec4<f32>(point, 1.0);
+    out.depth = clip.z / clip.w;
+    // Only after the derivatives above, which need every pixel around.
+    if (t < 0.0 || t > 1.0 || out.color.a <= 0.0) {
+        discard;
+    }
+    return out;
+}
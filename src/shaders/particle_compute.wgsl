@@ -0,0 +1,108 @@
+// Steps every particle in the pool: the living age, fall and change color,
+// and up to `spawn_count` of the dead are reborn in place at the emitter.
+// `step_particles` in particles.rs does the same on the CPU.
+
+struct Particle {
+    position: vec3<f32>,
+    age: f32,
+    velocity: vec3<f32>,
+    lifetime: f32,
+    color: vec4<f32>,
+    size: f32,
+};
+
+struct SimulationParams {
+    origin: vec3<f32>,
+    dt: f32,
+    direction: vec3<f32>,
+    // The cone's half angle, in radians.
+    cone_angle: f32,
+    gravity: vec3<f32>,
+    size: f32,
+    start_color: vec4<f32>,
+    end_color: vec4<f32>,
+    speed: vec2<f32>,
+    lifetime: vec2<f32>,
+    count: u32,
+    spawn_count: u32,
+    seed: u32,
+};
+
+struct Counters {
+    live: atomic<u32>,
+    spawned: atomic<u32>,
+};
+
+override workgroup_size: u32 = 64;
+
+@group(0) @binding(0) var<storage, read_write> particles: array<Particle>;
+@group(0) @binding(1) var<uniform> params: SimulationParams;
+// Cleared before every step; `live` is read back for the stats.
+@group(0) @binding(2) var<storage, read_write> counters: Counters;
+
+fn hash(seed: u32) -> f32 {
+    var x = seed;
+    x = (x ^ 61u) ^ (x >> 16u);
+    x = x * 9u;
+    x = x ^ (x >> 4u);
+    x = x * 0x27d4eb2du;
+    x = x ^ (x >> 15u);
+    return f32(x) / 4294967295.0;
+}
+
+// A start velocity in the cone from three random numbers in 0..1.
+fn spawn_velocity(random: vec3<f32>) -> vec3<f32> {
+    let direction = params.direction;
+    let cos_theta = 1.0 - random.x * (1.0 - cos(params.cone_angle));
+    let sin_theta = sqrt(max(1.0 - cos_theta * cos_theta, 0.0));
+    let phi = 6.28318530718 * random.y;
+    var helper = vec3<f32>(1.0, 0.0, 0.0);
+    if (abs(direction.y) < 0.99) {
+        helper = vec3<f32>(0.0, 1.0, 0.0);
+    }
+    let tangent = normalize(cross(helper, direction));
+    let bitangent = cross(direction, tangent);
+    let around = tangent * cos(phi) + bitangent * sin(phi);
+    let speed = mix(params.speed.x, params.speed.y, random.z);
+    return (direction * cos_theta + around * sin_theta) * speed;
+}
+
+@compute @workgroup_size(workgroup_size)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    let index = id.x;
+    // The last workgroup runs past the end when count isn't a multiple of workgroup_size.
+    if (index >= params.count) {
+        return;
+    }
+
+    var particle = particles[index];
+    if (particle.age >= particle.lifetime) {
+        // Only take a spawn slot while there are any left.
+        if (atomicLoad(&counters.spawned) >= params.spawn_count) {
+            return;
+        }
+        if (atomicAdd(&counters.spawned, 1u) >= params.spawn_count) {
+            return;
+        }
+        let seed = index * 4u + params.seed * 7919u;
+        let random = vec3<f32>(hash(seed), hash(seed + 1u), hash(seed + 2u));
+        particle.position = params.origin;
+        particle.age = 0.0;
+        particle.velocity = spawn_velocity(random);
+        particle.lifetime = mix(params.lifetime.x, params.lifetime.y, hash(seed + 3u));
+        particle.color = params.start_color;
+        particle.size = params.size;
+    } else {
+        particle.age += params.dt;
+        if (particle.age >= particle.lifetime) {
+            particles[index] = particle;
+            return;
+        }
+        particle.velocity += params.gravity * params.dt;
+        particle.position += particle.velocity * params.dt;
+        particle.color = mix(params.start_color, params.end_color, particle.age / particle.lifetime);
+    }
+
+    atomicAdd(&counters.live, 1u);
+    particles[index] = particle;
+}
@@ -0,0 +1,50 @@
+@group(0) @binding(0) var<uniform> viewProjection: mat4x4<f32>;
+
+struct ParticleInstance {
+    @location(0) position_age: vec4<f32>,
+    @location(1) velocity_lifetime: vec4<f32>,
+    @location(2) color: vec4<f32>,
+    @location(3) size: f32,
+};
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) corner: vec2<f32>,
+};
+
+// How far through its life a particle starts fading out.
+const FADE_START: f32 = 0.7;
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex_index: u32, particle: ParticleInstance) -> VertexPayload {
+    let corner = vec2<f32>(f32(vertex_index & 1u), f32(vertex_index >> 1u)) * 2.0 - 1.0;
+    var out: VertexPayload;
+    let life = particle.position_age.w / particle.velocity_lifetime.w;
+    // Dead particles collapse to a point off screen and draw nothing.
+    if (!(life < 1.0)) {
+        out.position = vec4<f32>(2.0, 2.0, 2.0, 1.0);
+        return out;
+    }
+
+    // The first two rows of the view-projection are the camera's right and
+    // up scaled by the projection, for perspective and orthographic alike.
+    let right = normalize(vec3<f32>(viewProjection[0].x, viewProjection[1].x, viewProjection[2].x));
+    let up = normalize(vec3<f32>(viewProjection[0].y, viewProjection[1].y, viewProjection[2].y));
+    let offset = (right * corner.x + up * corner.y) * particle.size * 0.5;
+    out.position = viewProjection * vec4<f32>(particle.position_age.xyz + offset, 1.0);
+    let fade = 1.0 - smoothstep(FADE_START, 1.0, life);
+    out.color = vec4<f32>(particle.color.rgb, particle.color.a * fade);
+    out.corner = corner;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
+    // Round, with soft edges.
+    let falloff = 1.0 - smoothstep(0.5, 1.0, length(in.corner));
+    if (falloff <= 0.0) {
+        discard;
+    }
+    return vec4<f32>(in.color.rgb, in.color.a * falloff);
+}
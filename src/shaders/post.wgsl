@@ -0,0 +1,39 @@
+// Which `PostEffect` this pipeline applies: 1 inverts, 2 is grayscale and
+// anything else passes the frame through.
+override effect: u32 = 0;
+
+// The frame comes in with premultiplied alpha. Set for compositors that
+// want straight alpha instead.
+override unpremultiply: bool = false;
+
+@group(0) @binding(0) var frame: texture_2d<f32>;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle that covers the screen: (-1, -1), (3, -1) and (-1, 3).
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    // The frame is the same size as the target, so each pixel reads exactly
+    // its own texel and nothing gets filtered.
+    var color = textureLoad(frame, vec2<i32>(position.xy), 0);
+    switch effect {
+        case 1u: {
+            // 1 - rgb on straight alpha, premultiplied again.
+            color = vec4<f32>(color.a - color.rgb, color.a);
+        }
+        case 2u: {
+            // Rec. 709 luminance, on linear values.
+            let luminance = dot(color.rgb, vec3<f32>(0.2126, 0.7152, 0.0722));
+            color = vec4<f32>(vec3<f32>(luminance), color.a);
+        }
+        default: {}
+    }
+    if (unpremultiply && color.a > 0.0) {
+        color = vec4<f32>(color.rgb / color.a, color.a);
+    }
+    return color;
+}
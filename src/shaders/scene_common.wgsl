@@ -0,0 +1,172 @@
+// What the scene shaders share, appended to each when it's loaded. They
+// differ only in where the model and object matrices come from, and pass
+// those to `scene_vertex` and `pick_vertex`.
+
+@group(0) @binding(0) var<uniform> viewProjection: mat4x4<f32>;
+
+// Travels along `direction`, which points away from the light.
+struct Light {
+    direction: vec3<f32>,
+    ambient: f32,
+    color: vec3<f32>,
+};
+
+@group(0) @binding(1) var<uniform> light: Light;
+
+// The light's view of the scene, drawn into `shadowMap` before the frame.
+struct Shadow {
+    lightViewProjection: mat4x4<f32>,
+    // Comes off each fragment's depth from the light before the
+    // comparison: more keeps lit surfaces from shadowing themselves, too
+    // much lifts shadows off whatever casts them.
+    bias: f32,
+    // One shadow map texel in UV, how far apart the filter's taps are.
+    texelSize: f32,
+    // 0 while shadows are off.
+    enabled: f32,
+};
+
+@group(0) @binding(3) var<uniform> shadow: Shadow;
+@group(0) @binding(4) var shadowMap: texture_depth_2d;
+@group(0) @binding(5) var shadowSampler: sampler_comparison;
+
+@group(1) @binding(0) var myTexture: texture_2d<f32>;
+@group(1) @binding(1) var mySampler: sampler;
+// Tangent space, green up; a flat one for materials without a normal map.
+@group(1) @binding(2) var normalMap: texture_2d<f32>;
+@group(1) @binding(3) var normalSampler: sampler;
+// Multiplies the vertex color; the selected scene node's is tinted.
+@group(1) @binding(4) var<uniform> materialColor: vec4<f32>;
+
+// Set when HDR output was asked for but the surface is 8-bit; a Reinhard
+// curve brings colors above 1.0 back into range instead of clipping them.
+override tonemap: bool = false;
+
+// Above 1.0 for the selection outline, which redraws the selected mesh
+// scaled up around its origin.
+override outline_scale: f32 = 1.0;
+
+const OUTLINE_COLOR: vec4<f32> = vec4<f32>(1.0, 0.45, 0.0, 1.0);
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) uv: vec2<f32>,
+    @location(3) normal: vec3<f32>,
+    // w flips the bitangent on mirrored UVs.
+    @location(4) tangent: vec4<f32>,
+};
+
+struct VertexPayload {
+    // Invariant so the depth pre-pass, which runs vs_main in a pipeline of
+    // its own, computes bit-identical depth for the Equal test.
+    @builtin(position) @invariant position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) textCord: vec2<f32>,
+    @location(2) normal: vec3<f32>,
+    @location(3) tangent: vec4<f32>,
+    @location(4) worldPosition: vec3<f32>,
+};
+
+// Inverse-transpose of the upper 3x3, up to scale: the cofactor matrix. A
+// plain model matrix would skew normals under non-uniform scale. The sign
+// of the determinant keeps them pointing out of mirrored objects.
+fn normal_matrix(m: mat4x4<f32>) -> mat3x3<f32> {
+    let a = m[0].xyz;
+    let b = m[1].xyz;
+    let c = m[2].xyz;
+    let cofactor = mat3x3<f32>(cross(b, c), cross(c, a), cross(a, b));
+    return cofactor * sign(dot(a, cross(b, c)));
+}
+
+// vs_main for a vertex placed in the world by `world`.
+fn scene_vertex(vertex: Vertex, world: mat4x4<f32>) -> VertexPayload {
+    var out: VertexPayload;
+    let position = vec4<f32>(vertex.position * outline_scale, 1.0);
+    out.position = viewProjection * world * position;
+    out.worldPosition = (world * position).xyz;
+    out.color = vertex.color;
+    out.textCord = vertex.uv;
+    out.normal = normal_matrix(world) * vertex.normal;
+    // Tangents lie along the surface and move with it; mirroring the
+    // object turns the bitangent around too.
+    let handedness = vertex.tangent.w * sign(dot(world[0].xyz, cross(world[1].xyz, world[2].xyz)));
+    out.tangent = vec4<f32>((world * vec4<f32>(vertex.tangent.xyz, 0.0)).xyz, handedness);
+    return out;
+}
+
+struct PickPayload {
+    @builtin(position) @invariant position: vec4<f32>,
+    @location(0) @interpolate(flat) id: u32,
+};
+
+// Positions exactly as scene_vertex does, so the ids pass a LessEqual test
+// against the depth the scene left.
+fn pick_vertex(vertex: Vertex, world: mat4x4<f32>, object: u32) -> PickPayload {
+    var out: PickPayload;
+    out.position = viewProjection * world * vec4<f32>(vertex.position * outline_scale, 1.0);
+    // 0 is left for nothing drawn.
+    out.id = object + 1u;
+    return out;
+}
+
+// The normal bent by the normal map. Meshes without tangents have zero
+// ones and keep the normal they have.
+fn mapped_normal(in: VertexPayload) -> vec3<f32> {
+    let n = normalize(in.normal);
+    let along = in.tangent.xyz - n * dot(n, in.tangent.xyz);
+    let t = along * inverseSqrt(max(dot(along, along), 1e-12));
+    let b = cross(n, t) * in.tangent.w;
+    let m = textureSample(normalMap, normalSampler, in.textCord).xyz * 2.0 - 1.0;
+    return normalize(t * m.x + b * m.y + n * m.z);
+}
+
+// How much of the light reaches `position`, from 0 to 1: the share of a
+// 3x3 block of shadow map texels around it that nothing nearer the light
+// covers. Where the light's view doesn't reach nothing is known to be in
+// the way, so it's lit.
+fn shadow_visibility(position: vec3<f32>) -> f32 {
+    let clip = shadow.lightViewProjection * vec4<f32>(position, 1.0);
+    let ndc = clip.xyz / clip.w;
+    // Clip space y runs up, texture coordinates down.
+    let uv = vec2<f32>(ndc.x * 0.5 + 0.5, 0.5 - ndc.y * 0.5);
+    let depth = ndc.z - shadow.bias;
+    var lit = 0.0;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let offset = vec2<f32>(f32(x), f32(y)) * shadow.texelSize;
+            lit += textureSampleCompareLevel(shadowMap, shadowSampler, uv + offset, depth);
+        }
+    }
+    let inside = all(uv >= vec2<f32>(0.0)) && all(uv <= vec2<f32>(1.0)) && ndc.z >= 0.0 && ndc.z <= 1.0;
+    return select(1.0, lit / 9.0, inside && shadow.enabled != 0.0);
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
+    // Lambert diffuse plus a constant ambient term.
+    let diffuse = max(dot(mapped_normal(in), -light.direction), 0.0) * shadow_visibility(in.worldPosition);
+    let base = in.color * materialColor;
+    let lit = base.rgb * (light.ambient + diffuse) * light.color;
+    let color = vec4<f32>(lit, base.a) * textureSample(myTexture, mySampler, in.textCord);
+    if (tonemap) {
+        return vec4<f32>(color.rgb / (color.rgb + vec3<f32>(1.0)), color.a);
+    }
+    return color;
+}
+
+// Lines and points have no normals to light them by.
+@fragment
+fn fs_unlit(in: VertexPayload) -> @location(0) vec4<f32> {
+    return in.color * materialColor;
+}
+
+@fragment
+fn fs_pick(in: PickPayload) -> @location(0) u32 {
+    return in.id;
+}
+
+@fragment
+fn fs_outline() -> @location(0) vec4<f32> {
+    return OUTLINE_COLOR;
+}
@@ -1,27 +1,16 @@
-@group(0) @binding(0) var myTexture: texture_2d<f32>;
-@group(0) @binding(1) var mySampler: sampler;
+// The scene, with each object's transform read from a storage buffer by
+// its instance index. scene_common.wgsl is appended when it's loaded.
 
-struct Vertex {
-    @location(0) position: vec3<f32>,
-    @location(1) color: vec3<f32>,
-};
+@group(2) @binding(0) var<storage, read> transforms: array<mat4x4<f32>>;
 
-struct VertexPayload {
-    @builtin(position) position: vec4<f32>,
-    @location(0) color: vec3<f32>,
-    @location(1) textCord: vec2<f32>,
-};
+@group(3) @binding(0) var<uniform> model: mat4x4<f32>;
 
 @vertex
-fn vs_main(vertex: Vertex) -> VertexPayload {
-    var out: VertexPayload;
-    out.position = vec4<f32>(vertex.position, 1.0);
-    out.color = vertex.color;
-    out.textCord = vec2<f32>(vertex.position.x, -vertex.position.y);
-    return out;
+fn vs_main(vertex: Vertex, @builtin(instance_index) object: u32) -> VertexPayload {
+    return scene_vertex(vertex, model * transforms[object]);
 }
 
-@fragment
-fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
-    return vec4<f32>(in.color, 1.0) *textureSample(myTexture, mySampler, in.textCord);
-}
\ No newline at end of file
+@vertex
+fn vs_pick(vertex: Vertex, @builtin(instance_index) object: u32) -> PickPayload {
+    return pick_vertex(vertex, model * transforms[object], object);
+}
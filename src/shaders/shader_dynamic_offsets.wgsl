@@ -0,0 +1,12 @@
+// Same as shader.wgsl, but for targets without vertex shader storage
+// buffers: the object's transform is bound at a dynamic offset per draw.
+// There's no picking without storage buffers, so no vs_pick.
+
+@group(2) @binding(0) var<uniform> object: mat4x4<f32>;
+
+@group(3) @binding(0) var<uniform> model: mat4x4<f32>;
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexPayload {
+    return scene_vertex(vertex, model * object);
+}
@@ -0,0 +1,16 @@
+// Same as shader.wgsl, but the model matrix is pushed before each draw
+// instead of bound as a uniform buffer.
+
+@group(2) @binding(0) var<storage, read> transforms: array<mat4x4<f32>>;
+
+var<push_constant> model: mat4x4<f32>;
+
+@vertex
+fn vs_main(vertex: Vertex, @builtin(instance_index) object: u32) -> VertexPayload {
+    return scene_vertex(vertex, model * transforms[object]);
+}
+
+@vertex
+fn vs_pick(vertex: Vertex, @builtin(instance_index) object: u32) -> PickPayload {
+    return pick_vertex(vertex, model * transforms[object], object);
+}
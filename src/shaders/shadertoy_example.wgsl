@@ -0,0 +1,11 @@
+// A starting point for --shadertoy: Shadertoy's default shader, with a dot
+// following the mouse while the left button is held.
+
+fn main_image(frag_coord: vec2<f32>) -> vec4<f32> {
+    let uv = frag_coord / globals.iResolution.xy;
+    var color = 0.5 + 0.5 * cos(globals.iTime + uv.xyx + vec3<f32>(0.0, 2.0, 4.0));
+    if (globals.iMouse.z > 0.0 && distance(frag_coord, globals.iMouse.xy) < 10.0) {
+        color = vec3<f32>(1.0);
+    }
+    return vec4<f32>(color, 1.0);
+}
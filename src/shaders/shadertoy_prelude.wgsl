@@ -0,0 +1,33 @@
+// Appended to a --shadertoy shader, after it so its line numbers stay the
+// same in errors. The shader supplies
+//
+//     fn main_image(frag_coord: vec2<f32>) -> vec4<f32>
+//
+// which gets the pixel's center with the origin at the bottom left, as on
+// Shadertoy, and reads everything else from `globals`.
+
+struct Globals {
+    // Pixels across, pixels down and 1.0 for the pixel aspect ratio.
+    iResolution: vec3<f32>,
+    // Seconds since the start, or since R was last pressed.
+    iTime: f32,
+    // xy: where the cursor was while the left button was last held; zw:
+    // where the button went down, negative while it's up.
+    iMouse: vec4<f32>,
+    iFrame: i32,
+};
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    // One triangle that covers the screen: (-1, -1), (3, -1) and (-1, 3).
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let frag_coord = vec2<f32>(position.x, globals.iResolution.y - position.y);
+    return main_image(frag_coord);
+}
@@ -0,0 +1,30 @@
+// Shows the shadow map in the square the pass's viewport leaves, near the
+// light dark and far from it light.
+
+// Bound as a plain float texture: GLSL only reads depth textures through
+// comparisons.
+@group(0) @binding(0) var shadowMap: texture_2d<f32>;
+
+struct Payload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> Payload {
+    // One triangle that covers the viewport: (-1, -1), (3, -1) and (-1, 3).
+    let corner = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: Payload;
+    out.position = vec4<f32>(corner * 2.0 - 1.0, 0.0, 1.0);
+    // Texture coordinates run down from the top.
+    out.uv = vec2<f32>(corner.x, 1.0 - corner.y);
+    return out;
+}
+
+@fragment
+fn fs_main(in: Payload) -> @location(0) vec4<f32> {
+    let size = vec2<f32>(textureDimensions(shadowMap));
+    let texel = vec2<i32>(min(in.uv * size, size - 1.0));
+    let depth = textureLoad(shadowMap, texel, 0).r;
+    return vec4<f32>(vec3<f32>(depth), 1.0);
+}
@@ -0,0 +1,41 @@
+struct Sky {
+    view: mat4x4<f32>,
+    projection: mat4x4<f32>,
+};
+
+@group(0) @binding(0) var<uniform> sky: Sky;
+
+@group(1) @binding(0) var skyTexture: texture_cube<f32>;
+@group(1) @binding(1) var skySampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+};
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) direction: vec3<f32>,
+};
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexPayload {
+    // Only the view's rotation: the sky stays put however far the camera moves.
+    let rotation = mat4x4<f32>(
+        vec4<f32>(sky.view[0].xyz, 0.0),
+        vec4<f32>(sky.view[1].xyz, 0.0),
+        vec4<f32>(sky.view[2].xyz, 0.0),
+        vec4<f32>(0.0, 0.0, 0.0, 1.0),
+    );
+    let clip = sky.projection * rotation * vec4<f32>(vertex.position, 1.0);
+
+    var out: VertexPayload;
+    // z = w puts the cube on the far plane, behind everything else.
+    out.position = clip.xyww;
+    out.direction = vertex.position;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
+    return textureSample(skyTexture, skySampler, in.direction);
+}
@@ -0,0 +1,31 @@
+@group(0) @binding(0) var<uniform> projection: mat4x4<f32>;
+
+@group(1) @binding(0) var atlas: texture_2d<f32>;
+@group(1) @binding(1) var atlasSampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec2<f32>,
+    @location(1) uv: vec2<f32>,
+    @location(2) color: vec4<f32>,
+};
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) color: vec4<f32>,
+};
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexPayload {
+    var out: VertexPayload;
+    out.position = projection * vec4<f32>(vertex.position, 0.0, 1.0);
+    out.uv = vertex.uv;
+    out.color = vertex.color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
+    let coverage = textureSample(atlas, atlasSampler, in.uv).a;
+    return vec4<f32>(in.color.rgb, in.color.a * coverage);
+}
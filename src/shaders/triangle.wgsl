@@ -0,0 +1,46 @@
+@group(0) @binding(0) var<uniform> viewProjection: mat4x4<f32>;
+
+struct Globals {
+    time: f32,
+    delta_time: f32,
+    resolution: vec2<f32>,
+};
+
+@group(0) @binding(2) var<uniform> globals: Globals;
+
+// How fast the triangle turns about the Y axis, in radians a second.
+const SPIN_SPEED: f32 = 1.0;
+
+@group(1) @binding(0) var myTexture: texture_2d<f32>;
+@group(1) @binding(1) var mySampler: sampler;
+
+struct Vertex {
+    @location(0) position: vec3<f32>,
+    @location(1) color: vec4<f32>,
+    @location(2) uv: vec2<f32>,
+};
+
+struct VertexPayload {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+    @location(1) textCord: vec2<f32>,
+};
+
+@vertex
+fn vs_main(vertex: Vertex) -> VertexPayload {
+    var out: VertexPayload;
+    let angle = globals.time * SPIN_SPEED;
+    let c = cos(angle);
+    let s = sin(angle);
+    let p = vertex.position;
+    let spun = vec3<f32>(c * p.x + s * p.z, p.y, c * p.z - s * p.x);
+    out.position = viewProjection * vec4<f32>(spun, 1.0);
+    out.color = vertex.color;
+    out.textCord = vertex.uv;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexPayload) -> @location(0) vec4<f32> {
+    return in.color * textureSample(myTexture, mySampler, in.textCord);
+}
@@ -0,0 +1,222 @@
+use std::path::{Path, PathBuf};
+
+use crate::renderer_backend::{
+    bind_group, bind_group_layout, pipeline,
+    shader::{self, ShaderError},
+    uniform,
+};
+
+pub const PRELUDE_FILENAME: &str = "shaders/shadertoy_prelude.wgsl";
+/// A shader to start from, `--shadertoy src/shaders/shadertoy_example.wgsl`.
+pub const EXAMPLE_FILENAME: &str = "shaders/shadertoy_example.wgsl";
+
+/// `Globals` in the prelude.
+#[repr(C)]
+#[derive(Clone, Copy, bytemuck::Pod, bytemuck::Zeroable)]
+struct ShadertoyGlobals {
+    resolution: [f32; 3],
+    time: f32,
+    mouse: [f32; 4],
+    frame: i32,
+    _padding: [i32; 3],
+}
+
+const _: () = assert!(std::mem::size_of::<ShadertoyGlobals>() == 48);
+
+/// A fullscreen triangle running the fragment shader in one WGSL file,
+/// Shadertoy style, in place of the scene. The file is reloaded whenever it
+/// changes; while it doesn't compile the last version that did keeps
+/// running and `error` says what's wrong.
+pub struct ShadertoyPass {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    path: PathBuf,
+    watcher: shader::Watcher,
+    format: wgpu::TextureFormat,
+    bind_group_layout: bind_group_layout::Layout,
+    bind_group: wgpu::BindGroup,
+    globals_buffer: wgpu::Buffer,
+    pipeline: Option<wgpu::RenderPipeline>,
+    error: Option<String>,
+    time: f32,
+    frame: i32,
+    mouse: [f32; 4],
+}
+
+impl ShadertoyPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        path: &Path,
+    ) -> Self {
+        let bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::FRAGMENT);
+            bind_group_layout = builder.build("Shadertoy Bind Group Layout");
+        }
+
+        let globals = ShadertoyGlobals {
+            resolution: [1.0; 3],
+            time: 0.0,
+            mouse: [0.0; 4],
+            frame: 0,
+            _padding: [0; 3],
+        };
+        let globals_buffer = uniform::make_uniform_buffer(
+            device,
+            "Shadertoy Globals Buffer",
+            bytemuck::bytes_of(&globals),
+        );
+
+        let bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&bind_group_layout);
+            builder.add_buffer(&globals_buffer);
+            bind_group = builder.build("Shadertoy Bind Group");
+        }
+
+        let mut pass = ShadertoyPass {
+            device: device.clone(),
+            queue: queue.clone(),
+            path: path.to_path_buf(),
+            watcher: shader::Watcher::new(path),
+            format: format,
+            bind_group_layout: bind_group_layout,
+            bind_group: bind_group,
+            globals_buffer: globals_buffer,
+            pipeline: None,
+            error: None,
+            time: 0.0,
+            frame: 0,
+            mouse: [0.0; 4],
+        };
+        pass.reload();
+        pass
+    }
+
+    fn build_pipeline(&self) -> Result<wgpu::RenderPipeline, ShaderError> {
+        // The prelude goes after the shader so the lines in errors are the
+        // shader file's own.
+        let source = format!(
+            "{}\n{}",
+            shader::read_wgsl(&self.path)?,
+            shader::read_wgsl(Path::new(PRELUDE_FILENAME))?
+        );
+        let mut builder = pipeline::Builder::new(&self.device);
+        builder.set_shader_source(&self.path.to_string_lossy(), source, "vs_main", "fs_main");
+        builder.set_pixel_format(self.format);
+        builder.set_cull_mode(None);
+        builder.add_bind_group_layout(&self.bind_group_layout);
+        builder.build_pipeline("Shadertoy Pipeline")
+    }
+
+    /// Compiles the shader again, keeping the last pipeline that worked if
+    /// it fails.
+    pub fn reload(&mut self) {
+        match self.build_pipeline() {
+            Ok(pipeline) => {
+                println!("Loaded {}", self.path.display());
+                self.pipeline = Some(pipeline);
+                self.error = None;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                self.error = Some(e.to_string());
+            }
+        }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Why the shader last failed to compile, until it compiles again.
+    pub fn error(&self) -> Option<&str> {
+        self.error.as_deref()
+    }
+
+    pub fn reset_time(&mut self) {
+        self.time = 0.0;
+        self.frame = 0;
+    }
+
+    /// Follows the left button and the cursor, in framebuffer pixels from
+    /// the top left, the way Shadertoy's `iMouse` does.
+    pub fn set_mouse(&mut self, cursor: Option<(f32, f32)>, pressed: bool, height: u32) {
+        let was_pressed = self.mouse[2] > 0.0;
+        if !pressed {
+            if was_pressed {
+                self.mouse[2] = -self.mouse[2];
+                self.mouse[3] = -self.mouse[3];
+            }
+            return;
+        }
+        let Some((x, y)) = cursor else {
+            return;
+        };
+        let y = height as f32 - y;
+        match was_pressed {
+            true => {
+                self.mouse[0] = x;
+                self.mouse[1] = y;
+            }
+            false => self.mouse = [x, y, x, y],
+        }
+    }
+
+    /// Moves `iTime` `dt` seconds on and picks up changes to the file.
+    /// `format` is the one the next `encode` draws into.
+    pub fn update(&mut self, dt: f32, format: wgpu::TextureFormat) {
+        self.time += dt;
+        if self.watcher.changed() || format != self.format {
+            self.format = format;
+            self.reload();
+        }
+    }
+
+    /// Draws the shader over all of `view`, `width` by `height` pixels.
+    pub fn encode(
+        &mut self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        let globals = ShadertoyGlobals {
+            resolution: [width as f32, height as f32, 1.0],
+            time: self.time,
+            mouse: self.mouse,
+            frame: self.frame,
+            _padding: [0; 3],
+        };
+        self.queue
+            .write_buffer(&self.globals_buffer, 0, bytemuck::bytes_of(&globals));
+        self.frame += 1;
+
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: view,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                store: wgpu::StoreOp::Store,
+            },
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Shadertoy Pass"),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        if let Some(pipeline) = &self.pipeline {
+            renderpass.set_pipeline(pipeline);
+            renderpass.set_bind_group(0, &self.bind_group, &[]);
+            renderpass.draw(0..3, 0..1);
+        }
+    }
+}
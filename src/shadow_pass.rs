@@ -0,0 +1,398 @@
+use serde::{Deserialize, Serialize};
+
+use crate::renderer_backend::{
+    bind_group, bind_group_layout,
+    camera::{Camera, OrthographicSize, Projection},
+    frame_uploader::FrameUploader,
+    frustum::Aabb,
+    pipeline,
+    shader::ShaderError,
+    texture::Texture,
+    uniform,
+};
+
+/// Draws the shadow map over a corner of the frame.
+pub const SHADER_FILENAME: &str = "shaders/shadow_map.wgsl";
+pub const FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+pub const DEFAULT_RESOLUTION: u32 = 2048;
+pub const DEFAULT_BIAS: f32 = 0.002;
+/// How far `ShadowSettings::bias` goes in the debug window.
+pub const MAX_BIAS: f32 = 0.02;
+
+/// The overlay's side as a part of the frame's shorter side, and its
+/// distance from the frame's edges in pixels.
+const OVERLAY_SCALE: f32 = 0.3;
+const OVERLAY_MARGIN: f32 = 16.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(default, deny_unknown_fields)]
+pub struct ShadowSettings {
+    pub enabled: bool,
+    /// Texels along each side of the shadow map.
+    pub resolution: u32,
+    /// Light-space depth taken off each fragment before it's compared with
+    /// the shadow map. Too little and lit surfaces shadow themselves in
+    /// stripes, acne; too much and shadows come loose from the objects
+    /// casting them, peter-panning.
+    pub bias: f32,
+}
+
+impl Default for ShadowSettings {
+    fn default() -> Self {
+        ShadowSettings {
+            enabled: true,
+            resolution: DEFAULT_RESOLUTION,
+            bias: DEFAULT_BIAS,
+        }
+    }
+}
+
+/// An orthographic camera looking along `direction`, the way the light
+/// travels, just big enough for a sphere around `bounds`, so everything in
+/// them can cast a shadow. Fitting the sphere rather than the box keeps the
+/// view the same size however the light turns.
+pub fn fit_light(direction: glm::Vec3, bounds: &Aabb) -> Camera {
+    let direction = glm::normalize(direction);
+    let radius = glm::length(bounds.half_extents()).max(1.0);
+    let mut camera = Camera::new(1.0);
+    camera.target = bounds.center();
+    camera.position = camera.target - direction * (radius * 2.0);
+    camera.up = match direction.y.abs() > 0.99 {
+        true => glm::vec3(0.0, 0.0, 1.0),
+        false => glm::vec3(0.0, 1.0, 0.0),
+    };
+    camera.projection = Projection::Orthographic {
+        size: OrthographicSize::Height(radius * 2.0),
+        near: radius,
+        far: radius * 3.0,
+    };
+    camera
+}
+
+/// The scene's depth as the directional light sees it, drawn before the
+/// scene pass by the renderer's shadow pipeline and sampled by the lit
+/// shaders through a comparison sampler. Fragments outside the light's
+/// view are lit.
+pub struct ShadowPass {
+    settings: ShadowSettings,
+    texture: Texture,
+    sampler: wgpu::Sampler,
+    /// `ShadowUniform`, for the scene's fragment stage.
+    uniform_buffer: wgpu::Buffer,
+    /// The light's view-projection as a `CameraUniform`, which the shadow
+    /// pipeline's vertex stage reads in place of the camera's.
+    caster_buffer: wgpu::Buffer,
+    caster_bind_group_layout: bind_group_layout::Layout,
+    caster_bind_group: wgpu::BindGroup,
+    overlay_bind_group_layout: bind_group_layout::Layout,
+    overlay_bind_group: wgpu::BindGroup,
+    overlay_pipeline: wgpu::RenderPipeline,
+    light_view_projection: glm::Mat4,
+    /// Set while the light's view stays where it was, see `toggle_freeze`.
+    frozen: bool,
+}
+
+impl ShadowPass {
+    pub fn new(
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+        settings: ShadowSettings,
+    ) -> Result<Self, ShaderError> {
+        let settings = Self::clamp_settings(device, settings);
+        let texture = Self::create_texture(device, settings.resolution);
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("Shadow Sampler"),
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            // Lit where the fragment is no farther from the light than
+            // what the shadow map saw first.
+            compare: Some(wgpu::CompareFunction::LessEqual),
+            ..Default::default()
+        });
+
+        let light_view_projection = uniform::identity();
+        let uniform_buffer = uniform::make_uniform_buffer(
+            device,
+            "Shadow Uniform Buffer",
+            bytemuck::bytes_of(&uniform::ShadowUniform::new(
+                &light_view_projection,
+                settings.bias,
+                settings.resolution,
+                false,
+            )),
+        );
+        let caster_buffer = uniform::make_uniform_buffer(
+            device,
+            "Shadow Caster Uniform Buffer",
+            bytemuck::bytes_of(&uniform::CameraUniform::new(&light_view_projection)),
+        );
+
+        // Only the view-projection: the shadow pipeline has no fragment
+        // stage, and the shadow map can't be bound while it's drawn into.
+        let caster_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            caster_bind_group_layout = builder.build("Shadow Caster Bind Group Layout");
+        }
+        let caster_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&caster_bind_group_layout);
+            builder.add_buffer(&caster_buffer);
+            caster_bind_group = builder.build("Shadow Caster Bind Group");
+        }
+
+        let overlay_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_unfilterable_texture(wgpu::ShaderStages::FRAGMENT);
+            overlay_bind_group_layout = builder.build("Shadow Map Overlay Bind Group Layout");
+        }
+        let overlay_bind_group =
+            Self::build_overlay_bind_group(device, &overlay_bind_group_layout, &texture);
+        let overlay_pipeline: wgpu::RenderPipeline;
+        {
+            let mut builder = pipeline::Builder::new(device);
+            builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+            builder.set_pixel_format(format);
+            builder.set_cull_mode(None);
+            builder.add_bind_group_layout(&overlay_bind_group_layout);
+            overlay_pipeline = builder.build_pipeline("Shadow Map Overlay Pipeline")?;
+        }
+
+        Ok(ShadowPass {
+            settings: settings,
+            texture: texture,
+            sampler: sampler,
+            uniform_buffer: uniform_buffer,
+            caster_buffer: caster_buffer,
+            caster_bind_group_layout: caster_bind_group_layout,
+            caster_bind_group: caster_bind_group,
+            overlay_bind_group_layout: overlay_bind_group_layout,
+            overlay_bind_group: overlay_bind_group,
+            overlay_pipeline: overlay_pipeline,
+            light_view_projection: light_view_projection,
+            frozen: false,
+        })
+    }
+
+    fn create_texture(device: &wgpu::Device, resolution: u32) -> Texture {
+        Texture::create_sampled_target(device, resolution, resolution, FORMAT, "Shadow Map")
+    }
+
+    fn build_overlay_bind_group(
+        device: &wgpu::Device,
+        layout: &bind_group_layout::Layout,
+        texture: &Texture,
+    ) -> wgpu::BindGroup {
+        let mut builder = bind_group::Builder::new(device);
+        builder.set_layout(layout);
+        builder.add_texture(&texture.view);
+        builder.build("Shadow Map Overlay Bind Group")
+    }
+
+    /// Keeps the resolution within what the device can make.
+    fn clamp_settings(device: &wgpu::Device, settings: ShadowSettings) -> ShadowSettings {
+        let max = device.limits().max_texture_dimension_2d;
+        if settings.resolution > max {
+            eprintln!(
+                "Warning: {} texel shadow maps are too large, using {}",
+                settings.resolution, max
+            );
+        }
+        ShadowSettings {
+            resolution: settings.resolution.clamp(1, max),
+            bias: settings.bias.max(0.0),
+            ..settings
+        }
+    }
+
+    pub fn settings(&self) -> ShadowSettings {
+        self.settings
+    }
+
+    /// A new resolution means a new shadow map, and bind groups holding
+    /// `view` have to be built again.
+    pub fn set_settings(&mut self, device: &wgpu::Device, settings: ShadowSettings) {
+        let settings = Self::clamp_settings(device, settings);
+        if settings.resolution != self.settings.resolution {
+            self.texture = Self::create_texture(device, settings.resolution);
+            self.overlay_bind_group = Self::build_overlay_bind_group(
+                device,
+                &self.overlay_bind_group_layout,
+                &self.texture,
+            );
+        }
+        self.settings = settings;
+    }
+
+    pub fn view(&self) -> &wgpu::TextureView {
+        &self.texture.view
+    }
+
+    pub fn sampler(&self) -> &wgpu::Sampler {
+        &self.sampler
+    }
+
+    pub fn uniform_buffer(&self) -> &wgpu::Buffer {
+        &self.uniform_buffer
+    }
+
+    /// Group 0 of the shadow pipeline.
+    pub fn caster_bind_group_layout(&self) -> &bind_group_layout::Layout {
+        &self.caster_bind_group_layout
+    }
+
+    pub fn caster_bind_group(&self) -> &wgpu::BindGroup {
+        &self.caster_bind_group
+    }
+
+    pub fn light_view_projection(&self) -> glm::Mat4 {
+        self.light_view_projection
+    }
+
+    pub fn frozen(&self) -> bool {
+        self.frozen
+    }
+
+    /// Keeps the light's view where it is while the scene and the light
+    /// move on, so what falls outside it can be seen. Toggles back.
+    pub fn toggle_freeze(&mut self) {
+        self.frozen = !self.frozen;
+    }
+
+    /// Keeps the light's view at `light_view_projection`.
+    pub fn freeze(&mut self, light_view_projection: glm::Mat4) {
+        self.light_view_projection = light_view_projection;
+        self.frozen = true;
+    }
+
+    /// Fits the light's view around `bounds`, unless it's frozen, and
+    /// uploads it with the settings.
+    pub fn prepare(&mut self, uploader: &mut FrameUploader, direction: glm::Vec3, bounds: &Aabb) {
+        if !self.frozen {
+            self.light_view_projection = fit_light(direction, bounds).build_view_projection();
+        }
+        let shadow_uniform = uniform::ShadowUniform::new(
+            &self.light_view_projection,
+            self.settings.bias,
+            self.settings.resolution,
+            self.settings.enabled,
+        );
+        uploader.write(&self.uniform_buffer, 0, &[shadow_uniform]);
+        let caster_uniform = uniform::CameraUniform::new(&self.light_view_projection);
+        uploader.write(&self.caster_buffer, 0, &[caster_uniform]);
+    }
+
+    /// Clears the shadow map to the far plane for the casters to draw into.
+    pub fn depth_attachment(&self) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.texture.view,
+            depth_ops: Some(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: wgpu::StoreOp::Store,
+            }),
+            stencil_ops: None,
+        }
+    }
+
+    /// Draws the shadow map, near the light dark and far light, into the
+    /// bottom right corner of `target`, `width` by `height` pixels.
+    pub fn record_overlay(
+        &self,
+        command_encoder: &mut wgpu::CommandEncoder,
+        target: &wgpu::TextureView,
+        width: u32,
+        height: u32,
+    ) {
+        let size = width.min(height) as f32 * OVERLAY_SCALE;
+        let x = width as f32 - size - OVERLAY_MARGIN;
+        let y = height as f32 - size - OVERLAY_MARGIN;
+        if x < 0.0 || y < 0.0 {
+            return;
+        }
+
+        let color_attachment = wgpu::RenderPassColorAttachment {
+            view: target,
+            resolve_target: None,
+            ops: wgpu::Operations {
+                load: wgpu::LoadOp::Load,
+                store: wgpu::StoreOp::Store,
+            },
+        };
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Shadow Map Overlay Pass"),
+            color_attachments: &[Some(color_attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        renderpass.set_viewport(x, y, size, size, 0.0, 1.0);
+        renderpass.set_pipeline(&self.overlay_pipeline);
+        renderpass.set_bind_group(0, &self.overlay_bind_group, &[]);
+        renderpass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::renderer::DEFAULT_LIGHT_DIRECTION;
+
+    /// The light's view has to take in every corner of what it's fitted to,
+    /// from any direction, straight down included.
+    #[test]
+    fn fitted_light_sees_every_corner() {
+        let boxes = [
+            Aabb {
+                min: glm::vec3(-1.0, -1.0, -1.0),
+                max: glm::vec3(1.0, 1.0, 1.0),
+            },
+            Aabb {
+                min: glm::vec3(-12.0, -2.0, -30.0),
+                max: glm::vec3(8.0, 5.0, 2.0),
+            },
+            // A single point still gets a view of some size.
+            Aabb {
+                min: glm::vec3(3.0, 0.0, 0.0),
+                max: glm::vec3(3.0, 0.0, 0.0),
+            },
+        ];
+        let directions = [
+            *glm::Vec3::from_array(&DEFAULT_LIGHT_DIRECTION),
+            glm::vec3(0.0, -1.0, 0.0),
+            glm::vec3(0.0, 1.0, 0.0),
+            glm::vec3(1.0, 0.0, 0.0),
+        ];
+        for bounds in &boxes {
+            for direction in directions {
+                let view_projection = fit_light(direction, bounds).build_view_projection();
+                for i in 0..8 {
+                    let pick = |bit: usize, axis: usize| match i & bit {
+                        0 => bounds.min[axis],
+                        _ => bounds.max[axis],
+                    };
+                    let corner = glm::vec3(pick(1, 0), pick(2, 1), pick(4, 2));
+                    let clip = view_projection * corner.extend(1.0);
+                    let ndc = clip.truncate(3) / clip.w;
+                    assert!(
+                        ndc.x.abs() <= 1.0 + 1e-4
+                            && ndc.y.abs() <= 1.0 + 1e-4
+                            && (-1e-4..=1.0 + 1e-4).contains(&ndc.z),
+                        "corner {:?} of {:?} lit along {:?} lands on {:?}",
+                        corner.as_array(),
+                        bounds,
+                        direction.as_array(),
+                        ndc.as_array()
+                    );
+                }
+            }
+        }
+    }
+}
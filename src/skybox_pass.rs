@@ -0,0 +1,177 @@
+use std::path::Path;
+
+use crate::renderer_backend::{
+    bind_group, bind_group_layout,
+    camera::Camera,
+    error::RendererError,
+    frame_uploader::FrameUploader,
+    mesh_builder, pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    shader::ShaderError,
+    texture::Texture,
+    uniform,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/skybox.wgsl";
+
+/// Cubemap faces in layer order: +X, -X, +Y, -Y, +Z, -Z.
+pub const FACE_FILENAMES: [&str; 6] = [
+    "img/skybox/px.png",
+    "img/skybox/nx.png",
+    "img/skybox/py.png",
+    "img/skybox/ny.png",
+    "img/skybox/pz.png",
+    "img/skybox/nz.png",
+];
+
+/// A cubemap drawn around the camera wherever the scene left the depth
+/// buffer at the far plane.
+pub struct SkyboxPass {
+    mesh: mesh_builder::Mesh,
+    pipeline: wgpu::RenderPipeline,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    uniform_buffer: wgpu::Buffer,
+    uniform_bind_group_layout: bind_group_layout::Layout,
+    uniform_bind_group: wgpu::BindGroup,
+    cubemap_bind_group_layout: bind_group_layout::Layout,
+    cubemap_bind_group: wgpu::BindGroup,
+}
+
+impl SkyboxPass {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        info: &TargetInfo,
+    ) -> Result<Self, RendererError> {
+        let cubemap = Texture::cubemap_from_files(device, queue, FACE_FILENAMES.map(Path::new))?;
+        // We look at the cube from inside, so its faces wind the other way;
+        // the pipeline doesn't cull.
+        let mesh = mesh_builder::load_obj(device, Path::new("models/cube.obj"))?;
+
+        let uniform_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_uniform_buffer(wgpu::ShaderStages::VERTEX);
+            uniform_bind_group_layout = builder.build("Skybox Uniform Bind Group Layout");
+        }
+        let cubemap_bind_group_layout: bind_group_layout::Layout;
+        {
+            let mut builder = bind_group_layout::Builder::new(device);
+            builder.add_cubemap(wgpu::ShaderStages::FRAGMENT);
+            cubemap_bind_group_layout = builder.build("Skybox Cubemap Bind Group Layout");
+        }
+
+        let skybox_uniform =
+            uniform::SkyboxUniform::new(&uniform::identity(), &uniform::identity());
+        let uniform_buffer = uniform::make_uniform_buffer(
+            device,
+            "Skybox Uniform Buffer",
+            bytemuck::bytes_of(&skybox_uniform),
+        );
+        let uniform_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&uniform_bind_group_layout);
+            builder.add_buffer(&uniform_buffer);
+            uniform_bind_group = builder.build("Skybox Uniform Bind Group");
+        }
+        let cubemap_bind_group: wgpu::BindGroup;
+        {
+            let mut builder = bind_group::Builder::new(device);
+            builder.set_layout(&cubemap_bind_group_layout);
+            builder.add_material(
+                &cubemap.view,
+                cubemap
+                    .sampler
+                    .as_ref()
+                    .expect("cubemaps are created with a sampler"),
+            );
+            cubemap_bind_group = builder.build("Skybox Cubemap Bind Group");
+        }
+
+        let pipeline = Self::build_pipeline(
+            device,
+            info,
+            &uniform_bind_group_layout,
+            &cubemap_bind_group_layout,
+        )?;
+        Ok(SkyboxPass {
+            mesh: mesh,
+            pipeline: pipeline,
+            depth_format: info.depth_format,
+            sample_count: info.sample_count,
+            uniform_buffer: uniform_buffer,
+            uniform_bind_group_layout: uniform_bind_group_layout,
+            uniform_bind_group: uniform_bind_group,
+            cubemap_bind_group_layout: cubemap_bind_group_layout,
+            cubemap_bind_group: cubemap_bind_group,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        uniform_bind_group_layout: &wgpu::BindGroupLayout,
+        cubemap_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(info.format);
+        builder.set_depth_format(info.depth_format);
+        // Drawn at the far plane: passes where the scene left the cleared
+        // depth and never hides anything drawn after it.
+        builder.set_depth_test(false, wgpu::CompareFunction::LessEqual);
+        builder.set_cull_mode(None);
+        builder.set_sample_count(info.sample_count);
+        builder.add_vertex_buffer_layout(mesh_builder::Vertex::get_layout());
+        builder.add_bind_group_layout(uniform_bind_group_layout);
+        builder.add_bind_group_layout(cubemap_bind_group_layout);
+        builder.build_pipeline("Skybox Pipeline")
+    }
+}
+
+impl RenderPass for SkyboxPass {
+    fn prepare(&mut self, uploader: &mut FrameUploader, camera: &Camera) {
+        // The sky is infinitely far away, out of any orthographic view.
+        let projection = camera.as_perspective().build_projection();
+        let skybox_uniform = uniform::SkyboxUniform::new(&camera.build_view(), &projection);
+        uploader.write(&self.uniform_buffer, 0, &[skybox_uniform]);
+    }
+
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Skybox Pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        targets.apply_viewport(&mut renderpass);
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.uniform_bind_group, &[]);
+        renderpass.set_bind_group(1, &self.cubemap_bind_group, &[]);
+        self.mesh.draw(&mut renderpass);
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        if info.sample_count == self.sample_count && info.depth_format == self.depth_format {
+            return;
+        }
+        match Self::build_pipeline(
+            device,
+            info,
+            &self.uniform_bind_group_layout,
+            &self.cubemap_bind_group_layout,
+        ) {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.depth_format = info.depth_format;
+                self.sample_count = info.sample_count;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::path::Path;
+
+use glm::{vec2, Vec2};
+
+use crate::hash_unit;
+use crate::renderer_backend::{
+    sprite_batch::{SpriteBatch, SpriteImage},
+    texture::TextureError,
+    texture_atlas::TextureAtlas,
+};
+
+/// Sprites the stress demo starts with, enough to need many times the
+/// batch's first buffers.
+pub const SPRITE_COUNT: usize = 5000;
+
+const ATLAS_IMAGES: [&str; 2] = ["img/grunge.jpg", "img/satin.jpg"];
+
+struct Bouncer {
+    image: SpriteImage,
+    position: Vec2,
+    velocity: Vec2,
+    size: Vec2,
+    rotation: f32,
+    spin: f32,
+    color: [f32; 4],
+}
+
+/// Sprites flying around the window and bouncing off its edges, to see how
+/// many `SpriteBatch` takes. A third of them are plain white and the rest
+/// come from one atlas, so they always draw in two batches.
+pub struct BouncingSprites {
+    bouncers: Vec<Bouncer>,
+}
+
+impl BouncingSprites {
+    /// Scatters `count` sprites over a `width` by `height` pixel window,
+    /// adding the atlas they're drawn from to `batch`.
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        batch: &mut SpriteBatch,
+        count: usize,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, TextureError> {
+        let paths = ATLAS_IMAGES.map(Path::new);
+        let atlas = TextureAtlas::from_files(device, queue, &paths)?;
+        let texture = batch.add_texture(&atlas.texture);
+        let mut images = vec![SpriteImage::from(batch.white())];
+        for path in paths {
+            let name = path.file_stem().unwrap_or_default().to_string_lossy();
+            if let Some(region) = atlas.region(&name, true) {
+                images.push(texture.region(region));
+            }
+        }
+
+        let bouncers = (0..count)
+            .map(|i| {
+                let random = |salt: f32| hash_unit(i as f32 * 7.31 + salt);
+                let speed = 50.0 + 200.0 * random(1.0);
+                let heading = std::f32::consts::TAU * random(2.0);
+                let side = 16.0 + 32.0 * random(3.0);
+                Bouncer {
+                    image: images[i % images.len()],
+                    position: vec2(width as f32 * random(4.0), height as f32 * random(5.0)),
+                    velocity: vec2(heading.cos(), heading.sin()) * speed,
+                    size: vec2(side, side),
+                    rotation: std::f32::consts::TAU * random(6.0),
+                    spin: 6.0 * random(7.0) - 3.0,
+                    color: [
+                        0.5 + 0.5 * random(8.0),
+                        0.5 + 0.5 * random(9.0),
+                        0.5 + 0.5 * random(10.0),
+                        0.8,
+                    ],
+                }
+            })
+            .collect();
+        Ok(BouncingSprites { bouncers: bouncers })
+    }
+
+    /// Moves every sprite `dt` seconds on, turning it back at the edges of
+    /// a `width` by `height` pixel window.
+    pub fn update(&mut self, dt: f32, width: u32, height: u32) {
+        let bounds = vec2(width as f32, height as f32);
+        for bouncer in &mut self.bouncers {
+            bouncer.position = bouncer.position + bouncer.velocity * dt;
+            bouncer.rotation += bouncer.spin * dt;
+            for axis in 0..2 {
+                let half_size = bouncer.size[axis] * 0.5;
+                if bouncer.position[axis] < half_size {
+                    bouncer.position[axis] = half_size;
+                    bouncer.velocity[axis] = bouncer.velocity[axis].abs();
+                } else if bouncer.position[axis] > bounds[axis] - half_size {
+                    bouncer.position[axis] = (bounds[axis] - half_size).max(half_size);
+                    bouncer.velocity[axis] = -bouncer.velocity[axis].abs();
+                }
+            }
+        }
+    }
+
+    pub fn queue(&self, batch: &mut SpriteBatch) {
+        for bouncer in &self.bouncers {
+            batch.draw_sprite(
+                bouncer.image,
+                bouncer.position,
+                bouncer.size,
+                bouncer.rotation,
+                bouncer.color,
+            );
+        }
+    }
+}
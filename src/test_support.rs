@@ -0,0 +1,77 @@
+//! Devices and renderers for the tests, on whatever adapter there is with no
+//! window, software fallbacks included. Where there's no adapter at all these
+//! print why and return `None`, and the test returns without checking
+//! anything.
+
+use crate::renderer::{self, Renderer};
+use crate::renderer_backend::{
+    adapter::{self, AdapterOptions},
+    error::RendererError,
+    mesh_builder::{Mesh, Vertex},
+};
+
+pub fn headless_device() -> Option<(wgpu::Device, wgpu::Queue)> {
+    let options = AdapterOptions::default();
+    let instance = adapter::create_instance(&options);
+    skip_without_adapter(pollster::block_on(async {
+        let adapter = adapter::request_headless(&instance, &options).await?;
+        renderer::request_device(&adapter, None).await
+    }))
+}
+
+/// A `Renderer::headless` drawing `width` by `height` pixels. Building it
+/// builds every pipeline, so anything else going wrong panics.
+pub fn headless_renderer(width: u32, height: u32) -> Option<Renderer> {
+    skip_without_adapter(pollster::block_on(Renderer::headless(
+        width,
+        height,
+        &AdapterOptions::default(),
+    )))
+}
+
+fn skip_without_adapter<T>(result: Result<T, RendererError>) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(
+            error @ (RendererError::NoAdapter
+            | RendererError::NoAdapterOnBackend { .. }
+            | RendererError::RequestDevice(_)),
+        ) => {
+            println!("skipped, nothing to run on: {}", error);
+            None
+        }
+        Err(error) => panic!("{}", error),
+    }
+}
+
+/// Asserts `mesh` has the vertex and index counts its builder documents, in
+/// buffers big enough for them.
+pub fn assert_mesh(mesh: &Mesh, vertices: u32, indices: u32) {
+    let label = mesh.label();
+    assert_eq!(
+        (mesh.vertex_count, mesh.index_count),
+        (vertices, indices),
+        "{}: vertex and index counts",
+        label
+    );
+    let vertex_bytes = vertices as u64 * std::mem::size_of::<Vertex>() as u64;
+    assert!(
+        mesh.vertex_buffer.size() >= vertex_bytes,
+        "{}: {} byte vertex buffer for {} bytes of vertices",
+        label,
+        mesh.vertex_buffer.size(),
+        vertex_bytes
+    );
+    let index_bytes = indices as u64 * mesh.index_format.byte_size() as u64;
+    let index_buffer_size = mesh.index_buffer.as_ref().map_or(0, |buffer| buffer.size());
+    assert!(
+        index_buffer_size >= index_bytes,
+        "{}: {} byte index buffer for {} bytes of indices",
+        label,
+        index_buffer_size,
+        index_bytes
+    );
+    if let Some(index) = mesh.indices().iter().find(|&&index| index >= vertices) {
+        panic!("{}: index {} out of range", label, index);
+    }
+}
@@ -0,0 +1,102 @@
+use std::path::Path;
+
+use crate::hash_unit;
+use crate::renderer_backend::{
+    camera::Camera,
+    tilemap::{Tilemap, TilemapError, TilemapStats},
+};
+
+pub const MAP_PATH: &str = "maps/sample.csv";
+
+/// The map's tiles 0 to 5.
+const TILE_IMAGES: [&str; 6] = [
+    "img/skybox/px.png",
+    "img/skybox/nx.png",
+    "img/skybox/py.png",
+    "img/skybox/ny.png",
+    "img/skybox/pz.png",
+    "img/skybox/nz.png",
+];
+
+/// Pixels along a tile's side.
+const TILE_SIZE: f32 = 32.0;
+
+/// Seconds between the tiles the demo changes, each of which rebuilds only
+/// its own chunk.
+const CHANGE_INTERVAL: f32 = 0.5;
+
+/// The bundled map drawn over the scene through a 2D camera that scrolls
+/// as the scene's camera moves over the ground, one tile per world unit.
+pub struct TilemapDemo {
+    tilemap: Tilemap,
+    camera: Camera,
+    since_change: f32,
+    changes: u32,
+}
+
+impl TilemapDemo {
+    pub fn new(
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        format: wgpu::TextureFormat,
+        width: u32,
+        height: u32,
+    ) -> Result<Self, TilemapError> {
+        let tile_paths = TILE_IMAGES.map(Path::new);
+        let tilemap = Tilemap::from_files(
+            device,
+            queue,
+            format,
+            Path::new(MAP_PATH),
+            &tile_paths,
+            TILE_SIZE,
+        )?;
+        Ok(TilemapDemo {
+            tilemap: tilemap,
+            camera: Camera::pixel_space(width, height),
+            since_change: 0.0,
+            changes: 0,
+        })
+    }
+
+    pub fn resize(&mut self, width: u32, height: u32) {
+        self.camera.set_viewport(width, height);
+    }
+
+    pub fn stats(&self) -> TilemapStats {
+        self.tilemap.stats()
+    }
+
+    /// Follows `scene_camera` and changes a tile every `CHANGE_INTERVAL`.
+    pub fn update(&mut self, dt: f32, scene_camera: &Camera) {
+        let (map_width, map_height) = self.tilemap.size();
+        let view_height = self.camera.viewport_height;
+        let view_width = view_height * self.camera.aspect;
+        let center_x = map_width * 0.5 + scene_camera.position.x * TILE_SIZE;
+        let center_y = map_height * 0.5 + scene_camera.position.z * TILE_SIZE;
+        self.camera.position.x = center_x - view_width * 0.5;
+        self.camera.position.y = center_y - view_height * 0.5;
+        self.camera.target = self.camera.position + glm::vec3(0.0, 0.0, 1.0);
+
+        self.since_change += dt;
+        if self.since_change < CHANGE_INTERVAL {
+            return;
+        }
+        self.since_change = 0.0;
+        self.changes += 1;
+        let grid = self.tilemap.grid();
+        let seed = self.changes as f32;
+        let x = (hash_unit(seed) * grid.width as f32) as u32;
+        let y = (hash_unit(seed + 0.5) * grid.height as f32) as u32;
+        let tile = grid
+            .get(x, y)
+            .map(|tile| (tile + 1) % TILE_IMAGES.len() as u16);
+        self.tilemap.set_tile(x, y, tile);
+    }
+
+    /// Rebuilds what changed and draws the map over `view`.
+    pub fn encode(&mut self, command_encoder: &mut wgpu::CommandEncoder, view: &wgpu::TextureView) {
+        self.tilemap.prepare(&self.camera);
+        self.tilemap.encode(command_encoder, view);
+    }
+}
@@ -0,0 +1,125 @@
+use crate::renderer_backend::{
+    bind_group_layout::Layout,
+    color::ColorSpace,
+    material::Material,
+    mesh_builder, pipeline,
+    render_pass::{FrameTargets, RenderPass, TargetInfo},
+    sampler::SamplerDesc,
+    shader::ShaderError,
+};
+
+pub const SHADER_FILENAME: &str = "shaders/triangle.wgsl";
+
+/// The textured triangle in front of the camera, drawn in a pass of its own
+/// after the scene.
+pub struct TrianglePass {
+    mesh: mesh_builder::Mesh,
+    pipeline: wgpu::RenderPipeline,
+    depth_format: wgpu::TextureFormat,
+    sample_count: u32,
+    colors: ColorSpace,
+    camera_bind_group_layout: wgpu::BindGroupLayout,
+    camera_bind_group: wgpu::BindGroup,
+    material_bind_group_layout: Layout,
+    material: Material,
+    sampler: SamplerDesc,
+}
+
+impl TrianglePass {
+    pub fn new(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        camera_bind_group: &wgpu::BindGroup,
+        material_bind_group_layout: &Layout,
+        material: &Material,
+    ) -> Result<Self, ShaderError> {
+        let pipeline = Self::build_pipeline(
+            device,
+            info,
+            camera_bind_group_layout,
+            material_bind_group_layout,
+        )?;
+        Ok(TrianglePass {
+            mesh: mesh_builder::make_triangle(device, info.colors),
+            pipeline: pipeline,
+            depth_format: info.depth_format,
+            sample_count: info.sample_count,
+            colors: info.colors,
+            camera_bind_group_layout: camera_bind_group_layout.clone(),
+            camera_bind_group: camera_bind_group.clone(),
+            material_bind_group_layout: material_bind_group_layout.clone(),
+            material: material.clone(),
+            sampler: info.sampler.desc,
+        })
+    }
+
+    fn build_pipeline(
+        device: &wgpu::Device,
+        info: &TargetInfo,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        material_bind_group_layout: &wgpu::BindGroupLayout,
+    ) -> Result<wgpu::RenderPipeline, ShaderError> {
+        let mut builder = pipeline::Builder::new(device);
+        builder.set_shader_module(SHADER_FILENAME, "vs_main", "fs_main");
+        builder.set_pixel_format(info.format);
+        builder.set_depth_format(info.depth_format);
+        builder.set_sample_count(info.sample_count);
+        // It turns about Y, showing its back half the time.
+        builder.set_cull_mode(None);
+        builder.add_vertex_buffer_layout(mesh_builder::Vertex::get_layout());
+        builder.add_bind_group_layout(camera_bind_group_layout);
+        builder.add_bind_group_layout(material_bind_group_layout);
+        builder.build_pipeline("Triangle Pipeline")
+    }
+}
+
+impl RenderPass for TrianglePass {
+    fn record(&self, command_encoder: &mut wgpu::CommandEncoder, targets: &FrameTargets) {
+        let render_pass_descriptor = wgpu::RenderPassDescriptor {
+            label: Some("Triangle Pass"),
+            color_attachments: &[Some(targets.color_attachment(wgpu::LoadOp::Load))],
+            depth_stencil_attachment: Some(targets.depth_attachment(wgpu::LoadOp::Load)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        };
+
+        let mut renderpass = command_encoder.begin_render_pass(&render_pass_descriptor);
+        targets.apply_viewport(&mut renderpass);
+        renderpass.set_pipeline(&self.pipeline);
+        renderpass.set_bind_group(0, &self.camera_bind_group, &[]);
+        renderpass.set_bind_group(1, &self.material.bind_group, &[]);
+        self.mesh.draw(&mut renderpass);
+    }
+
+    fn resize(&mut self, device: &wgpu::Device, info: &TargetInfo) {
+        if info.colors != self.colors {
+            self.mesh = mesh_builder::make_triangle(device, info.colors);
+            self.colors = info.colors;
+        }
+        if info.sampler.desc != self.sampler {
+            self.material.set_sampler(
+                device,
+                &self.material_bind_group_layout,
+                &info.sampler.sampler,
+            );
+            self.sampler = info.sampler.desc;
+        }
+        if info.sample_count == self.sample_count && info.depth_format == self.depth_format {
+            return;
+        }
+        match Self::build_pipeline(
+            device,
+            info,
+            &self.camera_bind_group_layout,
+            &self.material_bind_group_layout,
+        ) {
+            Ok(pipeline) => {
+                self.pipeline = pipeline;
+                self.depth_format = info.depth_format;
+                self.sample_count = info.sample_count;
+            }
+            Err(e) => eprintln!("{}", e),
+        }
+    }
+}
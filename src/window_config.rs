@@ -0,0 +1,79 @@
+use std::path::{Path, PathBuf};
+
+use crate::args;
+use crate::renderer_backend::error::RendererError;
+use crate::settings::Settings;
+
+/// How the main window is created.
+pub struct WindowConfig {
+    pub title: String,
+    /// Size of the window's content area in screen coordinates.
+    pub width: u32,
+    pub height: u32,
+    pub resizable: bool,
+    pub decorated: bool,
+    pub maximized: bool,
+    /// Whether the framebuffer's alpha reaches the compositor, so the
+    /// desktop can show through. Not every platform supports it.
+    pub transparent: bool,
+    /// A PNG to use as the window icon.
+    pub icon_path: Option<PathBuf>,
+}
+
+impl Default for WindowConfig {
+    fn default() -> Self {
+        WindowConfig {
+            title: "It's Graphics Time".to_string(),
+            width: 800,
+            height: 600,
+            resizable: true,
+            decorated: true,
+            maximized: false,
+            transparent: false,
+            icon_path: None,
+        }
+    }
+}
+
+/// Everything the windowed app takes from the command line before it opens
+/// its window.
+pub struct StartupOptions {
+    pub window: WindowConfig,
+    pub fullscreen: bool,
+    /// Whether presenting waits for vertical blank.
+    pub vsync: bool,
+    /// Takes the place of `vsync` when set.
+    pub present_mode: Option<wgpu::PresentMode>,
+    pub sample_count: u32,
+    /// Frame rate cap for when presenting doesn't wait for vertical blank.
+    pub max_fps: Option<u32>,
+}
+
+/// Sample counts `--msaa` and the settings file accept; the adapter may
+/// support fewer.
+pub const SAMPLE_COUNTS: [u32; 5] = [1, 2, 4, 8, 16];
+
+/// Reads `--icon`, `--undecorated`, `--transparent` and `--fullscreen`.
+/// The window's title and size, vsync, the sample count and the frame rate
+/// cap come from `settings`, which the command line has been applied to
+/// already.
+pub fn from_args(settings: &Settings) -> Result<StartupOptions, RendererError> {
+    let mut window = settings.window_config();
+    window.icon_path = args::flag_value("--icon")?.map(PathBuf::from);
+    window.decorated = !args::has_flag("--undecorated");
+    window.transparent = args::has_flag("--transparent");
+
+    Ok(StartupOptions {
+        window: window,
+        fullscreen: args::has_flag("--fullscreen"),
+        vsync: settings.display.vsync,
+        present_mode: settings.display.present_mode.map(|mode| mode.to_wgpu()),
+        sample_count: settings.display.msaa,
+        max_fps: settings.display.max_fps,
+    })
+}
+
+/// The PNG `path` names, for a window icon.
+pub fn load_icon(path: &Path) -> Result<image::RgbaImage, image::ImageError> {
+    Ok(image::open(path)?.to_rgba8())
+}
@@ -0,0 +1,397 @@
+use std::sync::Arc;
+
+use crate::gpu_context::GpuContext;
+use crate::platform::{NativeWindow, Window, WindowEvent};
+use crate::renderer_backend::error::RendererError;
+
+/// Surface format used for `--hdr`: linear, with room above 1.0.
+pub const HDR_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba16Float;
+
+/// Which format a `WindowSurface` is configured with.
+#[derive(Clone, Copy, Debug)]
+pub enum FormatRequest {
+    /// The first sRGB format the surface offers, trying `HDR_FORMAT` before
+    /// that when `hdr` is set.
+    Preferred { hdr: bool },
+    /// Exactly this format, so windows sharing pipelines agree on it. An
+    /// sRGB format can also be met by a view of its non-sRGB sibling.
+    Exactly(wgpu::TextureFormat),
+}
+
+/// The format to configure a surface that offers `formats` with for
+/// `request`, and the format its images are viewed and drawn in. When the
+/// surface has no sRGB format, the chosen one is viewed as its sRGB sibling
+/// if it has one and `srgb_views` says views may differ from the surface,
+/// so output is still gamma-encoded. `None` if `request` can't be met.
+pub fn choose_formats(
+    formats: &[wgpu::TextureFormat],
+    request: FormatRequest,
+    srgb_views: bool,
+) -> Option<(wgpu::TextureFormat, wgpu::TextureFormat)> {
+    let srgb_sibling = |format: wgpu::TextureFormat| {
+        let srgb = format.add_srgb_suffix();
+        (srgb_views && srgb != format).then_some(srgb)
+    };
+    match request {
+        FormatRequest::Exactly(format) if formats.contains(&format) => Some((format, format)),
+        FormatRequest::Exactly(format) => {
+            let base = format.remove_srgb_suffix();
+            (formats.contains(&base) && srgb_sibling(base) == Some(format))
+                .then_some((base, format))
+        }
+        FormatRequest::Preferred { hdr: true } if formats.contains(&HDR_FORMAT) => {
+            Some((HDR_FORMAT, HDR_FORMAT))
+        }
+        FormatRequest::Preferred { .. } => match formats.iter().find(|f| f.is_srgb()) {
+            Some(&format) => Some((format, format)),
+            None => {
+                let format = *formats.first()?;
+                Some((format, srgb_sibling(format).unwrap_or(format)))
+            }
+        },
+    }
+}
+
+/// A window's size in the two coordinate spaces windows work in, and its
+/// content scale.
+///
+/// Window sizes and cursor positions are in screen coordinates.
+/// The surface, the depth and MSAA textures, viewports, scissor rects and
+/// the text overlay are all in framebuffer pixels. On a Retina display a
+/// screen coordinate covers several pixels; elsewhere the two usually match
+/// and the content scale alone says how much bigger UI should be drawn.
+#[derive(Clone, Copy, Debug)]
+pub struct WindowMetrics {
+    /// In screen coordinates.
+    pub window_size: (i32, i32),
+    /// In framebuffer pixels.
+    pub framebuffer_size: (i32, i32),
+    /// How much the monitor asks content to be scaled by, e.g. 2.0 at 200%.
+    pub content_scale: f32,
+}
+
+impl WindowMetrics {
+    /// Framebuffer pixels per screen coordinate.
+    pub fn pixel_ratio(&self) -> f32 {
+        self.framebuffer_size.0 as f32 / self.window_size.0.max(1) as f32
+    }
+
+    /// Converts a cursor position to framebuffer pixels.
+    pub fn cursor_to_pixels(&self, x: f64, y: f64) -> (f32, f32) {
+        let ratio = self.pixel_ratio();
+        (x as f32 * ratio, y as f32 * ratio)
+    }
+}
+
+/// A window together with the surface and swapchain configuration that
+/// present to it.
+pub struct WindowSurface {
+    // Declared before `window` so it's dropped first; it holds the window's
+    // raw handle.
+    surface: wgpu::Surface<'static>,
+    pub config: wgpu::SurfaceConfiguration,
+    pub window: NativeWindow,
+    gpu: Arc<GpuContext>,
+    open: bool,
+}
+
+impl WindowSurface {
+    /// Configures `surface`, created with `Window::create_surface`, at the
+    /// window's framebuffer size.
+    pub fn new(
+        gpu: Arc<GpuContext>,
+        window: NativeWindow,
+        surface: wgpu::Surface<'static>,
+        format: FormatRequest,
+    ) -> Result<Self, RendererError> {
+        let surface_capabilities = surface.get_capabilities(&gpu.adapter);
+        let formats = &surface_capabilities.formats;
+        if formats.is_empty() {
+            return Err(RendererError::UnsupportedSurface);
+        }
+        let (surface_format, view_format) =
+            choose_formats(formats, format, supports_srgb_views(&gpu.adapter))
+                .ok_or(RendererError::UnsupportedSurface)?;
+
+        let size = window.framebuffer_size();
+        let config = wgpu::SurfaceConfiguration {
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
+            format: surface_format,
+            width: size.0.max(1) as u32,
+            height: size.1.max(1) as u32,
+            present_mode: surface_capabilities.present_modes[0],
+            alpha_mode: surface_capabilities.alpha_modes[0],
+            view_formats: match view_format == surface_format {
+                true => vec![],
+                false => vec![view_format],
+            },
+            desired_maximum_frame_latency: 2,
+        };
+        surface.configure(&gpu.device, &config);
+
+        Ok(WindowSurface {
+            surface: surface,
+            config: config,
+            window: window,
+            gpu: gpu,
+            open: true,
+        })
+    }
+
+    /// Events received since the last call.
+    pub fn take_events(&self) -> Vec<WindowEvent> {
+        self.window.take_events()
+    }
+
+    pub fn metrics(&self) -> WindowMetrics {
+        WindowMetrics {
+            window_size: self.window.size(),
+            framebuffer_size: self.window.framebuffer_size(),
+            content_scale: self.window.content_scale(),
+        }
+    }
+
+    pub fn surface(&self) -> &wgpu::Surface<'static> {
+        &self.surface
+    }
+
+    /// The format the swapchain images are viewed and drawn in, which
+    /// pipelines are built for. It's `config.format` unless that isn't
+    /// sRGB, see `choose_formats`.
+    pub fn view_format(&self) -> wgpu::TextureFormat {
+        self.config
+            .view_formats
+            .first()
+            .copied()
+            .unwrap_or(self.config.format)
+    }
+
+    /// The configuration as the renderer sees its target: images in
+    /// `view_format`.
+    pub fn target_config(&self) -> wgpu::SurfaceConfiguration {
+        wgpu::SurfaceConfiguration {
+            format: self.view_format(),
+            view_formats: Vec::new(),
+            ..self.config.clone()
+        }
+    }
+
+    /// Configures the surface for `gpu`'s device, which replaces a lost one.
+    /// The format stays the same, since pipelines were built for it.
+    pub fn set_gpu(&mut self, gpu: Arc<GpuContext>) -> Result<(), RendererError> {
+        let surface_capabilities = self.surface.get_capabilities(&gpu.adapter);
+        if !surface_capabilities.formats.contains(&self.config.format)
+            || (!self.config.view_formats.is_empty() && !supports_srgb_views(&gpu.adapter))
+        {
+            return Err(RendererError::UnsupportedSurface);
+        }
+        if !surface_capabilities
+            .present_modes
+            .contains(&self.config.present_mode)
+        {
+            self.config.present_mode = surface_capabilities.present_modes[0];
+        }
+        if !surface_capabilities
+            .alpha_modes
+            .contains(&self.config.alpha_mode)
+        {
+            self.config.alpha_mode = surface_capabilities.alpha_modes[0];
+        }
+        self.surface.configure(&gpu.device, &self.config);
+        self.gpu = gpu;
+        Ok(())
+    }
+
+    /// Lets the desktop show through wherever the frame's alpha is below 1,
+    /// with the first of premultiplied and postmultiplied alpha the surface
+    /// supports. Needs a window created with a transparent framebuffer.
+    /// Returns the alpha mode, or `None`, leaving the surface opaque, when
+    /// there's no way to composite it.
+    pub fn set_see_through(&mut self) -> Option<wgpu::CompositeAlphaMode> {
+        if !self.window.is_framebuffer_transparent() {
+            return None;
+        }
+        let surface_capabilities = self.surface.get_capabilities(&self.gpu.adapter);
+        let alpha_mode = [
+            wgpu::CompositeAlphaMode::PreMultiplied,
+            wgpu::CompositeAlphaMode::PostMultiplied,
+        ]
+        .into_iter()
+        .find(|mode| surface_capabilities.alpha_modes.contains(mode))?;
+        self.config.alpha_mode = alpha_mode;
+        self.surface.configure(&self.gpu.device, &self.config);
+        Some(alpha_mode)
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// Hides the window and stops it from being rendered. The window itself
+    /// goes away when this is dropped.
+    pub fn close(&mut self) {
+        self.open = false;
+        self.window.hide();
+    }
+
+    pub fn resize(&mut self, width: i32, height: i32) {
+        self.config.width = width.max(1) as u32;
+        self.config.height = height.max(1) as u32;
+        self.surface.configure(&self.gpu.device, &self.config);
+    }
+
+    pub fn present_mode(&self) -> wgpu::PresentMode {
+        self.config.present_mode
+    }
+
+    pub fn set_present_mode(&mut self, mode: wgpu::PresentMode) -> bool {
+        let surface_capabilities = self.surface.get_capabilities(&self.gpu.adapter);
+        if !surface_capabilities.present_modes.contains(&mode) {
+            return false;
+        }
+        self.config.present_mode = mode;
+        self.surface.configure(&self.gpu.device, &self.config);
+        true
+    }
+
+    /// Lets the swapchain images be copied from, for reading frames back.
+    /// False if the surface doesn't allow it.
+    pub fn set_copyable(&mut self, copyable: bool) -> bool {
+        let surface_capabilities = self.surface.get_capabilities(&self.gpu.adapter);
+        if copyable
+            && !surface_capabilities
+                .usages
+                .contains(wgpu::TextureUsages::COPY_SRC)
+        {
+            return false;
+        }
+        self.config
+            .usage
+            .set(wgpu::TextureUsages::COPY_SRC, copyable);
+        self.surface.configure(&self.gpu.device, &self.config);
+        true
+    }
+
+    /// Acquires the next swapchain image, lets `record` draw into it and
+    /// presents it. `record` gets the image's texture too, for copies out
+    /// of it after `set_copyable`, and returns command buffers to submit
+    /// ahead of its encoder, if it recorded any.
+    pub fn render(
+        &mut self,
+        record: impl FnOnce(
+            &mut wgpu::CommandEncoder,
+            &wgpu::TextureView,
+            &wgpu::Texture,
+        ) -> Vec<wgpu::CommandBuffer>,
+    ) -> Result<(), wgpu::SurfaceError> {
+        let drawable = self.surface.get_current_texture()?;
+        let image_view_descriptor = wgpu::TextureViewDescriptor {
+            format: Some(self.view_format()),
+            ..Default::default()
+        };
+        let image_view = drawable.texture.create_view(&image_view_descriptor);
+
+        let command_encoder_descriptor = wgpu::CommandEncoderDescriptor {
+            label: Some("Render Encoder"),
+        };
+        let mut command_encoder = self
+            .gpu
+            .device
+            .create_command_encoder(&command_encoder_descriptor);
+        let mut command_buffers = record(&mut command_encoder, &image_view, &drawable.texture);
+        command_buffers.push(command_encoder.finish());
+        self.gpu.queue.submit(command_buffers);
+
+        drawable.present();
+        Ok(())
+    }
+}
+
+/// Whether swapchain images can be viewed in a format other than the
+/// surface's, which GLES, WebGL and Vulkan on Android can't.
+fn supports_srgb_views(adapter: &wgpu::Adapter) -> bool {
+    adapter
+        .get_downlevel_capabilities()
+        .flags
+        .contains(wgpu::DownlevelFlags::SURFACE_VIEW_FORMATS)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_are_chosen_with_srgb_views() {
+        use wgpu::TextureFormat::{Bgra8Unorm, Bgra8UnormSrgb, Rgba16Float, Rgba8Unorm};
+        let preferred = FormatRequest::Preferred { hdr: false };
+        let cases = [
+            // (offered, request, sRGB views allowed, expected)
+            (
+                &[Bgra8Unorm, Bgra8UnormSrgb][..],
+                preferred,
+                true,
+                Some((Bgra8UnormSrgb, Bgra8UnormSrgb)),
+            ),
+            (
+                &[Bgra8Unorm],
+                preferred,
+                true,
+                Some((Bgra8Unorm, Bgra8UnormSrgb)),
+            ),
+            (
+                &[Bgra8Unorm],
+                preferred,
+                false,
+                Some((Bgra8Unorm, Bgra8Unorm)),
+            ),
+            // No sRGB sibling to view it as.
+            (
+                &[Rgba16Float],
+                preferred,
+                true,
+                Some((Rgba16Float, Rgba16Float)),
+            ),
+            (
+                &[Bgra8Unorm, Rgba16Float],
+                FormatRequest::Preferred { hdr: true },
+                true,
+                Some((Rgba16Float, Rgba16Float)),
+            ),
+            // A second window drawn with the first one's pipelines.
+            (
+                &[Bgra8Unorm],
+                FormatRequest::Exactly(Bgra8UnormSrgb),
+                true,
+                Some((Bgra8Unorm, Bgra8UnormSrgb)),
+            ),
+            (
+                &[Bgra8Unorm],
+                FormatRequest::Exactly(Bgra8UnormSrgb),
+                false,
+                None,
+            ),
+            (
+                &[Rgba8Unorm],
+                FormatRequest::Exactly(Bgra8UnormSrgb),
+                true,
+                None,
+            ),
+            (
+                &[Bgra8Unorm],
+                FormatRequest::Exactly(Bgra8Unorm),
+                true,
+                Some((Bgra8Unorm, Bgra8Unorm)),
+            ),
+        ];
+        for (offered, request, srgb_views, expected) in cases {
+            assert_eq!(
+                choose_formats(offered, request, srgb_views),
+                expected,
+                "{:?} from {:?} with sRGB views {}",
+                request,
+                offered,
+                srgb_views
+            );
+        }
+    }
+}
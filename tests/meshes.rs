@@ -0,0 +1,44 @@
+use wgpu_beginner::renderer_backend::{
+    color::ColorSpace, mesh_builder, texture_atlas::AtlasRegion,
+};
+use wgpu_beginner::test_support::{self, assert_mesh};
+
+#[test]
+fn builders_make_the_documented_counts() {
+    let Some((device, _queue)) = test_support::headless_device() else {
+        return;
+    };
+    let device = &device;
+    assert_mesh(&mesh_builder::make_triangle(device, ColorSpace::Srgb), 3, 0);
+    assert_mesh(
+        &mesh_builder::make_textured_quad(device, ColorSpace::Srgb, &AtlasRegion::FULL),
+        4,
+        6,
+    );
+    assert_mesh(&mesh_builder::make_quad(device, 1.0), 4, 6);
+    assert_mesh(&mesh_builder::make_circle(device, 1.0, 16), 17, 48);
+    assert_mesh(&mesh_builder::make_cube(device, 1.0), 24, 36);
+    assert_mesh(&mesh_builder::make_uv_sphere(device, 1.0, 8, 12), 117, 504);
+    assert_mesh(&mesh_builder::make_plane(device, 1.0, 1.0, 4), 25, 96);
+    // Below the minimums the builders clamp rather than fail.
+    assert_mesh(&mesh_builder::make_circle(device, 1.0, 0), 4, 9);
+    assert_mesh(&mesh_builder::make_uv_sphere(device, 1.0, 0, 0), 12, 18);
+    assert_mesh(&mesh_builder::make_plane(device, 1.0, 1.0, 0), 4, 6);
+
+    // The pole rings too, where every sector meets at one point.
+    for (rings, sectors) in [(8, 12), (0, 0)] {
+        let sphere = mesh_builder::uv_sphere_data(1.0, rings, sectors);
+        for (i, vertex) in sphere.vertices.iter().enumerate() {
+            let normal = vertex.normal();
+            assert!(
+                normal.as_array().iter().all(|x| x.is_finite())
+                    && (glm::length(normal) - 1.0).abs() < 1e-4,
+                "{}x{} sphere vertex {} has normal {:?}",
+                rings,
+                sectors,
+                i,
+                normal.as_array()
+            );
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use std::path::Path;
+
+use wgpu_beginner::particles::{Emitter, ParticleSystem};
+use wgpu_beginner::renderer::HEADLESS_FORMAT;
+use wgpu_beginner::renderer_backend::{
+    camera::Camera,
+    frame_uploader::FrameUploader,
+    screenshot,
+    sprite_batch::SpriteBatch,
+    texture::Texture,
+    texture_atlas::TextureAtlas,
+    tilemap::{TileGrid, Tilemap},
+    validation,
+};
+use wgpu_beginner::shadertoy_pass::{self, ShadertoyPass};
+use wgpu_beginner::test_support;
+
+/// Size of the offscreen targets the tests draw into.
+const TARGET_SIZE: u32 = 64;
+
+/// Runs `f` under validation and submits what it records.
+fn submit(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    scope: &str,
+    f: impl FnOnce(&mut wgpu::CommandEncoder),
+) {
+    validation::with_validation(device, scope, || {
+        let mut command_encoder = device.create_command_encoder(&Default::default());
+        f(&mut command_encoder);
+        queue.submit([command_encoder.finish()]);
+    })
+    .unwrap_or_else(|error| panic!("{}", error));
+}
+
+/// Draws interleaved sprites from two textures twice: each frame should take
+/// one draw call per texture, and the second shouldn't grow the buffers.
+#[test]
+fn sprites_batch_by_texture() {
+    const SPRITES: u32 = 3000;
+    let Some((device, queue)) = test_support::headless_device() else {
+        return;
+    };
+    let target = Texture::create_readback_target(
+        &device,
+        TARGET_SIZE,
+        TARGET_SIZE,
+        HEADLESS_FORMAT,
+        "Sprite Target",
+    );
+    let mut sprites = SpriteBatch::new(&device, &queue, HEADLESS_FORMAT, TARGET_SIZE, TARGET_SIZE)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let textures = [
+        sprites.white(),
+        sprites.add_texture(&Texture::white(&device, &queue)),
+    ];
+
+    let mut reallocations = 0;
+    for frame in 0..2 {
+        for i in 0..SPRITES {
+            let position = glm::vec2((i % TARGET_SIZE) as f32, (i / TARGET_SIZE) as f32);
+            let size = glm::vec2(4.0, 2.0);
+            sprites.draw_sprite(textures[i as usize % 2], position, size, i as f32, [1.0; 4]);
+        }
+        submit(&device, &queue, "sprite test", |command_encoder| {
+            sprites.encode(command_encoder, &target.view)
+        });
+
+        let stats = sprites.stats();
+        assert_eq!(
+            (stats.sprites, stats.batches),
+            (SPRITES, 2),
+            "frame {}",
+            frame
+        );
+        assert!(stats.capacity >= SPRITES);
+        if frame == 1 {
+            assert_eq!(
+                stats.reallocations, reallocations,
+                "the second frame grew the buffers"
+            );
+        }
+        reallocations = stats.reallocations;
+    }
+}
+
+/// Draws a map of several chunks twice: the first frame should build every
+/// chunk, and after one tile changes the second only that tile's chunk.
+#[test]
+fn tilemap_rebuilds_only_changed_chunks() {
+    let Some((device, queue)) = test_support::headless_device() else {
+        return;
+    };
+    let target = Texture::create_readback_target(
+        &device,
+        TARGET_SIZE,
+        TARGET_SIZE,
+        HEADLESS_FORMAT,
+        "Tilemap Target",
+    );
+    let paths = [
+        Path::new("img/skybox/px.png"),
+        Path::new("img/skybox/nx.png"),
+    ];
+    let atlas = TextureAtlas::from_files(&device, &queue, &paths)
+        .unwrap_or_else(|error| panic!("{}", error));
+    let mut grid = TileGrid::new(100, 100);
+    for y in 0..grid.height {
+        for x in 0..grid.width {
+            grid.set(x, y, Some(((x + y) % 2) as u16));
+        }
+    }
+    let chunks = grid.chunks().0 * grid.chunks().1;
+    // Tiles three units wide, so the whole view is inside the first chunk.
+    let mut tiles = Tilemap::new(
+        &device,
+        &queue,
+        HEADLESS_FORMAT,
+        grid,
+        &atlas,
+        &["px", "nx"],
+        3.0,
+    )
+    .unwrap_or_else(|error| panic!("{}", error));
+    let camera = Camera::pixel_space(TARGET_SIZE, TARGET_SIZE);
+
+    let (x, y) = (70, 40);
+    let changed = tiles.grid().chunk_of(x, y);
+    for frame in 0..2 {
+        if frame == 1 {
+            assert!(
+                tiles.set_tile(x, y, None),
+                "couldn't clear tile ({}, {})",
+                x,
+                y
+            );
+        }
+        submit(&device, &queue, "tilemap test", |command_encoder| {
+            tiles.prepare(&camera);
+            tiles.encode(command_encoder, &target.view)
+        });
+
+        let stats = tiles.stats();
+        assert_eq!((stats.chunks, stats.drawn), (chunks, 1), "frame {}", frame);
+        let rebuilt = tiles.rebuilt_chunks();
+        match frame {
+            0 => assert_eq!(rebuilt.len(), chunks as usize),
+            _ => assert_eq!(rebuilt, [changed]),
+        }
+    }
+}
+
+/// Two steps of the compute simulation, read back: the first spawns what
+/// the rate asks for, the second more than there's room for.
+#[test]
+fn particle_compute_caps_spawns() {
+    const CAPACITY: u32 = 256;
+    let Some(renderer) = test_support::headless_renderer(TARGET_SIZE, TARGET_SIZE) else {
+        return;
+    };
+    if !renderer.particle_stats().on_gpu {
+        println!("skipped, no compute shaders");
+        return;
+    }
+    let (device, queue) = (&renderer.device, &renderer.queue);
+    let mut particles =
+        ParticleSystem::new(device, CAPACITY, 64, true).unwrap_or_else(|error| panic!("{}", error));
+    let mut emitter = Emitter::fountain();
+    emitter.spawn_rate = 1000.0;
+    emitter.lifetime_range = 10.0..10.0;
+    particles.set_emitter(emitter);
+    let mut uploader = FrameUploader::new();
+
+    for (dt, expected) in [(0.1, 100), (0.5, CAPACITY)] {
+        particles.update(device, &mut uploader, dt);
+        submit(device, queue, "particle test", |command_encoder| {
+            uploader.record(device, command_encoder);
+            particles.encode_compute(command_encoder, None);
+        });
+        uploader.recall();
+        particles.frame_submitted();
+        let _ = device.poll(wgpu::Maintain::Wait);
+        // Picks up the count; a step of nothing spawns nothing.
+        particles.update(device, &mut uploader, 0.0);
+        assert_eq!(particles.stats().live, expected, "after a {} s step", dt);
+    }
+}
+
+/// Draws the example `--shadertoy` shader, which is red on the left edge.
+#[test]
+fn shadertoy_example_draws() {
+    let Some((device, queue)) = test_support::headless_device() else {
+        return;
+    };
+    let target = Texture::create_readback_target(
+        &device,
+        TARGET_SIZE,
+        TARGET_SIZE,
+        HEADLESS_FORMAT,
+        "Shadertoy Target",
+    );
+    let path = Path::new(shadertoy_pass::EXAMPLE_FILENAME);
+    let mut shadertoy = ShadertoyPass::new(&device, &queue, HEADLESS_FORMAT, path);
+    assert_eq!(shadertoy.error(), None);
+    submit(&device, &queue, "shadertoy test", |command_encoder| {
+        shadertoy.encode(command_encoder, &target.view, TARGET_SIZE, TARGET_SIZE)
+    });
+    let rgba = screenshot::read_texture(&device, &queue, &target.texture)
+        .unwrap_or_else(|error| panic!("{}", error));
+    assert!(
+        matches!(rgba[..4], [red, _, _, 255] if red > 200),
+        "top left pixel is {:?}",
+        &rgba[..4]
+    );
+}
@@ -0,0 +1,102 @@
+use wgpu_beginner::renderer::Renderer;
+use wgpu_beginner::renderer_backend::{
+    per_draw,
+    uniform::{
+        BloomUniform, CameraUniform, GlobalsUniform, LightUniform, ModelUniform, ShadowUniform,
+        SkyboxUniform,
+    },
+    validation,
+};
+use wgpu_beginner::shadow_pass::ShadowSettings;
+use wgpu_beginner::test_support;
+
+/// Size of the offscreen target the tests render into.
+const TARGET_SIZE: u32 = 64;
+
+/// Building the renderer builds every pipeline, which panics if any fails.
+#[test]
+fn builds_every_pipeline() {
+    test_support::headless_renderer(TARGET_SIZE, TARGET_SIZE);
+}
+
+#[test]
+fn uniforms_fit_the_device() {
+    let Some(renderer) = test_support::headless_renderer(TARGET_SIZE, TARGET_SIZE) else {
+        return;
+    };
+    let limits = renderer.device.limits();
+    let sizes = [
+        ("ModelUniform", std::mem::size_of::<ModelUniform>()),
+        ("CameraUniform", std::mem::size_of::<CameraUniform>()),
+        ("SkyboxUniform", std::mem::size_of::<SkyboxUniform>()),
+        ("LightUniform", std::mem::size_of::<LightUniform>()),
+        ("BloomUniform", std::mem::size_of::<BloomUniform>()),
+        ("GlobalsUniform", std::mem::size_of::<GlobalsUniform>()),
+        ("ShadowUniform", std::mem::size_of::<ShadowUniform>()),
+    ];
+    for (name, size) in sizes {
+        assert!(
+            size as u32 <= limits.max_uniform_buffer_binding_size,
+            "{} is {} bytes, the device binds at most {}",
+            name,
+            size,
+            limits.max_uniform_buffer_binding_size
+        );
+    }
+    if per_draw::Mode::for_device(&renderer.device) == per_draw::Mode::PushConstants {
+        assert!(per_draw::PUSH_CONSTANT_SIZE <= limits.max_push_constant_size);
+    }
+}
+
+/// Renders offscreen under validation; any validation error fails the test.
+fn render(renderer: &mut Renderer, scope: &str) -> Vec<u8> {
+    let device = renderer.device.clone();
+    validation::with_validation(&device, scope, || renderer.render_offscreen())
+        .unwrap_or_else(|error| panic!("{}", error))
+        .unwrap_or_else(|error| panic!("{}", error))
+}
+
+#[test]
+fn renders_a_frame() {
+    let Some(mut renderer) = test_support::headless_renderer(TARGET_SIZE, TARGET_SIZE) else {
+        return;
+    };
+    renderer.update(1.0 / 60.0);
+    let rgba = render(&mut renderer, "frame test");
+    assert_eq!(rgba.len(), (TARGET_SIZE * TARGET_SIZE * 4) as usize);
+}
+
+/// Draws frames with the shadow map at another resolution, with its
+/// overlay and with the light's view frozen, then with shadows off.
+#[test]
+fn renders_shadows() {
+    let Some(mut renderer) = test_support::headless_renderer(TARGET_SIZE, TARGET_SIZE) else {
+        return;
+    };
+    let settings = renderer.shadow_settings();
+    let (_, light_color) = renderer.light();
+    renderer.set_shadow_settings(ShadowSettings {
+        resolution: 512,
+        ..settings
+    });
+    let mut views = renderer.debug_views();
+    views.shadow_map = true;
+    renderer.set_debug_views(views);
+    render(&mut renderer, "shadow test");
+
+    let light_view_projection = renderer.light_view_projection();
+    renderer.toggle_light_freeze();
+    renderer.set_light(glm::vec3(1.0, -0.5, 0.0), light_color);
+    render(&mut renderer, "frozen shadow test");
+    assert!(
+        renderer.light_view_projection() == light_view_projection,
+        "the light's view moved while frozen"
+    );
+
+    renderer.toggle_light_freeze();
+    renderer.set_shadow_settings(ShadowSettings {
+        enabled: false,
+        ..settings
+    });
+    render(&mut renderer, "unshadowed test");
+}